@@ -0,0 +1,464 @@
+//! How arguments and return values cross function boundaries.
+//!
+//! Scalars are passed as themselves, and the code generator leaves their
+//! placement to LLVM. Structs and unions are classified here following the
+//! platform's calling convention: the System V AMD64 psABI, the ARM 64-bit
+//! procedure call standard or the Microsoft x64 convention. Everywhere else
+//! they are passed and returned in memory, as on 32-bit x86.
+
+use std::collections::HashMap;
+
+use super::parser::{Struct, Type};
+use super::target::{TargetInfo, VaList};
+
+/// A register-sized piece of an aggregate passed in registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// The given number of bits of a general-purpose register
+    Integer(u32),
+    Float,
+    /// Two `float`s sharing a vector register
+    FloatPair,
+    Double,
+    LongDouble,
+}
+
+/// How a scalar narrower than `int` is widened for a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extension {
+    None,
+    Sign,
+    Zero,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassMode {
+    /// A scalar, passed as itself
+    Value(Extension),
+    /// Nothing is passed for an empty struct
+    Ignore,
+    /// In registers, as these pieces of consecutive eightbytes
+    Direct(Vec<Register>),
+    /// In registers allocated together, as an array of `count` of them
+    Consecutive(Register, u32),
+    /// In memory: an argument is copied onto the stack, and a return value
+    /// is written to memory the caller provides
+    Memory,
+    /// An argument is passed as a pointer to a copy the caller makes
+    Reference,
+    /// An argument of array type is passed as a pointer to the caller's
+    /// object itself, as `va_list` is on x86-64
+    Shared,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionAbi {
+    pub return_mode: PassMode,
+    pub parameters: Vec<PassMode>,
+}
+
+/// How the return value and the parameters of a function are passed
+pub fn function_abi(
+    target: &TargetInfo,
+    return_type: &Type,
+    parameters: &[Type],
+    structs: &HashMap<String, Struct>,
+) -> FunctionAbi {
+    let classifier = Classifier { target, structs };
+    match target.arch() {
+        "x86_64" if target.is_windows() => classifier.win64(return_type, parameters),
+        "x86_64" => classifier.sysv(return_type, parameters),
+        "aarch64" | "arm64" => classifier.aapcs64(return_type, parameters),
+        _ => FunctionAbi {
+            return_mode: classifier.scalar_or(return_type, PassMode::Memory),
+            parameters: parameters.iter().map(|parameter| classifier.scalar_or(parameter, PassMode::Memory)).collect(),
+        },
+    }
+}
+
+/// A scalar within an aggregate, at `offset` bytes from its start
+struct Leaf {
+    offset: u64,
+    size: u64,
+    /// The floating type of the scalar, or `None` for an integer or pointer
+    floating: Option<Type>,
+}
+
+/// The class of an eightbyte of an aggregate in the System V AMD64 ABI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Empty,
+    Integer,
+    Sse,
+    X87,
+    X87Up,
+    Memory,
+}
+
+struct Classifier<'a> {
+    target: &'a TargetInfo,
+    structs: &'a HashMap<String, Struct>,
+}
+
+impl Classifier<'_> {
+    /// The mode of a scalar, or `aggregate` for a struct or union
+    fn scalar_or(&self, value_type: &Type, aggregate: PassMode) -> PassMode {
+        match value_type.unqualified() {
+            Type::Void => PassMode::Ignore,
+            Type::Struct(_) | Type::Union(_) if self.size(value_type) == 0 => PassMode::Ignore,
+            Type::Struct(_) | Type::Union(_) => aggregate,
+            Type::VaList => match self.target.va_list {
+                VaList::Pointer => PassMode::Value(Extension::None),
+                VaList::SysV => PassMode::Shared,
+                VaList::Aapcs64 => aggregate,
+            },
+            Type::Bool => PassMode::Value(Extension::Zero),
+            integer if integer.is_integer() && self.target.integer_width(integer) < 32 => {
+                PassMode::Value(if self.target.is_signed(integer) { Extension::Sign } else { Extension::Zero })
+            }
+            _ => PassMode::Value(Extension::None),
+        }
+    }
+
+    fn size(&self, object_type: &Type) -> u64 {
+        self.target.layout(object_type, self.structs).map_or(0, |layout| layout.size)
+    }
+
+    /// The scalars an object is made of, in no particular order
+    fn leaves(&self, object_type: &Type, offset: u64, leaves: &mut Vec<Leaf>) {
+        match object_type.unqualified() {
+            Type::Struct(tag) | Type::Union(tag) => {
+                let definition = &self.structs[tag];
+                let layout = self.target.record_layout(definition, self.structs).expect("incomplete record");
+                for (field, bit_offset) in definition.fields.iter().zip(layout.field_offsets) {
+                    match field.bit_width {
+                        Some(0) => {}
+                        Some(width) => leaves.push(Leaf {
+                            offset: offset + bit_offset / 8,
+                            size: (bit_offset % 8 + width as u64).div_ceil(8),
+                            floating: None,
+                        }),
+                        None => self.leaves(&field.var_type, offset + bit_offset / 8, leaves),
+                    }
+                }
+            }
+            Type::Array(element, length) => {
+                let size = self.size(element);
+                for index in 0..length.unwrap_or(0) as u64 {
+                    self.leaves(element, offset + index * size, leaves);
+                }
+            }
+            scalar => leaves.push(Leaf {
+                offset,
+                size: self.size(scalar),
+                floating: scalar.is_floating().then(|| scalar.clone()),
+            }),
+        }
+    }
+
+    // System V AMD64
+
+    fn sysv(&self, return_type: &Type, parameters: &[Type]) -> FunctionAbi {
+        // Registers left for arguments
+        let mut integers = 6;
+        let mut vectors = 8;
+
+        let return_mode = match self.scalar_or(return_type, PassMode::Memory) {
+            PassMode::Memory => match self.sysv_classes(return_type) {
+                Some(classes) => PassMode::Direct(self.sysv_registers(return_type, &classes)),
+                None => PassMode::Memory,
+            },
+            mode => mode,
+        };
+        // The address of a returned aggregate takes the first argument register
+        if return_mode == PassMode::Memory {
+            integers -= 1;
+        }
+
+        let parameters = parameters
+            .iter()
+            .map(|parameter| match self.scalar_or(parameter, PassMode::Memory) {
+                PassMode::Memory => {
+                    let classes = self.sysv_classes(parameter).filter(|classes| !classes.contains(&Class::X87));
+                    let classes = match classes {
+                        Some(classes) => classes,
+                        None => return PassMode::Memory,
+                    };
+                    let needed_integers = classes.iter().filter(|class| **class == Class::Integer).count();
+                    let needed_vectors = classes.iter().filter(|class| **class == Class::Sse).count();
+                    // An aggregate is never split between registers and the stack
+                    if needed_integers > integers || needed_vectors > vectors {
+                        return PassMode::Memory;
+                    }
+                    integers -= needed_integers;
+                    vectors -= needed_vectors;
+                    PassMode::Direct(self.sysv_registers(parameter, &classes))
+                }
+                mode => {
+                    match parameter.unqualified() {
+                        Type::LongDouble => {}
+                        floating if floating.is_floating() => vectors = vectors.saturating_sub(1),
+                        _ => integers = integers.saturating_sub(1),
+                    }
+                    mode
+                }
+            })
+            .collect();
+        FunctionAbi { return_mode, parameters }
+    }
+
+    /// The classes of the eightbytes of an aggregate, or `None` when it is
+    /// passed in memory
+    fn sysv_classes(&self, aggregate: &Type) -> Option<Vec<Class>> {
+        let size = self.size(aggregate);
+        if size > 16 {
+            return None;
+        }
+        let mut leaves = Vec::new();
+        self.leaves(aggregate, 0, &mut leaves);
+        let mut classes = vec![Class::Empty; size.div_ceil(8) as usize];
+        for leaf in &leaves {
+            let first = (leaf.offset / 8) as usize;
+            let last = ((leaf.offset + leaf.size.max(1) - 1) / 8) as usize;
+            for (eightbyte, class) in classes.iter_mut().enumerate().take(last + 1).skip(first) {
+                let leaf_class = match &leaf.floating {
+                    None => Class::Integer,
+                    Some(Type::LongDouble) if eightbyte == first => Class::X87,
+                    Some(Type::LongDouble) => Class::X87Up,
+                    Some(_) => Class::Sse,
+                };
+                *class = merge(*class, leaf_class);
+            }
+        }
+        let orphan_x87_up = classes
+            .iter()
+            .enumerate()
+            .any(|(index, class)| *class == Class::X87Up && (index == 0 || classes[index - 1] != Class::X87));
+        if classes.contains(&Class::Memory) || orphan_x87_up {
+            return None;
+        }
+        Some(classes)
+    }
+
+    fn sysv_registers(&self, aggregate: &Type, classes: &[Class]) -> Vec<Register> {
+        let size = self.size(aggregate);
+        let mut leaves = Vec::new();
+        self.leaves(aggregate, 0, &mut leaves);
+        let mut registers = Vec::new();
+        for (index, class) in classes.iter().enumerate() {
+            let start = index as u64 * 8;
+            let bytes = (size - start).min(8);
+            let in_eightbyte = |leaf: &&Leaf| leaf.offset >= start && leaf.offset < start + 8;
+            match class {
+                Class::Sse => {
+                    let has_double = leaves.iter().filter(in_eightbyte).any(|leaf| leaf.size == 8);
+                    let has_upper = leaves.iter().filter(in_eightbyte).any(|leaf| leaf.offset - start >= 4);
+                    registers.push(if has_double {
+                        Register::Double
+                    } else if has_upper {
+                        Register::FloatPair
+                    } else {
+                        Register::Float
+                    });
+                }
+                Class::X87 => registers.push(Register::LongDouble),
+                Class::X87Up => {}
+                _ => {
+                    // An upper half that is only padding is left out
+                    let upper_used =
+                        leaves.iter().any(|leaf| leaf.offset < start + 8 && leaf.offset + leaf.size > start + 4);
+                    let bytes = if bytes > 4 && !upper_used { 4 } else { bytes };
+                    registers.push(Register::Integer(bytes as u32 * 8));
+                }
+            }
+        }
+        registers
+    }
+
+    // AAPCS64
+
+    fn aapcs64(&self, return_type: &Type, parameters: &[Type]) -> FunctionAbi {
+        FunctionAbi {
+            return_mode: self.aapcs64_mode(return_type, PassMode::Memory),
+            parameters: parameters.iter().map(|parameter| self.aapcs64_mode(parameter, PassMode::Reference)).collect(),
+        }
+    }
+
+    fn aapcs64_mode(&self, value_type: &Type, large: PassMode) -> PassMode {
+        let mode = self.scalar_or(value_type, PassMode::Memory);
+        if mode != PassMode::Memory {
+            return mode;
+        }
+        let size = self.size(value_type);
+        if let Some((register, count)) = self.homogeneous_aggregate(value_type) {
+            PassMode::Consecutive(register, count)
+        } else if size > 16 {
+            large
+        } else if size > 8 {
+            PassMode::Consecutive(Register::Integer(64), 2)
+        } else {
+            PassMode::Direct(vec![Register::Integer(64)])
+        }
+    }
+
+    /// The member type and count of a homogeneous floating-point aggregate:
+    /// one to four members of the same floating type and nothing else
+    fn homogeneous_aggregate(&self, aggregate: &Type) -> Option<(Register, u32)> {
+        let size = self.size(aggregate);
+        if size > 64 {
+            return None;
+        }
+        let mut leaves = Vec::new();
+        self.leaves(aggregate, 0, &mut leaves);
+        let base = leaves.first()?.floating.clone()?;
+        let base_size = self.size(&base);
+        let uniform = leaves.iter().all(|leaf| leaf.floating.as_ref() == Some(&base) && leaf.offset % base_size == 0);
+        let count = size / base_size;
+        if !uniform || !size.is_multiple_of(base_size) || !(1..=4).contains(&count) {
+            return None;
+        }
+        let register = match base {
+            Type::Float => Register::Float,
+            Type::Double => Register::Double,
+            _ => Register::LongDouble,
+        };
+        Some((register, count as u32))
+    }
+
+    // Microsoft x64
+
+    fn win64(&self, return_type: &Type, parameters: &[Type]) -> FunctionAbi {
+        FunctionAbi {
+            return_mode: self.win64_mode(return_type, PassMode::Memory),
+            parameters: parameters.iter().map(|parameter| self.win64_mode(parameter, PassMode::Reference)).collect(),
+        }
+    }
+
+    /// Aggregates of the size of an integer are passed as one; others are
+    /// passed by reference and returned in memory
+    fn win64_mode(&self, value_type: &Type, other: PassMode) -> PassMode {
+        match self.scalar_or(value_type, PassMode::Memory) {
+            PassMode::Memory => match self.size(value_type) {
+                size @ (1 | 2 | 4 | 8) => PassMode::Direct(vec![Register::Integer(size as u32 * 8)]),
+                _ => other,
+            },
+            mode => mode,
+        }
+    }
+}
+
+fn merge(a: Class, b: Class) -> Class {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Class::Empty, other) | (other, Class::Empty) => other,
+        (Class::Memory, _) | (_, Class::Memory) => Class::Memory,
+        (Class::Integer, _) | (_, Class::Integer) => Class::Integer,
+        (Class::X87 | Class::X87Up, _) | (_, Class::X87 | Class::X87Up) => Class::Memory,
+        _ => Class::Sse,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::source::FileId;
+    use crate::compiler::target::create_target_machine_for;
+    use crate::compiler::{lexer, parser};
+
+    /// The ABI of the function `f` declared in `source`
+    fn abi(triple: &str, source: &str) -> FunctionAbi {
+        let target = TargetInfo::new(&create_target_machine_for(triple));
+        let tokens = lexer::tokenize(source, FileId(0)).expect("lexing failed");
+        let ast = parser::parse(tokens, &target).expect("parsing failed");
+        let function = &ast.functions["f"];
+        let parameters: Vec<Type> = function.parameters.iter().map(|parameter| parameter.var_type.clone()).collect();
+        function_abi(&target, &function.return_type, &parameters, &ast.structs)
+    }
+
+    fn direct(registers: &[Register]) -> PassMode {
+        PassMode::Direct(registers.to_vec())
+    }
+
+    #[test]
+    fn test_sysv_classification() {
+        let sysv = |source: &str| abi("x86_64-unknown-linux-gnu", source);
+        let point = "struct point { int x, y; };";
+        assert_eq!(
+            sysv(&format!("{} struct point f(struct point p, char c);", point)),
+            FunctionAbi {
+                return_mode: direct(&[Register::Integer(64)]),
+                parameters: vec![direct(&[Register::Integer(64)]), PassMode::Value(Extension::Sign)],
+            }
+        );
+
+        let cases = [
+            ("struct s { int a, b, c; };", direct(&[Register::Integer(64), Register::Integer(32)])),
+            ("struct s { double d; int i; };", direct(&[Register::Double, Register::Integer(32)])),
+            ("struct s { float a, b, c; };", direct(&[Register::FloatPair, Register::Float])),
+            ("struct s { float f; char c; };", direct(&[Register::Integer(64)])),
+            ("struct s { char c[3]; };", direct(&[Register::Integer(24)])),
+            ("struct s { union { float f; double d; }; };", direct(&[Register::Double])),
+            ("struct s { long a, b, c; };", PassMode::Memory),
+            ("struct s { long double x; };", PassMode::Memory),
+            ("struct s { };", PassMode::Ignore),
+        ];
+        for (definition, expected) in cases {
+            let function = sysv(&format!("{} void f(struct s a);", definition));
+            assert_eq!(function.parameters[0], expected, "{}", definition);
+        }
+
+        // `long double` alone is returned on the x87 stack
+        let x87 = sysv("struct s { long double x; }; struct s f(void);");
+        assert_eq!(x87.return_mode, direct(&[Register::LongDouble]));
+    }
+
+    #[test]
+    fn test_sysv_register_exhaustion() {
+        // Five integer registers are left after the first five arguments, and
+        // the pair no longer fits once a returned aggregate takes one more
+        let pair = "struct pair { long a, b; };";
+        let fits =
+            abi("x86_64-unknown-linux-gnu", &format!("{} void f(int a, int b, int c, int d, struct pair p);", pair));
+        assert_eq!(fits.parameters[4], direct(&[Register::Integer(64), Register::Integer(64)]));
+        let spills = abi(
+            "x86_64-unknown-linux-gnu",
+            &format!("{} struct big {{ long x[3]; }}; struct big f(int a, int b, int c, int d, struct pair p);", pair),
+        );
+        assert_eq!(spills.return_mode, PassMode::Memory);
+        assert_eq!(spills.parameters[4], PassMode::Memory);
+    }
+
+    #[test]
+    fn test_aapcs64_and_win64_classification() {
+        let arm = |source: &str| abi("aarch64-unknown-linux-gnu", source).parameters[0].clone();
+        assert_eq!(arm("struct s { float x, y, z; }; void f(struct s a);"), PassMode::Consecutive(Register::Float, 3));
+        assert_eq!(arm("struct s { double d[4]; }; void f(struct s a);"), PassMode::Consecutive(Register::Double, 4));
+        assert_eq!(
+            arm("struct s { int a, b, c; }; void f(struct s a);"),
+            PassMode::Consecutive(Register::Integer(64), 2)
+        );
+        assert_eq!(arm("struct s { short a; }; void f(struct s a);"), direct(&[Register::Integer(64)]));
+        assert_eq!(arm("struct s { long a, b, c; }; void f(struct s a);"), PassMode::Reference);
+        let large_return = abi("aarch64-unknown-linux-gnu", "struct s { long a, b, c; }; struct s f(void);");
+        assert_eq!(large_return.return_mode, PassMode::Memory);
+
+        let windows = |source: &str| abi("x86_64-pc-windows-msvc", source);
+        let point = windows("struct s { int x, y; }; struct s f(struct s a);");
+        assert_eq!(point.return_mode, direct(&[Register::Integer(64)]));
+        assert_eq!(point.parameters[0], direct(&[Register::Integer(64)]));
+        let triple = windows("struct s { int a, b, c; }; struct s f(struct s a);");
+        assert_eq!(triple.return_mode, PassMode::Memory);
+        assert_eq!(triple.parameters[0], PassMode::Reference);
+
+        // `va_list` is an array on x86-64, a struct on AArch64 and a pointer
+        // elsewhere
+        let vprint = "int f(__builtin_va_list ap);";
+        assert_eq!(abi("x86_64-unknown-linux-gnu", vprint).parameters[0], PassMode::Shared);
+        assert_eq!(abi("aarch64-unknown-linux-gnu", vprint).parameters[0], PassMode::Reference);
+        assert_eq!(abi("x86_64-pc-windows-msvc", vprint).parameters[0], PassMode::Value(Extension::None));
+
+        let x86 = abi("i686-unknown-linux-gnu", "struct s { int x; }; struct s f(struct s a, short b);");
+        assert_eq!(x86.return_mode, PassMode::Memory);
+        assert_eq!(x86.parameters, vec![PassMode::Memory, PassMode::Value(Extension::Sign)]);
+    }
+}
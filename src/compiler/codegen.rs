@@ -0,0 +1,2772 @@
+//! Lowering of the analyzed AST to LLVM IR.
+//!
+//! Every C object lives in memory: locals in allocas at the top of the entry
+//! block, globals and `static` locals in module globals. Variable length
+//! arrays are allocated where their declaration is reached instead, and
+//! released when their block is left. Scalars are loaded and stored in their
+//! LLVM types, with `_Bool` held as an `i8`. Structs and unions are byte
+//! arrays of the size their layout gives, and their members are reached by
+//! byte offset, so every ABI's layout rules and bit-fields are handled in one
+//! place, `TargetInfo::record_layout`.
+//!
+//! Semantic analysis has made every conversion explicit, so the operands of
+//! an operator always have the types it is carried out in.
+
+use std::collections::{HashMap, VecDeque};
+
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::Builder;
+use inkwell::context::Context as LLVMContext;
+use inkwell::module::{Linkage, Module};
+use inkwell::targets::TargetMachine;
+use inkwell::types::{
+    AnyType, BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FloatType, FunctionType, IntType, PointerType,
+};
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, GlobalValue, IntValue, PointerValue,
+};
+use inkwell::{AddressSpace, AtomicOrdering, FloatPredicate, IntPredicate};
+use log::debug;
+
+use super::abi::{self, Extension, FunctionAbi, PassMode, Register};
+use super::parser::{
+    BinaryOperator, Block, Designator, Expression, ExpressionKind, Field, Function, Initializer, Literal,
+    Statement, StatementKind, StorageClass, Struct, Type, UnaryOperator, Variable, AST,
+};
+use super::target::{LongDouble, TargetInfo, VaList};
+
+/// Lower an analyzed translation unit to an LLVM module for `machine`
+pub fn generate<'ctx>(
+    context: &'ctx LLVMContext,
+    ast: &AST,
+    target: &TargetInfo,
+    machine: &TargetMachine,
+) -> Module<'ctx> {
+    let module = context.create_module("tlstuc_module");
+    module.set_triple(&machine.get_triple());
+    module.set_data_layout(&machine.get_target_data().get_data_layout());
+
+    let mut generator = CodeGenerator::new(context, module, &ast.structs, target);
+    let mut functions: Vec<&Function> = ast.functions.values().collect();
+    functions.sort_by_key(|function| (function.span.file.0, function.span.start));
+    for function in &functions {
+        generator.declare_function(function);
+    }
+    generator.global_variables(&ast.global_variables);
+    for function in functions {
+        if let Some(body) = &function.body {
+            generator.function(function, body);
+        }
+    }
+    generator.module
+}
+
+/// Where the value of an lvalue is stored
+#[derive(Debug, Clone, Copy)]
+enum Lvalue<'ctx> {
+    /// An object in memory
+    Address(PointerValue<'ctx>),
+    /// A bit-field `width` bits wide, `offset` bits into the storage unit of
+    /// its declared type at `unit`
+    BitField { unit: PointerValue<'ctx>, unit_type: IntType<'ctx>, offset: u32, width: u32 },
+}
+
+/// The blocks of the `case` and `default` labels of a `switch` statement, in
+/// the order the body reaches them
+struct SwitchLabels<'ctx> {
+    cases: VecDeque<BasicBlock<'ctx>>,
+    default: Option<BasicBlock<'ctx>>,
+}
+
+/// Part of the constant value of an object with static storage duration
+enum Piece<'ctx> {
+    Value(BasicValueEnum<'ctx>),
+    Bytes(Vec<u8>),
+}
+
+struct CodeGenerator<'a, 'ctx> {
+    context: &'ctx LLVMContext,
+    module: Module<'ctx>,
+    builder: Builder<'ctx>,
+    structs: &'a HashMap<String, Struct>,
+    target: &'a TargetInfo,
+    /// String literals already emitted, by element width and code units
+    strings: HashMap<(u32, Vec<u32>), PointerValue<'ctx>>,
+    /// The function being lowered and its name
+    function: Option<(FunctionValue<'ctx>, String)>,
+    /// How the function being lowered returns its value, and where to when
+    /// the caller provides the memory
+    return_mode: PassMode,
+    return_slot: Option<PointerValue<'ctx>>,
+    /// Local variables, innermost scope last
+    scopes: Vec<HashMap<String, PointerValue<'ctx>>>,
+    labels: HashMap<String, BasicBlock<'ctx>>,
+    /// Targets of `break` and `continue` with the number of scopes open
+    /// there, innermost last
+    breaks: Vec<(BasicBlock<'ctx>, usize)>,
+    continues: Vec<(BasicBlock<'ctx>, usize)>,
+    switches: Vec<SwitchLabels<'ctx>>,
+    /// Where the length of each variable length array was stored when its
+    /// declaration was reached, by `VariableLength::id`
+    lengths: HashMap<usize, PointerValue<'ctx>>,
+    /// The stack pointer saved before the first variable length array of a
+    /// block, with the number of scopes open there, innermost last; leaving
+    /// the block restores it to release the arrays
+    saved_stacks: Vec<(usize, PointerValue<'ctx>)>,
+}
+
+impl<'a, 'ctx> CodeGenerator<'a, 'ctx> {
+    fn new(
+        context: &'ctx LLVMContext,
+        module: Module<'ctx>,
+        structs: &'a HashMap<String, Struct>,
+        target: &'a TargetInfo,
+    ) -> Self {
+        CodeGenerator {
+            context,
+            module,
+            builder: context.create_builder(),
+            structs,
+            target,
+            strings: HashMap::new(),
+            function: None,
+            return_mode: PassMode::Ignore,
+            return_slot: None,
+            scopes: Vec::new(),
+            labels: HashMap::new(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            switches: Vec::new(),
+            lengths: HashMap::new(),
+            saved_stacks: Vec::new(),
+        }
+    }
+
+    // Types
+
+    /// The LLVM type of an object type
+    fn llvm_type(&self, object_type: &Type) -> BasicTypeEnum<'ctx> {
+        match object_type {
+            Type::Qualified(inner, _) => self.llvm_type(inner),
+            Type::Enum(_, underlying) => self.llvm_type(underlying),
+            integer if integer.is_integer() => self.int_type(integer).into(),
+            floating if floating.is_floating() => self.float_type(floating).into(),
+            Type::Pointer(pointee) => self.pointer_to(pointee).into(),
+            // Variable length arrays are allocated by size, and pointers to
+            // them point to their elements
+            Type::Array(element, _) | Type::VariableArray(element, _) if object_type.is_variable_length() => {
+                self.llvm_type(element)
+            }
+            Type::Array(element, size) => self.llvm_type(element).array_type(size.unwrap_or(0) as u32).into(),
+            Type::Struct(_) | Type::Union(_) => {
+                let size = self.target.layout(object_type, self.structs).map_or(0, |layout| layout.size);
+                self.context.i8_type().array_type(size as u32).into()
+            }
+            Type::VaList => self.va_list_type(),
+            // `void *` points to bytes
+            Type::Void => self.context.i8_type().into(),
+            other => unreachable!("`{}` is not an object type", other),
+        }
+    }
+
+    fn va_list_type(&self) -> BasicTypeEnum<'ctx> {
+        let i32_type = self.context.i32_type();
+        let bytes = self.context.i8_type().ptr_type(AddressSpace::default());
+        match self.target.va_list {
+            VaList::Pointer => bytes.into(),
+            VaList::SysV => {
+                let tag =
+                    self.context.struct_type(&[i32_type.into(), i32_type.into(), bytes.into(), bytes.into()], false);
+                tag.array_type(1).into()
+            }
+            VaList::Aapcs64 => self
+                .context
+                .struct_type(&[bytes.into(), bytes.into(), bytes.into(), i32_type.into(), i32_type.into()], false)
+                .into(),
+        }
+    }
+
+    fn int_type(&self, integer: &Type) -> IntType<'ctx> {
+        match integer.unqualified() {
+            // `_Bool` is stored in a byte
+            Type::Bool => self.context.i8_type(),
+            integer => self.context.custom_width_int_type(self.target.integer_width(integer)),
+        }
+    }
+
+    fn float_type(&self, floating: &Type) -> FloatType<'ctx> {
+        match floating.unqualified() {
+            Type::Float => self.context.f32_type(),
+            Type::LongDouble => match self.target.long_double {
+                LongDouble::X87 => self.context.x86_f80_type(),
+                LongDouble::Quad => self.context.f128_type(),
+                LongDouble::Double => self.context.f64_type(),
+            },
+            _ => self.context.f64_type(),
+        }
+    }
+
+    /// The integer type as wide as a pointer
+    fn intptr_type(&self) -> IntType<'ctx> {
+        self.int_type(&self.target.size_type())
+    }
+
+    fn pointer_to(&self, pointee: &Type) -> PointerType<'ctx> {
+        match pointee.unqualified() {
+            Type::Function(return_type, parameters, is_variadic) => {
+                self.function_type(return_type, parameters, *is_variadic).ptr_type(AddressSpace::default())
+            }
+            pointee => self.llvm_type(pointee).ptr_type(AddressSpace::default()),
+        }
+    }
+
+    /// How calls to a function of this type pass their values
+    fn abi(&self, return_type: &Type, parameters: &[Type]) -> FunctionAbi {
+        abi::function_abi(self.target, return_type, parameters, self.structs)
+    }
+
+    /// The LLVM type of a function, with its values passed as the ABI says
+    fn function_type(&self, return_type: &Type, parameters: &[Type], is_variadic: bool) -> FunctionType<'ctx> {
+        let abi = self.abi(return_type, parameters);
+        let mut types: Vec<BasicMetadataTypeEnum> = Vec::new();
+        if abi.return_mode == PassMode::Memory {
+            types.push(self.pointer_to(return_type).into());
+        }
+        for (parameter, mode) in parameters.iter().zip(&abi.parameters) {
+            match mode {
+                PassMode::Value(_) => types.push(self.llvm_type(parameter).into()),
+                PassMode::Ignore => {}
+                PassMode::Direct(registers) => types.extend(
+                    registers.iter().map(|register| BasicMetadataTypeEnum::from(self.register_type(*register))),
+                ),
+                PassMode::Consecutive(register, count) => {
+                    types.push(self.register_type(*register).array_type(*count).into())
+                }
+                PassMode::Memory | PassMode::Reference | PassMode::Shared => {
+                    types.push(self.pointer_to(parameter).into())
+                }
+            }
+        }
+        match &abi.return_mode {
+            PassMode::Value(_) => self.llvm_type(return_type).fn_type(&types, is_variadic),
+            PassMode::Direct(registers) if registers.len() == 1 => {
+                self.register_type(registers[0]).fn_type(&types, is_variadic)
+            }
+            PassMode::Direct(registers) => {
+                let fields: Vec<BasicTypeEnum> =
+                    registers.iter().map(|register| self.register_type(*register)).collect();
+                self.context.struct_type(&fields, false).fn_type(&types, is_variadic)
+            }
+            PassMode::Consecutive(register, count) => {
+                self.register_type(*register).array_type(*count).fn_type(&types, is_variadic)
+            }
+            PassMode::Ignore | PassMode::Memory | PassMode::Reference | PassMode::Shared => {
+                self.context.void_type().fn_type(&types, is_variadic)
+            }
+        }
+    }
+
+    fn register_type(&self, register: Register) -> BasicTypeEnum<'ctx> {
+        match register {
+            Register::Integer(bits) => self.context.custom_width_int_type(bits).into(),
+            Register::Float => self.context.f32_type().into(),
+            Register::FloatPair => self.context.f32_type().vec_type(2).into(),
+            Register::Double => self.context.f64_type().into(),
+            Register::LongDouble => self.float_type(&Type::LongDouble).into(),
+        }
+    }
+
+    /// The attributes that tell LLVM how a function's values are passed,
+    /// which its declaration and every call to it carry
+    fn abi_attributes(
+        &self,
+        return_type: &Type,
+        parameters: &[Type],
+        abi: &FunctionAbi,
+    ) -> Vec<(AttributeLoc, Attribute)> {
+        let attribute =
+            |name: &str, value: u64| self.context.create_enum_attribute(Attribute::get_named_enum_kind_id(name), value);
+        let extension = |extension: &Extension| match extension {
+            Extension::None => None,
+            Extension::Sign => Some(attribute("signext", 0)),
+            Extension::Zero => Some(attribute("zeroext", 0)),
+        };
+        let mut attributes = Vec::new();
+        let mut index = 0;
+        match &abi.return_mode {
+            PassMode::Value(kind) => {
+                attributes.extend(extension(kind).map(|extension| (AttributeLoc::Return, extension)))
+            }
+            PassMode::Memory => {
+                let sret = Attribute::get_named_enum_kind_id("sret");
+                let return_type = self.llvm_type(return_type).as_any_type_enum();
+                attributes.push((AttributeLoc::Param(0), self.context.create_type_attribute(sret, return_type)));
+                index = 1;
+            }
+            _ => {}
+        }
+        for (parameter, mode) in parameters.iter().zip(&abi.parameters) {
+            match mode {
+                PassMode::Value(kind) => {
+                    attributes.extend(extension(kind).map(|extension| (AttributeLoc::Param(index), extension)))
+                }
+                PassMode::Memory => {
+                    let byval = Attribute::get_named_enum_kind_id("byval");
+                    let parameter_type = self.llvm_type(parameter).as_any_type_enum();
+                    attributes
+                        .push((AttributeLoc::Param(index), self.context.create_type_attribute(byval, parameter_type)));
+                    let align = self.align_of(parameter).max(self.size_of(&self.target.size_type()) as u32);
+                    attributes.push((AttributeLoc::Param(index), attribute("align", align as u64)));
+                }
+                _ => {}
+            }
+            index += abi_arity(mode) as u32;
+        }
+        attributes
+    }
+
+    /// A temporary to move a value between its own type and the registers
+    /// it is passed in, which may cover padding beyond its end
+    fn abi_temporary(&self, value_type: &Type, mode: &PassMode, name: &str) -> PointerValue<'ctx> {
+        match mode {
+            PassMode::Direct(_) | PassMode::Consecutive(..) => {
+                let size = self.size_of(value_type).div_ceil(8) * 8;
+                let align = self.align_of(value_type).max(8);
+                let bytes = self.context.i8_type().array_type(size as u32);
+                let address = self.entry_alloca(bytes.into(), align, name);
+                self.cast_pointer(address, value_type)
+            }
+            _ => self.alloca(value_type, name),
+        }
+    }
+
+    /// Store the values a value of `value_type` is passed as to `address`
+    fn store_abi(&self, address: PointerValue<'ctx>, mode: &PassMode, values: &[BasicValueEnum<'ctx>]) {
+        let offsets: Vec<u64> = match mode {
+            PassMode::Direct(_) => (0..values.len() as u64).map(|index| index * 8).collect(),
+            _ => vec![0; values.len()],
+        };
+        for (value, offset) in values.iter().zip(offsets) {
+            let piece = self.byte_address(address, offset, value.get_type());
+            self.builder.build_store(piece, *value);
+        }
+    }
+
+    /// Load the values a value of `value_type` at `address` is passed as
+    fn load_abi(&self, address: PointerValue<'ctx>, mode: &PassMode, value_type: &Type) -> Vec<BasicValueEnum<'ctx>> {
+        let pieces: Vec<BasicTypeEnum> = match mode {
+            PassMode::Value(_) => vec![self.llvm_type(value_type)],
+            PassMode::Direct(registers) => registers.iter().map(|register| self.register_type(*register)).collect(),
+            PassMode::Consecutive(register, count) => vec![self.register_type(*register).array_type(*count).into()],
+            _ => Vec::new(),
+        };
+        let step = if let PassMode::Direct(_) = mode { 8 } else { 0 };
+        pieces
+            .into_iter()
+            .enumerate()
+            .map(|(index, piece)| {
+                let piece_address = self.byte_address(address, index as u64 * step, piece);
+                self.builder.build_load(piece, piece_address, "")
+            })
+            .collect()
+    }
+
+    /// A pointer to a `pointee` at `offset` bytes from `address`
+    fn byte_address(
+        &self,
+        address: PointerValue<'ctx>,
+        offset: u64,
+        pointee: BasicTypeEnum<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let bytes = self.cast_pointer(address, &Type::Char);
+        let offset = self.intptr_type().const_int(offset, false);
+        let piece = unsafe { self.builder.build_gep(self.context.i8_type(), bytes, &[offset], "") };
+        self.builder.build_pointer_cast(piece, pointee.ptr_type(AddressSpace::default()), "")
+    }
+
+    fn size_of(&self, object_type: &Type) -> u64 {
+        self.target.layout(object_type, self.structs).map_or(0, |layout| layout.size)
+    }
+
+    fn align_of(&self, object_type: &Type) -> u32 {
+        match object_type.unqualified() {
+            // A variable length array is aligned like its elements
+            Type::Array(element, _) | Type::VariableArray(element, _) if object_type.is_variable_length() => {
+                self.align_of(element)
+            }
+            _ => self.target.layout(object_type, self.structs).map_or(1, |layout| layout.align as u32),
+        }
+    }
+
+    /// The alignment of a variable, which `_Alignas` may make stricter than
+    /// that of its type
+    fn variable_align(&self, variable: &Variable) -> u32 {
+        self.align_of(&variable.var_type).max(variable.alignment.unwrap_or(1) as u32)
+    }
+
+    /// The size of an object of the type; the size of a variable length
+    /// array is computed from the lengths stored for its declaration
+    fn runtime_size(&self, object_type: &Type) -> IntValue<'ctx> {
+        let intptr_type = self.intptr_type();
+        let (element, length) = match object_type.unqualified() {
+            Type::VariableArray(element, length) => {
+                let slot = self.lengths[&length.id];
+                (element, self.builder.build_load(intptr_type, slot, "vla.length").into_int_value())
+            }
+            Type::Array(element, Some(length)) if element.is_variable_length() => {
+                (element, intptr_type.const_int(*length as u64, false))
+            }
+            fixed => return intptr_type.const_int(self.size_of(fixed), false),
+        };
+        let element_size = self.runtime_size(element);
+        self.builder.build_int_mul(length, element_size, "vla.size")
+    }
+
+    /// A member of a struct or union and its offset in bits
+    fn member(&self, record: &Type, name: &str) -> (&'a Field, u64) {
+        let definition = match record.unqualified() {
+            Type::Struct(tag) | Type::Union(tag) => &self.structs[tag],
+            other => unreachable!("`{}` is not a struct or union", other),
+        };
+        let layout = self.target.record_layout(definition, self.structs).expect("incomplete record");
+        let index = definition.fields.iter().position(|field| field.name == name).expect("unknown member");
+        (&definition.fields[index], layout.field_offsets[index])
+    }
+
+    // Declarations
+
+    fn declare_function(&mut self, function: &Function) {
+        let parameters: Vec<Type> = function.parameters.iter().map(|parameter| parameter.var_type.clone()).collect();
+        let function_type = self.function_type(&function.return_type, &parameters, function.is_variadic);
+        let linkage = if function.storage == Some(StorageClass::Static) {
+            Linkage::Internal
+        } else if function.is_inline && function.body.is_some() && function.storage != Some(StorageClass::Extern) {
+            // An inline definition leaves the external definition to another
+            // translation unit, unless a declaration of it is `extern`
+            Linkage::AvailableExternally
+        } else {
+            Linkage::External
+        };
+        let llvm_function = self.module.add_function(&function.name, function_type, Some(linkage));
+        let abi = self.abi(&function.return_type, &parameters);
+        for (location, attribute) in self.abi_attributes(&function.return_type, &parameters, &abi) {
+            llvm_function.add_attribute(location, attribute);
+        }
+    }
+
+    /// Define the file-scope variables. Several declarations of one variable
+    /// make up one object, defined by its initializer, or else tentatively
+    /// with zeroes unless every declaration is `extern`.
+    fn global_variables(&mut self, declarations: &[(Variable, Option<Initializer>)]) {
+        let mut order = Vec::new();
+        let mut objects: HashMap<&str, Vec<&(Variable, Option<Initializer>)>> = HashMap::new();
+        for declaration in declarations {
+            let name = declaration.0.name.as_str();
+            if !objects.contains_key(name) {
+                order.push(name);
+            }
+            objects.entry(name).or_default().push(declaration);
+        }
+
+        // Every global exists before any initializer refers to one
+        let mut globals = Vec::new();
+        for name in order {
+            let declarations = &objects[name];
+            let initialized = declarations.iter().find(|(_, initializer)| initializer.is_some());
+            let (variable, initializer) = initialized.copied().unwrap_or(declarations[declarations.len() - 1]);
+            let is_definition = initializer.is_some()
+                || declarations.iter().any(|(variable, _)| variable.storage != Some(StorageClass::Extern));
+            let mut variable = variable.clone();
+            variable.storage = declarations
+                .iter()
+                .find(|(variable, _)| variable.storage == Some(StorageClass::Static))
+                .map_or(variable.storage, |_| Some(StorageClass::Static));
+            // A tentative definition of an array of unknown size has one element
+            if let (Type::Array(element, None), true) = (&variable.var_type, is_definition) {
+                variable.var_type = Type::Array(element.clone(), Some(1));
+            }
+            let global = self.add_global(&variable, name);
+            globals.push((global, variable, initializer.as_ref(), is_definition));
+        }
+        for (global, variable, initializer, is_definition) in globals {
+            if is_definition {
+                self.define_global(global, &variable, initializer);
+            }
+        }
+    }
+
+    /// Declare a global for a variable with static storage duration
+    fn add_global(&mut self, variable: &Variable, name: &str) -> GlobalValue<'ctx> {
+        let global = self.module.add_global(self.llvm_type(&variable.var_type), None, name);
+        global.set_alignment(self.variable_align(variable));
+        global.set_thread_local(variable.is_thread_local);
+        if variable.storage == Some(StorageClass::Static) {
+            global.set_linkage(Linkage::Internal);
+        }
+        global
+    }
+
+    /// Give a global its initializer. An initializer of a different LLVM
+    /// type than the object's, as a union initialized through a member other
+    /// than the largest one has, replaces the global with one of its type.
+    fn define_global(&mut self, global: GlobalValue<'ctx>, variable: &Variable, initializer: Option<&Initializer>) {
+        let value = self.constant(&variable.var_type, initializer);
+        let global = if value.get_type() == self.llvm_type(&variable.var_type) {
+            global
+        } else {
+            let name = global.get_name().to_string_lossy().into_owned();
+            let replacement = self.module.add_global(value.get_type(), None, "");
+            replacement.set_alignment(self.variable_align(variable));
+            replacement.set_thread_local(variable.is_thread_local);
+            replacement.set_linkage(global.get_linkage());
+            let pointer = global.as_pointer_value();
+            pointer.replace_all_uses_with(replacement.as_pointer_value().const_cast(pointer.get_type()));
+            unsafe { global.delete() };
+            replacement.set_name(&name);
+            replacement
+        };
+        global.set_initializer(&value);
+        global.set_constant(variable.qualifiers.is_const);
+    }
+
+    /// The constant value of an object with static storage duration. Unless
+    /// it is a scalar, it is a packed struct of the initialized parts of the
+    /// object, with the gaps between them zeroed.
+    fn constant(&mut self, object_type: &Type, initializer: Option<&Initializer>) -> BasicValueEnum<'ctx> {
+        let initializer = match initializer {
+            Some(initializer) => initializer,
+            None => return self.llvm_type(object_type).const_zero(),
+        };
+        if let Initializer::Expression(value) = initializer {
+            if object_type.is_scalar() {
+                return self.constant_scalar(value);
+            }
+        }
+
+        let mut pieces = Vec::new();
+        self.constant_pieces(&mut pieces, 0, object_type, initializer);
+        pieces.sort_by_key(|(offset, _)| *offset);
+        let mut values: Vec<BasicValueEnum> = Vec::new();
+        let mut end = 0;
+        for (offset, piece) in pieces {
+            if offset > end {
+                values.push(self.context.i8_type().array_type((offset - end) as u32).const_zero().into());
+            }
+            let value = match piece {
+                Piece::Value(value) => value,
+                Piece::Bytes(bytes) => self.context.const_string(&bytes, false).into(),
+            };
+            end = offset + self.target_size(value.get_type());
+            values.push(value);
+        }
+        let size = self.size_of(object_type);
+        if size > end {
+            values.push(self.context.i8_type().array_type((size - end) as u32).const_zero().into());
+        }
+        self.context.const_struct(&values, true).into()
+    }
+
+    fn target_size(&self, llvm_type: BasicTypeEnum<'ctx>) -> u64 {
+        // Scalars and byte arrays are the only pieces of a constant
+        match llvm_type {
+            BasicTypeEnum::ArrayType(array) => array.len() as u64,
+            BasicTypeEnum::IntType(integer) => integer.get_bit_width().div_ceil(8) as u64,
+            BasicTypeEnum::FloatType(floating) if floating == self.context.f32_type() => 4,
+            BasicTypeEnum::FloatType(floating) if floating == self.context.f64_type() => 8,
+            BasicTypeEnum::FloatType(_) => self.size_of(&Type::LongDouble),
+            BasicTypeEnum::PointerType(_) => self.size_of(&Type::Pointer(Box::new(Type::Void))),
+            other => unreachable!("unexpected constant of type {:?}", other),
+        }
+    }
+
+    /// Collect the initialized parts of an object at `offset` bytes
+    fn constant_pieces(
+        &mut self,
+        pieces: &mut Vec<(u64, Piece<'ctx>)>,
+        offset: u64,
+        object_type: &Type,
+        initializer: &Initializer,
+    ) {
+        let items = match initializer {
+            Initializer::Expression(value) => {
+                let piece = match string_literal(value) {
+                    Some(units) => Piece::Bytes(self.string_bytes(units, object_type)),
+                    None => Piece::Value(self.constant_scalar(value)),
+                };
+                pieces.push((offset, piece));
+                return;
+            }
+            Initializer::List(items, _) => items,
+        };
+        for item in items {
+            match (&item.designators[0], object_type.unqualified()) {
+                (Designator::Index(index), Type::Array(element, _)) => {
+                    let offset = offset + *index as u64 * self.size_of(element);
+                    self.constant_pieces(pieces, offset, element, &item.initializer);
+                }
+                (Designator::Member(name), record) => {
+                    let (field, bit_offset) = self.member(record, name);
+                    match field.bit_width {
+                        Some(width) => {
+                            let value = match &item.initializer {
+                                Initializer::Expression(value) => folded_integer(value).unwrap_or(0),
+                                Initializer::List(..) => unreachable!("bit-fields are scalars"),
+                            };
+                            constant_bit_field(pieces, offset * 8 + bit_offset, width, value);
+                        }
+                        None => {
+                            let offset = offset + bit_offset / 8;
+                            self.constant_pieces(pieces, offset, &field.qualified_type(), &item.initializer);
+                        }
+                    }
+                }
+                (designator, other) => unreachable!("{:?} does not designate part of `{}`", designator, other),
+            }
+        }
+    }
+
+    /// The value of a scalar constant expression
+    fn constant_scalar(&mut self, value: &Expression) -> BasicValueEnum<'ctx> {
+        // Constant operands are folded by the builder rather than emitted
+        let value = self.value(value);
+        debug_assert!(value.as_instruction_value().is_none(), "initializer is not constant");
+        value
+    }
+
+    /// The bytes of a string literal initializing an array, in the target's
+    /// byte order and padded with zeroes or cut to the size of the array
+    fn string_bytes(&self, units: &[u32], array: &Type) -> Vec<u8> {
+        let width = match array.unqualified() {
+            Type::Array(element, _) => self.size_of(element) as usize,
+            other => unreachable!("string literal initializes `{}`", other),
+        };
+        let mut bytes: Vec<u8> = units
+            .iter()
+            .flat_map(|&unit| {
+                let unit = u64::from(unit);
+                if self.target.big_endian {
+                    unit.to_be_bytes()[8 - width..].to_vec()
+                } else {
+                    unit.to_le_bytes()[..width].to_vec()
+                }
+            })
+            .collect();
+        bytes.resize(self.size_of(array) as usize, 0);
+        bytes
+    }
+
+    /// A pointer to the array of a string literal of type `array`
+    fn string_literal(&mut self, units: &[u32], array: &Type) -> PointerValue<'ctx> {
+        let element = match array {
+            Type::Array(element, _) => self.int_type(element),
+            other => unreachable!("string literal of type `{}`", other),
+        };
+        // Literals with the same units and element width share an array
+        let key = (element.get_bit_width(), units.to_vec());
+        if let Some(pointer) = self.strings.get(&key) {
+            return *pointer;
+        }
+        let values: Vec<_> = units.iter().chain([&0]).map(|&unit| element.const_int(u64::from(unit), false)).collect();
+        let value = element.const_array(&values);
+        let global = self.module.add_global(value.get_type(), None, ".str");
+        global.set_linkage(Linkage::Private);
+        global.set_initializer(&value);
+        global.set_constant(true);
+        global.set_unnamed_addr(true);
+        let pointer = global.as_pointer_value();
+        self.strings.insert(key, pointer);
+        pointer
+    }
+
+    // Functions and statements
+
+    fn function(&mut self, function: &Function, body: &Block) {
+        debug!("Generating code for {}", function.name);
+        let llvm_function = self.module.get_function(&function.name).expect("function was declared");
+        let entry = self.context.append_basic_block(llvm_function, "entry");
+        self.builder.position_at_end(entry);
+        self.function = Some((llvm_function, function.name.clone()));
+        self.labels.clear();
+        self.lengths.clear();
+        self.saved_stacks.clear();
+
+        let parameter_types: Vec<Type> =
+            function.parameters.iter().map(|parameter| parameter.var_type.clone()).collect();
+        let abi = self.abi(&function.return_type, &parameter_types);
+        let mut values = llvm_function.get_param_iter();
+        self.return_slot = match abi.return_mode {
+            PassMode::Memory => Some(values.next().unwrap().into_pointer_value()),
+            _ => None,
+        };
+        self.return_mode = abi.return_mode;
+
+        let mut parameters = HashMap::new();
+        for (parameter, mode) in function.parameters.iter().zip(&abi.parameters) {
+            let address = match mode {
+                // The parameter is the caller's copy, or its very object
+                PassMode::Memory | PassMode::Reference | PassMode::Shared => {
+                    let address = values.next().unwrap().into_pointer_value();
+                    address.set_name(&parameter.name);
+                    address
+                }
+                mode => {
+                    let pieces: Vec<BasicValueEnum> = values.by_ref().take(abi_arity(mode)).collect();
+                    if let [value] = pieces.as_slice() {
+                        value.set_name(&parameter.name);
+                    }
+                    let address = self.abi_temporary(&parameter.var_type, mode, &parameter.name);
+                    self.store_abi(address, mode, &pieces);
+                    address
+                }
+            };
+            if !parameter.name.is_empty() {
+                parameters.insert(parameter.name.clone(), address);
+            }
+        }
+        // The parameters are in the outermost block of the body
+        self.scopes = vec![parameters];
+        for parameter in &function.parameters {
+            self.evaluate_lengths(&parameter.var_type);
+        }
+        self.statements(&body.statements);
+        self.scopes.clear();
+
+        // Reaching the end of `main` returns 0; other functions return an
+        // unspecified value, which the caller may not use
+        for block in llvm_function.get_basic_blocks() {
+            if block.get_terminator().is_none() {
+                self.builder.position_at_end(block);
+                match llvm_function.get_type().get_return_type() {
+                    Some(return_type) => self.builder.build_return(Some(&return_type.const_zero())),
+                    None => self.builder.build_return(None),
+                };
+            }
+        }
+        self.function = None;
+    }
+
+    /// Allocate a local object at the start of the entry block
+    fn alloca(&self, object_type: &Type, name: &str) -> PointerValue<'ctx> {
+        self.entry_alloca(self.llvm_type(object_type), self.align_of(object_type), name)
+    }
+
+    fn entry_alloca(&self, llvm_type: BasicTypeEnum<'ctx>, align: u32, name: &str) -> PointerValue<'ctx> {
+        let (function, _) = self.function.as_ref().expect("alloca outside of a function");
+        let entry = function.get_first_basic_block().unwrap();
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => builder.position_before(&first),
+            None => builder.position_at_end(entry),
+        }
+        let address = builder.build_alloca(llvm_type, name);
+        let _ = address.as_instruction_value().unwrap().set_alignment(align);
+        address
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(HashMap::new());
+        self.statements(&block.statements);
+        self.leave_scope();
+    }
+
+    /// Close the innermost scope, releasing its variable length arrays
+    fn leave_scope(&mut self) {
+        if let Some(&(depth, saved)) = self.saved_stacks.last() {
+            if depth == self.scopes.len() {
+                self.saved_stacks.pop();
+                if !self.is_terminated() {
+                    self.restore_stack(saved);
+                }
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        // Code after a jump is only reachable through a label
+        if self.is_terminated() && !is_labeled(statement) {
+            let dead = self.append_block("dead");
+            self.builder.position_at_end(dead);
+        }
+
+        match &statement.kind {
+            StatementKind::Declaration(variable, initializer) => self.local_variable(variable, initializer.as_ref()),
+            StatementKind::Typedef(_, typedef) => self.evaluate_lengths(typedef),
+            StatementKind::If(condition, then_block, else_block) => {
+                let then_bb = self.append_block("if.then");
+                let else_bb = self.append_block("if.else");
+                let end = self.append_block("if.end");
+                let condition = self.condition(condition);
+                self.builder.build_conditional_branch(condition, then_bb, else_bb);
+                self.builder.position_at_end(then_bb);
+                self.block(then_block);
+                self.branch(end);
+                self.builder.position_at_end(else_bb);
+                if let Some(else_block) = else_block {
+                    self.block(else_block);
+                }
+                self.branch(end);
+                self.builder.position_at_end(end);
+            }
+            StatementKind::While(condition, body) => {
+                let condition_bb = self.append_block("while.cond");
+                let body_bb = self.append_block("while.body");
+                let end = self.append_block("while.end");
+                self.branch(condition_bb);
+                self.builder.position_at_end(condition_bb);
+                let condition = self.condition(condition);
+                self.builder.build_conditional_branch(condition, body_bb, end);
+                self.builder.position_at_end(body_bb);
+                self.loop_body(body, end, condition_bb);
+                self.branch(condition_bb);
+                self.builder.position_at_end(end);
+            }
+            StatementKind::DoWhile(body, condition) => {
+                let body_bb = self.append_block("do.body");
+                let condition_bb = self.append_block("do.cond");
+                let end = self.append_block("do.end");
+                self.branch(body_bb);
+                self.builder.position_at_end(body_bb);
+                self.loop_body(body, end, condition_bb);
+                self.branch(condition_bb);
+                self.builder.position_at_end(condition_bb);
+                let condition = self.condition(condition);
+                self.builder.build_conditional_branch(condition, body_bb, end);
+                self.builder.position_at_end(end);
+            }
+            StatementKind::For(init, condition, step, body) => {
+                self.scopes.push(HashMap::new());
+                match &init.kind {
+                    // Declarations of several variables share the loop's scope
+                    StatementKind::Block(declarations) => self.statements(&declarations.statements),
+                    _ => self.statement(init),
+                }
+                let condition_bb = self.append_block("for.cond");
+                let body_bb = self.append_block("for.body");
+                let step_bb = self.append_block("for.step");
+                let end = self.append_block("for.end");
+                self.branch(condition_bb);
+                self.builder.position_at_end(condition_bb);
+                let condition = self.condition(condition);
+                self.builder.build_conditional_branch(condition, body_bb, end);
+                self.builder.position_at_end(body_bb);
+                self.loop_body(body, end, step_bb);
+                self.branch(step_bb);
+                self.builder.position_at_end(step_bb);
+                self.statement(step);
+                self.branch(condition_bb);
+                self.builder.position_at_end(end);
+                self.leave_scope();
+            }
+            StatementKind::Switch(condition, body) => self.switch_statement(condition, body),
+            StatementKind::Case(_, statement) => {
+                let block = self.switches.last_mut().unwrap().cases.pop_front().unwrap();
+                self.branch(block);
+                self.builder.position_at_end(block);
+                self.statement(statement);
+            }
+            StatementKind::Default(statement) => {
+                let block = self.switches.last().unwrap().default.unwrap();
+                self.branch(block);
+                self.builder.position_at_end(block);
+                self.statement(statement);
+            }
+            StatementKind::Label(name, statement) => {
+                let block = self.label(name);
+                self.branch(block);
+                self.builder.position_at_end(block);
+                self.statement(statement);
+            }
+            StatementKind::Goto(name) => {
+                let block = self.label(name);
+                self.builder.build_unconditional_branch(block);
+            }
+            StatementKind::Break => {
+                let (target, depth) = *self.breaks.last().unwrap();
+                self.jump_out(target, depth);
+            }
+            StatementKind::Continue => {
+                let (target, depth) = *self.continues.last().unwrap();
+                self.jump_out(target, depth);
+            }
+            StatementKind::Return(value) => match value {
+                Some(value) if *value.ty() != Type::Void => {
+                    let value_type = value.ty().unqualified().clone();
+                    let value = self.value(value);
+                    self.return_value(value, &value_type);
+                }
+                Some(value) => {
+                    self.value(value);
+                    self.builder.build_return(None);
+                }
+                None => {
+                    self.builder.build_return(None);
+                }
+            },
+            StatementKind::Expression(expression) => {
+                self.value(expression);
+            }
+            StatementKind::Block(block) => self.block(block),
+        }
+    }
+
+    fn loop_body(&mut self, body: &Block, end: BasicBlock<'ctx>, next: BasicBlock<'ctx>) {
+        self.breaks.push((end, self.scopes.len()));
+        self.continues.push((next, self.scopes.len()));
+        self.block(body);
+        self.breaks.pop();
+        self.continues.pop();
+    }
+
+    fn switch_statement(&mut self, condition: &Expression, body: &Block) {
+        let value = self.value(condition).into_int_value();
+        let mut values = Vec::new();
+        let mut has_default = false;
+        collect_cases(&body.statements, &mut values, &mut has_default);
+
+        let end = self.append_block("switch.end");
+        let mut cases = Vec::new();
+        let mut blocks = VecDeque::new();
+        for case_value in values {
+            let constant = folded_integer(case_value).expect("case values are folded");
+            let constant = self.target.wrap_integer(constant, condition.ty());
+            let block = self.append_block("switch.case");
+            cases.push((value.get_type().const_int(constant as u64, true), block));
+            blocks.push_back(block);
+        }
+        let default = has_default.then(|| self.append_block("switch.default"));
+        self.builder.build_switch(value, default.unwrap_or(end), &cases);
+
+        self.switches.push(SwitchLabels { cases: blocks, default });
+        self.breaks.push((end, self.scopes.len()));
+        self.block(body);
+        self.breaks.pop();
+        self.switches.pop();
+        self.branch(end);
+        self.builder.position_at_end(end);
+    }
+
+    fn local_variable(&mut self, variable: &Variable, initializer: Option<&Initializer>) {
+        let address = match variable.storage {
+            // Block-scope `extern` declarations name the file-scope object
+            Some(StorageClass::Extern) => match self.module.get_global(&variable.name) {
+                Some(global) => global.as_pointer_value(),
+                None => self.add_global(variable, &variable.name).as_pointer_value(),
+            },
+            Some(StorageClass::Static) => {
+                let (_, function) = self.function.as_ref().unwrap();
+                let name = format!("{}.{}", function, variable.name);
+                let global = self.add_global(variable, &name);
+                global.set_linkage(Linkage::Internal);
+                // The variable is in scope in its own initializer
+                self.scopes.last_mut().unwrap().insert(variable.name.clone(), global.as_pointer_value());
+                self.define_global(global, variable, initializer);
+                self.module.get_global(&name).unwrap().as_pointer_value()
+            }
+            _ => {
+                self.evaluate_lengths(&variable.var_type);
+                let align = self.variable_align(variable);
+                let address = if variable.var_type.is_variable_length() {
+                    self.variable_length_alloca(&variable.var_type, align, &variable.name)
+                } else {
+                    self.entry_alloca(self.llvm_type(&variable.var_type), align, &variable.name)
+                };
+                self.scopes.last_mut().unwrap().insert(variable.name.clone(), address);
+                if let Some(initializer) = initializer {
+                    self.initialize(address, &variable.var_type, initializer);
+                }
+                address
+            }
+        };
+        self.scopes.last_mut().unwrap().insert(variable.name.clone(), address);
+    }
+
+    /// Evaluate the lengths of the variable length arrays in the type of a
+    /// declaration or type name and store them for the objects and pointers
+    /// of the type. The lengths of an array type named by a typedef were
+    /// evaluated where the typedef was declared.
+    fn evaluate_lengths(&mut self, declared: &Type) {
+        match declared {
+            Type::Pointer(inner) | Type::Array(inner, _) | Type::Qualified(inner, _) => self.evaluate_lengths(inner),
+            Type::VariableArray(_, length) if length.typedef => {}
+            Type::VariableArray(element, length) => {
+                let value = self.value(&length.length);
+                let size_type = self.target.size_type();
+                let slot = self.entry_alloca(self.intptr_type().into(), self.align_of(&size_type), "vla.length");
+                self.builder.build_store(slot, value);
+                self.lengths.insert(length.id, slot);
+                self.evaluate_lengths(element);
+            }
+            _ => {}
+        }
+    }
+
+    /// Allocate a variable length array where its declaration is reached,
+    /// saving the stack pointer first if it is the first in its block
+    fn variable_length_alloca(&mut self, object_type: &Type, align: u32, name: &str) -> PointerValue<'ctx> {
+        let depth = self.scopes.len();
+        if self.saved_stacks.last().is_none_or(|(scope, _)| *scope != depth) {
+            let bytes = self.context.i8_type().ptr_type(AddressSpace::default());
+            let stacksave = self
+                .module
+                .get_function("llvm.stacksave")
+                .unwrap_or_else(|| self.module.add_function("llvm.stacksave", bytes.fn_type(&[], false), None));
+            let call = self.builder.build_call(stacksave, &[], "savedstack");
+            let saved = call.try_as_basic_value().left().unwrap().into_pointer_value();
+            self.saved_stacks.push((depth, saved));
+        }
+        let size = self.runtime_size(object_type);
+        let address = self.builder.build_array_alloca(self.context.i8_type(), size, name);
+        let _ = address.as_instruction_value().unwrap().set_alignment(align);
+        self.cast_pointer(address, object_type)
+    }
+
+    fn restore_stack(&self, saved: PointerValue<'ctx>) {
+        let stackrestore = self.module.get_function("llvm.stackrestore").unwrap_or_else(|| {
+            let function_type = self.context.void_type().fn_type(&[saved.get_type().into()], false);
+            self.module.add_function("llvm.stackrestore", function_type, None)
+        });
+        self.builder.build_call(stackrestore, &[saved.into()], "");
+    }
+
+    /// Jump to a block outside the scopes deeper than `depth`, releasing
+    /// the variable length arrays allocated in them
+    fn jump_out(&self, target: BasicBlock<'ctx>, depth: usize) {
+        if let Some(&(_, saved)) = self.saved_stacks.iter().find(|(scope, _)| *scope > depth) {
+            self.restore_stack(saved);
+        }
+        self.builder.build_unconditional_branch(target);
+    }
+
+    /// Store an initializer into a local object; the parts of an aggregate
+    /// without an initializer are zeroed
+    fn initialize(&mut self, address: PointerValue<'ctx>, object_type: &Type, initializer: &Initializer) {
+        if let Initializer::List(..) = initializer {
+            let size = self.intptr_type().const_int(self.size_of(object_type), false);
+            let zero = self.context.i8_type().const_zero();
+            let bytes = self.cast_pointer(address, &Type::Char);
+            self.builder.build_memset(bytes, self.align_of(object_type), zero, size).expect("valid alignment");
+        }
+        self.initialize_parts(address, object_type, initializer);
+    }
+
+    fn initialize_parts(&mut self, address: PointerValue<'ctx>, object_type: &Type, initializer: &Initializer) {
+        let items = match initializer {
+            Initializer::Expression(value) => {
+                match string_literal(value) {
+                    Some(units) => {
+                        let bytes = self.context.const_string(&self.string_bytes(units, object_type), false);
+                        let address = self.builder.build_pointer_cast(
+                            address,
+                            bytes.get_type().ptr_type(AddressSpace::default()),
+                            "",
+                        );
+                        self.builder.build_store(address, bytes);
+                    }
+                    None => {
+                        let value = self.value(value);
+                        self.store(Lvalue::Address(address), object_type, value);
+                    }
+                }
+                return;
+            }
+            Initializer::List(items, _) => items,
+        };
+        for item in items {
+            match (&item.designators[0], object_type.unqualified()) {
+                (Designator::Index(index), Type::Array(element, _)) => {
+                    let index = self.intptr_type().const_int(*index as u64, false);
+                    let element_address = self.element_address(address, element, index);
+                    self.initialize_parts(element_address, element, &item.initializer);
+                }
+                (Designator::Member(name), _) => {
+                    let (field, offset) = self.member(object_type, name);
+                    let field_type = field.qualified_type();
+                    match self.member_lvalue(address, field, offset) {
+                        Lvalue::Address(member) => self.initialize_parts(member, &field_type, &item.initializer),
+                        bit_field => {
+                            let value = match &item.initializer {
+                                Initializer::Expression(value) => self.value(value),
+                                Initializer::List(..) => unreachable!("bit-fields are scalars"),
+                            };
+                            self.store(bit_field, &field_type, value);
+                        }
+                    }
+                }
+                (designator, other) => unreachable!("{:?} does not designate part of `{}`", designator, other),
+            }
+        }
+    }
+
+    // Control flow
+
+    fn append_block(&self, name: &str) -> BasicBlock<'ctx> {
+        let (function, _) = self.function.as_ref().expect("block outside of a function");
+        self.context.append_basic_block(*function, name)
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.builder.get_insert_block().is_some_and(|block| block.get_terminator().is_some())
+    }
+
+    /// Branch to `target` unless the current block already ends in a jump
+    fn branch(&self, target: BasicBlock<'ctx>) {
+        if !self.is_terminated() {
+            self.builder.build_unconditional_branch(target);
+        }
+    }
+
+    fn label(&mut self, name: &str) -> BasicBlock<'ctx> {
+        if let Some(block) = self.labels.get(name) {
+            return *block;
+        }
+        let block = self.append_block(name);
+        self.labels.insert(name.to_string(), block);
+        block
+    }
+
+    /// Evaluate a scalar as a condition, true when it compares unequal to 0
+    fn condition(&mut self, condition: &Expression) -> IntValue<'ctx> {
+        let value = self.value(condition);
+        self.truth_value(value, condition.ty())
+    }
+
+    fn truth_value(&self, value: BasicValueEnum<'ctx>, scalar: &Type) -> IntValue<'ctx> {
+        match value {
+            BasicValueEnum::IntValue(value) => {
+                self.builder.build_int_compare(IntPredicate::NE, value, value.get_type().const_zero(), "tobool")
+            }
+            BasicValueEnum::FloatValue(value) => {
+                // NaN compares unequal to 0
+                self.builder.build_float_compare(FloatPredicate::UNE, value, value.get_type().const_zero(), "tobool")
+            }
+            BasicValueEnum::PointerValue(value) => self.builder.build_is_not_null(value, "tobool"),
+            _ => unreachable!("`{}` is not a scalar type", scalar),
+        }
+    }
+
+    // Expressions
+
+    /// Evaluate an expression for its value. The value of a `void`
+    /// expression is a placeholder that is never used.
+    fn value(&mut self, expression: &Expression) -> BasicValueEnum<'ctx> {
+        let expr_type = expression.ty();
+        match &expression.kind {
+            ExpressionKind::Literal(Literal::Int(value)) => {
+                self.int_type(expr_type).const_int(*value as u64, true).into()
+            }
+            ExpressionKind::Literal(Literal::Float { digits: Some(digits), .. }) => {
+                self.float_type(expr_type).const_float_from_string(digits).into()
+            }
+            ExpressionKind::Literal(Literal::Float { value, .. }) => self.float_type(expr_type).const_float(*value).into(),
+            ExpressionKind::Literal(Literal::Null) => self.llvm_type(expr_type).const_zero(),
+            ExpressionKind::Literal(Literal::Integer { .. } | Literal::Char(..)) => {
+                unreachable!("constants as written are folded by analysis")
+            }
+            ExpressionKind::Literal(Literal::String(..))
+            | ExpressionKind::Variable(_)
+            | ExpressionKind::Index(..)
+            | ExpressionKind::Member(..)
+            | ExpressionKind::Pointer(_)
+            | ExpressionKind::CompoundLiteral(..) => {
+                let lvalue = self.lvalue(expression);
+                self.load(lvalue, expr_type)
+            }
+            ExpressionKind::ImplicitCast(operand, target) => match operand.ty().unqualified() {
+                // Arrays and functions decay to pointers to their first element
+                // and to themselves
+                Type::Array(..) | Type::VariableArray(..) | Type::Function(..) => {
+                    let address = self.address(operand);
+                    self.builder.build_pointer_cast(address, self.llvm_type(target).into_pointer_type(), "decay").into()
+                }
+                _ => {
+                    let value = self.value(operand);
+                    self.convert(value, operand.ty(), target)
+                }
+            },
+            ExpressionKind::Cast(operand, target) => {
+                self.evaluate_lengths(target);
+                let value = self.value(operand);
+                self.convert(value, operand.ty(), target)
+            }
+            ExpressionKind::BinaryOp(lhs, BinaryOperator::LogicalAnd, rhs) => self.logical(lhs, true, rhs),
+            ExpressionKind::BinaryOp(lhs, BinaryOperator::LogicalOr, rhs) => self.logical(lhs, false, rhs),
+            ExpressionKind::BinaryOp(lhs, operator, rhs) => {
+                let lhs_value = self.value(lhs);
+                let rhs_value = self.value(rhs);
+                self.binary(*operator, (lhs_value, lhs.ty()), (rhs_value, rhs.ty()), expr_type)
+            }
+            ExpressionKind::UnaryOp(operator, operand) => self.unary(*operator, operand),
+            ExpressionKind::Call(callee, arguments) => self.call(callee, arguments, expr_type),
+            ExpressionKind::Address(operand) => {
+                let address = self.address(operand);
+                self.builder.build_pointer_cast(address, self.llvm_type(expr_type).into_pointer_type(), "").into()
+            }
+            ExpressionKind::Assignment(target, value) => {
+                let value = self.value(value);
+                let lvalue = self.lvalue(target);
+                self.store(lvalue, target.ty(), value)
+            }
+            ExpressionKind::CompoundAssignment(target, operator, value, computation) => {
+                let computation = computation.as_ref().expect("computation type is set by analysis");
+                let rhs = self.value(value);
+                let lvalue = self.lvalue(target);
+                let target_type = target.ty().unqualified();
+                let (_, new) = self.update(lvalue, target.ty(), |generator, old| {
+                    let old = generator.convert(old, target_type, computation);
+                    let result = generator.binary(*operator, (old, computation), (rhs, value.ty()), computation);
+                    generator.convert(result, computation, target_type)
+                });
+                new
+            }
+            ExpressionKind::Conditional(condition, then, otherwise) => self.conditional(condition, then, otherwise),
+            ExpressionKind::Comma(lhs, rhs) => {
+                self.value(lhs);
+                self.value(rhs)
+            }
+            // Only the size of a variable length array is left to compute, and
+            // the array is then evaluated
+            ExpressionKind::SizeOf(operand) => {
+                self.lvalue(operand);
+                self.runtime_size(operand.ty()).into()
+            }
+            ExpressionKind::SizeOfType(operand) => {
+                self.evaluate_lengths(operand);
+                self.runtime_size(operand).into()
+            }
+            ExpressionKind::AlignOf(_) => unreachable!("`_Alignof` is folded by analysis"),
+            ExpressionKind::Generic(..) => unreachable!("generic selections are replaced by analysis"),
+            ExpressionKind::VaStart(list) => self.va_intrinsic("llvm.va_start", &[list]),
+            ExpressionKind::VaArg(list, arg_type) => {
+                let address = self.va_arg(list, arg_type);
+                self.load(Lvalue::Address(address), arg_type)
+            }
+            ExpressionKind::VaEnd(list) => self.va_intrinsic("llvm.va_end", &[list]),
+            ExpressionKind::VaCopy(destination, source) => self.va_intrinsic("llvm.va_copy", &[destination, source]),
+        }
+    }
+
+    /// Call a function, passing the arguments and taking the returned value
+    /// as the ABI says
+    fn call(&mut self, callee: &Expression, arguments: &[Expression], return_type: &Type) -> BasicValueEnum<'ctx> {
+        // A function named directly is called directly, anything else
+        // through the pointer the callee evaluates to. C leaves the order the
+        // callee and the arguments are evaluated in unspecified.
+        let function = match &callee.kind {
+            ExpressionKind::ImplicitCast(operand, _) => match &operand.kind {
+                ExpressionKind::Variable(name) if matches!(operand.ty(), Type::Function(..)) => {
+                    self.module.get_function(name)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let return_type = return_type.unqualified();
+        let parameters: Vec<Type> = arguments.iter().map(|argument| argument.ty().unqualified().clone()).collect();
+        let abi = self.abi(return_type, &parameters);
+
+        let mut values: Vec<BasicMetadataValueEnum> = Vec::new();
+        let return_slot = match abi.return_mode {
+            PassMode::Memory => Some(self.alloca(return_type, "agg.result")),
+            _ => None,
+        };
+        values.extend(return_slot.map(BasicMetadataValueEnum::from));
+        for ((argument, parameter), mode) in arguments.iter().zip(&parameters).zip(&abi.parameters) {
+            if *mode == PassMode::Shared {
+                values.push(self.address_or_temporary(argument).into());
+                continue;
+            }
+            let value = self.value(argument);
+            match mode {
+                PassMode::Value(_) => values.push(value.into()),
+                PassMode::Ignore => {}
+                // The callee may modify its copy
+                PassMode::Memory | PassMode::Reference => {
+                    let copy = self.alloca(parameter, "agg.arg");
+                    self.builder.build_store(copy, value);
+                    values.push(copy.into());
+                }
+                mode => {
+                    let temporary = self.abi_temporary(parameter, mode, "agg.arg");
+                    self.builder.build_store(temporary, value);
+                    values.extend(
+                        self.load_abi(temporary, mode, parameter).into_iter().map(BasicMetadataValueEnum::from),
+                    );
+                }
+            }
+        }
+        let call = match function {
+            Some(function) => self.builder.build_call(function, &values, "call"),
+            None => {
+                let Some(Type::Function(_, declared, is_variadic)) = callee.ty().pointee() else {
+                    unreachable!("callee is a pointer to a function")
+                };
+                let function_type = self.function_type(return_type, declared, *is_variadic);
+                let pointer = self.value(callee).into_pointer_value();
+                let pointer =
+                    self.builder.build_pointer_cast(pointer, function_type.ptr_type(AddressSpace::default()), "callee");
+                self.builder.build_indirect_call(function_type, pointer, &values, "call")
+            }
+        };
+        for (location, attribute) in self.abi_attributes(return_type, &parameters, &abi) {
+            call.add_attribute(location, attribute);
+        }
+
+        let result = call.try_as_basic_value().left();
+        match (&abi.return_mode, result) {
+            (PassMode::Value(_), Some(result)) => result,
+            (PassMode::Memory, _) => self.load(Lvalue::Address(return_slot.unwrap()), return_type),
+            (mode @ (PassMode::Direct(_) | PassMode::Consecutive(..)), Some(result)) => {
+                let pieces = match (mode, result) {
+                    (PassMode::Direct(registers), BasicValueEnum::StructValue(pieces)) if registers.len() > 1 => (0
+                        ..registers.len())
+                        .map(|index| self.builder.build_extract_value(pieces, index as u32, "").unwrap())
+                        .collect(),
+                    _ => vec![result],
+                };
+                let temporary = self.abi_temporary(return_type, mode, "agg.result");
+                self.store_abi(temporary, mode, &pieces);
+                self.load(Lvalue::Address(temporary), return_type)
+            }
+            _ if *return_type == Type::Void => self.void_value(),
+            // An empty struct
+            _ => self.llvm_type(return_type).const_zero(),
+        }
+    }
+
+    /// Return a value from the function being lowered as the ABI says
+    fn return_value(&mut self, value: BasicValueEnum<'ctx>, value_type: &Type) {
+        match self.return_mode.clone() {
+            PassMode::Value(_) => {
+                self.builder.build_return(Some(&value));
+            }
+            PassMode::Memory => {
+                let slot = self.cast_pointer(self.return_slot.unwrap(), value_type);
+                self.builder.build_store(slot, value);
+                self.builder.build_return(None);
+            }
+            PassMode::Direct(_) | PassMode::Consecutive(..) => {
+                let mode = self.return_mode.clone();
+                let temporary = self.abi_temporary(value_type, &mode, "retval");
+                self.builder.build_store(temporary, value);
+                match self.load_abi(temporary, &mode, value_type).as_slice() {
+                    [value] => self.builder.build_return(Some(value)),
+                    pieces => self.builder.build_aggregate_return(pieces),
+                };
+            }
+            PassMode::Ignore | PassMode::Reference | PassMode::Shared => {
+                self.builder.build_return(None);
+            }
+        }
+    }
+
+    fn void_value(&self) -> BasicValueEnum<'ctx> {
+        self.context.i8_type().get_undef().into()
+    }
+
+    // Variable arguments
+
+    /// Call one of the `llvm.va_*` intrinsics on the addresses of `va_list`
+    /// objects
+    fn va_intrinsic(&mut self, name: &str, lists: &[&Expression]) -> BasicValueEnum<'ctx> {
+        let bytes = self.context.i8_type().ptr_type(AddressSpace::default());
+        let intrinsic = self.module.get_function(name).unwrap_or_else(|| {
+            let parameters = vec![BasicMetadataTypeEnum::from(bytes); lists.len()];
+            self.module.add_function(name, self.context.void_type().fn_type(&parameters, false), None)
+        });
+        let arguments: Vec<BasicMetadataValueEnum> = lists
+            .iter()
+            .map(|list| {
+                let address = self.address(list);
+                self.builder.build_pointer_cast(address, bytes, "").into()
+            })
+            .collect();
+        self.builder.build_call(intrinsic, &arguments, "");
+        self.void_value()
+    }
+
+    /// The address of the next variable argument in a `va_list`, which is
+    /// then advanced past it. LLVM's `va_arg` instruction is incomplete on
+    /// most targets, so this follows each ABI's `va_list` layout instead.
+    fn va_arg(&mut self, list: &Expression, arg_type: &Type) -> PointerValue<'ctx> {
+        let list = self.address(list);
+        let mode = self.abi(&Type::Void, std::slice::from_ref(arg_type)).parameters.remove(0);
+        let address = match self.target.va_list {
+            VaList::Pointer => self.va_arg_pointer(list, arg_type, &mode),
+            VaList::SysV => self.va_arg_sysv(list, arg_type, &mode),
+            VaList::Aapcs64 => self.va_arg_aapcs64(list, arg_type, &mode),
+        };
+        self.cast_pointer(address, arg_type)
+    }
+
+    /// `va_arg` where `va_list` points to the next argument, each in slots
+    /// as wide as a pointer
+    fn va_arg_pointer(&mut self, list: PointerValue<'ctx>, arg_type: &Type, mode: &PassMode) -> PointerValue<'ctx> {
+        let bytes = self.context.i8_type().ptr_type(AddressSpace::default());
+        let slot = self.size_of(&Type::Pointer(Box::new(Type::Void)));
+        let list = self.cast_pointer(list, &Type::Pointer(Box::new(Type::Char)));
+        let current = self.builder.build_load(bytes, list, "argp").into_pointer_value();
+        let (address, size) = if *mode == PassMode::Reference {
+            (current, slot)
+        } else {
+            // Only 64-bit targets align arguments beyond their slots
+            let align = if slot == 8 { (self.align_of(arg_type) as u64).max(slot) } else { slot };
+            (self.align_pointer(current, align), self.size_of(arg_type))
+        };
+        let next = self.offset_pointer(address, self.intptr_type().const_int(size.div_ceil(slot) * slot, false));
+        self.builder.build_store(list, next);
+        self.va_indirect(address, mode)
+    }
+
+    /// `va_arg` on x86-64 System V, taking the argument from the register
+    /// save area while enough registers of its classes are left, or else from
+    /// the stack
+    fn va_arg_sysv(&mut self, list: PointerValue<'ctx>, arg_type: &Type, mode: &PassMode) -> PointerValue<'ctx> {
+        let (integers, vectors) = match mode {
+            PassMode::Value(_) if arg_type.is_floating() && *arg_type.unqualified() != Type::LongDouble => (0, 1),
+            PassMode::Value(_) if !arg_type.is_floating() => (1, 0),
+            PassMode::Direct(registers) => {
+                let integers = registers.iter().filter(|register| matches!(register, Register::Integer(_))).count();
+                (integers as u64, (registers.len() - integers) as u64)
+            }
+            _ => (0, 0),
+        };
+        let bytes = self.context.i8_type().ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let end = self.append_block("vaarg.end");
+        let mut incoming = Vec::new();
+
+        if integers + vectors > 0 {
+            let gp_offset_address = self.byte_address(list, 0, i32_type.into());
+            let fp_offset_address = self.byte_address(list, 4, i32_type.into());
+            let gp_offset = self.builder.build_load(i32_type, gp_offset_address, "gp_offset").into_int_value();
+            let fp_offset = self.builder.build_load(i32_type, fp_offset_address, "fp_offset").into_int_value();
+            // Six integer registers of 8 bytes, then eight vector registers
+            // of 16 bytes
+            let fits = |generator: &Self, offset, limit| {
+                let limit = i32_type.const_int(limit, false);
+                generator.builder.build_int_compare(IntPredicate::ULE, offset, limit, "fits")
+            };
+            let mut in_registers = self.context.bool_type().const_int(1, false);
+            if integers > 0 {
+                let fits = fits(self, gp_offset, 48 - 8 * integers);
+                in_registers = self.builder.build_and(in_registers, fits, "");
+            }
+            if vectors > 0 {
+                let fits = fits(self, fp_offset, 176 - 16 * vectors);
+                in_registers = self.builder.build_and(in_registers, fits, "");
+            }
+            let register_block = self.append_block("vaarg.in_reg");
+            let stack_block = self.append_block("vaarg.in_mem");
+            self.builder.build_conditional_branch(in_registers, register_block, stack_block);
+
+            self.builder.position_at_end(register_block);
+            let save_area_address = self.byte_address(list, 16, bytes.into());
+            let save_area = self.builder.build_load(bytes, save_area_address, "reg_save_area").into_pointer_value();
+            let gp_address = self.offset_pointer(save_area, gp_offset);
+            let fp_address = self.offset_pointer(save_area, fp_offset);
+            let address = match mode {
+                PassMode::Direct(registers) => {
+                    // The pieces are not adjacent in the save area
+                    let temporary = self.abi_temporary(arg_type, mode, "vaarg.tmp");
+                    let (mut gp_address, mut fp_address) = (gp_address, fp_address);
+                    for (index, register) in registers.iter().enumerate() {
+                        let register_type = self.register_type(*register);
+                        let (source, step) = match register {
+                            Register::Integer(_) => (&mut gp_address, 8),
+                            _ => (&mut fp_address, 16),
+                        };
+                        let piece_address = self.builder.build_pointer_cast(
+                            *source,
+                            register_type.ptr_type(AddressSpace::default()),
+                            "",
+                        );
+                        let piece = self.builder.build_load(register_type, piece_address, "");
+                        self.builder.build_store(self.byte_address(temporary, index as u64 * 8, register_type), piece);
+                        *source = self.offset_pointer(*source, self.intptr_type().const_int(step, false));
+                    }
+                    self.builder.build_pointer_cast(temporary, bytes, "")
+                }
+                _ if integers > 0 => gp_address,
+                _ => fp_address,
+            };
+            if integers > 0 {
+                let next = self.builder.build_int_add(gp_offset, i32_type.const_int(8 * integers, false), "");
+                self.builder.build_store(gp_offset_address, next);
+            }
+            if vectors > 0 {
+                let next = self.builder.build_int_add(fp_offset, i32_type.const_int(16 * vectors, false), "");
+                self.builder.build_store(fp_offset_address, next);
+            }
+            incoming.push((address, self.builder.get_insert_block().unwrap()));
+            self.builder.build_unconditional_branch(end);
+            self.builder.position_at_end(stack_block);
+        }
+
+        let overflow_address = self.byte_address(list, 8, bytes.into());
+        let overflow = self.builder.build_load(bytes, overflow_address, "overflow_arg_area").into_pointer_value();
+        let address = self.align_pointer(overflow, (self.align_of(arg_type) as u64).max(8));
+        let size = self.size_of(arg_type).div_ceil(8) * 8;
+        let next = self.offset_pointer(address, self.intptr_type().const_int(size, false));
+        self.builder.build_store(overflow_address, next);
+        incoming.push((address, self.builder.get_insert_block().unwrap()));
+        self.builder.build_unconditional_branch(end);
+        self.merge_addresses(end, &incoming)
+    }
+
+    /// `va_arg` on AArch64, taking the argument from the general or vector
+    /// register save area, whose negative offsets count up to zero as
+    /// registers are used, or else from the stack
+    fn va_arg_aapcs64(&mut self, list: PointerValue<'ctx>, arg_type: &Type, mode: &PassMode) -> PointerValue<'ctx> {
+        // The register class, and how many registers of it the argument takes
+        let (vector, count) = match mode {
+            PassMode::Value(_) => (arg_type.is_floating(), 1),
+            PassMode::Consecutive(Register::Integer(_), count) => (false, *count as u64),
+            PassMode::Consecutive(_, count) => (true, *count as u64),
+            PassMode::Direct(_) | PassMode::Reference => (false, 1),
+            _ => (false, 0),
+        };
+        let bytes = self.context.i8_type().ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let end = self.append_block("vaarg.end");
+        let mut incoming = Vec::new();
+
+        if count > 0 {
+            let (top_offset, offsets_offset, step) = if vector { (16, 28, 16) } else { (8, 24, 8) };
+            let offset_address = self.byte_address(list, offsets_offset, i32_type.into());
+            let mut offset = self.builder.build_load(i32_type, offset_address, "offs").into_int_value();
+            let zero = i32_type.const_zero();
+            let maybe_register_block = self.append_block("vaarg.maybe_reg");
+            let register_block = self.append_block("vaarg.in_reg");
+            let stack_block = self.append_block("vaarg.on_stack");
+            // A non-negative offset means the registers were used up
+            let exhausted = self.builder.build_int_compare(IntPredicate::SGE, offset, zero, "");
+            self.builder.build_conditional_branch(exhausted, stack_block, maybe_register_block);
+
+            self.builder.position_at_end(maybe_register_block);
+            if !vector && self.align_of(arg_type) == 16 {
+                // Quad-word aligned arguments start at an even register
+                let rounded = self.builder.build_int_add(offset, i32_type.const_int(15, false), "");
+                offset = self.builder.build_and(rounded, i32_type.const_int(-16i64 as u64, true), "");
+            }
+            let next = self.builder.build_int_add(offset, i32_type.const_int(step * count, false), "");
+            self.builder.build_store(offset_address, next);
+            let fits = self.builder.build_int_compare(IntPredicate::SLE, next, zero, "");
+            self.builder.build_conditional_branch(fits, register_block, stack_block);
+
+            self.builder.position_at_end(register_block);
+            let top_address = self.byte_address(list, top_offset, bytes.into());
+            let top = self.builder.build_load(bytes, top_address, "reg_top").into_pointer_value();
+            let offset = self.builder.build_int_s_extend(offset, self.intptr_type(), "");
+            let mut address = self.offset_pointer(top, offset);
+            if let PassMode::Consecutive(register, count) = mode {
+                if vector && *count > 1 {
+                    // Each member of a homogeneous aggregate has a whole
+                    // vector register to itself
+                    let register_type = self.register_type(*register);
+                    let member_size = match register {
+                        Register::Float => 4,
+                        Register::Double => 8,
+                        _ => 16,
+                    };
+                    let temporary = self.alloca(arg_type, "vaarg.tmp");
+                    for index in 0..*count as u64 {
+                        let source = self.byte_address(address, index * 16, register_type);
+                        let member = self.builder.build_load(register_type, source, "");
+                        self.builder
+                            .build_store(self.byte_address(temporary, index * member_size, register_type), member);
+                    }
+                    address = self.builder.build_pointer_cast(temporary, bytes, "");
+                }
+            }
+            incoming.push((address, self.builder.get_insert_block().unwrap()));
+            self.builder.build_unconditional_branch(end);
+            self.builder.position_at_end(stack_block);
+        }
+
+        let stack_address = self.byte_address(list, 0, bytes.into());
+        let stack = self.builder.build_load(bytes, stack_address, "stack").into_pointer_value();
+        let (align, size) = match mode {
+            PassMode::Reference => (8, 8),
+            _ => ((self.align_of(arg_type) as u64).max(8), self.size_of(arg_type).div_ceil(8) * 8),
+        };
+        let address = self.align_pointer(stack, align);
+        let next = self.offset_pointer(address, self.intptr_type().const_int(size, false));
+        self.builder.build_store(stack_address, next);
+        incoming.push((address, self.builder.get_insert_block().unwrap()));
+        self.builder.build_unconditional_branch(end);
+        let address = self.merge_addresses(end, &incoming);
+        self.va_indirect(address, mode)
+    }
+
+    /// Continue at `end` with the byte address reached from each block
+    fn merge_addresses(
+        &self,
+        end: BasicBlock<'ctx>,
+        incoming: &[(PointerValue<'ctx>, BasicBlock<'ctx>)],
+    ) -> PointerValue<'ctx> {
+        self.builder.position_at_end(end);
+        let phi = self.builder.build_phi(self.context.i8_type().ptr_type(AddressSpace::default()), "vaarg.addr");
+        for (address, block) in incoming {
+            phi.add_incoming(&[(address, *block)]);
+        }
+        phi.as_basic_value().into_pointer_value()
+    }
+
+    /// The address of an argument passed by reference, from the slot that
+    /// holds it
+    fn va_indirect(&self, address: PointerValue<'ctx>, mode: &PassMode) -> PointerValue<'ctx> {
+        if *mode != PassMode::Reference {
+            return address;
+        }
+        let bytes = self.context.i8_type().ptr_type(AddressSpace::default());
+        let slot = self.builder.build_pointer_cast(address, bytes.ptr_type(AddressSpace::default()), "");
+        self.builder.build_load(bytes, slot, "indirect").into_pointer_value()
+    }
+
+    /// `pointer`, a byte address, moved `offset` bytes
+    fn offset_pointer(&self, pointer: PointerValue<'ctx>, offset: IntValue<'ctx>) -> PointerValue<'ctx> {
+        unsafe { self.builder.build_gep(self.context.i8_type(), pointer, &[offset], "") }
+    }
+
+    /// `pointer`, a byte address, rounded up to a multiple of `align`
+    fn align_pointer(&self, pointer: PointerValue<'ctx>, align: u64) -> PointerValue<'ctx> {
+        let intptr_type = self.intptr_type();
+        let integer = self.builder.build_ptr_to_int(pointer, intptr_type, "");
+        let rounded = self.builder.build_int_add(integer, intptr_type.const_int(align - 1, false), "");
+        let aligned = self.builder.build_and(rounded, intptr_type.const_int(!(align - 1), false), "");
+        self.builder.build_int_to_ptr(aligned, pointer.get_type(), "")
+    }
+
+    /// The object an lvalue expression designates
+    fn lvalue(&mut self, expression: &Expression) -> Lvalue<'ctx> {
+        let address = match &expression.kind {
+            ExpressionKind::Variable(name) => {
+                let address = self.variable(name);
+                match expression.ty() {
+                    Type::Function(..) => address,
+                    object_type => self.cast_pointer(address, object_type),
+                }
+            }
+            ExpressionKind::Literal(Literal::String(_, units)) => self.string_literal(units, expression.ty()),
+            ExpressionKind::Index(base, index) => {
+                let (pointer, offset) = if base.ty().pointee().is_some() { (base, index) } else { (index, base) };
+                let element = pointer.ty().pointee().unwrap().clone();
+                let pointer_value = self.value(pointer).into_pointer_value();
+                let offset_value = self.value(offset).into_int_value();
+                let offset_value = self.index_value(offset_value, offset.ty());
+                self.element_address(pointer_value, &element, offset_value)
+            }
+            ExpressionKind::Pointer(operand) => {
+                let pointer = self.value(operand).into_pointer_value();
+                match expression.ty() {
+                    Type::Function(..) => pointer,
+                    object_type => self.cast_pointer(pointer, object_type),
+                }
+            }
+            ExpressionKind::Member(base, name) => {
+                let base_address = self.address_or_temporary(base);
+                let (field, offset) = self.member(base.ty(), name);
+                return self.member_lvalue(base_address, field, offset);
+            }
+            ExpressionKind::CompoundLiteral(literal_type, initializer) => match self.function {
+                Some(_) => {
+                    let address = self.alloca(literal_type, "compoundliteral");
+                    self.initialize(address, literal_type, initializer);
+                    address
+                }
+                // Compound literals outside a function have static storage
+                None => {
+                    let variable = Variable {
+                        name: String::new(),
+                        var_type: literal_type.clone(),
+                        qualifiers: literal_type.qualifiers(),
+                        storage: Some(StorageClass::Static),
+                        is_thread_local: false,
+                        alignment: None,
+                        span: expression.span,
+                    };
+                    let global = self.add_global(&variable, ".compoundliteral");
+                    global.set_linkage(Linkage::Private);
+                    let name = global.get_name().to_string_lossy().into_owned();
+                    self.define_global(global, &variable, Some(initializer));
+                    let global = self.module.get_global(&name).unwrap();
+                    self.cast_pointer(global.as_pointer_value(), literal_type)
+                }
+            },
+            _ => unreachable!("not an lvalue"),
+        };
+        Lvalue::Address(address)
+    }
+
+    fn address(&mut self, expression: &Expression) -> PointerValue<'ctx> {
+        match self.lvalue(expression) {
+            Lvalue::Address(address) => address,
+            Lvalue::BitField { .. } => unreachable!("bit-fields have no address"),
+        }
+    }
+
+    /// The address of a struct or union, storing one that is the value of a
+    /// call, assignment or similar in a temporary first
+    fn address_or_temporary(&mut self, record: &Expression) -> PointerValue<'ctx> {
+        if is_addressable(record) {
+            return self.address(record);
+        }
+        let value = self.value(record);
+        let temporary = self.alloca(record.ty(), "tmp");
+        self.builder.build_store(temporary, value);
+        temporary
+    }
+
+    /// The address of a variable, local or global, or of a function
+    fn variable(&self, name: &str) -> PointerValue<'ctx> {
+        if let Some(address) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return *address;
+        }
+        if let Some(global) = self.module.get_global(name) {
+            return global.as_pointer_value();
+        }
+        let function = self.module.get_function(name).expect("undeclared name survived analysis");
+        function.as_global_value().as_pointer_value()
+    }
+
+    fn member_lvalue(&self, record: PointerValue<'ctx>, field: &Field, offset: u64) -> Lvalue<'ctx> {
+        let bytes = self.cast_pointer(record, &Type::Char);
+        match field.bit_width {
+            None => {
+                let offset = self.intptr_type().const_int(offset / 8, false);
+                let member = unsafe { self.builder.build_gep(self.context.i8_type(), bytes, &[offset], "member") };
+                Lvalue::Address(self.cast_pointer(member, &field.var_type))
+            }
+            Some(width) => {
+                // The storage unit is an object of the declared type that the
+                // bit-field lies within
+                let unit_bits = self.size_of(&field.var_type) * 8;
+                let unit_offset = offset / unit_bits * unit_bits / 8;
+                let unit_type = self.context.custom_width_int_type(unit_bits as u32);
+                let index = self.intptr_type().const_int(unit_offset, false);
+                let unit = unsafe { self.builder.build_gep(self.context.i8_type(), bytes, &[index], "bitfield") };
+                let unit = self.builder.build_pointer_cast(unit, unit_type.ptr_type(AddressSpace::default()), "");
+                Lvalue::BitField { unit, unit_type, offset: (offset % unit_bits) as u32, width }
+            }
+        }
+    }
+
+    fn cast_pointer(&self, pointer: PointerValue<'ctx>, pointee: &Type) -> PointerValue<'ctx> {
+        self.builder.build_pointer_cast(pointer, self.pointer_to(pointee), "")
+    }
+
+    /// The address `index` elements after `pointer`
+    fn element_address(
+        &self,
+        pointer: PointerValue<'ctx>,
+        element: &Type,
+        index: IntValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        if element.is_variable_length() {
+            // The elements are a run-time size apart
+            let offset = self.builder.build_int_mul(index, self.runtime_size(element), "");
+            let bytes = self.cast_pointer(pointer, &Type::Char);
+            let address = unsafe { self.builder.build_gep(self.context.i8_type(), bytes, &[offset], "element") };
+            return self.cast_pointer(address, element);
+        }
+        let pointer = self.cast_pointer(pointer, element);
+        unsafe { self.builder.build_gep(self.llvm_type(element), pointer, &[index], "element") }
+    }
+
+    /// Widen an integer to the width of a pointer to use it as an index
+    fn index_value(&self, index: IntValue<'ctx>, index_type: &Type) -> IntValue<'ctx> {
+        let signed = self.target.is_signed(index_type.unqualified());
+        self.builder.build_int_cast_sign_flag(index, self.intptr_type(), signed, "idx")
+    }
+
+    /// Read the value of an lvalue
+    fn load(&self, lvalue: Lvalue<'ctx>, object_type: &Type) -> BasicValueEnum<'ctx> {
+        let qualifiers = object_type.qualifiers();
+        match lvalue {
+            Lvalue::Address(address) => {
+                let address = self.cast_pointer(address, object_type);
+                let value = self.builder.build_load(self.llvm_type(object_type), address, "load");
+                let instruction = value.as_instruction_value().unwrap();
+                let _ = instruction.set_alignment(self.align_of(object_type));
+                if qualifiers.is_volatile {
+                    let _ = instruction.set_volatile(true);
+                }
+                if qualifiers.is_atomic {
+                    let _ = instruction.set_atomic_ordering(AtomicOrdering::SequentiallyConsistent);
+                }
+                value
+            }
+            Lvalue::BitField { unit, unit_type, offset, width } => {
+                let value = self.builder.build_load(unit_type, unit, "bitfield").into_int_value();
+                if qualifiers.is_volatile {
+                    let _ = value.as_instruction_value().unwrap().set_volatile(true);
+                }
+                // Shift the field to the top of the unit, then back down with
+                // sign extension for signed fields
+                let unit_bits = unit_type.get_bit_width();
+                let up = unit_type.const_int((unit_bits - offset - width) as u64, false);
+                let down = unit_type.const_int((unit_bits - width) as u64, false);
+                let value = self.builder.build_left_shift(value, up, "");
+                let signed = self.target.is_signed(object_type.unqualified());
+                let value = self.builder.build_right_shift(value, down, signed, "");
+                self.builder.build_int_cast_sign_flag(value, self.int_type(object_type), signed, "").into()
+            }
+        }
+    }
+
+    /// Write a value to an lvalue, returning the value the lvalue then has
+    fn store(&self, lvalue: Lvalue<'ctx>, object_type: &Type, value: BasicValueEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        let qualifiers = object_type.qualifiers();
+        match lvalue {
+            Lvalue::Address(address) => {
+                let address = self.cast_pointer(address, object_type);
+                let instruction = self.builder.build_store(address, value);
+                let _ = instruction.set_alignment(self.align_of(object_type));
+                if qualifiers.is_volatile {
+                    let _ = instruction.set_volatile(true);
+                }
+                if qualifiers.is_atomic {
+                    let _ = instruction.set_atomic_ordering(AtomicOrdering::SequentiallyConsistent);
+                }
+                value
+            }
+            Lvalue::BitField { unit, unit_type, offset, width } => {
+                let signed = self.target.is_signed(object_type.unqualified());
+                let bits = self.builder.build_int_cast_sign_flag(value.into_int_value(), unit_type, signed, "");
+                let field_mask = ((1u128 << width) - 1) as u64;
+                let mask = unit_type.const_int(field_mask, false);
+                let bits = self.builder.build_and(bits, mask, "");
+                let shifted = self.builder.build_left_shift(bits, unit_type.const_int(offset as u64, false), "");
+                let old = self.builder.build_load(unit_type, unit, "bitfield").into_int_value();
+                let keep = unit_type.const_int(!(field_mask << offset), false);
+                let kept = self.builder.build_and(old, keep, "");
+                let new = self.builder.build_or(kept, shifted, "");
+                let instruction = self.builder.build_store(unit, new);
+                if qualifiers.is_volatile {
+                    let _ = old.as_instruction_value().unwrap().set_volatile(true);
+                    let _ = instruction.set_volatile(true);
+                }
+                // The value of the field is the stored value cut to its width
+                self.load(Lvalue::BitField { unit, unit_type, offset, width }, object_type)
+            }
+        }
+    }
+
+    /// Replace the value of an lvalue by a function of its old value,
+    /// returning both; an atomic object is updated in a compare-and-swap loop
+    fn update(
+        &mut self,
+        lvalue: Lvalue<'ctx>,
+        object_type: &Type,
+        mut function: impl FnMut(&mut Self, BasicValueEnum<'ctx>) -> BasicValueEnum<'ctx>,
+    ) -> (BasicValueEnum<'ctx>, BasicValueEnum<'ctx>) {
+        let address = match lvalue {
+            Lvalue::Address(address) if object_type.qualifiers().is_atomic => address,
+            _ => {
+                let old = self.load(lvalue, object_type);
+                let new = function(self, old);
+                let new = self.store(lvalue, object_type, new);
+                return (old, new);
+            }
+        };
+
+        // The exchange works on integers and pointers, so floating values are
+        // exchanged as integers of the same size
+        let value_type = self.llvm_type(object_type);
+        let exchange_type: BasicTypeEnum = match value_type {
+            BasicTypeEnum::FloatType(_) => {
+                self.context.custom_width_int_type(self.size_of(object_type) as u32 * 8).into()
+            }
+            other => other,
+        };
+        let address = self.builder.build_pointer_cast(address, exchange_type.ptr_type(AddressSpace::default()), "");
+        let initial = self.builder.build_load(exchange_type, address, "atomic");
+        let instruction = initial.as_instruction_value().unwrap();
+        let _ = instruction.set_alignment(self.align_of(object_type));
+        let _ = instruction.set_atomic_ordering(AtomicOrdering::SequentiallyConsistent);
+
+        let entry = self.builder.get_insert_block().unwrap();
+        let loop_bb = self.append_block("atomic.loop");
+        let end = self.append_block("atomic.end");
+        self.builder.build_unconditional_branch(loop_bb);
+        self.builder.position_at_end(loop_bb);
+        let expected = self.builder.build_phi(exchange_type, "expected");
+        expected.add_incoming(&[(&initial, entry)]);
+        let old = self.builder.build_bitcast(expected.as_basic_value(), value_type, "");
+        let new = function(self, old);
+        let desired = self.builder.build_bitcast(new, exchange_type, "");
+        let ordering = AtomicOrdering::SequentiallyConsistent;
+        let result = self
+            .builder
+            .build_cmpxchg(address, expected.as_basic_value(), desired, ordering, ordering)
+            .expect("valid compare-and-swap");
+        let actual = self.builder.build_extract_value(result, 0, "").unwrap();
+        let success = self.builder.build_extract_value(result, 1, "").unwrap().into_int_value();
+        expected.add_incoming(&[(&actual, self.builder.get_insert_block().unwrap())]);
+        self.builder.build_conditional_branch(success, end, loop_bb);
+        self.builder.position_at_end(end);
+        (old, new)
+    }
+
+    /// Convert a value between scalar types, or pass through a struct or
+    /// union value
+    fn convert(&self, value: BasicValueEnum<'ctx>, from: &Type, to: &Type) -> BasicValueEnum<'ctx> {
+        let (from, to) = (from.unqualified(), to.unqualified());
+        if *to == Type::Void || from == to || !to.is_scalar() {
+            return value;
+        }
+        if *to == Type::Bool {
+            let truth = self.truth_value(value, from);
+            return self.builder.build_int_z_extend(truth, self.int_type(to), "").into();
+        }
+        match value {
+            BasicValueEnum::IntValue(integer) => {
+                let signed = self.target.is_signed(from);
+                if to.is_integer() {
+                    self.builder.build_int_cast_sign_flag(integer, self.int_type(to), signed, "conv").into()
+                } else if to.is_floating() && signed {
+                    self.builder.build_signed_int_to_float(integer, self.float_type(to), "conv").into()
+                } else if to.is_floating() {
+                    self.builder.build_unsigned_int_to_float(integer, self.float_type(to), "conv").into()
+                } else {
+                    let integer = self.builder.build_int_cast_sign_flag(integer, self.intptr_type(), signed, "");
+                    self.builder.build_int_to_ptr(integer, self.llvm_type(to).into_pointer_type(), "conv").into()
+                }
+            }
+            BasicValueEnum::FloatValue(floating) => {
+                if to.is_floating() {
+                    self.builder.build_float_cast(floating, self.float_type(to), "conv").into()
+                } else if self.target.is_signed(to) {
+                    self.builder.build_float_to_signed_int(floating, self.int_type(to), "conv").into()
+                } else {
+                    self.builder.build_float_to_unsigned_int(floating, self.int_type(to), "conv").into()
+                }
+            }
+            BasicValueEnum::PointerValue(pointer) => {
+                if to.is_integer() {
+                    self.builder.build_ptr_to_int(pointer, self.int_type(to), "conv").into()
+                } else {
+                    self.builder.build_pointer_cast(pointer, self.llvm_type(to).into_pointer_type(), "conv").into()
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Apply a binary operator other than `&&` and `||` to operands of the
+    /// types analysis has converted them to
+    fn binary(
+        &self,
+        operator: BinaryOperator,
+        (lhs, lhs_type): (BasicValueEnum<'ctx>, &Type),
+        (rhs, rhs_type): (BasicValueEnum<'ctx>, &Type),
+        result_type: &Type,
+    ) -> BasicValueEnum<'ctx> {
+        let (lhs_type, rhs_type) = (lhs_type.unqualified(), rhs_type.unqualified());
+        // Pointer arithmetic
+        match (lhs, rhs, operator) {
+            (BasicValueEnum::PointerValue(pointer), BasicValueEnum::IntValue(offset), BinaryOperator::Add)
+            | (BasicValueEnum::IntValue(offset), BasicValueEnum::PointerValue(pointer), BinaryOperator::Add) => {
+                let (pointer_type, offset_type) =
+                    if lhs.is_pointer_value() { (lhs_type, rhs_type) } else { (rhs_type, lhs_type) };
+                let offset = self.index_value(offset, offset_type);
+                return self.element_address(pointer, pointer_type.pointee().unwrap(), offset).into();
+            }
+            (BasicValueEnum::PointerValue(pointer), BasicValueEnum::IntValue(offset), BinaryOperator::Subtract) => {
+                let offset = self.index_value(offset, rhs_type);
+                let offset = self.builder.build_int_neg(offset, "");
+                return self.element_address(pointer, lhs_type.pointee().unwrap(), offset).into();
+            }
+            (BasicValueEnum::PointerValue(left), BasicValueEnum::PointerValue(right), BinaryOperator::Subtract) => {
+                let left = self.builder.build_ptr_to_int(left, self.intptr_type(), "");
+                let right = self.builder.build_ptr_to_int(right, self.intptr_type(), "");
+                let bytes = self.builder.build_int_sub(left, right, "");
+                let pointee = lhs_type.pointee().unwrap();
+                let size = if pointee.is_variable_length() {
+                    self.runtime_size(pointee)
+                } else {
+                    self.intptr_type().const_int(self.size_of(pointee).max(1), false)
+                };
+                let difference = self.builder.build_int_exact_signed_div(bytes, size, "ptrdiff");
+                return self.builder.build_int_cast_sign_flag(difference, self.int_type(result_type), true, "").into();
+            }
+            _ => {}
+        }
+
+        let comparison = match operator {
+            BinaryOperator::Equal => Some((IntPredicate::EQ, IntPredicate::EQ, FloatPredicate::OEQ)),
+            BinaryOperator::NotEqual => Some((IntPredicate::NE, IntPredicate::NE, FloatPredicate::UNE)),
+            BinaryOperator::LessThan => Some((IntPredicate::SLT, IntPredicate::ULT, FloatPredicate::OLT)),
+            BinaryOperator::GreaterThan => Some((IntPredicate::SGT, IntPredicate::UGT, FloatPredicate::OGT)),
+            BinaryOperator::LessThanOrEqual => Some((IntPredicate::SLE, IntPredicate::ULE, FloatPredicate::OLE)),
+            BinaryOperator::GreaterThanOrEqual => Some((IntPredicate::SGE, IntPredicate::UGE, FloatPredicate::OGE)),
+            _ => None,
+        };
+        if let Some((signed_predicate, unsigned_predicate, float_predicate)) = comparison {
+            let truth = match (lhs, rhs) {
+                (BasicValueEnum::FloatValue(lhs), BasicValueEnum::FloatValue(rhs)) => {
+                    self.builder.build_float_compare(float_predicate, lhs, rhs, "cmp")
+                }
+                (BasicValueEnum::IntValue(lhs), BasicValueEnum::IntValue(rhs)) => {
+                    let predicate = if self.target.is_signed(lhs_type) { signed_predicate } else { unsigned_predicate };
+                    self.builder.build_int_compare(predicate, lhs, rhs, "cmp")
+                }
+                // Pointers compare as unsigned addresses
+                (lhs, rhs) => {
+                    let lhs = self.builder.build_ptr_to_int(lhs.into_pointer_value(), self.intptr_type(), "");
+                    let rhs = self.builder.build_ptr_to_int(rhs.into_pointer_value(), self.intptr_type(), "");
+                    self.builder.build_int_compare(unsigned_predicate, lhs, rhs, "cmp")
+                }
+            };
+            return self.builder.build_int_z_extend(truth, self.int_type(result_type), "").into();
+        }
+
+        if let (BasicValueEnum::FloatValue(lhs), BasicValueEnum::FloatValue(rhs)) = (lhs, rhs) {
+            return match operator {
+                BinaryOperator::Add => self.builder.build_float_add(lhs, rhs, "add"),
+                BinaryOperator::Subtract => self.builder.build_float_sub(lhs, rhs, "sub"),
+                BinaryOperator::Multiply => self.builder.build_float_mul(lhs, rhs, "mul"),
+                BinaryOperator::Divide => self.builder.build_float_div(lhs, rhs, "div"),
+                other => unreachable!("{:?} on floating operands", other),
+            }
+            .into();
+        }
+
+        let (lhs, rhs) = (lhs.into_int_value(), rhs.into_int_value());
+        let signed = self.target.is_signed(lhs_type);
+        match operator {
+            BinaryOperator::Add => self.builder.build_int_add(lhs, rhs, "add"),
+            BinaryOperator::Subtract => self.builder.build_int_sub(lhs, rhs, "sub"),
+            BinaryOperator::Multiply => self.builder.build_int_mul(lhs, rhs, "mul"),
+            BinaryOperator::Divide if signed => self.builder.build_int_signed_div(lhs, rhs, "div"),
+            BinaryOperator::Divide => self.builder.build_int_unsigned_div(lhs, rhs, "div"),
+            BinaryOperator::Modulo if signed => self.builder.build_int_signed_rem(lhs, rhs, "rem"),
+            BinaryOperator::Modulo => self.builder.build_int_unsigned_rem(lhs, rhs, "rem"),
+            BinaryOperator::BitwiseAnd => self.builder.build_and(lhs, rhs, "and"),
+            BinaryOperator::BitwiseOr => self.builder.build_or(lhs, rhs, "or"),
+            BinaryOperator::BitwiseXor => self.builder.build_xor(lhs, rhs, "xor"),
+            // The operands of a shift are promoted separately
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                let rhs_signed = self.target.is_signed(rhs_type);
+                let rhs = self.builder.build_int_cast_sign_flag(rhs, lhs.get_type(), rhs_signed, "");
+                match operator {
+                    BinaryOperator::LeftShift => self.builder.build_left_shift(lhs, rhs, "shl"),
+                    _ => self.builder.build_right_shift(lhs, rhs, signed, "shr"),
+                }
+            }
+            other => unreachable!("{:?} is not an arithmetic operator", other),
+        }
+        .into()
+    }
+
+    /// `&&` and `||`, which evaluate the right operand only when the left
+    /// one does not decide the result
+    fn logical(&mut self, lhs: &Expression, is_and: bool, rhs: &Expression) -> BasicValueEnum<'ctx> {
+        let rhs_bb = self.append_block(if is_and { "and.rhs" } else { "or.rhs" });
+        let end = self.append_block(if is_and { "and.end" } else { "or.end" });
+        let lhs_truth = self.condition(lhs);
+        let lhs_end = self.builder.get_insert_block().unwrap();
+        if is_and {
+            self.builder.build_conditional_branch(lhs_truth, rhs_bb, end);
+        } else {
+            self.builder.build_conditional_branch(lhs_truth, end, rhs_bb);
+        }
+        self.builder.position_at_end(rhs_bb);
+        let rhs_truth = self.condition(rhs);
+        let rhs_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end);
+
+        self.builder.position_at_end(end);
+        let bool_type = self.context.bool_type();
+        let phi = self.builder.build_phi(bool_type, "");
+        let short_circuit = bool_type.const_int(!is_and as u64, false);
+        phi.add_incoming(&[(&short_circuit, lhs_end), (&rhs_truth, rhs_end)]);
+        self.builder.build_int_z_extend(phi.as_basic_value().into_int_value(), self.context.i32_type(), "").into()
+    }
+
+    fn conditional(
+        &mut self,
+        condition: &Expression,
+        then: &Expression,
+        otherwise: &Expression,
+    ) -> BasicValueEnum<'ctx> {
+        let then_bb = self.append_block("cond.true");
+        let else_bb = self.append_block("cond.false");
+        let end = self.append_block("cond.end");
+        let truth = self.condition(condition);
+        self.builder.build_conditional_branch(truth, then_bb, else_bb);
+
+        self.builder.position_at_end(then_bb);
+        let then_value = self.value(then);
+        let then_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end);
+        self.builder.position_at_end(else_bb);
+        let else_value = self.value(otherwise);
+        let else_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end);
+
+        self.builder.position_at_end(end);
+        if *then.ty() == Type::Void {
+            return self.void_value();
+        }
+        let phi = self.builder.build_phi(then_value.get_type(), "cond");
+        phi.add_incoming(&[(&then_value, then_end), (&else_value, else_end)]);
+        phi.as_basic_value()
+    }
+
+    fn unary(&mut self, operator: UnaryOperator, operand: &Expression) -> BasicValueEnum<'ctx> {
+        match operator {
+            UnaryOperator::Plus => self.value(operand),
+            UnaryOperator::Negate => match self.value(operand) {
+                BasicValueEnum::FloatValue(value) => self.builder.build_float_neg(value, "neg").into(),
+                value => self.builder.build_int_neg(value.into_int_value(), "neg").into(),
+            },
+            UnaryOperator::BitwiseNot => {
+                let value = self.value(operand).into_int_value();
+                self.builder.build_not(value, "not").into()
+            }
+            UnaryOperator::LogicalNot => {
+                let truth = self.condition(operand);
+                let not = self.builder.build_not(truth, "lnot");
+                self.builder.build_int_z_extend(not, self.context.i32_type(), "").into()
+            }
+            UnaryOperator::PreIncrement
+            | UnaryOperator::PostIncrement
+            | UnaryOperator::PreDecrement
+            | UnaryOperator::PostDecrement => {
+                let increment = matches!(operator, UnaryOperator::PreIncrement | UnaryOperator::PostIncrement);
+                let lvalue = self.lvalue(operand);
+                let operand_type = operand.ty().unqualified().clone();
+                let (old, new) =
+                    self.update(lvalue, operand.ty(), |generator, old| generator.step(old, &operand_type, increment));
+                match operator {
+                    UnaryOperator::PreIncrement | UnaryOperator::PreDecrement => new,
+                    _ => old,
+                }
+            }
+        }
+    }
+
+    /// `value + 1` or `value - 1` in the type of the value
+    fn step(&self, value: BasicValueEnum<'ctx>, value_type: &Type, increment: bool) -> BasicValueEnum<'ctx> {
+        let operator = if increment { BinaryOperator::Add } else { BinaryOperator::Subtract };
+        if *value_type == Type::Bool {
+            // `_Bool` is incremented as an `int` and converted back
+            let value = self.convert(value, value_type, &Type::Int);
+            let result = self.binary(operator, (value, &Type::Int), (self.one(&Type::Int), &Type::Int), &Type::Int);
+            return self.convert(result, &Type::Int, value_type);
+        }
+        let one_type = if value_type.pointee().is_some() { Type::Int } else { value_type.clone() };
+        self.binary(operator, (value, value_type), (self.one(&one_type), &one_type), value_type)
+    }
+
+    fn one(&self, arithmetic: &Type) -> BasicValueEnum<'ctx> {
+        if arithmetic.is_floating() {
+            self.float_type(arithmetic).const_float(1.0).into()
+        } else {
+            self.int_type(arithmetic).const_int(1, false).into()
+        }
+    }
+}
+
+/// Merge the value of a bit-field `width` bits wide, `bit_offset` bits into
+/// a constant, into the bytes it occupies. Targets are little-endian.
+fn constant_bit_field(pieces: &mut Vec<(u64, Piece)>, bit_offset: u64, width: u32, value: i64) {
+    let bits = (value as u128 & ((1u128 << width) - 1)) << (bit_offset % 8);
+    let first = bit_offset / 8;
+    let last = (bit_offset + width as u64 - 1) / 8;
+    for byte in first..=last {
+        let bits = (bits >> ((byte - first) * 8)) as u8;
+        let existing = pieces.iter_mut().find_map(|(offset, piece)| match piece {
+            Piece::Bytes(bytes) if *offset == byte && bytes.len() == 1 => Some(bytes),
+            _ => None,
+        });
+        match existing {
+            Some(bytes) => bytes[0] |= bits,
+            None => pieces.push((byte, Piece::Bytes(vec![bits]))),
+        }
+    }
+}
+
+/// How many LLVM values a value passed in `mode` takes
+fn abi_arity(mode: &PassMode) -> usize {
+    match mode {
+        PassMode::Ignore => 0,
+        PassMode::Direct(registers) => registers.len(),
+        _ => 1,
+    }
+}
+
+/// Whether an expression designates an object in memory, rather than being a
+/// value such as the result of a call
+fn is_addressable(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::Variable(_)
+        | ExpressionKind::Index(..)
+        | ExpressionKind::Pointer(_)
+        | ExpressionKind::CompoundLiteral(..)
+        | ExpressionKind::Literal(Literal::String(..)) => true,
+        ExpressionKind::Member(base, _) => is_addressable(base),
+        _ => false,
+    }
+}
+
+/// The code units of a string literal initializing an array
+fn string_literal(expression: &Expression) -> Option<&[u32]> {
+    match (&expression.kind, expression.ty()) {
+        (ExpressionKind::Literal(Literal::String(_, units)), Type::Array(..)) => Some(units),
+        _ => None,
+    }
+}
+
+fn is_labeled(statement: &Statement) -> bool {
+    matches!(statement.kind, StatementKind::Label(..) | StatementKind::Case(..) | StatementKind::Default(_))
+}
+
+/// The value of an integer constant expression, which semantic analysis has
+/// folded to a literal
+fn folded_integer(expression: &Expression) -> Option<i64> {
+    match expression.kind {
+        ExpressionKind::Literal(Literal::Int(value)) => Some(value),
+        _ => None,
+    }
+}
+
+/// Collect the `case` values of a `switch` body in order, without those of
+/// nested `switch` statements
+fn collect_cases<'e>(statements: &'e [Statement], values: &mut Vec<&'e Expression>, has_default: &mut bool) {
+    for statement in statements {
+        collect_statement_cases(statement, values, has_default);
+    }
+}
+
+fn collect_statement_cases<'e>(statement: &'e Statement, values: &mut Vec<&'e Expression>, has_default: &mut bool) {
+    match &statement.kind {
+        StatementKind::Case(value, inner) => {
+            values.push(value);
+            collect_statement_cases(inner, values, has_default);
+        }
+        StatementKind::Default(inner) => {
+            *has_default = true;
+            collect_statement_cases(inner, values, has_default);
+        }
+        StatementKind::Label(_, inner) => collect_statement_cases(inner, values, has_default),
+        StatementKind::If(_, then_block, else_block) => {
+            collect_cases(&then_block.statements, values, has_default);
+            if let Some(else_block) = else_block {
+                collect_cases(&else_block.statements, values, has_default);
+            }
+        }
+        StatementKind::While(_, body) | StatementKind::DoWhile(body, _) | StatementKind::Block(body) => {
+            collect_cases(&body.statements, values, has_default)
+        }
+        StatementKind::For(init, _, step, body) => {
+            collect_statement_cases(init, values, has_default);
+            collect_statement_cases(step, values, has_default);
+            collect_cases(&body.statements, values, has_default);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer;
+    use crate::compiler::{parser, sema};
+    use crate::compiler::source::FileId;
+    use crate::compiler::target::{create_target_machine, create_target_machine_for};
+    use crate::utils::CompilerOptions;
+    use inkwell::targets::FileType;
+    use inkwell::OptimizationLevel;
+
+    /// Compile a program for the host and return its verified module
+    fn module<'ctx>(context: &'ctx LLVMContext, source: &str) -> Module<'ctx> {
+        let machine = create_target_machine(&CompilerOptions::default(), OptimizationLevel::None).expect("host target");
+        module_for(context, source, &machine)
+    }
+
+    fn module_for<'ctx>(context: &'ctx LLVMContext, source: &str, machine: &TargetMachine) -> Module<'ctx> {
+        let target = TargetInfo::new(machine);
+        let tokens = lexer::tokenize(source, FileId(0)).expect("lexing failed");
+        let mut ast = parser::parse(tokens, &target).expect("parsing failed");
+        sema::analyze(&mut ast, &target).expect("analysis failed");
+        let module = generate(context, &ast, &target, machine);
+        if let Err(message) = module.verify() {
+            panic!("invalid module: {}\n{}", message.to_string(), module.print_to_string().to_string());
+        }
+        module
+    }
+
+    /// Compile a program and run its `main`, returning the exit status
+    fn run(source: &str) -> i32 {
+        let context = LLVMContext::create();
+        let module = module(&context, source);
+        let engine = module.create_jit_execution_engine(OptimizationLevel::None).expect("JIT");
+        unsafe {
+            let main = engine.get_function::<unsafe extern "C" fn() -> i32>("main").expect("main");
+            main.call()
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_and_signedness() {
+        assert_eq!(run("int main(void) { int a = 7, b = -2; return a / b * 10 + a % b; }"), -29);
+        assert_eq!(run("int main(void) { unsigned a = 7; return (-1 / (unsigned)2) > a; }"), 1);
+        assert_eq!(run("int main(void) { int x = -16; return (x >> 2) + ((unsigned)x >> 28); }"), 11);
+        assert_eq!(run("int main(void) { char c = 200; unsigned char u = 200; return (c < 0) + (u > 0) * 2; }"), 3);
+        assert_eq!(run("int main(void) { double d = 7.5; float f = 2; return (int)(d * f) + (d > 7); }"), 16);
+        assert_eq!(run("int main(void) { long l = (long)1 << 40; return l >> 38; }"), 4);
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "
+            int main(void) {
+                int total = 0;
+                for (int i = 0; i < 10; i++) {
+                    if (i == 3) continue;
+                    if (i == 8) break;
+                    total += i;
+                }
+                int n = 0;
+                do n++; while (n < 5);
+                while (n > 2) n--;
+                goto done;
+                total = 0;
+            done:
+                return total + n;
+            }";
+        assert_eq!(run(source), 27);
+        let source = "
+            int classify(int n) {
+                switch (n) {
+                case 0: return 10;
+                case 1:
+                case 2: n += 5;
+                default: n *= 2; break;
+                case 9: { switch (n) { case 9: n = 1; } } return n;
+                }
+                return n;
+            }
+            int main(void) { return classify(0) + classify(2) + classify(4) + classify(9); }";
+        assert_eq!(run(source), 10 + 14 + 8 + 1);
+    }
+
+    #[test]
+    fn test_short_circuit_and_conditional() {
+        let source = "
+            int calls;
+            int touch(int value) { calls++; return value; }
+            int main(void) {
+                int a = touch(0) && touch(1);
+                int b = touch(1) || touch(1);
+                int c = touch(1) && touch(2);
+                int d = calls ? 5 : touch(7);
+                return a + b * 10 + c * 100 + d * 1000 + calls * 10000;
+            }";
+        assert_eq!(run(source), 10 + 100 + 5000 + 40000);
+        assert_eq!(run("int main(void) { int x = 3, y; y = (x++, x * 2); return !y + y + !!x; }"), 9);
+    }
+
+    #[test]
+    fn test_pointers_arrays_and_structs() {
+        let source = "
+            struct point { char tag; int x, y; };
+            int sum(int *values, int count) {
+                int total = 0;
+                for (int *p = values; p < values + count; ++p) total += *p;
+                return total;
+            }
+            int main(void) {
+                int values[4] = {1, 2, [3] = 4};
+                int *end = &values[4];
+                struct point pt = {'a', .y = 5}, *pp = &pt;
+                pp->x = 2;
+                return sum(values, 4) + (end - values) * 10 + pt.x * 100 + pp->y * 1000 + (pt.tag == 'a');
+            }";
+        assert_eq!(run(source), 7 + 40 + 200 + 5000 + 1);
+        let source = "
+            int main(void) {
+                char text[8] = \"hi\";
+                const char *s = \"hey\";
+                return text[1] - 'h' + s[2] - 'y' + sizeof text + (text[5] == 0);
+            }";
+        assert_eq!(run(source), 1 + 8 + 1);
+    }
+
+    #[test]
+    fn test_multidimensional_and_variable_length_arrays() {
+        let source = "
+            int grid[2][3] = {{1, 2, 3}, {4, 5, 6}};
+            int sum(int rows, int cols, int m[rows][cols]) {
+                int total = 0;
+                for (int i = 0; i < rows; i++)
+                    for (int j = 0; j < cols; j++) total += m[i][j];
+                return total;
+            }
+            int main(void) {
+                int *second = grid[1];
+                int (*last)[3] = grid + 1;
+                return grid[1][2] + second[0] * 10 + (*last)[1] * 100 + sum(2, 3, grid) * 1000 + sizeof grid[0];
+            }";
+        assert_eq!(run(source), 6 + 40 + 500 + 21000 + 12);
+        let source = "
+            int main(void) {
+                int n = 3, cols = n + 1;
+                int m[n][cols];
+                for (int i = 0; i < n; i++)
+                    for (int j = 0; j < cols; j++) m[i][j] = i * 10 + j;
+                int (*row)[cols] = m + 1;
+                typedef char line[cols * 2];
+                line text;
+                long steps = 0;
+                for (int k = 1; k < 50000; k++) {
+                    double scratch[k % 7 + 200];
+                    scratch[0] = k;
+                    if (k % 2) continue;
+                    steps += sizeof scratch == (k % 7 + 200) * sizeof(double);
+                }
+                return row[1][3] + (&m[2] - row) * 100 + sizeof m * 1000 + sizeof text * 100000
+                    + sizeof(int[n]) * 1000000 + (steps == 24999) * 100000000;
+            }";
+        assert_eq!(run(source), 23 + 100 + 48000 + 800000 + 12000000 + 100000000);
+    }
+
+    #[test]
+    fn test_variable_length_typedefs() {
+        // The length is evaluated where the typedef is declared, not where
+        // the name is first used
+        let source = "
+            int main(void) {
+                int n = 3;
+                typedef int row[n];
+                n = 10;
+                row r;
+                row *p = &r;
+                return sizeof r + sizeof(row) * 100 + sizeof *p * 10000;
+            }";
+        assert_eq!(run(source), 12 + 1200 + 120000);
+        // Every branch that uses the name sees the same length
+        let source = "
+            int size(int flag, int n) {
+                typedef int row[n];
+                n = 100;
+                if (flag) {
+                    row a;
+                    return sizeof a;
+                } else {
+                    row b;
+                    return sizeof b;
+                }
+            }
+            int main(void) { return size(1, 3) * 100 + size(0, 5); }";
+        assert_eq!(run(source), 1220);
+    }
+
+    #[test]
+    fn test_alignment_specifiers() {
+        let source = "
+            _Alignas(64) char global;
+            struct s { char c; _Alignas(16) int i; };
+            int main(void) {
+                _Alignas(32) char local[3];
+                static _Alignas(double) short saved;
+                struct s pair[2];
+                int misaligned = (unsigned long)&global % 64 + (unsigned long)local % 32 + (unsigned long)&saved % 8;
+                return misaligned + (int)((char *)&pair[1].i - (char *)pair) + sizeof pair * 100;
+            }";
+        assert_eq!(run(source), 48 + 6400);
+        let context = LLVMContext::create();
+        let ir = module(&context, source).print_to_string().to_string();
+        assert!(ir.contains("@global = global i8 0, align 64"), "{}", ir);
+        assert!(ir.contains("alloca [3 x i8], align 32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_old_style_definitions() {
+        let source = "
+            int scale(value, factor, text) int value; char *text; { return value * factor + text[0]; }
+            int main(void) { return scale(3, 4, \"!\"); }";
+        assert_eq!(run(source), 12 + '!' as i32);
+    }
+
+    #[test]
+    fn test_bit_fields_and_unions() {
+        let source = "
+            struct flags { unsigned a : 3; int b : 4; unsigned c : 9; };
+            union bits { int i; unsigned char bytes[4]; };
+            int main(void) {
+                struct flags f = {5, -3};
+                f.c = 0x3ff;
+                union bits u = {.i = 0x01020304};
+                f.a++;
+                return (f.a == 6) + (f.b == -3) * 2 + (f.c == 0x1ff) * 4 + (u.bytes[0] == 4) * 8;
+            }";
+        assert_eq!(run(source), 15);
+    }
+
+    #[test]
+    fn test_static_storage() {
+        let source = "
+            struct pair { int a; short b : 5; short c : 5; };
+            static int table[] = {3, 1, 4};
+            int *second = &table[1];
+            struct pair pair = {1, 2, 3};
+            union { char c; int i; } shared = {'x'};
+            const char *greeting = \"hi\";
+            int counter(void) { static int count = 10; return ++count; }
+            int main(void) {
+                counter();
+                return table[2] + *second * 10 + pair.b * 100 + pair.c * 1000
+                    + (shared.c == 'x') * 10000 + (greeting[1] == 'i') + counter() * 100000;
+            }";
+        assert_eq!(run(source), 4 + 10 + 200 + 3000 + 10000 + 1 + 1_200_000);
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Mixed {
+        d: f64,
+        i: i32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Floats {
+        x: f32,
+        y: f32,
+        z: f32,
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Large {
+        values: [i64; 3],
+    }
+
+    #[test]
+    fn test_struct_arguments_and_returns() {
+        let source = "
+            typedef struct { int x; int y; } Point;
+            struct large { long values[3]; };
+            Point create_point(int x, int y) { Point p; p.x = x; p.y = y; return p; }
+            struct large scale(struct large l, int by) {
+                for (int i = 0; i < 3; i++) l.values[i] *= by;
+                return l;
+            }
+            struct pair { long x, y; };
+            long crowded(long a, long b, long c, long d, long e, struct pair pair) {
+                return a + b + c + d + e + pair.x * pair.y;
+            }
+            int main(void) {
+                Point p = create_point(10, 20);
+                struct large l = {{1, 2, 3}};
+                struct large scaled = scale(l, 2);
+                struct pair pair = {3, 4};
+                return p.x + p.y + scaled.values[2] + l.values[2] + create_point(1, 2).y + crowded(1, 2, 3, 4, 5, pair);
+            }";
+        assert_eq!(run(source), 30 + 6 + 3 + 2 + 27);
+    }
+
+    #[test]
+    fn test_struct_passing_matches_the_c_abi() {
+        // Functions compiled here are called through Rust's `extern "C"`,
+        // which follows the platform calling convention
+        let source = "
+            struct mixed { double d; int i; };
+            struct floats { float x, y, z; };
+            struct large { long long values[3]; };
+            struct mixed mix(struct floats f, struct mixed m, short s) {
+                struct mixed result = {f.x + f.y + f.z + m.d, m.i * s};
+                return result;
+            }
+            struct floats reverse(struct floats f) { struct floats r = {f.z, f.y, f.x}; return r; }
+            struct large add(struct large a, struct large b) {
+                for (int i = 0; i < 3; i++) a.values[i] += b.values[i];
+                return a;
+            }";
+        let context = LLVMContext::create();
+        let module = module(&context, source);
+        let engine = module.create_jit_execution_engine(OptimizationLevel::None).expect("JIT");
+        let floats = Floats { x: 1.0, y: 2.0, z: 3.0 };
+        unsafe {
+            let mix = engine.get_function::<unsafe extern "C" fn(Floats, Mixed, i16) -> Mixed>("mix").unwrap();
+            assert_eq!(mix.call(floats, Mixed { d: 0.5, i: 7 }, -3), Mixed { d: 6.5, i: -21 });
+            let reverse = engine.get_function::<unsafe extern "C" fn(Floats) -> Floats>("reverse").unwrap();
+            assert_eq!(reverse.call(floats), Floats { x: 3.0, y: 2.0, z: 1.0 });
+            let add = engine.get_function::<unsafe extern "C" fn(Large, Large) -> Large>("add").unwrap();
+            let sum = add.call(Large { values: [1, 2, 3] }, Large { values: [10, 20, 30] });
+            assert_eq!(sum, Large { values: [11, 22, 33] });
+        }
+    }
+
+    #[test]
+    fn test_variadic_functions() {
+        let source = "
+            struct mixed { double d; int i; };
+            long sum(int count, ...) {
+                __builtin_va_list ap;
+                __builtin_va_start(ap, count);
+                long total = 0;
+                for (int i = 0; i < count; i++) total += __builtin_va_arg(ap, int);
+                __builtin_va_end(ap);
+                return total;
+            }
+            double average(int count, __builtin_va_list ap) {
+                double total = 0;
+                for (int i = 0; i < count; i++) total += __builtin_va_arg(ap, double);
+                return total / count;
+            }
+            double twice(int count, ...) {
+                __builtin_va_list ap, again;
+                __builtin_va_start(ap, count);
+                __builtin_va_copy(again, ap);
+                double result = average(count, ap) + average(count, again);
+                __builtin_va_end(again);
+                __builtin_va_end(ap);
+                return result;
+            }
+            int records(int count, ...) {
+                __builtin_va_list ap;
+                __builtin_va_start(ap, count);
+                int total = 0;
+                for (int i = 0; i < count; i++) {
+                    struct mixed m = __builtin_va_arg(ap, struct mixed);
+                    total += m.d * m.i;
+                }
+                __builtin_va_end(ap);
+                return total;
+            }
+            int main(void) {
+                struct mixed a = {1.5, 2}, b = {2.5, 4};
+                if (sum(9, 1, 2, 3, 4, 5, 6, 7, 8, 9) != 45) return 1;
+                if (twice(10, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0) != 11) return 2;
+                if (records(4, a, b, a, b) != 26) return 3;
+                return 0;
+            }";
+        assert_eq!(run(source), 0);
+    }
+
+    #[test]
+    fn test_va_arg_on_other_targets() {
+        let source = "
+            struct pair { double a, b; };
+            struct big { long long values[4]; };
+            double take(int count, ...) {
+                __builtin_va_list ap;
+                __builtin_va_start(ap, count);
+                double total = __builtin_va_arg(ap, int) + __builtin_va_arg(ap, double);
+                struct pair p = __builtin_va_arg(ap, struct pair);
+                struct big b = __builtin_va_arg(ap, struct big);
+                __builtin_va_end(ap);
+                return total + p.a + p.b + b.values[3];
+            }";
+        let context = LLVMContext::create();
+        for triple in
+            ["aarch64-unknown-linux-gnu", "aarch64-apple-darwin", "x86_64-pc-windows-msvc", "i686-unknown-linux-gnu"]
+        {
+            let machine = create_target_machine_for(triple);
+            let module = module_for(&context, source, &machine);
+            assert!(machine.write_to_memory_buffer(&module, FileType::Object).is_ok(), "{}", triple);
+        }
+    }
+
+    #[test]
+    fn test_literals() {
+        let source = "
+            static const int *greeting = L\"h\\u00e9\";
+            static unsigned short utf16[4] = u\"\\U0001F600\";
+            int main(void) {
+                unsigned U[] = U\"ab\" \"c\";
+                char bytes[] = \"a\\0b\";
+                float f = 0.1f;
+                return (greeting[1] == 0xE9) + (utf16[0] == 0xD83D && utf16[1] == 0xDE00 && !utf16[3]) * 2
+                    + (U[2] == 'c' && sizeof U == 16) * 4 + (sizeof bytes == 4 && bytes[2] == 'b') * 8
+                    + (f == 0.1f && f != 0.1) * 16 + ('ab' == 0x6162) * 32 + (0xFFFFFFFF > 0) * 64
+                    + (sizeof 10ULL == 8) * 128;
+            }";
+        assert_eq!(run(source), 255);
+
+        // Character constants follow the target's `char` signedness and wide
+        // strings its byte order
+        let context = LLVMContext::create();
+        let source = "int c = '\\xff'; unsigned short s[] = u\"\\x102\";";
+        let ir = module_for(&context, source, &create_target_machine_for("aarch64-unknown-linux-gnu"))
+            .print_to_string()
+            .to_string();
+        assert!(ir.contains("@c = global i32 255"), "{}", ir);
+        let ir = module_for(&context, source, &create_target_machine_for("powerpc64-unknown-linux-gnu"))
+            .print_to_string()
+            .to_string();
+        assert!(ir.contains("@c = global i32 -1") && ir.contains("c\"\\01\\02\\00\\00\""), "{}", ir);
+
+        // `long double` constants are built from their digits, not a double
+        let source = "long double tenth = 0.1L, big = 1e4000L, tiny = -0x1p-16445L;";
+        let ir = module_for(&context, source, &create_target_machine_for("x86_64-unknown-linux-gnu"))
+            .print_to_string()
+            .to_string();
+        assert!(ir.contains("@tenth = global x86_fp80 0xK3FFBCCCCCCCCCCCCCCCD"), "{}", ir);
+        assert!(ir.contains("@big = global x86_fp80 0xK73E6D1BA8323FE558C61"), "{}", ir);
+        assert!(ir.contains("@tiny = global x86_fp80 0xK80000000000000000001"), "{}", ir);
+        let ir = module_for(&context, source, &create_target_machine_for("aarch64-unknown-linux-gnu"))
+            .print_to_string()
+            .to_string();
+        assert!(ir.contains("@tenth = global fp128 0xL999999999999999A3FFB999999999999"), "{}", ir);
+    }
+
+    #[test]
+    fn test_variadic_functions_match_the_c_abi() {
+        // `format` is called through Rust's C-variadic calls and hands its
+        // `va_list` to the C library
+        let source = "
+            int vsnprintf(char *buffer, unsigned long size, const char *format, __builtin_va_list ap);
+            int format(char *buffer, const char *format, ...) {
+                __builtin_va_list ap;
+                __builtin_va_start(ap, format);
+                int length = vsnprintf(buffer, 64, format, ap);
+                __builtin_va_end(ap);
+                return length;
+            }";
+        let context = LLVMContext::create();
+        let module = module(&context, source);
+        let engine = module.create_jit_execution_engine(OptimizationLevel::None).expect("JIT");
+        let mut buffer = [0u8; 64];
+        let address = engine.get_function_address("format").expect("format");
+        let length = unsafe {
+            // `JitFunction` has no variadic signatures
+            let format: unsafe extern "C" fn(*mut u8, *const u8, ...) -> i32 = std::mem::transmute(address);
+            format(buffer.as_mut_ptr(), c"%d %s %.2f %ld".as_ptr().cast(), 42, c"x".as_ptr(), 2.5f64, -7i64)
+        };
+        assert_eq!(&buffer[..length as usize], b"42 x 2.50 -7");
+    }
+
+    #[test]
+    fn test_qualified_objects() {
+        let source = "
+            _Atomic int shared = 1;
+            _Thread_local int local;
+            static volatile int status;
+            int main(void) {
+                shared += 2;
+                shared++;
+                status = 4;
+                local = 3;
+                return shared + status + local;
+            }";
+        let context = LLVMContext::create();
+        let ir = module(&context, source).print_to_string().to_string();
+        assert!(ir.contains("cmpxchg"), "{}", ir);
+        assert!(ir.contains("store volatile i32 4"), "{}", ir);
+        assert!(ir.contains("@local = thread_local global i32 0"), "{}", ir);
+        assert!(ir.contains("@status = internal global i32 0"), "{}", ir);
+    }
+}
@@ -0,0 +1,704 @@
+//! Constant expression evaluation. Semantic analysis evaluates analyzed
+//! expressions where C requires a constant: array sizes, case labels,
+//! enumerators, bit-field widths, static assertions and the initializers of
+//! objects with static storage duration. The preprocessor evaluates `#if`
+//! with the same integer arithmetic, in `intmax_t` and `uintmax_t`.
+
+use super::parser::{BinaryOperator, Expression, ExpressionKind, Literal, Type, UnaryOperator};
+use super::source::{CompileError, Span};
+use super::target::TargetInfo;
+
+/// An integer value: its bits, sign- or zero-extended to 64 bits by the
+/// signedness of its type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Integer {
+    pub bits: u64,
+    pub unsigned: bool,
+}
+
+impl Integer {
+    pub fn signed(value: i64) -> Integer {
+        Integer {
+            bits: value as u64,
+            unsigned: false,
+        }
+    }
+
+    pub fn is_true(self) -> bool {
+        self.bits != 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        !self.unsigned && (self.bits as i64) < 0
+    }
+
+    /// The value as a signed number; unsigned values above `i64::MAX` wrap
+    pub fn value(self) -> i64 {
+        self.bits as i64
+    }
+
+    fn to_float(self) -> f64 {
+        if self.unsigned {
+            self.bits as f64
+        } else {
+            self.bits as i64 as f64
+        }
+    }
+}
+
+/// Apply a binary operator to two integers, whose type is unsigned if either
+/// is. Shifting by the width or more yields the fully shifted value. Returns
+/// `None` for division by zero.
+pub fn binary(operator: BinaryOperator, lhs: Integer, rhs: Integer) -> Option<Integer> {
+    let unsigned = lhs.unsigned || rhs.unsigned;
+    let (a, b) = (lhs.bits, rhs.bits);
+    let arithmetic = |bits: u64| Integer { bits, unsigned };
+    let boolean = |condition: bool| Integer::signed(condition as i64);
+    let compare = |ordering: std::cmp::Ordering| {
+        if unsigned {
+            a.cmp(&b) == ordering
+        } else {
+            (a as i64).cmp(&(b as i64)) == ordering
+        }
+    };
+    let shift_amount = u32::try_from(b).ok().filter(|&amount| amount < 64 && !rhs.is_negative());
+
+    Some(match operator {
+        BinaryOperator::Add => arithmetic(a.wrapping_add(b)),
+        BinaryOperator::Subtract => arithmetic(a.wrapping_sub(b)),
+        BinaryOperator::Multiply => arithmetic(a.wrapping_mul(b)),
+        BinaryOperator::Divide | BinaryOperator::Modulo => {
+            if b == 0 {
+                return None;
+            }
+            let is_divide = operator == BinaryOperator::Divide;
+            arithmetic(match (unsigned, is_divide) {
+                (true, true) => a / b,
+                (true, false) => a % b,
+                (false, true) => (a as i64).wrapping_div(b as i64) as u64,
+                (false, false) => (a as i64).wrapping_rem(b as i64) as u64,
+            })
+        }
+        BinaryOperator::Equal => boolean(a == b),
+        BinaryOperator::NotEqual => boolean(a != b),
+        BinaryOperator::LessThan => boolean(compare(std::cmp::Ordering::Less)),
+        BinaryOperator::GreaterThan => boolean(compare(std::cmp::Ordering::Greater)),
+        BinaryOperator::LessThanOrEqual => boolean(!compare(std::cmp::Ordering::Greater)),
+        BinaryOperator::GreaterThanOrEqual => boolean(!compare(std::cmp::Ordering::Less)),
+        BinaryOperator::LogicalAnd => boolean(lhs.is_true() && rhs.is_true()),
+        BinaryOperator::LogicalOr => boolean(lhs.is_true() || rhs.is_true()),
+        BinaryOperator::BitwiseAnd => arithmetic(a & b),
+        BinaryOperator::BitwiseOr => arithmetic(a | b),
+        BinaryOperator::BitwiseXor => arithmetic(a ^ b),
+        // The result of a shift has the type of its left operand
+        BinaryOperator::LeftShift => Integer {
+            bits: shift_amount.map_or(0, |amount| a << amount),
+            unsigned: lhs.unsigned,
+        },
+        BinaryOperator::RightShift => Integer {
+            bits: match (shift_amount, lhs.unsigned) {
+                (Some(amount), true) => a >> amount,
+                (Some(amount), false) => ((a as i64) >> amount) as u64,
+                (None, true) => 0,
+                (None, false) => ((a as i64) >> 63) as u64,
+            },
+            unsigned: lhs.unsigned,
+        },
+    })
+}
+
+/// The exact result of a signed operation whose value does not fit a type
+/// `width` bits wide, which C leaves undefined; `None` if it fits, if the
+/// operation is unsigned or if `binary` rejects it anyway. The quotient
+/// decides for `%`, since `INT_MIN % -1` is undefined too.
+pub fn signed_overflow(operator: BinaryOperator, lhs: Integer, rhs: Integer, width: u32) -> Option<i128> {
+    // The operands of a shift are promoted separately
+    let is_shift = matches!(operator, BinaryOperator::LeftShift | BinaryOperator::RightShift);
+    if lhs.unsigned || (rhs.unsigned && !is_shift) {
+        return None;
+    }
+    let (a, b) = (i128::from(lhs.value()), i128::from(rhs.value()));
+    let exact = match operator {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Subtract => a - b,
+        BinaryOperator::Multiply => a * b,
+        BinaryOperator::Divide | BinaryOperator::Modulo if b != 0 => a / b,
+        BinaryOperator::LeftShift if (0..64).contains(&b) => a << b,
+        _ => return None,
+    };
+    let limit = 1i128 << (width - 1);
+    // Shifting a one into the sign bit is accepted, as GCC does
+    let upper = if operator == BinaryOperator::LeftShift { limit * 2 } else { limit };
+    (exact < -limit || exact >= upper).then_some(exact)
+}
+
+/// The value of a constant expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constant {
+    Integer(Integer),
+    Float(f64),
+    /// A pointer known at link time: a null pointer, an integer converted
+    /// to a pointer, or the address of a static object or function plus or
+    /// minus a constant. Code generation folds it.
+    Address,
+}
+
+/// Why an expression is not constant, and the part of it that is not
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotConstant {
+    pub reason: String,
+    pub span: Span,
+    /// The expression is constant but signed arithmetic in it overflows,
+    /// which is an error even where a constant is only optional
+    pub overflowed: bool,
+}
+
+impl NotConstant {
+    fn new(reason: impl Into<String>, span: Span) -> Self {
+        NotConstant {
+            reason: reason.into(),
+            span,
+            overflowed: false,
+        }
+    }
+
+    fn overflow(value: i128, result_type: &Type, span: Span) -> Self {
+        NotConstant {
+            reason: format!("value {} is outside the range of `{}`", value, result_type),
+            span,
+            overflowed: true,
+        }
+    }
+
+    /// The error for a constant required where `context` describes
+    pub fn error(self, context: &str) -> CompileError {
+        let context = if self.overflowed { "integer overflow in constant expression" } else { context };
+        CompileError::new(format!("{}: {}", context, self.reason), self.span)
+    }
+}
+
+/// Evaluates analyzed expressions. Floating arithmetic is folded where an
+/// integer constant is required, as GCC and Clang do.
+pub struct Evaluator<'a> {
+    target: &'a TargetInfo,
+    /// Whether a variable names a function or an object with static storage
+    /// duration
+    is_static: &'a dyn Fn(&str) -> bool,
+    /// Whether compound literals have static storage duration, as they do
+    /// outside functions
+    static_literals: bool,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(target: &'a TargetInfo, is_static: &'a dyn Fn(&str) -> bool, static_literals: bool) -> Self {
+        Evaluator {
+            target,
+            is_static,
+            static_literals,
+        }
+    }
+
+    /// Evaluate an integer constant expression
+    pub fn integer(&self, expression: &Expression) -> Result<Integer, NotConstant> {
+        if !expression.ty().is_integer() {
+            return Err(NotConstant::new(
+                format!("expression has type `{}`", expression.ty()),
+                expression.span,
+            ));
+        }
+        match self.evaluate(expression)? {
+            Constant::Integer(value) => Ok(value),
+            _ => Err(NotConstant::new("an address is not an integer constant", expression.span)),
+        }
+    }
+
+    /// Evaluate a constant expression
+    pub fn evaluate(&self, expression: &Expression) -> Result<Constant, NotConstant> {
+        let span = expression.span;
+        match &expression.kind {
+            ExpressionKind::Literal(Literal::Int(value)) => Ok(self.integer_of(*value as u64, expression.ty())),
+            ExpressionKind::Literal(Literal::Float { value, .. }) => Ok(Constant::Float(*value)),
+            ExpressionKind::Literal(Literal::Null) => Ok(Constant::Address),
+            ExpressionKind::Literal(Literal::Integer { .. } | Literal::Char(..) | Literal::String(..)) => {
+                unreachable!("analysis folds constants and decays strings")
+            }
+            ExpressionKind::Variable(name) => Err(NotConstant::new(format!("read of variable `{}`", name), span)),
+            ExpressionKind::ImplicitCast(operand, target) | ExpressionKind::Cast(operand, target) => {
+                self.convert(operand, target.unqualified(), span)
+            }
+            ExpressionKind::Address(operand) => self.address(operand),
+            ExpressionKind::UnaryOp(operator, operand) => self.unary(*operator, operand, expression.ty(), span),
+            ExpressionKind::BinaryOp(lhs, operator, rhs) => self.binary(lhs, *operator, rhs, expression.ty(), span),
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                if self.truth(condition)? {
+                    self.evaluate(then)
+                } else {
+                    self.evaluate(otherwise)
+                }
+            }
+            ExpressionKind::Call(..) => Err(NotConstant::new("function call", span)),
+            ExpressionKind::Assignment(..) | ExpressionKind::CompoundAssignment(..) => {
+                Err(NotConstant::new("assignment", span))
+            }
+            ExpressionKind::Comma(..) => Err(NotConstant::new("comma operator", span)),
+            ExpressionKind::Index(..) | ExpressionKind::Member(..) | ExpressionKind::Pointer(_) => {
+                Err(NotConstant::new("read of an object", span))
+            }
+            ExpressionKind::CompoundLiteral(..) => Err(NotConstant::new("compound literal", span)),
+            // Only `sizeof` a variable length array is left unfolded
+            ExpressionKind::SizeOf(_) | ExpressionKind::SizeOfType(_) | ExpressionKind::AlignOf(_) => {
+                Err(NotConstant::new("`sizeof` of a variable length array", span))
+            }
+            ExpressionKind::VaStart(_) | ExpressionKind::VaArg(..) | ExpressionKind::VaEnd(_) | ExpressionKind::VaCopy(..) => {
+                Err(NotConstant::new("variable argument access", span))
+            }
+            ExpressionKind::Generic(..) => unreachable!("analysis replaces generic selections"),
+        }
+    }
+
+    /// An integer of `integer_type` from bits wrapped to its width
+    fn integer_of(&self, bits: u64, integer_type: &Type) -> Constant {
+        let integer_type = integer_type.unqualified();
+        Constant::Integer(Integer {
+            bits: self.target.wrap_integer(bits as i64, integer_type) as u64,
+            unsigned: !self.target.is_signed(integer_type),
+        })
+    }
+
+    fn truth(&self, condition: &Expression) -> Result<bool, NotConstant> {
+        match self.evaluate(condition)? {
+            Constant::Integer(value) => Ok(value.is_true()),
+            Constant::Float(value) => Ok(value != 0.0),
+            Constant::Address => Err(NotConstant::new("comparison of an address", condition.span)),
+        }
+    }
+
+    fn convert(&self, operand: &Expression, target: &Type, span: Span) -> Result<Constant, NotConstant> {
+        // Arrays and functions decay to their address
+        if matches!(operand.ty(), Type::Array(..) | Type::VariableArray(..) | Type::Function(..)) {
+            return self.address(operand);
+        }
+        let value = self.evaluate(operand)?;
+        match (value, target) {
+            (Constant::Integer(value), Type::Bool) => Ok(Constant::Integer(Integer::signed(value.is_true() as i64))),
+            (Constant::Float(value), Type::Bool) => Ok(Constant::Integer(Integer::signed((value != 0.0) as i64))),
+            (Constant::Integer(value), integer) if integer.is_integer() => Ok(self.integer_of(value.bits, integer)),
+            (Constant::Float(value), integer) if integer.is_integer() => {
+                let truncated = value.trunc();
+                let fits = if self.target.is_signed(integer) {
+                    let half = 2f64.powi(self.target.integer_width(integer) as i32 - 1);
+                    truncated >= -half && truncated < half
+                } else {
+                    truncated > -1.0 && truncated < 2f64.powi(self.target.integer_width(integer) as i32)
+                };
+                if !fits {
+                    return Err(NotConstant::new(format!("value {} is outside the range of `{}`", value, integer), span));
+                }
+                let bits = if truncated < 0.0 { truncated as i64 as u64 } else { truncated as u64 };
+                Ok(self.integer_of(bits, integer))
+            }
+            (Constant::Integer(value), Type::Float) => Ok(Constant::Float(value.to_float() as f32 as f64)),
+            (Constant::Integer(value), floating) if floating.is_floating() => Ok(Constant::Float(value.to_float())),
+            (Constant::Float(value), Type::Float) => Ok(Constant::Float(value as f32 as f64)),
+            (Constant::Float(value), floating) if floating.is_floating() => Ok(Constant::Float(value)),
+            (Constant::Integer(_) | Constant::Address, Type::Pointer(_)) => Ok(Constant::Address),
+            // An address fits in an integer as wide as a pointer
+            (Constant::Address, integer)
+                if integer.is_integer()
+                    && *integer != Type::Bool
+                    && self.target.integer_width(integer) == self.target.integer_width(&self.target.size_type()) =>
+            {
+                Ok(Constant::Address)
+            }
+            (Constant::Address, _) => Err(NotConstant::new(
+                format!("conversion of an address to `{}`", target),
+                span,
+            )),
+            _ => Err(NotConstant::new(format!("conversion to `{}`", target), span)),
+        }
+    }
+
+    /// The address of an lvalue, which must designate a static object or a
+    /// function
+    fn address(&self, lvalue: &Expression) -> Result<Constant, NotConstant> {
+        let span = lvalue.span;
+        match &lvalue.kind {
+            ExpressionKind::Variable(name) if (self.is_static)(name) => Ok(Constant::Address),
+            ExpressionKind::Variable(name) => Err(NotConstant::new(
+                format!("address of `{}`, which does not have static storage duration", name),
+                span,
+            )),
+            ExpressionKind::Literal(Literal::String(..)) => Ok(Constant::Address),
+            ExpressionKind::CompoundLiteral(..) if self.static_literals => Ok(Constant::Address),
+            ExpressionKind::CompoundLiteral(..) => Err(NotConstant::new("address of a compound literal in a function", span)),
+            ExpressionKind::Member(base, _) => self.address(base),
+            ExpressionKind::Pointer(pointer) => self.pointer(pointer),
+            ExpressionKind::Index(lhs, rhs) => {
+                let (pointer, index) = if lhs.ty().pointee().is_some() { (lhs, rhs) } else { (rhs, lhs) };
+                self.integer(index)?;
+                self.pointer(pointer)
+            }
+            _ => Err(NotConstant::new("address of a value that is not an object", span)),
+        }
+    }
+
+    /// The value of an expression that must be an address constant
+    fn pointer(&self, pointer: &Expression) -> Result<Constant, NotConstant> {
+        match self.evaluate(pointer)? {
+            Constant::Address => Ok(Constant::Address),
+            _ => Err(NotConstant::new("pointer is not an address constant", pointer.span)),
+        }
+    }
+
+    fn unary(
+        &self,
+        operator: UnaryOperator,
+        operand: &Expression,
+        result_type: &Type,
+        span: Span,
+    ) -> Result<Constant, NotConstant> {
+        let operand_span = operand.span;
+        let value = match operator {
+            UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => {
+                return Err(NotConstant::new("increment", operand_span))
+            }
+            UnaryOperator::PreDecrement | UnaryOperator::PostDecrement => {
+                return Err(NotConstant::new("decrement", operand_span))
+            }
+            UnaryOperator::LogicalNot => {
+                return Ok(Constant::Integer(Integer::signed(!self.truth(operand)? as i64)))
+            }
+            _ => self.evaluate(operand)?,
+        };
+        match (operator, value) {
+            (UnaryOperator::Plus, value) => Ok(value),
+            (UnaryOperator::Negate, Constant::Integer(value)) => {
+                let width = self.target.integer_width(result_type.unqualified());
+                if let Some(exact) = signed_overflow(BinaryOperator::Subtract, Integer::signed(0), value, width) {
+                    return Err(NotConstant::overflow(exact, result_type, span));
+                }
+                Ok(self.integer_of(value.bits.wrapping_neg(), result_type))
+            }
+            (UnaryOperator::Negate, Constant::Float(value)) => Ok(Constant::Float(-value)),
+            (UnaryOperator::BitwiseNot, Constant::Integer(value)) => Ok(self.integer_of(!value.bits, result_type)),
+            _ => Err(NotConstant::new("arithmetic on an address", operand_span)),
+        }
+    }
+
+    fn binary(
+        &self,
+        lhs: &Expression,
+        operator: BinaryOperator,
+        rhs: &Expression,
+        result_type: &Type,
+        span: Span,
+    ) -> Result<Constant, NotConstant> {
+        // Only the left operand of `&&` and `||` is evaluated when it decides
+        // the result
+        if matches!(operator, BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr) {
+            let lhs = self.truth(lhs)?;
+            let result = match (operator, lhs) {
+                (BinaryOperator::LogicalAnd, false) => false,
+                (BinaryOperator::LogicalOr, true) => true,
+                _ => self.truth(rhs)?,
+            };
+            return Ok(Constant::Integer(Integer::signed(result as i64)));
+        }
+
+        match (self.evaluate(lhs)?, self.evaluate(rhs)?) {
+            (Constant::Integer(a), Constant::Integer(b)) => {
+                if matches!(operator, BinaryOperator::LeftShift | BinaryOperator::RightShift) {
+                    let width = self.target.integer_width(result_type.unqualified());
+                    if b.is_negative() {
+                        return Err(NotConstant::new(format!("negative shift count {}", b.value()), rhs.span));
+                    }
+                    if b.bits >= u64::from(width) {
+                        return Err(NotConstant::new(
+                            format!("shift count {} is not less than the width of `{}`", b.bits, result_type),
+                            rhs.span,
+                        ));
+                    }
+                }
+                let value = binary(operator, a, b).ok_or_else(|| NotConstant::new("division by zero", span))?;
+                let width = self.target.integer_width(result_type.unqualified());
+                if let Some(exact) = signed_overflow(operator, a, b, width) {
+                    return Err(NotConstant::overflow(exact, result_type, span));
+                }
+                Ok(self.integer_of(value.bits, result_type))
+            }
+            (Constant::Float(a), Constant::Float(b)) => {
+                let boolean = |condition: bool| Ok(Constant::Integer(Integer::signed(condition as i64)));
+                let value = match operator {
+                    BinaryOperator::Add => a + b,
+                    BinaryOperator::Subtract => a - b,
+                    BinaryOperator::Multiply => a * b,
+                    BinaryOperator::Divide => a / b,
+                    BinaryOperator::Equal => return boolean(a == b),
+                    BinaryOperator::NotEqual => return boolean(a != b),
+                    BinaryOperator::LessThan => return boolean(a < b),
+                    BinaryOperator::GreaterThan => return boolean(a > b),
+                    BinaryOperator::LessThanOrEqual => return boolean(a <= b),
+                    BinaryOperator::GreaterThanOrEqual => return boolean(a >= b),
+                    _ => unreachable!("{:?} on floating operands", operator),
+                };
+                Ok(Constant::Float(if *result_type.unqualified() == Type::Float {
+                    value as f32 as f64
+                } else {
+                    value
+                }))
+            }
+            // Pointer arithmetic moves an address by a constant
+            (Constant::Address, Constant::Integer(_)) | (Constant::Integer(_), Constant::Address)
+                if matches!(operator, BinaryOperator::Add | BinaryOperator::Subtract)
+                    && result_type.pointee().is_some() =>
+            {
+                Ok(Constant::Address)
+            }
+            _ => Err(NotConstant::new("arithmetic on an address", span)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer;
+    use crate::compiler::parser::{self, Initializer, StatementKind, AST};
+    use crate::compiler::sema;
+    use crate::compiler::source::FileId;
+    use crate::compiler::target::test_target;
+
+    fn parse(source: &str) -> Result<AST, CompileError> {
+        parser::parse(lexer::tokenize(source, FileId(0)).expect("lexing failed"), &test_target())
+    }
+
+    fn parse_source(source: &str) -> AST {
+        parse(source).expect("parsing failed")
+    }
+
+    fn parse_error(source: &str) -> CompileError {
+        parse(source).expect_err("parsing should fail")
+    }
+
+    /// Evaluate the initializer of the last declaration in `body`, the body
+    /// of a function on the third line after one line of `globals`, and
+    /// return the text its span covers when it is not constant
+    fn evaluate(globals: &str, body: &str) -> Result<Constant, (String, String)> {
+        let mut ast = parse_source(&format!("{}\nvoid test(void) {{\n{}\n}}", globals, body));
+        let target = test_target();
+        sema::analyze(&mut ast, &target).expect("analysis failed");
+        let is_static = |name: &str| {
+            ast.functions.contains_key(name) || ast.global_variables.iter().any(|(variable, _)| variable.name == name)
+        };
+        let value = match &ast.functions["test"].body.as_ref().unwrap().statements.last().unwrap().kind {
+            StatementKind::Declaration(_, Some(Initializer::Expression(value))) => value,
+            other => panic!("expected an initialized declaration, got {:?}", other),
+        };
+        Evaluator::new(&target, &is_static, false).evaluate(value).map_err(|not_constant| {
+            let (start, end) = (not_constant.span.start, not_constant.span.end);
+            assert_eq!(start.line, end.line, "{:?}", not_constant);
+            assert_eq!(start.line, 3, "{:?}", not_constant);
+            let spelled = body[start.column as usize - 1..end.column as usize - 1].to_string();
+            (not_constant.reason, spelled)
+        })
+    }
+
+    fn integer(globals: &str, body: &str) -> Integer {
+        match evaluate(globals, body) {
+            Ok(Constant::Integer(value)) => value,
+            other => panic!("expected an integer for {}, got {:?}", body, other),
+        }
+    }
+
+    fn float(body: &str) -> f64 {
+        match evaluate("", body) {
+            Ok(Constant::Float(value)) => value,
+            other => panic!("expected a floating value for {}, got {:?}", body, other),
+        }
+    }
+
+    fn not_constant(globals: &str, body: &str) -> (String, String) {
+        evaluate(globals, body).expect_err(body)
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let signed = Integer::signed;
+        let unsigned = |bits: u64| Integer { bits, unsigned: true };
+        let apply = |operator, lhs, rhs| binary(operator, lhs, rhs).expect("no division by zero");
+
+        assert_eq!(apply(BinaryOperator::Divide, signed(-7), signed(2)), signed(-3));
+        assert_eq!(apply(BinaryOperator::Modulo, signed(-7), signed(2)), signed(-1));
+        // Either operand being unsigned makes the operation unsigned
+        assert_eq!(apply(BinaryOperator::LessThan, signed(-1), unsigned(0)), signed(0));
+        assert_eq!(apply(BinaryOperator::Divide, signed(-2), unsigned(2)), unsigned(u64::MAX / 2));
+        assert_eq!(apply(BinaryOperator::RightShift, signed(-8), signed(1)), signed(-4));
+        assert_eq!(apply(BinaryOperator::RightShift, unsigned(8), signed(1)), unsigned(4));
+        assert_eq!(apply(BinaryOperator::LeftShift, signed(1), signed(64)), signed(0));
+        assert_eq!(apply(BinaryOperator::LogicalOr, signed(0), unsigned(5)), signed(1));
+        assert_eq!(binary(BinaryOperator::Modulo, signed(1), signed(0)), None);
+        assert_eq!(apply(BinaryOperator::Divide, signed(i64::MIN), signed(-1)), signed(i64::MIN));
+
+        let overflow = |operator, lhs, rhs, width| signed_overflow(operator, lhs, rhs, width);
+        assert_eq!(overflow(BinaryOperator::Add, signed(i32::MAX.into()), signed(1), 32), Some(1 << 31));
+        assert_eq!(overflow(BinaryOperator::Add, signed(i32::MAX.into()), signed(1), 64), None);
+        assert_eq!(overflow(BinaryOperator::Multiply, signed(i64::MAX), signed(-2), 64), Some(-(1 << 64) + 2));
+        assert_eq!(overflow(BinaryOperator::Modulo, signed(i32::MIN.into()), signed(-1), 32), Some(1 << 31));
+        assert_eq!(overflow(BinaryOperator::Subtract, signed(-2), signed(i32::MAX.into()), 32), Some(-(1 << 31) - 1));
+        assert_eq!(overflow(BinaryOperator::LeftShift, signed(1), signed(31), 32), None);
+        assert_eq!(overflow(BinaryOperator::LeftShift, signed(3), signed(31), 32), Some(3 << 31));
+        assert_eq!(overflow(BinaryOperator::LeftShift, signed(1), unsigned(4), 32), None);
+        // Unsigned arithmetic wraps by definition
+        assert_eq!(overflow(BinaryOperator::Add, unsigned(u64::MAX), signed(1), 64), None);
+    }
+
+    #[test]
+    fn test_constant_expressions() {
+        let ast = parse_source(
+            "struct pair { int a, b; };\n\
+             enum { SIZE = sizeof(struct pair), ALIGN = _Alignof(double), HALF = (int)(7.9 / 2) };\n\
+             long values[4];\n\
+             struct flags { unsigned low : sizeof(short) * 4; unsigned high : SIZE - ALIGN + 3; };\n\
+             int lengths[sizeof values / sizeof values[0] + HALF];\n\
+             _Static_assert(SIZE == 8, \"pairs are two ints\");\n\
+             void f(void) { _Static_assert(sizeof lengths == 28); int inner[sizeof(struct pair) > 4 ? 2 : 1]; }",
+        );
+        let flags = &ast.structs["flags"];
+        let widths: Vec<_> = flags.fields.iter().map(|f| f.bit_width).collect();
+        assert_eq!(widths, vec![Some(8), Some(3)]);
+        assert_eq!(ast.global_variables[1].0.var_type, Type::Array(Box::new(Type::Int), Some(7)));
+        match &ast.functions["f"].body.as_ref().unwrap().statements[0].kind {
+            StatementKind::Declaration(inner, None) => {
+                assert_eq!(inner.var_type, Type::Array(Box::new(Type::Int), Some(2)))
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+
+        let cases = [
+            ("_Static_assert(sizeof(int) == 2, \"int is \" \"16 bits\");", "static assertion failed: int is 16 bits"),
+            ("struct s { int a; _Static_assert(0); };", "static assertion failed"),
+            (
+                "void f(int n) { _Static_assert(n, \"\"); }",
+                "static assertion expression is not an integer constant expression: read of variable `n`",
+            ),
+            ("_Static_assert(1, 2);", "expected string literal in static assertion, found `2`"),
+            ("int x; int a[(x = 1)]; struct s { int b : (x = 2); };", "bit-field width is not an integer constant expression: assignment"),
+            (
+                "enum { BIG = 2147483647 + 1 };",
+                "integer overflow in constant expression: value 2147483648 is outside the range of `int`",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_casts() {
+        let unsigned = |bits: u64| Integer { bits, unsigned: true };
+        assert_eq!(integer("", "int x = (char)300;"), Integer::signed(44));
+        assert_eq!(integer("", "int x = (unsigned char)-1;"), Integer::signed(255));
+        assert_eq!(integer("", "unsigned x = -1;"), unsigned(u64::from(u32::MAX)));
+        assert_eq!(integer("", "long x = (unsigned)-1;"), Integer::signed(i64::from(u32::MAX)));
+        assert_eq!(integer("", "long x = (int)4294967295u;"), Integer::signed(-1));
+        assert_eq!(integer("", "_Bool x = 0.5;"), Integer::signed(1));
+        assert_eq!(integer("", "int x = (_Bool)256;"), Integer::signed(1));
+        assert_eq!(integer("", "int x = -2.9;"), Integer::signed(-2));
+        assert_eq!(integer("", "unsigned char x = 255.9;"), unsigned(255));
+        assert_eq!(float("double x = (float)0.1;"), f64::from(0.1f32));
+        assert_eq!(float("float x = 16777217;"), 16777216.0);
+        assert_eq!(
+            not_constant("", "short x = (short)1e10;"),
+            ("value 10000000000 is outside the range of `short`".to_string(), "(short)1e10".to_string())
+        );
+        assert_eq!(
+            not_constant("", "unsigned x = -1.0;"),
+            ("value -1 is outside the range of `unsigned int`".to_string(), "-1.0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_floating_constants() {
+        assert_eq!(float("double x = 1 / 3.0;"), 1.0 / 3.0);
+        // Arithmetic on `float` is rounded to `float`
+        assert_eq!(float("float x = 0.1f + 0.2f;"), f64::from(0.1f32 + 0.2f32));
+        assert_eq!(float("double x = 1.0 / 0;"), f64::INFINITY);
+        assert_eq!(float("double x = -(0.5 + 1);"), -1.5);
+        assert_eq!(integer("", "int x = 2.5 > 2;"), Integer::signed(1));
+        assert_eq!(integer("", "int x = 0.0 ? 1 : 2;"), Integer::signed(2));
+        assert_eq!(integer("", "int x = !0.0 && 0.1;"), Integer::signed(1));
+        assert_eq!(integer("", "int x = (int)(7.9 / 2) * 2;"), Integer::signed(6));
+    }
+
+    #[test]
+    fn test_address_constants() {
+        let globals = "int g; int array[4]; struct s { int a, b; } object; int f(void);";
+        let addresses = [
+            "int *p = &g;",
+            "int *p = array + 2;",
+            "int *p = &array[3];",
+            "int *p = 1 + array;",
+            "int *p = &object.b;",
+            "int (*p)(void) = f;",
+            "int (*p)(void) = &f;",
+            "const char *p = \"text\" + 1;",
+            "int *p = 0;",
+            "int *p = (int *)16;",
+            "long p = (long)&g;",
+        ];
+        for body in addresses {
+            assert_eq!(evaluate(globals, body), Ok(Constant::Address), "for {}", body);
+        }
+
+        let cases = [
+            ("int x = &array[1] - array;", "arithmetic on an address", "&array[1] - array"),
+            ("int x = (int)&g;", "conversion of an address to `int`", "(int)&g"),
+            ("_Bool x = &g;", "conversion of an address to `_Bool`", "&g"),
+            ("int x = &g == 0;", "arithmetic on an address", "&g == 0"),
+            ("int x = &g ? 1 : 2;", "comparison of an address", "&g"),
+            ("int local; int *p = &local;", "address of `local`, which does not have static storage duration", "local"),
+            ("int *p = &array[g];", "read of variable `g`", "g"),
+            ("int x = array[1];", "read of an object", "array[1]"),
+            ("int *p = &(int){1};", "address of a compound literal in a function", "(int){1}"),
+        ];
+        for (body, reason, spelled) in cases {
+            assert_eq!(not_constant(globals, body), (reason.to_string(), spelled.to_string()), "for {}", body);
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let globals = "int g; int f(void);";
+        let cases = [
+            ("int x = 1 + g * 2;", "read of variable `g`", "g"),
+            ("int x = f() + 1;", "function call", "f()"),
+            ("int x = (g = 1);", "assignment", "(g = 1)"),
+            ("int x = g++;", "increment", "g"),
+            ("int x = (1, 2);", "comma operator", "(1, 2)"),
+            ("int x = 2 + 1 / 0;", "division by zero", "1 / 0"),
+            ("int x = 1 << 40;", "shift count 40 is not less than the width of `int`", "40"),
+            ("int x = 1 >> -1;", "negative shift count -1", "-1"),
+            ("int x = 2147483647 + 1;", "value 2147483648 is outside the range of `int`", "2147483647 + 1"),
+            (
+                "long x = -(-9223372036854775807L - 1);",
+                "value 9223372036854775808 is outside the range of `long`",
+                "-(-9223372036854775807L - 1)",
+            ),
+        ];
+        for (body, reason, spelled) in cases {
+            assert_eq!(not_constant(globals, body), (reason.to_string(), spelled.to_string()), "for {}", body);
+        }
+        // Operands that are not evaluated do not have to be constant
+        assert_eq!(integer(globals, "int x = 1 ? 2 : g;"), Integer::signed(2));
+        assert_eq!(integer(globals, "int x = 0 && f();"), Integer::signed(0));
+
+        // A constant that is required but overflows says so, whatever the context
+        let overflow = NotConstant::overflow(1 << 31, &Type::Int, Span::default());
+        assert_eq!(
+            overflow.error("case value").message,
+            "integer overflow in constant expression: value 2147483648 is outside the range of `int`"
+        );
+        let variable = NotConstant::new("read of variable `n`", Span::default());
+        assert_eq!(variable.error("case value").message, "case value: read of variable `n`");
+    }
+}
@@ -0,0 +1,354 @@
+//! Reporting errors and warnings.
+//!
+//! A diagnostic is shown with an excerpt of the source lines it refers to,
+//! the primary span underlined with `^` and related spans with `-`, followed
+//! by its notes and help. Diagnostics on tokens that came from a macro
+//! expansion then point at the token of each macro's body involved,
+//! innermost first.
+
+use std::fmt::{self, Write};
+
+use super::source::{CompileError, FileId, MacroExpansion, Remark, SourceMap, Span};
+
+/// Columns a tab advances to in source excerpts
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// Returned once an error has been shown to the user, so that callers can
+/// tell it apart from failures that still have to be reported
+#[derive(Debug, thiserror::Error)]
+#[error("aborting due to previous error")]
+pub struct Reported;
+
+/// Shows the diagnostics of a compilation as they arise and counts them
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    errors: usize,
+    warnings: usize,
+    /// Rendered diagnostics are kept here rather than written to standard
+    /// error when set
+    captured: Option<String>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics::default()
+    }
+
+    /// Keep the rendered diagnostics instead of writing them out
+    #[cfg(test)]
+    pub fn captured() -> Self {
+        Diagnostics {
+            captured: Some(String::new()),
+            ..Diagnostics::default()
+        }
+    }
+
+    #[cfg(test)]
+    pub fn output(&self) -> &str {
+        self.captured.as_deref().unwrap_or_default()
+    }
+
+    /// Show an error; the returned `Reported` stops the compilation
+    pub fn error(&mut self, error: &CompileError, sources: &SourceMap) -> anyhow::Error {
+        self.errors += 1;
+        self.emit(&render(Severity::Error, error, sources));
+        Reported.into()
+    }
+
+    pub fn warning(&mut self, warning: &CompileError, sources: &SourceMap) {
+        self.warnings += 1;
+        self.emit(&render(Severity::Warning, warning, sources));
+    }
+
+    fn emit(&mut self, rendered: &str) {
+        match &mut self.captured {
+            Some(captured) => captured.push_str(rendered),
+            None => eprint!("{}", rendered),
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    /// How many errors and warnings were shown, as in "1 warning and 2
+    /// errors generated"; `None` if there were none
+    pub fn summary(&self) -> Option<String> {
+        let count = |n: usize, what: &str| format!("{} {}{}", n, what, if n == 1 { "" } else { "s" });
+        let counts = match (self.warnings, self.errors) {
+            (0, 0) => return None,
+            (warnings, 0) => count(warnings, "warning"),
+            (0, errors) => count(errors, "error"),
+            (warnings, errors) => format!("{} and {}", count(warnings, "warning"), count(errors, "error")),
+        };
+        Some(format!("{} generated", counts))
+    }
+}
+
+/// An underlined span in a source excerpt
+struct Annotation<'e> {
+    span: Span,
+    message: &'e str,
+    primary: bool,
+}
+
+/// Render a diagnostic with excerpts of the source it refers to
+pub fn render(severity: Severity, error: &CompileError, sources: &SourceMap) -> String {
+    let mut annotations = vec![Annotation {
+        span: error.span,
+        message: "",
+        primary: true,
+    }];
+    annotations.extend(error.labels.iter().map(|label| Annotation {
+        span: label.span,
+        message: &label.message,
+        primary: false,
+    }));
+
+    // Macro bodies are excerpted after the diagnostic, innermost first
+    let mut expansions = Vec::new();
+    let mut expansion = error.span.expansion;
+    while let Some(id) = expansion {
+        let macro_expansion = sources.expansion(id);
+        expansions.push(macro_expansion);
+        expansion = macro_expansion.parent;
+    }
+
+    let width = annotations
+        .iter()
+        .map(|annotation| annotation.span)
+        .chain(expansions.iter().map(|expansion| expansion.spelling))
+        .map(|span| span.start.line.to_string().len())
+        .max()
+        .unwrap_or(1);
+
+    let mut out = String::new();
+    write_diagnostic(&mut out, severity, error, &annotations, &expansions, sources, width)
+        .expect("formatting into a string cannot fail");
+    out
+}
+
+fn write_diagnostic(
+    out: &mut String,
+    severity: Severity,
+    error: &CompileError,
+    annotations: &[Annotation],
+    expansions: &[&MacroExpansion],
+    sources: &SourceMap,
+    width: usize,
+) -> fmt::Result {
+    writeln!(out, "{}: {}", severity, error.message)?;
+    excerpt(out, annotations, sources, width)?;
+    if !error.remarks.is_empty() {
+        writeln!(out, "{:width$} |", "")?;
+    }
+    for remark in &error.remarks {
+        match remark {
+            Remark::Note(note) => writeln!(out, "{:width$} = note: {}", "", note)?,
+            Remark::Help(help) => writeln!(out, "{:width$} = help: {}", "", help)?,
+        }
+    }
+    for expansion in expansions {
+        writeln!(out, "{}: expanded from macro `{}`", Severity::Note, expansion.name)?;
+        let spelling = Annotation {
+            span: expansion.spelling,
+            message: "",
+            primary: true,
+        };
+        excerpt(out, &[spelling], sources, width)?;
+    }
+    Ok(())
+}
+
+/// Write the lines the annotations refer to, grouped by file with the file
+/// of the first annotation first, each followed by its underlines
+fn excerpt(out: &mut String, annotations: &[Annotation], sources: &SourceMap, width: usize) -> fmt::Result {
+    let mut files: Vec<FileId> = Vec::new();
+    for annotation in annotations {
+        if !files.contains(&annotation.span.file) {
+            files.push(annotation.span.file);
+        }
+    }
+
+    for (index, &file) in files.iter().enumerate() {
+        let mut in_file: Vec<&Annotation> = annotations.iter().filter(|a| a.span.file == file).collect();
+        // The first file is introduced by the location of the diagnostic itself
+        let (arrow, first) = if index == 0 {
+            ("-->", annotations[0].span.start)
+        } else {
+            (":::", in_file.iter().map(|annotation| annotation.span.start).min().unwrap())
+        };
+        in_file.sort_by_key(|annotation| (annotation.span.start, !annotation.primary));
+        writeln!(
+            out,
+            "{:width$}{} {}:{}:{}",
+            "",
+            arrow,
+            sources.path(file).display(),
+            first.line,
+            first.column
+        )?;
+        writeln!(out, "{:width$} |", "")?;
+
+        let mut previous_line: Option<u32> = None;
+        for annotation in in_file {
+            let line_number = annotation.span.start.line;
+            let Some(text) = sources.line(file, line_number) else {
+                continue;
+            };
+            if previous_line != Some(line_number) {
+                if previous_line.is_some_and(|previous| line_number > previous + 1) {
+                    writeln!(out, "...")?;
+                }
+                writeln!(out, "{:>width$} | {}", line_number, expand_tabs(text))?;
+                previous_line = Some(line_number);
+            }
+
+            let start = display_column(text, annotation.span.start.column);
+            let end = if annotation.span.end.line == line_number {
+                display_column(text, annotation.span.end.column)
+            } else {
+                display_column(text, u32::MAX)
+            };
+            let mark = if annotation.primary { "^" } else { "-" };
+            let underline = mark.repeat(end.saturating_sub(start).max(1));
+            let line = format!("{:width$} | {:start$}{} {}", "", "", underline, annotation.message);
+            writeln!(out, "{}", line.trim_end())?;
+        }
+    }
+    Ok(())
+}
+
+/// The display width of the characters before the 1-based `column` of a
+/// line, with tabs expanded
+fn display_column(text: &str, column: u32) -> usize {
+    text.chars()
+        .take(column.saturating_sub(1) as usize)
+        .fold(0, |width, c| if c == '\t' { width + TAB_WIDTH - width % TAB_WIDTH } else { width + 1 })
+}
+
+fn expand_tabs(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - expanded.chars().count() % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::source::Location;
+    use std::path::Path;
+
+    fn span(file: FileId, line: u32, start: u32, end: u32) -> Span {
+        Span::new(file, Location { line, column: start }, Location { line, column: end })
+    }
+
+    #[test]
+    fn test_excerpts_with_labels_notes_and_help() {
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(Path::new("main.c"), "int f(void);\n\nlong f(void);\n\tx = 1;\n".to_string());
+        let error = CompileError::new("conflicting types for `f`", span(file, 3, 6, 7))
+            .with_label(span(file, 1, 5, 6), "previous declaration is here")
+            .with_note("the return types differ")
+            .with_help("declare `f` with the same type everywhere");
+        assert_eq!(
+            render(Severity::Error, &error, &sources),
+            "error: conflicting types for `f`\n \
+             --> main.c:3:6\n  \
+             |\n\
+             1 | int f(void);\n  \
+             |     - previous declaration is here\n\
+             ...\n\
+             3 | long f(void);\n  \
+             |      ^\n  \
+             |\n  \
+             = note: the return types differ\n  \
+             = help: declare `f` with the same type everywhere\n"
+        );
+
+        // Tabs are expanded, and a span covers the characters it spans
+        let warning = CompileError::new("unused value", span(file, 4, 2, 7));
+        assert_eq!(
+            render(Severity::Warning, &warning, &sources),
+            "warning: unused value\n --> main.c:4:2\n  |\n4 |     x = 1;\n  |     ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_labels_in_other_files_and_macro_expansions() {
+        let mut sources = SourceMap::new();
+        let header = sources.add_file(Path::new("limits.h"), "#define LIMIT (1 / 0)\n#define TWICE 2 * LIMIT\n".to_string());
+        let file = sources.add_file(Path::new("main.c"), "int a = TWICE;\n".to_string());
+
+        // The `/` of `LIMIT`, expanded from the `LIMIT` in `TWICE`, expanded
+        // in `main.c`
+        let mut invocation = span(file, 1, 9, 14);
+        invocation.expansion = Some(sources.add_expansion("TWICE", span(header, 2, 19, 24), invocation));
+        invocation.expansion = Some(sources.add_expansion("LIMIT", span(header, 1, 18, 19), invocation));
+        let error = CompileError::new("division by zero", invocation).with_label(span(header, 1, 9, 14), "defined here");
+        assert_eq!(
+            render(Severity::Error, &error, &sources),
+            "error: division by zero\n \
+             --> main.c:1:9\n  \
+             |\n\
+             1 | int a = TWICE;\n  \
+             |         ^^^^^\n \
+             ::: limits.h:1:9\n  \
+             |\n\
+             1 | #define LIMIT (1 / 0)\n  \
+             |         ----- defined here\n\
+             note: expanded from macro `LIMIT`\n \
+             --> limits.h:1:18\n  \
+             |\n\
+             1 | #define LIMIT (1 / 0)\n  \
+             |                  ^\n\
+             note: expanded from macro `TWICE`\n \
+             --> limits.h:2:19\n  \
+             |\n\
+             2 | #define TWICE 2 * LIMIT\n  \
+             |                   ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(Path::new("main.c"), "x\n".to_string());
+        let mut diagnostics = Diagnostics::captured();
+        assert_eq!(diagnostics.summary(), None);
+
+        diagnostics.warning(&CompileError::new("first", span(file, 1, 1, 2)), &sources);
+        assert_eq!(diagnostics.summary().as_deref(), Some("1 warning generated"));
+        let error = diagnostics.error(&CompileError::new("second", span(file, 1, 1, 2)), &sources);
+        assert!(error.is::<Reported>());
+        diagnostics.error(&CompileError::new("third", span(file, 1, 1, 2)), &sources);
+        assert_eq!(diagnostics.error_count(), 2);
+        assert_eq!(diagnostics.summary().as_deref(), Some("1 warning and 2 errors generated"));
+        assert!(diagnostics.output().starts_with("warning: first\n --> main.c:1:1\n"));
+        assert!(diagnostics.output().contains("error: third\n"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Standard headers embedded in the `tc` binary.
+//!
+//! The preprocessor falls back to these when a header is not found anywhere
+//! on the search path, so that programs using the standard library still
+//! compile on a machine without a C toolchain installed. Alongside a system
+//! libc they only fill in the compiler-provided headers (`stddef.h`,
+//! `stdarg.h`, `float.h`, ...), which is why `stddef.h` and `stdarg.h`
+//! understand glibc's `__need_*` requests the way GCC's copies do.
+
+/// Directory the bundled headers appear to live in, as shown in diagnostics
+pub const BUNDLED_DIR: &str = "<tlstuc>";
+
+const HEADERS: &[(&str, &str)] = &[
+    ("assert.h", include_str!("headers/assert.h")),
+    ("ctype.h", include_str!("headers/ctype.h")),
+    ("errno.h", include_str!("headers/errno.h")),
+    ("float.h", include_str!("headers/float.h")),
+    ("inttypes.h", include_str!("headers/inttypes.h")),
+    ("iso646.h", include_str!("headers/iso646.h")),
+    ("limits.h", include_str!("headers/limits.h")),
+    ("math.h", include_str!("headers/math.h")),
+    ("stdalign.h", include_str!("headers/stdalign.h")),
+    ("stdarg.h", include_str!("headers/stdarg.h")),
+    ("stdbool.h", include_str!("headers/stdbool.h")),
+    ("stddef.h", include_str!("headers/stddef.h")),
+    ("stdint.h", include_str!("headers/stdint.h")),
+    ("stdio.h", include_str!("headers/stdio.h")),
+    ("stdlib.h", include_str!("headers/stdlib.h")),
+    ("stdnoreturn.h", include_str!("headers/stdnoreturn.h")),
+    ("string.h", include_str!("headers/string.h")),
+    ("time.h", include_str!("headers/time.h")),
+];
+
+/// Text of the bundled header called `name`, if there is one
+pub fn find(name: &str) -> Option<&'static str> {
+    HEADERS
+        .iter()
+        .find(|(header, _)| *header == name)
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::diagnostics::Diagnostics;
+    use crate::compiler::lexer::{Token, TokenKind};
+    use crate::compiler::preprocessor;
+    use crate::compiler::source::SourceMap;
+    use crate::compiler::target::test_target;
+    use crate::utils::CompilerOptions;
+    use std::path::Path;
+
+    fn preprocess(name: &str, text: &str) -> Vec<Token> {
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(&Path::new(BUNDLED_DIR).join(name), text.to_string());
+        preprocessor::preprocess(&mut sources, file, &CompilerOptions::default(), &test_target(), &mut Diagnostics::captured())
+            .unwrap_or_else(|err| panic!("{} failed to preprocess: {}", name, err))
+    }
+
+    fn spelled(tokens: &[Token]) -> String {
+        let texts: Vec<&str> = tokens
+            .iter()
+            .filter(|token| token.kind != TokenKind::Eof)
+            .map(|token| token.text.as_str())
+            .collect();
+        texts.join(" ")
+    }
+
+    #[test]
+    fn test_every_header_preprocesses() {
+        for (name, text) in HEADERS {
+            preprocess(name, text);
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        assert!(find("stddef.h").is_some());
+        assert!(find("sys/types.h").is_none());
+        assert!(find("unknown.h").is_none());
+    }
+
+    #[test]
+    fn test_stddef_need_macros() {
+        let tokens = preprocess("test.c", "#define __need_size_t\n#include <stddef.h>\n");
+        assert_eq!(spelled(&tokens), spelled(&preprocess("t.c", "typedef __SIZE_TYPE__ size_t;")));
+
+        // A later full inclusion provides what was left out before
+        let tokens = preprocess(
+            "test.c",
+            "#define __need_NULL\n#include <stddef.h>\n#include <stddef.h>\nsize_t n = offsetof(struct s, m);\n",
+        );
+        let text = spelled(&tokens);
+        assert!(text.contains("ptrdiff_t ;"), "{}", text);
+        assert!(text.contains("max_align_t ;"), "{}", text);
+        assert!(text.ends_with("size_t n = ( ( size_t ) & ( ( struct s * ) 0 ) -> m ) ;"), "{}", text);
+    }
+
+    #[test]
+    fn test_stdarg_need_va_list() {
+        let tokens = preprocess("test.c", "#define __need___va_list\n#include <stdarg.h>\n");
+        assert_eq!(spelled(&tokens), "typedef __builtin_va_list __gnuc_va_list ;");
+
+        let tokens = preprocess("test.c", "#include <stdarg.h>\nva_start(ap, n);\n");
+        assert!(spelled(&tokens).ends_with("__builtin_va_start ( ap , n ) ;"));
+    }
+}
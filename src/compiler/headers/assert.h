@@ -0,0 +1,28 @@
+/* assert.h - bundled with tlstuc
+ *
+ * Deliberately not guarded: every inclusion redefines `assert` according to
+ * the current state of NDEBUG.
+ */
+
+#undef assert
+
+#ifdef NDEBUG
+#define assert(expression) ((void)0)
+#else
+
+#if defined(__APPLE__)
+_Noreturn void __assert_rtn(const char *function, const char *file, int line, const char *expression);
+#define assert(expression) ((expression) ? (void)0 : __assert_rtn(__func__, __FILE__, __LINE__, #expression))
+#elif defined(_WIN32)
+void _assert(const char *expression, const char *file, unsigned line);
+#define assert(expression) ((expression) ? (void)0 : _assert(#expression, __FILE__, __LINE__))
+#else
+_Noreturn void __assert_fail(const char *expression, const char *file, unsigned line, const char *function);
+#define assert(expression) ((expression) ? (void)0 : __assert_fail(#expression, __FILE__, __LINE__, __func__))
+#endif
+
+#endif
+
+#ifndef static_assert
+#define static_assert _Static_assert
+#endif
@@ -0,0 +1,56 @@
+/* errno.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_ERRNO_H
+#define __TLSTUC_ERRNO_H
+
+#if defined(__APPLE__)
+int *__error(void);
+#define errno (*__error())
+#define EDOM 33
+#define EILSEQ 92
+#define ERANGE 34
+#elif defined(_WIN32)
+int *_errno(void);
+#define errno (*_errno())
+#define EDOM 33
+#define EILSEQ 42
+#define ERANGE 34
+#else
+int *__errno_location(void);
+#define errno (*__errno_location())
+#define EDOM 33
+#define EILSEQ 84
+#define ERANGE 34
+#endif
+
+#define EPERM 1
+#define ENOENT 2
+#define ESRCH 3
+#define EINTR 4
+#define EIO 5
+#define ENXIO 6
+#define E2BIG 7
+#define ENOEXEC 8
+#define EBADF 9
+#define ECHILD 10
+#define ENOMEM 12
+#define EACCES 13
+#define EFAULT 14
+#define EBUSY 16
+#define EEXIST 17
+#define EXDEV 18
+#define ENODEV 19
+#define ENOTDIR 20
+#define EISDIR 21
+#define EINVAL 22
+#define ENFILE 23
+#define EMFILE 24
+#define ENOTTY 25
+#define EFBIG 27
+#define ENOSPC 28
+#define ESPIPE 29
+#define EROFS 30
+#define EMLINK 31
+#define EPIPE 32
+
+#endif
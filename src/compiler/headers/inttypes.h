@@ -0,0 +1,80 @@
+/* inttypes.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_INTTYPES_H
+#define __TLSTUC_INTTYPES_H
+
+#include <stdint.h>
+
+#define __need_wchar_t
+#include <stddef.h>
+
+typedef struct {
+    intmax_t quot;
+    intmax_t rem;
+} imaxdiv_t;
+
+#if __SIZEOF_LONG__ == 8 && !defined(__APPLE__)
+#define __PRI64 "l"
+#else
+#define __PRI64 "ll"
+#endif
+
+#if __SIZEOF_POINTER__ == 8 && __SIZEOF_LONG__ == 8
+#define __PRIPTR "l"
+#elif __SIZEOF_POINTER__ == 8
+#define __PRIPTR "ll"
+#else
+#define __PRIPTR ""
+#endif
+
+#define PRId8 "d"
+#define PRId16 "d"
+#define PRId32 "d"
+#define PRId64 __PRI64 "d"
+#define PRIi8 "i"
+#define PRIi16 "i"
+#define PRIi32 "i"
+#define PRIi64 __PRI64 "i"
+#define PRIu8 "u"
+#define PRIu16 "u"
+#define PRIu32 "u"
+#define PRIu64 __PRI64 "u"
+#define PRIx8 "x"
+#define PRIx16 "x"
+#define PRIx32 "x"
+#define PRIx64 __PRI64 "x"
+#define PRIX8 "X"
+#define PRIX16 "X"
+#define PRIX32 "X"
+#define PRIX64 __PRI64 "X"
+#define PRIo8 "o"
+#define PRIo16 "o"
+#define PRIo32 "o"
+#define PRIo64 __PRI64 "o"
+
+#define PRIdMAX __PRIPTR "d"
+#define PRIiMAX __PRIPTR "i"
+#define PRIuMAX __PRIPTR "u"
+#define PRIxMAX __PRIPTR "x"
+#define PRIXMAX __PRIPTR "X"
+#define PRIdPTR __PRIPTR "d"
+#define PRIiPTR __PRIPTR "i"
+#define PRIuPTR __PRIPTR "u"
+#define PRIxPTR __PRIPTR "x"
+#define PRIXPTR __PRIPTR "X"
+
+#define SCNd32 "d"
+#define SCNd64 __PRI64 "d"
+#define SCNi32 "i"
+#define SCNi64 __PRI64 "i"
+#define SCNu32 "u"
+#define SCNu64 __PRI64 "u"
+#define SCNx32 "x"
+#define SCNx64 __PRI64 "x"
+
+intmax_t imaxabs(intmax_t j);
+imaxdiv_t imaxdiv(intmax_t numer, intmax_t denom);
+intmax_t strtoimax(const char *restrict nptr, char **restrict endptr, int base);
+uintmax_t strtoumax(const char *restrict nptr, char **restrict endptr, int base);
+
+#endif
@@ -0,0 +1,18 @@
+/* iso646.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_ISO646_H
+#define __TLSTUC_ISO646_H
+
+#define and &&
+#define and_eq &=
+#define bitand &
+#define bitor |
+#define compl ~
+#define not !
+#define not_eq !=
+#define or ||
+#define or_eq |=
+#define xor ^
+#define xor_eq ^=
+
+#endif
@@ -0,0 +1,96 @@
+/* math.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_MATH_H
+#define __TLSTUC_MATH_H
+
+typedef float float_t;
+typedef double double_t;
+
+#define HUGE_VAL (1e1000)
+#define HUGE_VALF (1e1000f)
+#define HUGE_VALL (1e1000L)
+#define INFINITY (1e1000f)
+#define NAN (0.0f / 0.0f)
+
+#define FP_NAN 0
+#define FP_INFINITE 1
+#define FP_ZERO 2
+#define FP_SUBNORMAL 3
+#define FP_NORMAL 4
+
+#define isnan(x) ((x) != (x))
+#define isinf(x) (!isnan(x) && isnan((x) - (x)))
+#define isfinite(x) (!isnan((x) - (x)))
+
+#define M_E 2.7182818284590452354
+#define M_LOG2E 1.4426950408889634074
+#define M_LOG10E 0.43429448190325182765
+#define M_LN2 0.69314718055994530942
+#define M_LN10 2.30258509299404568402
+#define M_PI 3.14159265358979323846
+#define M_PI_2 1.57079632679489661923
+#define M_PI_4 0.78539816339744830962
+#define M_1_PI 0.31830988618379067154
+#define M_2_PI 0.63661977236758134308
+#define M_2_SQRTPI 1.12837916709551257390
+#define M_SQRT2 1.41421356237309504880
+#define M_SQRT1_2 0.70710678118654752440
+
+double acos(double x);
+double asin(double x);
+double atan(double x);
+double atan2(double y, double x);
+double cos(double x);
+double sin(double x);
+double tan(double x);
+double acosh(double x);
+double asinh(double x);
+double atanh(double x);
+double cosh(double x);
+double sinh(double x);
+double tanh(double x);
+
+double exp(double x);
+double exp2(double x);
+double expm1(double x);
+double frexp(double value, int *exp);
+double ldexp(double x, int exp);
+double log(double x);
+double log10(double x);
+double log1p(double x);
+double log2(double x);
+double modf(double value, double *iptr);
+
+double cbrt(double x);
+double fabs(double x);
+double hypot(double x, double y);
+double pow(double x, double y);
+double sqrt(double x);
+
+double ceil(double x);
+double floor(double x);
+double nearbyint(double x);
+double rint(double x);
+long lrint(double x);
+double round(double x);
+long lround(double x);
+double trunc(double x);
+double fmod(double x, double y);
+double remainder(double x, double y);
+
+double copysign(double x, double y);
+double nan(const char *tagp);
+double fdim(double x, double y);
+double fmax(double x, double y);
+double fmin(double x, double y);
+double fma(double x, double y, double z);
+
+float sqrtf(float x);
+float fabsf(float x);
+float sinf(float x);
+float cosf(float x);
+float powf(float x, float y);
+float floorf(float x);
+float ceilf(float x);
+
+#endif
@@ -0,0 +1,11 @@
+/* stdalign.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_STDALIGN_H
+#define __TLSTUC_STDALIGN_H
+
+#define alignas _Alignas
+#define alignof _Alignof
+#define __alignas_is_defined 1
+#define __alignof_is_defined 1
+
+#endif
@@ -0,0 +1,21 @@
+/* stdarg.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_GNUC_VA_LIST
+#define __TLSTUC_GNUC_VA_LIST
+typedef __builtin_va_list __gnuc_va_list;
+#endif
+
+/* glibc only asks for `__gnuc_va_list` through `__need___va_list` */
+#ifdef __need___va_list
+#undef __need___va_list
+#elif !defined(__TLSTUC_STDARG_H)
+#define __TLSTUC_STDARG_H
+
+typedef __builtin_va_list va_list;
+
+#define va_start(ap, param) __builtin_va_start(ap, param)
+#define va_end(ap) __builtin_va_end(ap)
+#define va_arg(ap, type) __builtin_va_arg(ap, type)
+#define va_copy(dest, src) __builtin_va_copy(dest, src)
+
+#endif
@@ -0,0 +1,57 @@
+/* stddef.h - bundled with tlstuc
+ *
+ * Like GCC's copy this honours the glibc `__need_*` protocol: when one of
+ * those macros is defined only the requested definition is provided.
+ */
+
+#if !defined(__need_size_t) && !defined(__need_ptrdiff_t) && !defined(__need_wchar_t) && \
+    !defined(__need_wint_t) && !defined(__need_NULL)
+#define __need_size_t
+#define __need_ptrdiff_t
+#define __need_wchar_t
+#define __need_NULL
+#define __TLSTUC_STDDEF_ALL
+#endif
+
+#if defined(__need_size_t) && !defined(__TLSTUC_SIZE_T)
+#define __TLSTUC_SIZE_T
+typedef __SIZE_TYPE__ size_t;
+#endif
+#undef __need_size_t
+
+#if defined(__need_ptrdiff_t) && !defined(__TLSTUC_PTRDIFF_T)
+#define __TLSTUC_PTRDIFF_T
+typedef __PTRDIFF_TYPE__ ptrdiff_t;
+#endif
+#undef __need_ptrdiff_t
+
+#if defined(__need_wchar_t) && !defined(__TLSTUC_WCHAR_T)
+#define __TLSTUC_WCHAR_T
+typedef __WCHAR_TYPE__ wchar_t;
+#endif
+#undef __need_wchar_t
+
+#if defined(__need_wint_t) && !defined(__TLSTUC_WINT_T)
+#define __TLSTUC_WINT_T
+typedef __WINT_TYPE__ wint_t;
+#endif
+#undef __need_wint_t
+
+#ifdef __need_NULL
+#undef NULL
+#define NULL ((void *)0)
+#endif
+#undef __need_NULL
+
+#if defined(__TLSTUC_STDDEF_ALL) && !defined(__TLSTUC_STDDEF_H)
+#define __TLSTUC_STDDEF_H
+
+typedef struct {
+    long long __max_align_ll;
+    long double __max_align_ld;
+} max_align_t;
+
+#define offsetof(type, member) ((size_t)&((type *)0)->member)
+
+#endif
+#undef __TLSTUC_STDDEF_ALL
@@ -0,0 +1,112 @@
+/* stdio.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_STDIO_H
+#define __TLSTUC_STDIO_H
+
+#define __need_size_t
+#define __need_NULL
+#include <stddef.h>
+
+#if defined(__APPLE__)
+typedef struct __sFILE FILE;
+typedef long long fpos_t;
+#elif defined(_WIN32)
+typedef struct _iobuf FILE;
+typedef long long fpos_t;
+#else
+typedef struct _IO_FILE FILE;
+typedef struct {
+    long long __pos;
+    char __state[8];
+} fpos_t;
+#endif
+
+#define EOF (-1)
+#define BUFSIZ 8192
+#define FILENAME_MAX 4096
+#define FOPEN_MAX 16
+#define L_tmpnam 20
+#define TMP_MAX 238328
+
+#define _IOFBF 0
+#define _IOLBF 1
+#define _IONBF 2
+
+#define SEEK_SET 0
+#define SEEK_CUR 1
+#define SEEK_END 2
+
+#if defined(__APPLE__)
+extern FILE *__stdinp;
+extern FILE *__stdoutp;
+extern FILE *__stderrp;
+#define stdin __stdinp
+#define stdout __stdoutp
+#define stderr __stderrp
+#elif defined(_WIN32)
+FILE *__acrt_iob_func(unsigned index);
+#define stdin (__acrt_iob_func(0))
+#define stdout (__acrt_iob_func(1))
+#define stderr (__acrt_iob_func(2))
+#else
+extern FILE *stdin;
+extern FILE *stdout;
+extern FILE *stderr;
+#define stdin stdin
+#define stdout stdout
+#define stderr stderr
+#endif
+
+int remove(const char *filename);
+int rename(const char *old, const char *new);
+FILE *tmpfile(void);
+char *tmpnam(char *s);
+
+int fclose(FILE *stream);
+int fflush(FILE *stream);
+FILE *fopen(const char *restrict filename, const char *restrict mode);
+FILE *freopen(const char *restrict filename, const char *restrict mode, FILE *restrict stream);
+void setbuf(FILE *restrict stream, char *restrict buf);
+int setvbuf(FILE *restrict stream, char *restrict buf, int mode, size_t size);
+
+int fprintf(FILE *restrict stream, const char *restrict format, ...);
+int fscanf(FILE *restrict stream, const char *restrict format, ...);
+int printf(const char *restrict format, ...);
+int scanf(const char *restrict format, ...);
+int snprintf(char *restrict s, size_t n, const char *restrict format, ...);
+int sprintf(char *restrict s, const char *restrict format, ...);
+int sscanf(const char *restrict s, const char *restrict format, ...);
+int vfprintf(FILE *restrict stream, const char *restrict format, __builtin_va_list arg);
+int vfscanf(FILE *restrict stream, const char *restrict format, __builtin_va_list arg);
+int vprintf(const char *restrict format, __builtin_va_list arg);
+int vscanf(const char *restrict format, __builtin_va_list arg);
+int vsnprintf(char *restrict s, size_t n, const char *restrict format, __builtin_va_list arg);
+int vsprintf(char *restrict s, const char *restrict format, __builtin_va_list arg);
+int vsscanf(const char *restrict s, const char *restrict format, __builtin_va_list arg);
+
+int fgetc(FILE *stream);
+char *fgets(char *restrict s, int n, FILE *restrict stream);
+int fputc(int c, FILE *stream);
+int fputs(const char *restrict s, FILE *restrict stream);
+int getc(FILE *stream);
+int getchar(void);
+int putc(int c, FILE *stream);
+int putchar(int c);
+int puts(const char *s);
+int ungetc(int c, FILE *stream);
+
+size_t fread(void *restrict ptr, size_t size, size_t nmemb, FILE *restrict stream);
+size_t fwrite(const void *restrict ptr, size_t size, size_t nmemb, FILE *restrict stream);
+
+int fgetpos(FILE *restrict stream, fpos_t *restrict pos);
+int fseek(FILE *stream, long offset, int whence);
+int fsetpos(FILE *stream, const fpos_t *pos);
+long ftell(FILE *stream);
+void rewind(FILE *stream);
+
+void clearerr(FILE *stream);
+int feof(FILE *stream);
+int ferror(FILE *stream);
+void perror(const char *s);
+
+#endif
@@ -0,0 +1,8 @@
+/* stdnoreturn.h - bundled with tlstuc */
+
+#ifndef __TLSTUC_STDNORETURN_H
+#define __TLSTUC_STDNORETURN_H
+
+#define noreturn _Noreturn
+
+#endif
@@ -0,0 +1,1399 @@
+use serde::Serialize;
+use std::fmt;
+
+use super::source::{CompileError, FileId, Location, Span};
+
+/// C11 keywords
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Keyword {
+    Auto,
+    Break,
+    Case,
+    Char,
+    Const,
+    Continue,
+    Default,
+    Do,
+    Double,
+    Else,
+    Enum,
+    Extern,
+    Float,
+    For,
+    Goto,
+    If,
+    Inline,
+    Int,
+    Long,
+    Register,
+    Restrict,
+    Return,
+    Short,
+    Signed,
+    Sizeof,
+    Static,
+    Struct,
+    Switch,
+    Typedef,
+    Union,
+    Unsigned,
+    Void,
+    Volatile,
+    While,
+    Alignas,
+    Alignof,
+    Atomic,
+    Bool,
+    Complex,
+    Generic,
+    Imaginary,
+    Noreturn,
+    StaticAssert,
+    ThreadLocal,
+}
+
+const KEYWORDS: &[(&str, Keyword)] = &[
+    ("auto", Keyword::Auto),
+    ("break", Keyword::Break),
+    ("case", Keyword::Case),
+    ("char", Keyword::Char),
+    ("const", Keyword::Const),
+    ("continue", Keyword::Continue),
+    ("default", Keyword::Default),
+    ("do", Keyword::Do),
+    ("double", Keyword::Double),
+    ("else", Keyword::Else),
+    ("enum", Keyword::Enum),
+    ("extern", Keyword::Extern),
+    ("float", Keyword::Float),
+    ("for", Keyword::For),
+    ("goto", Keyword::Goto),
+    ("if", Keyword::If),
+    ("inline", Keyword::Inline),
+    ("int", Keyword::Int),
+    ("long", Keyword::Long),
+    ("register", Keyword::Register),
+    ("restrict", Keyword::Restrict),
+    ("return", Keyword::Return),
+    ("short", Keyword::Short),
+    ("signed", Keyword::Signed),
+    ("sizeof", Keyword::Sizeof),
+    ("static", Keyword::Static),
+    ("struct", Keyword::Struct),
+    ("switch", Keyword::Switch),
+    ("typedef", Keyword::Typedef),
+    ("union", Keyword::Union),
+    ("unsigned", Keyword::Unsigned),
+    ("void", Keyword::Void),
+    ("volatile", Keyword::Volatile),
+    ("while", Keyword::While),
+    ("_Alignas", Keyword::Alignas),
+    ("_Alignof", Keyword::Alignof),
+    ("_Atomic", Keyword::Atomic),
+    ("_Bool", Keyword::Bool),
+    ("_Complex", Keyword::Complex),
+    ("_Generic", Keyword::Generic),
+    ("_Imaginary", Keyword::Imaginary),
+    ("_Noreturn", Keyword::Noreturn),
+    ("_Static_assert", Keyword::StaticAssert),
+    ("_Thread_local", Keyword::ThreadLocal),
+];
+
+impl Keyword {
+    /// Look up the keyword spelled `word`, if any
+    pub fn lookup(word: &str) -> Option<Keyword> {
+        KEYWORDS
+            .iter()
+            .find(|(spelling, _)| *spelling == word)
+            .map(|(_, keyword)| *keyword)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        KEYWORDS
+            .iter()
+            .find(|(_, keyword)| *keyword == self)
+            .map(|(spelling, _)| *spelling)
+            .unwrap_or_default()
+    }
+}
+
+/// C11 punctuators; digraphs map onto the punctuator they spell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Punct {
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Dot,
+    Arrow,
+    PlusPlus,
+    MinusMinus,
+    Amp,
+    Star,
+    Plus,
+    Minus,
+    Tilde,
+    Bang,
+    Slash,
+    Percent,
+    LessLess,
+    GreaterGreater,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    EqualEqual,
+    BangEqual,
+    Caret,
+    Pipe,
+    AmpAmp,
+    PipePipe,
+    Question,
+    Colon,
+    Semicolon,
+    Ellipsis,
+    Equal,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    PlusEqual,
+    MinusEqual,
+    LessLessEqual,
+    GreaterGreaterEqual,
+    AmpEqual,
+    CaretEqual,
+    PipeEqual,
+    Comma,
+    Hash,
+    HashHash,
+}
+
+/// Punctuator spellings, longest first so that matching is greedy
+const PUNCTUATORS: &[(&str, Punct)] = &[
+    ("%:%:", Punct::HashHash),
+    ("...", Punct::Ellipsis),
+    ("<<=", Punct::LessLessEqual),
+    (">>=", Punct::GreaterGreaterEqual),
+    ("->", Punct::Arrow),
+    ("++", Punct::PlusPlus),
+    ("--", Punct::MinusMinus),
+    ("<<", Punct::LessLess),
+    (">>", Punct::GreaterGreater),
+    ("<=", Punct::LessEqual),
+    (">=", Punct::GreaterEqual),
+    ("==", Punct::EqualEqual),
+    ("!=", Punct::BangEqual),
+    ("&&", Punct::AmpAmp),
+    ("||", Punct::PipePipe),
+    ("*=", Punct::StarEqual),
+    ("/=", Punct::SlashEqual),
+    ("%=", Punct::PercentEqual),
+    ("+=", Punct::PlusEqual),
+    ("-=", Punct::MinusEqual),
+    ("&=", Punct::AmpEqual),
+    ("^=", Punct::CaretEqual),
+    ("|=", Punct::PipeEqual),
+    ("##", Punct::HashHash),
+    ("<:", Punct::LeftBracket),
+    (":>", Punct::RightBracket),
+    ("<%", Punct::LeftBrace),
+    ("%>", Punct::RightBrace),
+    ("%:", Punct::Hash),
+    ("[", Punct::LeftBracket),
+    ("]", Punct::RightBracket),
+    ("(", Punct::LeftParen),
+    (")", Punct::RightParen),
+    ("{", Punct::LeftBrace),
+    ("}", Punct::RightBrace),
+    (".", Punct::Dot),
+    ("&", Punct::Amp),
+    ("*", Punct::Star),
+    ("+", Punct::Plus),
+    ("-", Punct::Minus),
+    ("~", Punct::Tilde),
+    ("!", Punct::Bang),
+    ("/", Punct::Slash),
+    ("%", Punct::Percent),
+    ("<", Punct::Less),
+    (">", Punct::Greater),
+    ("^", Punct::Caret),
+    ("|", Punct::Pipe),
+    ("?", Punct::Question),
+    (":", Punct::Colon),
+    (";", Punct::Semicolon),
+    ("=", Punct::Equal),
+    (",", Punct::Comma),
+    ("#", Punct::Hash),
+];
+
+impl Punct {
+    /// The canonical (non-digraph) spelling of the punctuator
+    pub fn as_str(self) -> &'static str {
+        PUNCTUATORS
+            .iter()
+            .rev()
+            .find(|(_, punct)| *punct == self)
+            .map(|(spelling, _)| *spelling)
+            .unwrap_or_default()
+    }
+}
+
+/// Suffix of an integer constant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum IntSuffix {
+    None,
+    Unsigned,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+}
+
+/// Suffix of a floating constant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum FloatSuffix {
+    None,
+    Float,
+    Long,
+}
+
+/// Encoding prefix of a character constant or string literal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Encoding {
+    /// No prefix
+    Plain,
+    /// `u8"..."`
+    Utf8,
+    /// `u'...'` / `u"..."`
+    Utf16,
+    /// `U'...'` / `U"..."`
+    Utf32,
+    /// `L'...'` / `L"..."`
+    Wide,
+}
+
+impl Encoding {
+    /// The value of a character constant with these code units when plain
+    /// `char` is signed. Plain constants of several characters pack them into
+    /// an `int`, first character in the highest byte.
+    pub fn char_value(self, units: &[u32]) -> i64 {
+        match (self, units) {
+            (Encoding::Plain, [unit]) => *unit as u8 as i8 as i64,
+            (Encoding::Plain, _) => units.iter().fold(0u32, |acc, unit| (acc << 8) | (unit & 0xFF)) as i32 as i64,
+            (Encoding::Wide, _) => units.first().map_or(0, |&unit| unit as i32 as i64),
+            _ => units.first().map_or(0, |&unit| unit as i64),
+        }
+    }
+
+    /// The largest code unit this encoding can hold
+    fn max_unit(self) -> u32 {
+        match self {
+            Encoding::Plain | Encoding::Utf8 => 0xFF,
+            Encoding::Utf16 => 0xFFFF,
+            Encoding::Utf32 | Encoding::Wide => u32::MAX,
+        }
+    }
+
+    /// Append the code units of `c`
+    pub fn push_char(self, units: &mut Vec<u32>, c: char) {
+        match self {
+            Encoding::Plain | Encoding::Utf8 => {
+                let mut buf = [0; 4];
+                units.extend(c.encode_utf8(&mut buf).bytes().map(u32::from));
+            }
+            Encoding::Utf16 => {
+                let mut buf = [0; 2];
+                units.extend(c.encode_utf16(&mut buf).iter().map(|&unit| u32::from(unit)));
+            }
+            Encoding::Utf32 | Encoding::Wide => units.push(c as u32),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Identifier(String),
+    Keyword(Keyword),
+    IntLiteral {
+        value: u64,
+        radix: u32,
+        suffix: IntSuffix,
+    },
+    FloatLiteral {
+        value: f64,
+        suffix: FloatSuffix,
+    },
+    /// A character constant; `value` holds one code unit per element
+    CharLiteral {
+        encoding: Encoding,
+        value: Vec<u32>,
+    },
+    /// A string literal without its terminating NUL; `value` holds code units
+    StringLiteral {
+        encoding: Encoding,
+        value: Vec<u32>,
+    },
+    Punct(Punct),
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    /// The token exactly as spelled in the source
+    pub text: String,
+    /// The token is the first one on its source line
+    pub at_line_start: bool,
+    /// The token is preceded by whitespace or a comment
+    pub leading_space: bool,
+    /// Set by the preprocessor on identifiers that must not be expanded again
+    pub no_expand: bool,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TokenKind::Eof => write!(f, "end of file"),
+            _ => write!(f, "`{}`", self.text),
+        }
+    }
+}
+
+/// Tokenize a whole source file
+#[cfg(test)]
+pub fn tokenize(source: &str, file: FileId) -> Result<Vec<Token>, CompileError> {
+    Lexer::new(source, file).tokenize()
+}
+
+/// Converts C source text into tokens
+pub struct Lexer {
+    /// Source characters with line splices already removed
+    chars: Vec<(char, Location)>,
+    pos: usize,
+    file: FileId,
+    end: Location,
+    /// A newline was skipped since the last token (or this is the start of the file)
+    saw_newline: bool,
+    /// Whitespace or a comment was skipped since the last token
+    saw_space: bool,
+}
+
+impl Lexer {
+    pub fn new(source: &str, file: FileId) -> Self {
+        let mut chars = Vec::with_capacity(source.len());
+        let mut location = Location::default();
+        let mut iter = source.chars().peekable();
+
+        while let Some(c) = iter.next() {
+            // Translation phase 2: a backslash immediately followed by a newline is deleted
+            if c == '\\' {
+                let mut lookahead = iter.clone();
+                let splice = match lookahead.next() {
+                    Some('\n') => Some(1),
+                    Some('\r') if lookahead.next() == Some('\n') => Some(2),
+                    _ => None,
+                };
+                if let Some(skip) = splice {
+                    for _ in 0..skip {
+                        iter.next();
+                    }
+                    location.line += 1;
+                    location.column = 1;
+                    continue;
+                }
+            }
+
+            chars.push((c, location));
+            if c == '\n' {
+                location.line += 1;
+                location.column = 1;
+            } else {
+                location.column += 1;
+            }
+        }
+
+        Lexer {
+            chars,
+            pos: 0,
+            file,
+            end: location,
+            saw_newline: true,
+            saw_space: false,
+        }
+    }
+
+    /// Lex every token in the input, ending with an `Eof` token
+    #[cfg(test)]
+    pub fn tokenize(mut self) -> Result<Vec<Token>, CompileError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            let done = token.kind == TokenKind::Eof;
+            tokens.push(token);
+            if done {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).map(|&(c, _)| c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn location(&self) -> Location {
+        self.chars
+            .get(self.pos)
+            .map(|&(_, location)| location)
+            .unwrap_or(self.end)
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        let start_location = self
+            .chars
+            .get(start)
+            .map(|&(_, location)| location)
+            .unwrap_or(self.end);
+        let end_location = match self.pos.checked_sub(1).and_then(|last| self.chars.get(last)) {
+            Some(&(_, location)) if self.pos > start => Location {
+                line: location.line,
+                column: location.column + 1,
+            },
+            _ => start_location,
+        };
+        Span::new(self.file, start_location, end_location)
+    }
+
+    fn text_from(&self, start: usize) -> String {
+        self.chars[start..self.pos].iter().map(|&(c, _)| c).collect()
+    }
+
+    fn error(&self, message: impl Into<String>, start: usize) -> CompileError {
+        CompileError::new(message, self.span_from(start))
+    }
+
+    /// Skip whitespace and comments, stopping in front of a newline unless `cross_lines` is set
+    fn skip_trivia(&mut self, cross_lines: bool) -> Result<(), CompileError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some('\n'), _) if !cross_lines => return Ok(()),
+                (Some(c), _) if c.is_whitespace() => {
+                    if c == '\n' {
+                        self.saw_newline = true;
+                    }
+                    self.saw_space = true;
+                    self.pos += 1;
+                }
+                (Some('/'), Some('/')) => {
+                    self.saw_space = true;
+                    while let Some(c) = self.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.saw_space = true;
+                    self.skip_block_comment()?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), CompileError> {
+        let start = self.pos;
+        self.pos += 2;
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some('*'), Some('/')) => {
+                    self.pos += 2;
+                    return Ok(());
+                }
+                (Some(_), _) => self.pos += 1,
+                (None, _) => {
+                    self.pos = start + 2;
+                    return Err(self.error("unterminated comment", start));
+                }
+            }
+        }
+    }
+
+    /// Skip whitespace and comments on the current line and report whether
+    /// nothing but a newline (or the end of the file) follows
+    pub fn at_line_end(&mut self) -> Result<bool, CompileError> {
+        self.skip_trivia(false)?;
+        Ok(matches!(self.peek(), None | Some('\n')))
+    }
+
+    /// Lex a header name (`<stdio.h>` or `"file.h"`) if one follows on the
+    /// current line. Its characters are taken literally, so e.g.
+    /// `<stubs-64.h>` does not go through number or escape decoding.
+    pub fn header_name(&mut self) -> Result<Option<(String, bool, Span)>, CompileError> {
+        self.skip_trivia(false)?;
+        let close = match self.peek() {
+            Some('<') => '>',
+            Some('"') => '"',
+            _ => return Ok(None),
+        };
+        let start = self.pos;
+        let mut end = start + 1;
+        loop {
+            match self.chars.get(end) {
+                Some(&(c, _)) if c == close => break,
+                Some(&(c, _)) if c != '\n' => end += 1,
+                _ => return Ok(None),
+            }
+        }
+        self.pos = end + 1;
+        self.saw_space = false;
+        let name = self.chars[start + 1..end].iter().map(|&(c, _)| c).collect();
+        Ok(Some((name, close == '"', self.span_from(start))))
+    }
+
+    /// Report whether the whole input has been consumed
+    pub fn at_eof(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    /// Report whether the next token on the current line is `#` (or `%:`)
+    pub fn at_directive(&mut self) -> Result<bool, CompileError> {
+        self.skip_trivia(false)?;
+        Ok(matches!((self.peek(), self.peek_at(1)), (Some('#'), _) | (Some('%'), Some(':'))))
+    }
+
+    /// Discard the rest of the current line without tokenizing it, as done
+    /// for lines in skipped conditional groups. Comments are still honoured
+    /// since they may span lines, and quotes are skipped so that a `/*` in a
+    /// string does not start one.
+    pub fn skip_line(&mut self) -> Result<(), CompileError> {
+        while let Some(c) = self.peek() {
+            match (c, self.peek_at(1)) {
+                ('\n', _) => {
+                    self.pos += 1;
+                    self.saw_newline = true;
+                    self.saw_space = true;
+                    return Ok(());
+                }
+                ('/', Some('*')) => self.skip_block_comment()?,
+                ('/', Some('/')) => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.pos += 1;
+                    }
+                }
+                ('"' | '\'', _) => {
+                    self.pos += 1;
+                    while let Some(inner) = self.peek() {
+                        if inner == '\n' {
+                            break;
+                        }
+                        self.pos += 1;
+                        if inner == '\\' {
+                            if self.peek() != Some('\n') {
+                                self.pos += 1;
+                            }
+                        } else if inner == c {
+                            break;
+                        }
+                    }
+                }
+                _ => self.pos += 1,
+            }
+        }
+        Ok(())
+    }
+
+    /// Lex the next token, returning `Eof` once the input is exhausted
+    pub fn next_token(&mut self) -> Result<Token, CompileError> {
+        self.skip_trivia(true)?;
+        let at_line_start = std::mem::replace(&mut self.saw_newline, false);
+        let leading_space = std::mem::replace(&mut self.saw_space, false);
+
+        let start = self.pos;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => {
+                let location = self.location();
+                return Ok(Token {
+                    kind: TokenKind::Eof,
+                    span: Span::new(self.file, location, location),
+                    text: String::new(),
+                    at_line_start,
+                    leading_space,
+                    no_expand: false,
+                });
+            }
+        };
+
+        let kind = if let Some((encoding, prefix_len, quote)) = self.literal_prefix() {
+            self.pos += prefix_len;
+            if quote == '\'' {
+                self.lex_char_literal(start, encoding)?
+            } else {
+                self.lex_string_literal(start, encoding)?
+            }
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            self.lex_identifier()
+        } else if c.is_ascii_digit() || (c == '.' && self.peek_at(1).is_some_and(|c| c.is_ascii_digit())) {
+            self.lex_number(start)?
+        } else if let Some(punct) = self.lex_punct() {
+            TokenKind::Punct(punct)
+        } else {
+            self.pos += 1;
+            return Err(self.error(format!("unexpected character '{}'", c), start));
+        };
+
+        Ok(Token {
+            kind,
+            span: self.span_from(start),
+            text: self.text_from(start),
+            at_line_start,
+            leading_space,
+            no_expand: false,
+        })
+    }
+
+    /// Detect the start of a character constant or string literal,
+    /// returning its encoding, prefix length and opening quote
+    fn literal_prefix(&self) -> Option<(Encoding, usize, char)> {
+        let quote_at = |offset: usize| match self.peek_at(offset) {
+            Some(q @ ('\'' | '"')) => Some(q),
+            _ => None,
+        };
+        match self.peek()? {
+            '\'' | '"' => Some((Encoding::Plain, 0, self.peek()?)),
+            'L' => quote_at(1).map(|q| (Encoding::Wide, 1, q)),
+            'U' => quote_at(1).map(|q| (Encoding::Utf32, 1, q)),
+            'u' if self.peek_at(1) == Some('8') => match quote_at(2) {
+                Some('"') => Some((Encoding::Utf8, 2, '"')),
+                _ => None,
+            },
+            'u' => quote_at(1).map(|q| (Encoding::Utf16, 1, q)),
+            _ => None,
+        }
+    }
+
+    fn lex_identifier(&mut self) -> TokenKind {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let word = self.text_from(start);
+        match Keyword::lookup(&word) {
+            Some(keyword) => TokenKind::Keyword(keyword),
+            None => TokenKind::Identifier(word),
+        }
+    }
+
+    fn lex_punct(&mut self) -> Option<Punct> {
+        for (spelling, punct) in PUNCTUATORS {
+            let matches = spelling
+                .chars()
+                .enumerate()
+                .all(|(i, expected)| self.peek_at(i) == Some(expected));
+            if matches {
+                self.pos += spelling.len();
+                return Some(*punct);
+            }
+        }
+        None
+    }
+
+    /// Lex a preprocessing number and decode it as an integer or floating constant
+    fn lex_number(&mut self, start: usize) -> Result<TokenKind, CompileError> {
+        while let Some(c) = self.peek() {
+            if matches!(c, 'e' | 'E' | 'p' | 'P') && matches!(self.peek_at(1), Some('+' | '-')) {
+                self.pos += 2;
+            } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        let text = self.text_from(start);
+        decode_number(&text).map_err(|message| self.error(message, start))
+    }
+
+    fn lex_char_literal(&mut self, start: usize, encoding: Encoding) -> Result<TokenKind, CompileError> {
+        let value = self.lex_quoted(start, encoding, '\'')?;
+        if value.is_empty() {
+            return Err(self.error("empty character constant", start));
+        }
+        Ok(TokenKind::CharLiteral { encoding, value })
+    }
+
+    fn lex_string_literal(&mut self, start: usize, encoding: Encoding) -> Result<TokenKind, CompileError> {
+        let value = self.lex_quoted(start, encoding, '"')?;
+        Ok(TokenKind::StringLiteral { encoding, value })
+    }
+
+    /// Lex the body of a quoted literal, decoding escapes into code units
+    fn lex_quoted(&mut self, start: usize, encoding: Encoding, quote: char) -> Result<Vec<u32>, CompileError> {
+        self.bump();
+        let mut units = Vec::new();
+        loop {
+            match self.peek() {
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(units);
+                }
+                Some('\\') => self.lex_escape(encoding, &mut units)?,
+                Some('\n') | None => {
+                    let what = if quote == '"' { "string literal" } else { "character constant" };
+                    return Err(self.error(format!("unterminated {}", what), start));
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    encoding.push_char(&mut units, c);
+                }
+            }
+        }
+    }
+
+    fn lex_escape(&mut self, encoding: Encoding, units: &mut Vec<u32>) -> Result<(), CompileError> {
+        let start = self.pos;
+        self.bump();
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Err(self.error("incomplete escape sequence", start)),
+        };
+
+        let simple = match c {
+            '\'' => Some(0x27),
+            '"' => Some(0x22),
+            '?' => Some(0x3F),
+            '\\' => Some(0x5C),
+            'a' => Some(0x07),
+            'b' => Some(0x08),
+            'f' => Some(0x0C),
+            'n' => Some(0x0A),
+            'r' => Some(0x0D),
+            't' => Some(0x09),
+            'v' => Some(0x0B),
+            _ => None,
+        };
+        if let Some(value) = simple {
+            units.push(value);
+            return Ok(());
+        }
+
+        match c {
+            '0'..='7' => {
+                let mut value = c.to_digit(8).unwrap_or_default();
+                for _ in 0..2 {
+                    match self.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            self.pos += 1;
+                        }
+                        None => break,
+                    }
+                }
+                self.push_raw_unit(encoding, units, u64::from(value), start)
+            }
+            'x' => {
+                let mut value: u64 = 0;
+                let mut digits = 0;
+                while let Some(digit) = self.peek().and_then(|c| c.to_digit(16)) {
+                    value = value.saturating_mul(16).saturating_add(u64::from(digit));
+                    digits += 1;
+                    self.pos += 1;
+                }
+                if digits == 0 {
+                    return Err(self.error("\\x used with no following hex digits", start));
+                }
+                self.push_raw_unit(encoding, units, value, start)
+            }
+            'u' | 'U' => {
+                let count = if c == 'u' { 4 } else { 8 };
+                let mut value = 0;
+                for _ in 0..count {
+                    match self.peek().and_then(|c| c.to_digit(16)) {
+                        Some(digit) => {
+                            value = value * 16 + digit;
+                            self.pos += 1;
+                        }
+                        None => return Err(self.error("incomplete universal character name", start)),
+                    }
+                }
+                match char::from_u32(value) {
+                    Some(c) => {
+                        encoding.push_char(units, c);
+                        Ok(())
+                    }
+                    None => Err(self.error("invalid universal character", start)),
+                }
+            }
+            _ => Err(self.error(format!("unknown escape sequence '\\{}'", c), start)),
+        }
+    }
+
+    fn push_raw_unit(
+        &self,
+        encoding: Encoding,
+        units: &mut Vec<u32>,
+        value: u64,
+        start: usize,
+    ) -> Result<(), CompileError> {
+        if value > u64::from(encoding.max_unit()) {
+            return Err(self.error("escape sequence out of range", start));
+        }
+        units.push(value as u32);
+        Ok(())
+    }
+}
+
+/// Decode the spelling of a preprocessing number
+fn decode_number(text: &str) -> Result<TokenKind, String> {
+    let lower = text.to_ascii_lowercase();
+    let is_hex = lower.starts_with("0x");
+    let is_float = if is_hex {
+        lower.contains('.') || lower.contains('p')
+    } else {
+        lower.contains('.') || lower.contains('e')
+    };
+
+    if is_float {
+        decode_float(text, is_hex)
+    } else {
+        decode_integer(text, is_hex)
+    }
+}
+
+fn decode_integer(text: &str, is_hex: bool) -> Result<TokenKind, String> {
+    let (radix, digits_start) = if is_hex {
+        (16, 2)
+    } else if text.starts_with("0b") || text.starts_with("0B") {
+        (2, 2)
+    } else if text.starts_with('0') {
+        (8, 0)
+    } else {
+        (10, 0)
+    };
+
+    let body = &text[digits_start..];
+    let digits_len = body
+        .find(|c: char| !(c.is_ascii_digit() || radix == 16 && c.is_ascii_hexdigit()))
+        .unwrap_or(body.len());
+    let (digits, suffix) = body.split_at(digits_len);
+
+    if digits_start > 0 && digits.is_empty() {
+        return Err(format!("invalid suffix '{}' on integer constant", &text[1..]));
+    }
+
+    let mut value: u64 = 0;
+    for c in digits.chars() {
+        let digit = c.to_digit(radix).ok_or_else(|| {
+            let base = if radix == 2 { "binary" } else { "octal" };
+            format!("invalid digit '{}' in {} constant", c, base)
+        })?;
+        value = value
+            .checked_mul(u64::from(radix))
+            .and_then(|value| value.checked_add(u64::from(digit)))
+            .ok_or_else(|| "integer constant is too large".to_string())?;
+    }
+
+    let suffix = parse_int_suffix(suffix)
+        .ok_or_else(|| format!("invalid suffix '{}' on integer constant", suffix))?;
+
+    Ok(TokenKind::IntLiteral { value, radix, suffix })
+}
+
+fn parse_int_suffix(suffix: &str) -> Option<IntSuffix> {
+    let (unsigned, rest) = if let Some(rest) = suffix.strip_prefix(['u', 'U']) {
+        (true, rest)
+    } else if let Some(rest) = suffix.strip_suffix(['u', 'U']) {
+        (true, rest)
+    } else {
+        (false, suffix)
+    };
+
+    let suffix = match (unsigned, rest) {
+        (false, "") => IntSuffix::None,
+        (true, "") => IntSuffix::Unsigned,
+        (false, "l" | "L") => IntSuffix::Long,
+        (true, "l" | "L") => IntSuffix::UnsignedLong,
+        (false, "ll" | "LL") => IntSuffix::LongLong,
+        (true, "ll" | "LL") => IntSuffix::UnsignedLongLong,
+        _ => return None,
+    };
+    Some(suffix)
+}
+
+fn decode_float(text: &str, is_hex: bool) -> Result<TokenKind, String> {
+    let (body, suffix) = match text.chars().last() {
+        Some('f' | 'F') if !is_hex || text.to_ascii_lowercase().contains('p') => {
+            (&text[..text.len() - 1], FloatSuffix::Float)
+        }
+        Some('l' | 'L') => (&text[..text.len() - 1], FloatSuffix::Long),
+        _ => (text, FloatSuffix::None),
+    };
+
+    let value = if is_hex {
+        decode_hex_float(&body[2..])?
+    } else {
+        if body.ends_with(['e', 'E', '+', '-']) {
+            return Err("exponent has no digits".to_string());
+        }
+        body.parse::<f64>()
+            .map_err(|_| format!("invalid floating constant '{}'", text))?
+    };
+
+    Ok(TokenKind::FloatLiteral { value, suffix })
+}
+
+/// Decode the part of a hexadecimal floating constant after `0x`.
+///
+/// The digits are collected into an integer mantissa and a binary exponent,
+/// then rounded to nearest-even exactly once, so the result is the correctly
+/// rounded `double`, including subnormals.
+fn decode_hex_float(body: &str) -> Result<f64, String> {
+    let (digits, exponent) = body
+        .split_once(['p', 'P'])
+        .ok_or_else(|| "hexadecimal floating constant requires an exponent".to_string())?;
+
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err("hexadecimal floating constant has no digits".to_string());
+    }
+
+    // Keep at least 60 significant bits; digits past that only matter for
+    // breaking ties, so they are folded into a sticky bit
+    let mut mantissa = 0u64;
+    let mut scale = 0i64;
+    let mut sticky = false;
+    for (index, c) in whole.chars().chain(fraction.chars()).enumerate() {
+        let digit = c
+            .to_digit(16)
+            .ok_or_else(|| format!("invalid digit '{}' in hexadecimal floating constant", c))?;
+        let in_fraction = index >= whole.len();
+        if mantissa < 1 << 60 {
+            mantissa = mantissa * 16 + u64::from(digit);
+            if in_fraction {
+                scale -= 4;
+            }
+        } else {
+            sticky |= digit != 0;
+            if !in_fraction {
+                scale += 4;
+            }
+        }
+    }
+
+    let (negative, magnitude) = match exponent.as_bytes().first() {
+        Some(b'-') => (true, &exponent[1..]),
+        Some(b'+') => (false, &exponent[1..]),
+        _ => (false, exponent),
+    };
+    if magnitude.is_empty() || !magnitude.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("exponent has no digits".to_string());
+    }
+    // Anything past this overflows or underflows whatever the digits are
+    let magnitude = magnitude
+        .bytes()
+        .fold(0i64, |value, b| (value * 10 + i64::from(b - b'0')).min(1 << 20));
+    let exponent = scale + if negative { -magnitude } else { magnitude };
+
+    if mantissa == 0 {
+        return Ok(0.0);
+    }
+
+    // The value is mantissa * 2^exponent. Round it to 53 significant bits,
+    // or fewer when it falls in the subnormal range below 2^-1022.
+    let bits = i64::from(64 - mantissa.leading_zeros());
+    let quantum = (exponent + bits - 53).max(-1074);
+    let shift = quantum - exponent;
+    let rounded = if shift <= 0 {
+        mantissa << -shift
+    } else if shift > 65 {
+        0
+    } else {
+        let wide = u128::from(mantissa);
+        let kept = (wide >> shift) as u64;
+        let rest = wide & ((1u128 << shift) - 1);
+        let half = 1u128 << (shift - 1);
+        if rest > half || (rest == half && (sticky || kept & 1 == 1)) {
+            kept + 1
+        } else {
+            kept
+        }
+    };
+
+    Ok(scale_by_power_of_two(rounded as f64, quantum))
+}
+
+/// `value * 2^exponent` for a `value` whose product is representable, so
+/// every step is exact
+fn scale_by_power_of_two(mut value: f64, mut exponent: i64) -> f64 {
+    let power = |exponent: i64| f64::from_bits(((exponent + 1023) as u64) << 52);
+    while exponent > 1023 {
+        value *= power(1023);
+        exponent -= 1023;
+        if value.is_infinite() {
+            return value;
+        }
+    }
+    while exponent < -1022 {
+        value *= power(-1022);
+        exponent += 1022;
+    }
+    value * power(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<TokenKind> {
+        tokenize(source, FileId(0))
+            .expect("lexing failed")
+            .into_iter()
+            .map(|token| token.kind)
+            .filter(|kind| *kind != TokenKind::Eof)
+            .collect()
+    }
+
+    fn lex_one(source: &str) -> TokenKind {
+        let mut kinds = lex(source);
+        assert_eq!(kinds.len(), 1, "expected a single token for {:?}: {:?}", source, kinds);
+        kinds.remove(0)
+    }
+
+    fn lex_error(source: &str) -> CompileError {
+        tokenize(source, FileId(0)).expect_err("lexing should fail")
+    }
+
+    fn int(value: u64, radix: u32, suffix: IntSuffix) -> TokenKind {
+        TokenKind::IntLiteral { value, radix, suffix }
+    }
+
+    fn float(value: f64, suffix: FloatSuffix) -> TokenKind {
+        TokenKind::FloatLiteral { value, suffix }
+    }
+
+    fn string(encoding: Encoding, value: &[u32]) -> TokenKind {
+        TokenKind::StringLiteral {
+            encoding,
+            value: value.to_vec(),
+        }
+    }
+
+    fn chars(encoding: Encoding, value: &[u32]) -> TokenKind {
+        TokenKind::CharLiteral {
+            encoding,
+            value: value.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_keywords_and_identifiers() {
+        assert_eq!(
+            lex("int main _Bool _Static_assert integer _x1"),
+            vec![
+                TokenKind::Keyword(Keyword::Int),
+                TokenKind::Identifier("main".to_string()),
+                TokenKind::Keyword(Keyword::Bool),
+                TokenKind::Keyword(Keyword::StaticAssert),
+                TokenKind::Identifier("integer".to_string()),
+                TokenKind::Identifier("_x1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_every_keyword_round_trips() {
+        for (spelling, keyword) in KEYWORDS {
+            assert_eq!(lex_one(spelling), TokenKind::Keyword(*keyword));
+            assert_eq!(keyword.as_str(), *spelling);
+        }
+    }
+
+    #[test]
+    fn test_integer_literals() {
+        assert_eq!(lex_one("0"), int(0, 8, IntSuffix::None));
+        assert_eq!(lex_one("42"), int(42, 10, IntSuffix::None));
+        assert_eq!(lex_one("0x1F"), int(31, 16, IntSuffix::None));
+        assert_eq!(lex_one("0755"), int(0o755, 8, IntSuffix::None));
+        assert_eq!(lex_one("10u"), int(10, 10, IntSuffix::Unsigned));
+        assert_eq!(lex_one("10l"), int(10, 10, IntSuffix::Long));
+        assert_eq!(lex_one("10UL"), int(10, 10, IntSuffix::UnsignedLong));
+        assert_eq!(lex_one("10lu"), int(10, 10, IntSuffix::UnsignedLong));
+        assert_eq!(lex_one("10LL"), int(10, 10, IntSuffix::LongLong));
+        assert_eq!(lex_one("10ULL"), int(10, 10, IntSuffix::UnsignedLongLong));
+        assert_eq!(lex_one("0xFFllu"), int(255, 16, IntSuffix::UnsignedLongLong));
+        assert_eq!(lex_one("0b1010"), int(10, 2, IntSuffix::None));
+        assert_eq!(lex_one("0B11u"), int(3, 2, IntSuffix::Unsigned));
+        assert_eq!(
+            lex_one("18446744073709551615u"),
+            int(u64::MAX, 10, IntSuffix::Unsigned)
+        );
+    }
+
+    #[test]
+    fn test_invalid_integer_literals() {
+        assert!(lex_error("089").message.contains("octal"));
+        assert!(lex_error("10lL").message.contains("invalid suffix"));
+        assert!(lex_error("10uu").message.contains("invalid suffix"));
+        assert!(lex_error("0x").message.contains("invalid suffix"));
+        assert!(lex_error("0b").message.contains("invalid suffix"));
+        assert!(lex_error("0b102").message.contains("binary"));
+        assert!(lex_error("18446744073709551616").message.contains("too large"));
+    }
+
+    #[test]
+    fn test_float_literals() {
+        assert_eq!(lex_one("2.75"), float(2.75, FloatSuffix::None));
+        assert_eq!(lex_one("2.75f"), float(2.75, FloatSuffix::Float));
+        assert_eq!(lex_one("2.5L"), float(2.5, FloatSuffix::Long));
+        assert_eq!(lex_one(".5"), float(0.5, FloatSuffix::None));
+        assert_eq!(lex_one("1."), float(1.0, FloatSuffix::None));
+        assert_eq!(lex_one("1e3"), float(1000.0, FloatSuffix::None));
+        assert_eq!(lex_one("1.5E-2F"), float(0.015, FloatSuffix::Float));
+        assert_eq!(lex_one("0x1p4"), float(16.0, FloatSuffix::None));
+        assert_eq!(lex_one("0x1.8p1"), float(3.0, FloatSuffix::None));
+        assert_eq!(lex_one("0X.8P+0f"), float(0.5, FloatSuffix::Float));
+        assert_eq!(lex_one("0xAp-2L"), float(2.5, FloatSuffix::Long));
+    }
+
+    #[test]
+    fn test_hex_float_rounding() {
+        let hex = |text: &str| match lex_one(text) {
+            TokenKind::FloatLiteral { value, .. } => value,
+            other => panic!("expected a floating constant, got {:?}", other),
+        };
+        // Subnormals keep their value instead of flushing to zero
+        assert_eq!(hex("0x1p-1074"), f64::from_bits(1));
+        assert_eq!(hex("0x1p-1060"), f64::from_bits(1 << 14));
+        assert_eq!(hex("0x1p-1075"), 0.0);
+        assert_eq!(hex("0x1.8p-1075"), f64::from_bits(1));
+        assert_eq!(hex("0x1.fffffffffffffp1023"), f64::MAX);
+        assert_eq!(hex("0x1p1024"), f64::INFINITY);
+        // Digits past the 53rd bit round to nearest, ties to even
+        assert_eq!(hex("0x1.0000000000000801p0"), 1.0 + f64::EPSILON);
+        assert_eq!(hex("0x1.00000000000008p0"), 1.0);
+        assert_eq!(hex("0x1.00000000000018p0"), 1.0 + 2.0 * f64::EPSILON);
+        assert_eq!(hex("0x10000000000000000001p-76"), 1.0);
+        assert_eq!(hex("0x0.0000000000000000000001p88"), 1.0);
+    }
+
+    #[test]
+    fn test_invalid_float_literals() {
+        assert!(lex_error("1e").message.contains("exponent"));
+        assert!(lex_error("1e+").message.contains("exponent"));
+        assert!(lex_error("0x1.8").message.contains("requires an exponent"));
+        assert!(lex_error("1.0q").message.contains("invalid floating constant"));
+    }
+
+    #[test]
+    fn test_char_literals() {
+        assert_eq!(lex_one("'A'"), chars(Encoding::Plain, &[65]));
+        assert_eq!(lex_one("'\\n'"), chars(Encoding::Plain, &[10]));
+        assert_eq!(lex_one("'\\0'"), chars(Encoding::Plain, &[0]));
+        assert_eq!(lex_one("'\\''"), chars(Encoding::Plain, &[39]));
+        assert_eq!(lex_one("'\\x41'"), chars(Encoding::Plain, &[0x41]));
+        assert_eq!(lex_one("'\\101'"), chars(Encoding::Plain, &[0o101]));
+        assert_eq!(lex_one("'ab'"), chars(Encoding::Plain, &[97, 98]));
+        assert_eq!(lex_one("L'x'"), chars(Encoding::Wide, &[120]));
+        assert_eq!(lex_one("u'\\u00e9'"), chars(Encoding::Utf16, &[0xE9]));
+        assert_eq!(lex_one("U'\\U0001F600'"), chars(Encoding::Utf32, &[0x1F600]));
+    }
+
+    #[test]
+    fn test_string_literals() {
+        assert_eq!(lex_one("\"hi\\n\""), string(Encoding::Plain, &[104, 105, 10]));
+        assert_eq!(lex_one("\"\""), string(Encoding::Plain, &[]));
+        assert_eq!(lex_one("\"a\\0b\""), string(Encoding::Plain, &[97, 0, 98]));
+        assert_eq!(lex_one("\"\\\"q\\\"\""), string(Encoding::Plain, &[34, 113, 34]));
+        assert_eq!(lex_one("u8\"\u{e9}\""), string(Encoding::Utf8, &[0xC3, 0xA9]));
+        assert_eq!(lex_one("\"\\u00e9\""), string(Encoding::Plain, &[0xC3, 0xA9]));
+        assert_eq!(lex_one("\"\\xff\""), string(Encoding::Plain, &[0xFF]));
+        assert_eq!(lex_one("u\"\\U0001F600\""), string(Encoding::Utf16, &[0xD83D, 0xDE00]));
+        assert_eq!(lex_one("U\"z\""), string(Encoding::Utf32, &[122]));
+        assert_eq!(lex_one("L\"\\x1234\""), string(Encoding::Wide, &[0x1234]));
+    }
+
+    #[test]
+    fn test_prefix_letters_are_identifiers_without_quotes() {
+        assert_eq!(
+            lex("L u U u8 Lx"),
+            vec![
+                TokenKind::Identifier("L".to_string()),
+                TokenKind::Identifier("u".to_string()),
+                TokenKind::Identifier("U".to_string()),
+                TokenKind::Identifier("u8".to_string()),
+                TokenKind::Identifier("Lx".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_literals() {
+        assert!(lex_error("\"abc").message.contains("unterminated string literal"));
+        assert!(lex_error("'a\n'").message.contains("unterminated character constant"));
+        assert!(lex_error("''").message.contains("empty character constant"));
+        assert!(lex_error("\"\\q\"").message.contains("unknown escape sequence"));
+        assert!(lex_error("\"\\x100\"").message.contains("out of range"));
+        assert!(lex_error("'\\u12'").message.contains("universal character"));
+    }
+
+    #[test]
+    fn test_every_punctuator() {
+        for (spelling, punct) in PUNCTUATORS {
+            assert_eq!(lex_one(spelling), TokenKind::Punct(*punct), "{}", spelling);
+        }
+    }
+
+    #[test]
+    fn test_punctuators_are_greedy() {
+        let puncts = |source| {
+            lex(source)
+                .into_iter()
+                .map(|kind| match kind {
+                    TokenKind::Punct(punct) => punct.as_str(),
+                    other => panic!("unexpected token {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(puncts("+++"), vec!["++", "+"]);
+        assert_eq!(puncts("<<=<="), vec!["<<=", "<="]);
+        assert_eq!(puncts("->-"), vec!["->", "-"]);
+        assert_eq!(puncts(".. ..."), vec![".", ".", "..."]);
+        assert_eq!(puncts("<::><%%>%:%:"), vec!["[", "]", "{", "}", "##"]);
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        assert_eq!(
+            lex("a // line comment\n/* block\ncomment */ b /**/c"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Identifier("b".to_string()),
+                TokenKind::Identifier("c".to_string()),
+            ]
+        );
+        assert_eq!(lex("a/**/b").len(), 2);
+        assert_eq!(lex("x / y").len(), 3);
+        assert!(lex_error("/* never closed").message.contains("unterminated comment"));
+    }
+
+    #[test]
+    fn test_line_splices() {
+        assert_eq!(
+            lex("in\\\nt x; // comment \\\n continues"),
+            vec![
+                TokenKind::Keyword(Keyword::Int),
+                TokenKind::Identifier("x".to_string()),
+                TokenKind::Punct(Punct::Semicolon),
+            ]
+        );
+        assert_eq!(lex_one("\"ab\\\r\ncd\""), string(Encoding::Plain, &[97, 98, 99, 100]));
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokens = tokenize("int x;\n  return 42;", FileId(3)).unwrap();
+        let spans: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.text.as_str(),
+                    token.span.start.line,
+                    token.span.start.column,
+                    token.span.end.column,
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("int", 1, 1, 4),
+                ("x", 1, 5, 6),
+                (";", 1, 6, 7),
+                ("return", 2, 3, 9),
+                ("42", 2, 10, 12),
+                (";", 2, 12, 13),
+                ("", 2, 13, 13),
+            ]
+        );
+        assert!(tokens.iter().all(|token| token.span.file == FileId(3)));
+    }
+
+    #[test]
+    fn test_line_start_and_leading_space() {
+        let tokens = tokenize("#define f(x)x\n  a/**/b /* multi\nline */ c", FileId(0)).unwrap();
+        let flags: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.text.as_str(), token.at_line_start, token.leading_space))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("#", true, false),
+                ("define", false, false),
+                ("f", false, true),
+                ("(", false, false),
+                ("x", false, false),
+                (")", false, false),
+                ("x", false, false),
+                ("a", true, true),
+                ("b", false, true),
+                ("c", false, true),
+                ("", false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_line_ignores_invalid_tokens() {
+        let mut lexer = Lexer::new("it's @ \"/* not a comment\n/* a\ncomment */ x\nnext", FileId(0));
+        lexer.skip_line().unwrap();
+        lexer.skip_line().unwrap();
+        let token = lexer.next_token().unwrap();
+        assert_eq!((token.text.as_str(), token.at_line_start), ("next", true));
+    }
+
+    #[test]
+    fn test_error_location() {
+        let error = lex_error("int x;\nint @y;");
+        assert_eq!(error.message, "unexpected character '@'");
+        assert_eq!((error.span.start.line, error.span.start.column), (2, 5));
+    }
+
+    #[test]
+    fn test_token_text_preserves_spelling() {
+        let tokens = tokenize("0x10UL <: L'a'", FileId(0)).unwrap();
+        let texts: Vec<_> = tokens.iter().map(|token| token.text.as_str()).collect();
+        assert_eq!(texts, vec!["0x10UL", "<:", "L'a'", ""]);
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let source = "#include <stdio.h>\n\nint main() {\n    printf(\"Hello, World!\\n\");\n    return 0;\n}\n";
+        let kinds = lex(source);
+        assert_eq!(kinds[0], TokenKind::Punct(Punct::Hash));
+        assert_eq!(kinds[1], TokenKind::Identifier("include".to_string()));
+        assert!(kinds.contains(&string(
+            Encoding::Plain,
+            &"Hello, World!\n".bytes().map(u32::from).collect::<Vec<_>>()
+        )));
+        assert_eq!(kinds.last(), Some(&TokenKind::Punct(Punct::RightBrace)));
+    }
+}
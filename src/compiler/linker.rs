@@ -0,0 +1,317 @@
+//! Linking object files into an executable.
+//!
+//! `tc` does not ship a linker. It drives the system's C compiler (`cc`,
+//! `clang` or `gcc`), which knows where the C runtime start-up objects and
+//! libc live. Without one it runs `ld.lld` or `ld` itself, handing it the
+//! start-up objects found in the usual glibc directories. On Windows the
+//! MSVC-style `lld-link` or `link` is run with the universal CRT.
+//!
+//! For another architecture the host's `cc` will not do. Debian's cross
+//! compiler (`aarch64-linux-gnu-gcc`, ...) is preferred, then `clang` given
+//! the target triple, then `ld.lld` with the start-up objects of the
+//! target's cross-compiled glibc.
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::target::{is_host, TargetInfo};
+use crate::utils::CompilerOptions;
+
+/// How a linker is invoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flavor {
+    /// A C compiler driver, which adds the C runtime and libc itself
+    Driver,
+    /// A GNU-style `ld`, which is given the C runtime explicitly
+    Gnu,
+    /// An MSVC-style `link.exe`
+    Msvc,
+}
+
+/// The linkers to look for on the `PATH`, most preferred first
+const UNIX_LINKERS: &[(&str, Flavor)] = &[
+    ("cc", Flavor::Driver),
+    ("clang", Flavor::Driver),
+    ("gcc", Flavor::Driver),
+    ("ld.lld", Flavor::Gnu),
+    ("ld", Flavor::Gnu),
+];
+const WINDOWS_LINKERS: &[(&str, Flavor)] =
+    &[("clang", Flavor::Driver), ("lld-link", Flavor::Msvc), ("link", Flavor::Msvc)];
+
+/// Directories glibc installs its start-up objects and libraries in
+const GLIBC_LIBRARY_DIRS: &[&str] =
+    &["/usr/lib/{multiarch}", "/lib/{multiarch}", "/usr/lib64", "/lib64", "/usr/lib", "/lib"];
+/// Directories a cross-compiled glibc is installed in
+const CROSS_GLIBC_LIBRARY_DIRS: &[&str] = &["/usr/{multiarch}/lib", "/usr/lib/{multiarch}", "/lib/{multiarch}"];
+
+#[derive(Debug)]
+struct Linker {
+    path: PathBuf,
+    flavor: Flavor,
+}
+
+/// The C runtime a GNU-style linker is given explicitly
+#[derive(Debug)]
+struct StartFiles {
+    /// `crt1.o` and `crti.o`, then GCC's `crtbegin.o` if it is installed
+    begin: Vec<PathBuf>,
+    /// GCC's `crtend.o` if it is installed, then `crtn.o`
+    end: Vec<PathBuf>,
+    library_dirs: Vec<PathBuf>,
+    dynamic_linker: String,
+}
+
+/// Link `objects` and the C library into the executable `output`
+pub fn link(objects: &[PathBuf], output: &Path, options: &CompilerOptions, target: &TargetInfo) -> Result<()> {
+    let host = is_host(options);
+    let cross_linkers = cross_linkers(target);
+    let candidates: Vec<(&str, Flavor)> = if host {
+        (if target.is_windows() { WINDOWS_LINKERS } else { UNIX_LINKERS }).to_vec()
+    } else {
+        cross_linkers.iter().map(|(name, flavor)| (name.as_str(), *flavor)).collect()
+    };
+    if candidates.is_empty() {
+        bail!("Linking for '{}' is not supported; use --emit=obj to stop at object files", target.triple);
+    }
+    let linker = find_linker(&candidates)?;
+    let arguments = match linker.flavor {
+        Flavor::Driver => {
+            // Unlike GCC, one `clang` links for every target
+            let is_clang = linker.path.file_stem().is_some_and(|stem| stem.to_string_lossy().starts_with("clang"));
+            let mut arguments = if is_clang { vec![format!("--target={}", target.triple).into()] } else { Vec::new() };
+            arguments.extend(driver_arguments(objects, output, options));
+            arguments
+        }
+        Flavor::Gnu => gnu_arguments(objects, output, options, &StartFiles::find(target, host)?),
+        Flavor::Msvc => msvc_arguments(objects, output, options),
+    };
+    debug!("Linking with {} {:?}", linker.path.display(), arguments);
+
+    let result = Command::new(&linker.path)
+        .args(&arguments)
+        .output()
+        .with_context(|| format!("Failed to run linker '{}'", linker.path.display()))?;
+    if !result.status.success() {
+        let mut message = String::from_utf8_lossy(&result.stderr).into_owned();
+        // MSVC's link reports errors on standard output
+        message.push_str(&String::from_utf8_lossy(&result.stdout));
+        bail!("Linker '{}' failed ({}):\n{}", linker.path.display(), result.status, message.trim_end());
+    }
+    Ok(())
+}
+
+/// The linkers that can link for `target` from another architecture, most
+/// preferred first
+fn cross_linkers(target: &TargetInfo) -> Vec<(String, Flavor)> {
+    if target.is_wasm() {
+        return Vec::new();
+    }
+    let mut linkers = Vec::new();
+    if let Some(multiarch) = target.multiarch() {
+        linkers.push((format!("{}-gcc", multiarch), Flavor::Driver));
+    }
+    linkers.push(("clang".to_string(), Flavor::Driver));
+    if target.is_linux() {
+        linkers.push(("ld.lld".to_string(), Flavor::Gnu));
+    }
+    linkers
+}
+
+/// The first of `candidates` found on the `PATH`
+fn find_linker(candidates: &[(&str, Flavor)]) -> Result<Linker> {
+    for (name, flavor) in candidates {
+        if let Ok(path) = which::which(name) {
+            return Ok(Linker { path, flavor: *flavor });
+        }
+    }
+    let names: Vec<String> = candidates.iter().map(|(name, _)| format!("'{}'", name)).collect();
+    bail!(
+        "No linker found: looked for {} on the PATH. Install a C compiler or a linker to build executables",
+        names.join(", ")
+    )
+}
+
+fn driver_arguments(objects: &[PathBuf], output: &Path, options: &CompilerOptions) -> Vec<OsString> {
+    let mut arguments: Vec<OsString> = vec!["-o".into(), output.into()];
+    arguments.extend(objects.iter().map(OsString::from));
+    arguments.extend(search_and_libraries(options));
+    arguments
+}
+
+fn gnu_arguments(objects: &[PathBuf], output: &Path, options: &CompilerOptions, start: &StartFiles) -> Vec<OsString> {
+    let mut arguments: Vec<OsString> = vec![
+        "-o".into(),
+        output.into(),
+        "--eh-frame-hdr".into(),
+        "-dynamic-linker".into(),
+        start.dynamic_linker.clone().into(),
+    ];
+    arguments.extend(start.begin.iter().map(OsString::from));
+    arguments.extend(objects.iter().map(OsString::from));
+    // The project's directories are searched before the system's
+    arguments.extend(search_and_libraries(options));
+    arguments.extend(start.library_dirs.iter().map(|dir| OsString::from(format!("-L{}", dir.display()))));
+    arguments.push("-lc".into());
+    arguments.extend(start.end.iter().map(OsString::from));
+    arguments
+}
+
+fn msvc_arguments(objects: &[PathBuf], output: &Path, options: &CompilerOptions) -> Vec<OsString> {
+    let mut arguments: Vec<OsString> =
+        vec!["/NOLOGO".into(), "/SUBSYSTEM:CONSOLE".into(), format!("/OUT:{}", output.display()).into()];
+    arguments.extend(objects.iter().map(OsString::from));
+    for dir in &options.library_paths {
+        arguments.push(format!("/LIBPATH:{}", dir.display()).into());
+    }
+    for library in &options.libraries {
+        arguments.push(format!("{}.lib", library).into());
+    }
+    // The C runtime; the stdio functions are inline in the UCRT headers and
+    // only exported by `legacy_stdio_definitions`
+    for library in ["msvcrt", "ucrt", "vcruntime", "legacy_stdio_definitions"] {
+        arguments.push(format!("/DEFAULTLIB:{}", library).into());
+    }
+    arguments
+}
+
+/// `-L` for the project's library directories, then `-l` for its libraries
+fn search_and_libraries(options: &CompilerOptions) -> Vec<OsString> {
+    let mut arguments: Vec<OsString> =
+        options.library_paths.iter().map(|dir| OsString::from(format!("-L{}", dir.display()))).collect();
+    arguments.extend(options.libraries.iter().map(|library| OsString::from(format!("-l{}", library))));
+    arguments
+}
+
+impl StartFiles {
+    /// Find glibc's start-up objects for `target` on this machine, or those of
+    /// a cross-compiled glibc if the target is not the `host`
+    fn find(target: &TargetInfo, host: bool) -> Result<Self> {
+        let dynamic_linker = match (target.arch(), target.is_linux()) {
+            ("x86_64", true) => "/lib64/ld-linux-x86-64.so.2",
+            ("aarch64", true) => "/lib/ld-linux-aarch64.so.1",
+            ("i386" | "i486" | "i586" | "i686", true) => "/lib/ld-linux.so.2",
+            ("riscv64", true) => "/lib/ld-linux-riscv64-lp64d.so.1",
+            (arch, true) if arch.starts_with("arm") && target.triple.ends_with("hf") => "/lib/ld-linux-armhf.so.3",
+            (arch, true) if arch.starts_with("arm") => "/lib/ld-linux.so.3",
+            _ => bail!("Linking for '{}' needs a C compiler such as 'cc' or 'clang'", target.triple),
+        };
+        let multiarch = target.multiarch().unwrap_or_default();
+        let library_dirs: Vec<PathBuf> = (if host { GLIBC_LIBRARY_DIRS } else { CROSS_GLIBC_LIBRARY_DIRS })
+            .iter()
+            .map(|dir| PathBuf::from(dir.replace("{multiarch}", &multiarch)))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        let find = |name: &str| {
+            library_dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file()).with_context(|| {
+                format!("Could not find the C runtime object '{}'; install the C library development files", name)
+            })
+        };
+        let mut begin = vec![find("crt1.o")?, find("crti.o")?];
+        let mut end = vec![find("crtn.o")?];
+        if let Some(gcc_dir) = gcc_library_dir(&multiarch) {
+            begin.push(gcc_dir.join("crtbegin.o"));
+            end.insert(0, gcc_dir.join("crtend.o"));
+        }
+        Ok(StartFiles { begin, end, library_dirs, dynamic_linker: dynamic_linker.to_string() })
+    }
+}
+
+/// The directory of the newest GCC installed for `multiarch`, natively or as
+/// a cross compiler, which holds the `crtbegin.o` and `crtend.o` that run
+/// constructors and destructors
+fn gcc_library_dir(multiarch: &str) -> Option<PathBuf> {
+    let mut versions: Vec<(Vec<u32>, PathBuf)> = ["gcc", "gcc-cross"]
+        .iter()
+        .filter_map(|gcc| std::fs::read_dir(format!("/usr/lib/{}/{}", gcc, multiarch)).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let version =
+                path.file_name()?.to_str()?.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+            path.join("crtbegin.o").is_file().then_some((version, path))
+        })
+        .collect();
+    versions.sort();
+    versions.pop().map(|(_, path)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::target::create_target_machine_for;
+    use crate::utils::Config;
+
+    fn options(library_paths: &[&str], libraries: &[&str]) -> CompilerOptions {
+        let mut options = Config::default().compiler_options;
+        options.library_paths = library_paths.iter().map(PathBuf::from).collect();
+        options.libraries = libraries.iter().map(|library| library.to_string()).collect();
+        options
+    }
+
+    fn strings(arguments: Vec<OsString>) -> Vec<String> {
+        arguments.into_iter().map(|argument| argument.into_string().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_driver_and_msvc_arguments() {
+        let objects = [PathBuf::from("main.o"), PathBuf::from("util.o")];
+        let options = options(&["vendor/lib"], &["m", "png"]);
+        assert_eq!(
+            strings(driver_arguments(&objects, Path::new("app"), &options)),
+            ["-o", "app", "main.o", "util.o", "-Lvendor/lib", "-lm", "-lpng"]
+        );
+        let msvc = strings(msvc_arguments(&objects, Path::new("app.exe"), &options));
+        assert_eq!(msvc[2..7], ["/OUT:app.exe", "main.o", "util.o", "/LIBPATH:vendor/lib", "m.lib"]);
+        assert!(msvc.contains(&"/DEFAULTLIB:ucrt".to_string()));
+    }
+
+    #[test]
+    fn test_gnu_arguments_wrap_objects_in_the_c_runtime() {
+        let start = StartFiles {
+            begin: vec![PathBuf::from("crt1.o"), PathBuf::from("crti.o")],
+            end: vec![PathBuf::from("crtn.o")],
+            library_dirs: vec![PathBuf::from("/usr/lib")],
+            dynamic_linker: "/lib64/ld-linux-x86-64.so.2".to_string(),
+        };
+        let arguments = gnu_arguments(&[PathBuf::from("main.o")], Path::new("app"), &options(&["lib"], &["m"]), &start);
+        assert_eq!(
+            strings(arguments)[3..],
+            [
+                "-dynamic-linker",
+                "/lib64/ld-linux-x86-64.so.2",
+                "crt1.o",
+                "crti.o",
+                "main.o",
+                "-Llib",
+                "-lm",
+                "-L/usr/lib",
+                "-lc",
+                "crtn.o"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cross_linkers() {
+        let target = |triple: &str| TargetInfo::new(&create_target_machine_for(triple));
+        let names = |target: &TargetInfo| -> Vec<String> { cross_linkers(target).into_iter().map(|(name, _)| name).collect() };
+        assert_eq!(names(&target("aarch64-unknown-linux-gnu")), ["aarch64-linux-gnu-gcc", "clang", "ld.lld"]);
+        assert_eq!(names(&target("armv7-unknown-linux-gnueabihf")), ["arm-linux-gnueabihf-gcc", "clang", "ld.lld"]);
+        assert_eq!(names(&target("aarch64-apple-darwin")), ["clang"]);
+        assert!(names(&target("wasm32-unknown-unknown")).is_empty());
+    }
+
+    #[test]
+    fn test_missing_linker() {
+        let error = find_linker(&[("tc-no-such-cc", Flavor::Driver), ("tc-no-such-ld", Flavor::Gnu)]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No linker found: looked for 'tc-no-such-cc', 'tc-no-such-ld' on the PATH. \
+             Install a C compiler or a linker to build executables"
+        );
+    }
+}
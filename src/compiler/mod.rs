@@ -1,143 +1,270 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use inkwell::context::Context as LLVMContext;
 use inkwell::module::Module;
-use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine};
-use inkwell::OptimizationLevel;
+use inkwell::targets::{FileType, TargetMachine};
 use log::{debug, info};
 use std::path::{Path, PathBuf};
-use tempfile::NamedTempFile;
 
+mod abi;
+mod codegen;
+mod constant;
+mod diagnostics;
+mod headers;
+mod lexer;
+mod linker;
+mod optimizer;
 mod parser;
+mod preprocessor;
+mod sema;
+mod source;
+mod target;
 
-/// Compile a C file to an executable
-pub fn compile(file_path: &Path) -> Result<PathBuf> {
-    info!("Compiling {}", file_path.display());
+use crate::utils::{CompilerOptions, EmitKind};
+use diagnostics::{Diagnostics, Reported};
+use source::SourceMap;
+use target::TargetInfo;
+
+/// Compile C files to an executable named after the first of them
+pub fn compile(files: &[PathBuf], options: &CompilerOptions) -> Result<PathBuf> {
+    let executable_path = executable_path(&files[0].with_extension(""), options);
+    build(files, &executable_path, options)?;
+    Ok(executable_path)
+}
+
+/// Compile each C file as a separate translation unit and link them into the
+/// executable `output_path`. With `--emit`, the output of the stages it
+/// names is written as well, and the units are only linked if it names `exe`.
+/// Errors and warnings in the source are shown as they are found, and
+/// counted at the end.
+pub fn build(files: &[PathBuf], output_path: &Path, options: &CompilerOptions) -> Result<()> {
+    // Type sizes and other target properties are needed from preprocessing on
+    let target_machine =
+        target::create_target_machine(options, optimizer::codegen_level(options.optimization_level))?;
+    let target = TargetInfo::new(&target_machine);
     
-    // Read the C file
-    let source_code = std::fs::read_to_string(file_path)
-        .context("Failed to read C file")?;
+    // The object files only live until they are linked, unless asked for
+    let objects_dir = tempfile::tempdir()
+        .context("Failed to create temporary directory for object code")?;
+    let extension = if target.is_windows() { "obj" } else { "o" };
+    let root = common_directory(files);
     
-    // Parse the C code
-    let ast = parser::parse(&source_code)
-        .context("Failed to parse C code")?;
+    let mut diagnostics = Diagnostics::new();
+    let mut objects = Vec::new();
+    for (index, file_path) in files.iter().enumerate() {
+        let emit_base = emit_base(file_path, &root, options);
+        if let Some(dir) = emit_base.parent().filter(|_| options.emit_dir.is_some()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+        }
+        let object_path = if options.emits(EmitKind::Obj) {
+            emit_base.with_extension(extension)
+        } else {
+            // Units in different directories may share a file name
+            let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+            objects_dir.path().join(format!("{}-{}.{}", index, stem, extension))
+        };
+        let unit = compile_to_object(
+            file_path,
+            &emit_base,
+            &object_path,
+            &target_machine,
+            &target,
+            options,
+            &mut diagnostics,
+        );
+        match unit {
+            // The error has been shown; the remaining units are still
+            // compiled so that theirs are too
+            Err(err) if err.is::<Reported>() => continue,
+            result => result.with_context(|| format!("Failed to compile {}", file_path.display()))?,
+        }
+        objects.push(object_path);
+    }
     
-    // Generate LLVM IR
-    let llvm_ir = generate_llvm_ir(&ast)
-        .context("Failed to generate LLVM IR")?;
+    match diagnostics.summary() {
+        Some(summary) if diagnostics.error_count() > 0 => anyhow::bail!(summary),
+        Some(summary) => eprintln!("{}.", summary),
+        None => {}
+    }
     
-    // Compile to machine code
-    let executable_path = compile_to_executable(file_path, &llvm_ir)
-        .context("Failed to compile to executable")?;
+    if !options.emits(EmitKind::Exe) {
+        return Ok(());
+    }
     
-    debug!("Compiled to {}", executable_path.display());
+    // Link the object files with the C library, which resolves the external
+    // names each unit uses but does not define
+    linker::link(&objects, output_path, options, &target)
+        .context("Failed to link executable")?;
     
-    Ok(executable_path)
+    debug!("Compiled to {}", output_path.display());
+    
+    Ok(())
 }
 
-/// Generate LLVM IR from the AST
-fn generate_llvm_ir(ast: &parser::AST) -> Result<Module> {
-    debug!("Generating LLVM IR");
-    
-    // Create a new LLVM context
-    let context = LLVMContext::create();
-    
-    // Create a new module
-    let module = context.create_module("tlstuc_module");
+/// Where to write the output of the stages for `file_path`, once the
+/// extension of each replaces `.c`: the file itself, or its path relative to
+/// `root` in `--out-dir` if one was given, so that units in different
+/// directories with the same name do not overwrite each other's output
+fn emit_base(file_path: &Path, root: &Path, options: &CompilerOptions) -> PathBuf {
+    match &options.emit_dir {
+        Some(dir) => {
+            let relative = file_path
+                .strip_prefix(root)
+                .ok()
+                .filter(|relative| relative.is_relative())
+                .unwrap_or_else(|| Path::new(file_path.file_name().unwrap_or_default()));
+            dir.join(relative)
+        }
+        None => file_path.to_path_buf(),
+    }
+}
+
+/// The deepest directory that contains every file
+fn common_directory(files: &[PathBuf]) -> PathBuf {
+    let mut common = files[0].parent().unwrap_or(Path::new("")).to_path_buf();
+    for file in &files[1..] {
+        while !file.starts_with(&common) && common.pop() {}
+    }
+    common
+}
+
+/// Whether programs compiled with `options` run on this machine
+pub fn is_host(options: &CompilerOptions) -> bool {
+    target::is_host(options)
+}
+
+/// The path of an executable called `stem` on the platform `options`
+/// compile for
+pub fn executable_path(stem: &Path, options: &CompilerOptions) -> PathBuf {
+    if target::target_triple(options).as_str().to_string_lossy().contains("windows") {
+        stem.with_extension("exe")
+    } else {
+        stem.to_path_buf()
+    }
+}
+
+/// Compile one translation unit to an object file, writing the output of
+/// the stages `--emit` asks for at `emit_base` with their extensions
+fn compile_to_object(
+    file_path: &Path,
+    emit_base: &Path,
+    object_path: &Path,
+    target_machine: &TargetMachine,
+    target: &TargetInfo,
+    options: &CompilerOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<()> {
+    info!("Compiling {}", file_path.display());
     
-    // TODO: Implement full C code generation
-    // For now, we'll just create a simple "Hello, World" program
+    // Read the C file
+    let source_code = std::fs::read_to_string(file_path)
+        .context("Failed to read C file")?;
     
-    // Get the i8 type
-    let i8_type = context.i8_type();
-    let i8_ptr_type = i8_type.ptr_type(inkwell::AddressSpace::Generic);
-    let i32_type = context.i32_type();
+    let mut sources = SourceMap::new();
+    let file = sources.add_file(file_path, source_code);
     
-    // Create the printf function type
-    let printf_type = i32_type.fn_type(&[i8_ptr_type.into()], true);
+    // Run the preprocessor
+    let tokens = preprocessor::preprocess(&mut sources, file, options, target, diagnostics)
+        .map_err(|err| diagnostics.error(&err, &sources))?;
+    if options.emits(EmitKind::Tokens) {
+        write_tokens(&tokens, &sources, &emit_base.with_extension("tokens"))?;
+    }
     
-    // Add the printf function to our module
-    let printf_func = module.add_function("printf", printf_type, None);
+    // Parse the C code
+    let mut ast = parser::parse(tokens, target)
+        .map_err(|err| diagnostics.error(&err, &sources))?;
     
-    // Create the main function type
-    let main_type = i32_type.fn_type(&[], false);
+    // Resolve names and check types
+    if let Err(errors) = sema::analyze(&mut ast, target) {
+        for err in &errors {
+            diagnostics.error(err, &sources);
+        }
+        return Err(Reported.into());
+    }
+    if options.emits(EmitKind::Ast) {
+        write_ast(&ast, &emit_base.with_extension("ast.json"))?;
+    }
     
-    // Add the main function to our module
-    let main_func = module.add_function("main", main_type, None);
+    // Generate LLVM IR
+    let context = LLVMContext::create();
+    let llvm_ir = generate_llvm_ir(&context, &ast, target, target_machine)
+        .context("Failed to generate LLVM IR")?;
     
-    // Create a basic block in the main function
-    let basic_block = context.append_basic_block(main_func, "entry");
+    // Optimize it
+    optimizer::optimize(&llvm_ir, target_machine, options)?;
     
-    // Create a builder
-    let builder = context.create_builder();
-    builder.position_at_end(basic_block);
+    if options.emits(EmitKind::LlvmIr) {
+        let ir_path = emit_base.with_extension("ll");
+        llvm_ir
+            .print_to_file(&ir_path)
+            .map_err(|err| anyhow!(err.to_string()))
+            .with_context(|| format!("Failed to write {}", ir_path.display()))?;
+    }
+    if options.emits(EmitKind::LlvmBc) {
+        let bitcode_path = emit_base.with_extension("bc");
+        if !llvm_ir.write_bitcode_to_path(&bitcode_path) {
+            anyhow::bail!("Failed to write {}", bitcode_path.display());
+        }
+    }
+    if options.emits(EmitKind::Asm) {
+        let asm_path = emit_base.with_extension("s");
+        target_machine
+            .write_to_file(&llvm_ir, FileType::Assembly, &asm_path)
+            .map_err(|err| anyhow!(err.to_string()))
+            .with_context(|| format!("Failed to write {}", asm_path.display()))?;
+    }
     
-    // Create a string constant
-    let hello_str = builder.build_global_string_ptr("Hello, World!\n", "hello_str");
+    // Write the object file
+    target_machine
+        .write_to_file(&llvm_ir, FileType::Object, object_path)
+        .map_err(|err| anyhow!(err.to_string()))
+        .context("Failed to write object file")?;
     
-    // Call printf
-    builder.build_call(printf_func, &[hello_str.as_pointer_value().into()], "printf_call");
+    Ok(())
+}
+
+/// Generate LLVM IR from the AST
+fn generate_llvm_ir<'ctx>(
+    context: &'ctx LLVMContext,
+    ast: &parser::AST,
+    target: &TargetInfo,
+    target_machine: &TargetMachine,
+) -> Result<Module<'ctx>> {
+    debug!("Generating LLVM IR");
     
-    // Return 0
-    builder.build_return(Some(&i32_type.const_int(0, false)));
+    let module = codegen::generate(context, ast, target, target_machine);
     
     // Verify the module
-    if module.verify().is_err() {
-        anyhow::bail!("Failed to verify module");
-    }
+    module.verify()
+        .map_err(|err| anyhow!(err.to_string()))
+        .context("Failed to verify module")?;
     
     Ok(module)
 }
 
-/// Compile LLVM IR to an executable
-fn compile_to_executable(source_path: &Path, module: &Module) -> Result<PathBuf> {
-    debug!("Compiling to executable");
-    
-    // Initialize LLVM targets
-    Target::initialize_all(&InitializationConfig::default());
-    
-    // Get the target triple for the current machine
-    let target_triple = TargetMachine::get_default_triple();
-    debug!("Target triple: {}", target_triple.as_str().to_string_lossy());
-    
-    // Get the target from the triple
-    let target = Target::from_triple(&target_triple)
-        .context("Failed to get target from triple")?;
-    
-    // Create a target machine
-    let target_machine = target
-        .create_target_machine(
-            &target_triple,
-            "generic",
-            "",
-            OptimizationLevel::Default,
-            RelocMode::Default,
-            CodeModel::Default,
-        )
-        .context("Failed to create target machine")?;
-    
-    // Create a temporary file for the object file
-    let obj_file = NamedTempFile::new()
-        .context("Failed to create temporary file for object code")?;
-    
-    // Write the object file
-    target_machine
-        .write_to_file(module, FileType::Object, obj_file.path())
-        .context("Failed to write object file")?;
-    
-    // Determine the output path
-    let output_path = if cfg!(windows) {
-        source_path.with_extension("exe")
-    } else {
-        let mut path = source_path.to_path_buf();
-        path.set_extension("");
-        path
-    };
-    
-    // Link the object file to create an executable
-    // In a real implementation, we would use a proper linker
-    // For now, we'll just copy the object file to the output path
-    std::fs::copy(obj_file.path(), &output_path)
-        .context("Failed to create executable")?;
-    
-    Ok(output_path)
-}
\ No newline at end of file
+/// Write the preprocessed tokens one per line, each after the location it
+/// was spelled at
+fn write_tokens(tokens: &[lexer::Token], sources: &SourceMap, path: &Path) -> Result<()> {
+    let mut dump = String::new();
+    for token in tokens.iter().filter(|token| token.kind != lexer::TokenKind::Eof) {
+        dump.push_str(&format!(
+            "{}:{}:{}\t{:?}\t{}\n",
+            sources.path(token.span.file).display(),
+            token.span.start.line,
+            token.span.start.column,
+            token.kind,
+            token.text,
+        ));
+    }
+    std::fs::write(path, dump).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Write the analyzed AST as JSON
+fn write_ast(ast: &parser::AST, path: &Path) -> Result<()> {
+    // Going through a `Value` sorts the functions and structs by name
+    // rather than leaving them in hash map order
+    let json = serde_json::to_value(ast)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .context("Failed to serialize the AST")?;
+    std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
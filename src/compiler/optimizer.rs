@@ -0,0 +1,159 @@
+//! The LLVM optimization pipeline.
+//!
+//! Each optimization level runs one of the new pass manager's default
+//! pipelines, `default<O0>` to `default<Oz>`, the way `clang` does. The
+//! size levels also mark every function `optsize`, and `-Oz` `minsize`,
+//! since several passes look at those attributes rather than the pipeline.
+
+use anyhow::{anyhow, Context, Result};
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::module::Module;
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::TargetMachine;
+use log::debug;
+use std::sync::Once;
+
+use crate::utils::{CompilerOptions, OptimizationLevel};
+
+/// The pass pipeline for an optimization level, in `opt -passes` syntax
+pub fn pipeline(level: OptimizationLevel) -> &'static str {
+    match level {
+        OptimizationLevel::None => "default<O0>",
+        OptimizationLevel::Less => "default<O1>",
+        OptimizationLevel::Default => "default<O2>",
+        OptimizationLevel::Aggressive => "default<O3>",
+        OptimizationLevel::Size => "default<Os>",
+        OptimizationLevel::MinSize => "default<Oz>",
+    }
+}
+
+/// How hard the code generator works at an optimization level
+pub fn codegen_level(level: OptimizationLevel) -> inkwell::OptimizationLevel {
+    match level {
+        OptimizationLevel::None => inkwell::OptimizationLevel::None,
+        OptimizationLevel::Less => inkwell::OptimizationLevel::Less,
+        OptimizationLevel::Default | OptimizationLevel::Size | OptimizationLevel::MinSize => {
+            inkwell::OptimizationLevel::Default
+        }
+        OptimizationLevel::Aggressive => inkwell::OptimizationLevel::Aggressive,
+    }
+}
+
+/// Run the pipeline for `options.optimization_level` over `module`
+pub fn optimize(module: &Module, machine: &TargetMachine, options: &CompilerOptions) -> Result<()> {
+    let level = options.optimization_level;
+    debug!("Running {}", pipeline(level));
+
+    if options.time_passes {
+        enable_pass_timing();
+    }
+    mark_size_attributes(module, level);
+
+    let builder = PassBuilderOptions::create();
+    // Whatever the pipeline, a module that reaches it has been verified
+    builder.set_verify_each(false);
+    builder.set_debug_logging(options.print_passes);
+    // As `clang` does, vectorize and unroll loops from -O2 on; -Oz does not
+    // trade size for speed at all
+    let speed = matches!(level, OptimizationLevel::Default | OptimizationLevel::Aggressive | OptimizationLevel::Size);
+    builder.set_loop_vectorization(speed);
+    builder.set_loop_slp_vectorization(speed);
+    builder.set_loop_interleaving(speed);
+    builder.set_loop_unrolling(speed);
+
+    module
+        .run_passes(pipeline(level), machine, builder)
+        .map_err(|err| anyhow!(err.to_string()))
+        .with_context(|| format!("Failed to run the '{}' pass pipeline", pipeline(level)))
+}
+
+/// Mark each function defined in `module` as optimized for size
+fn mark_size_attributes(module: &Module, level: OptimizationLevel) {
+    let attributes: &[&str] = match level {
+        OptimizationLevel::Size => &["optsize"],
+        OptimizationLevel::MinSize => &["optsize", "minsize"],
+        _ => return,
+    };
+    let context = module.get_context();
+    for function in module.get_functions().filter(|function| function.count_basic_blocks() > 0) {
+        for name in attributes {
+            let kind = Attribute::get_named_enum_kind_id(name);
+            function.add_attribute(AttributeLoc::Function, context.create_enum_attribute(kind, 0));
+        }
+    }
+}
+
+/// Have LLVM time every pass it runs. The report is written to standard
+/// error once each module's pipeline has finished.
+fn enable_pass_timing() {
+    // LLVM's options are global and may only be given once per process
+    static TIME_PASSES: Once = Once::new();
+    TIME_PASSES.call_once(|| {
+        let arguments = [c"tc".as_ptr(), c"-time-passes".as_ptr()];
+        // Safety: the arguments are NUL-terminated strings that outlive the call
+        unsafe {
+            llvm_sys::support::LLVMParseCommandLineOptions(
+                arguments.len() as i32,
+                arguments.as_ptr(),
+                std::ptr::null(),
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::target::create_target_machine;
+    use inkwell::context::Context as LLVMContext;
+    use inkwell::memory_buffer::MemoryBuffer;
+
+    const LOOP: &str = r#"
+define i32 @sum(i32 %n) {
+entry:
+  %slot = alloca i32
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %loop ]
+  %total = phi i32 [ 0, %entry ], [ %added, %loop ]
+  store i32 %i, i32* %slot
+  %value = load i32, i32* %slot
+  %added = add i32 %total, %value
+  %next = add i32 %i, 1
+  %done = icmp sge i32 %next, %n
+  br i1 %done, label %exit, label %loop
+exit:
+  ret i32 %added
+}
+"#;
+
+    fn optimized(level: OptimizationLevel) -> String {
+        let context = LLVMContext::create();
+        let buffer = MemoryBuffer::create_from_memory_range_copy(LOOP.as_bytes(), "loop");
+        let module = context.create_module_from_ir(buffer).unwrap();
+        let options = CompilerOptions { optimization_level: level, ..CompilerOptions::default() };
+        let machine = create_target_machine(&options, codegen_level(level)).unwrap();
+        optimize(&module, &machine, &options).unwrap();
+        module.verify().unwrap();
+        module.print_to_string().to_string()
+    }
+
+    #[test]
+    fn test_pipelines() {
+        assert_eq!(pipeline(OptimizationLevel::None), "default<O0>");
+        assert_eq!(pipeline(OptimizationLevel::MinSize), "default<Oz>");
+
+        // -O0 leaves the code alone
+        let unoptimized = optimized(OptimizationLevel::None);
+        assert!(unoptimized.contains("alloca"), "{}", unoptimized);
+
+        // -O2 promotes the slot to a register and folds the loop away
+        let optimized_ir = optimized(OptimizationLevel::Default);
+        assert!(!optimized_ir.contains("alloca"), "{}", optimized_ir);
+        assert!(!optimized_ir.contains("optsize"), "{}", optimized_ir);
+
+        let small = optimized(OptimizationLevel::MinSize);
+        assert!(!small.contains("alloca"), "{}", small);
+        assert!(small.contains("minsize") && small.contains("optsize"), "{}", small);
+    }
+}
@@ -1,92 +1,577 @@
-use anyhow::{Context, Result};
 use log::debug;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::fmt;
+
+use super::constant::{Integer, NotConstant};
+use super::lexer::{Encoding, FloatSuffix, IntSuffix, Keyword, Punct, Token, TokenKind};
+use super::sema;
+use super::source::{CompileError, Span};
+use super::target::TargetInfo;
+
+/// The strictest alignment `_Alignas` may ask for, as in Clang
+const MAX_ALIGNMENT: u64 = 1 << 28;
 
 /// A simple AST representation for C code
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AST {
     pub functions: HashMap<String, Function>,
-    pub global_variables: Vec<Variable>,
+    /// Global variables and their initializers, in source order. Semantic
+    /// analysis folds the initializers to constants.
+    pub global_variables: Vec<(Variable, Option<Initializer>)>,
+    /// Struct and union definitions by tag; the two share one namespace
     pub structs: HashMap<String, Struct>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Function {
     pub name: String,
     pub return_type: Type,
     pub parameters: Vec<Variable>,
-    pub body: Block,
+    /// Whether the parameter list ends in `...`
+    pub is_variadic: bool,
+    /// `None` for a function that is only declared
+    pub body: Option<Block>,
+    /// `static` or `extern` from any declaration of the function; `static`
+    /// gives it internal linkage
+    pub storage: Option<StorageClass>,
+    /// Whether every file-scope declaration is `inline`; unless one of them
+    /// is also `extern`, the definition is then an inline definition that
+    /// does not provide the external one
+    pub is_inline: bool,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Variable {
+    /// Empty for unnamed parameters
     pub name: String,
+    /// The type without its top-level qualifiers, which are kept in
+    /// `qualifiers`; `const char *const p` is a `const` variable of type
+    /// `const char *`
     pub var_type: Type,
+    pub qualifiers: Qualifiers,
+    pub storage: Option<StorageClass>,
+    pub is_thread_local: bool,
+    /// The alignment `_Alignas` asks for in bytes; the object is aligned to
+    /// the stricter of it and the alignment of its type
+    pub alignment: Option<u64>,
+    pub span: Span,
+}
+
+impl Variable {
+    /// The type of the variable as an lvalue, including its qualifiers
+    pub fn qualified_type(&self) -> Type {
+        self.var_type.clone().qualified(self.qualifiers)
+    }
+}
+
+/// Type qualifiers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Qualifiers {
     pub is_const: bool,
+    pub is_volatile: bool,
+    /// Only valid on pointer types
+    pub is_restrict: bool,
+    pub is_atomic: bool,
+}
+
+impl Qualifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == Qualifiers::default()
+    }
+
+    pub fn union(self, other: Qualifiers) -> Qualifiers {
+        Qualifiers {
+            is_const: self.is_const || other.is_const,
+            is_volatile: self.is_volatile || other.is_volatile,
+            is_restrict: self.is_restrict || other.is_restrict,
+            is_atomic: self.is_atomic || other.is_atomic,
+        }
+    }
+}
+
+impl fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keywords = [
+            (self.is_const, "const"),
+            (self.is_volatile, "volatile"),
+            (self.is_restrict, "restrict"),
+            (self.is_atomic, "_Atomic"),
+        ];
+        let present: Vec<&str> = keywords.iter().filter(|(present, _)| *present).map(|(_, keyword)| *keyword).collect();
+        f.write_str(&present.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StorageClass {
+    Auto,
+    Register,
+    Static,
+    Extern,
+}
+
+impl StorageClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StorageClass::Auto => "auto",
+            StorageClass::Register => "register",
+            StorageClass::Static => "static",
+            StorageClass::Extern => "extern",
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Struct {
     pub name: String,
-    pub fields: Vec<Variable>,
+    pub is_union: bool,
+    pub fields: Vec<Field>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+/// A member of a struct or union
+#[derive(Debug, Clone, Serialize)]
+pub struct Field {
+    /// Empty for unnamed bit-fields; an anonymous struct or union member is
+    /// named after its type, e.g. `(anonymous union #2)`
+    pub name: String,
+    pub var_type: Type,
+    pub qualifiers: Qualifiers,
+    /// The width of a bit-field
+    pub bit_width: Option<u32>,
+    /// The alignment `_Alignas` asks for in bytes
+    pub alignment: Option<u64>,
+    pub span: Span,
+}
+
+impl Field {
+    pub fn qualified_type(&self) -> Type {
+        self.var_type.clone().qualified(self.qualifiers)
+    }
+
+    /// Whether the member is an anonymous struct or union, whose members
+    /// are accessed as members of the enclosing one
+    pub fn is_anonymous(&self) -> bool {
+        self.name.starts_with('(')
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Type {
     Void,
+    Bool,
+    /// Plain `char`, whose signedness depends on the target
+    Char,
+    SignedChar,
+    UnsignedChar,
+    Short,
+    UnsignedShort,
     Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
     Float,
     Double,
-    Char,
-    Bool,
+    LongDouble,
     Pointer(Box<Type>),
     Array(Box<Type>, Option<usize>),
+    /// An array whose length is only known once its declaration is reached
+    VariableArray(Box<Type>, VariableLength),
     Struct(String),
-    Function(Box<Type>, Vec<Type>),
+    Union(String),
+    /// An enumerated type with its compatible integer type
+    Enum(String, Box<Type>),
+    /// The return type, the parameter types and whether further arguments
+    /// may follow them (`...`)
+    Function(Box<Type>, Vec<Type>, bool),
+    /// `__builtin_va_list`, the type behind `va_list`, laid out as the
+    /// target's ABI says
+    VaList,
+    /// A type with qualifiers, never empty or nested; qualifying an array
+    /// qualifies its element type instead
+    Qualified(Box<Type>, Qualifiers),
+}
+
+/// The length of a variable length array, evaluated when the declaration of
+/// the array is reached
+#[derive(Debug, Clone, Serialize)]
+pub struct VariableLength {
+    /// Tells the arrays of different declarators apart; the length of each
+    /// is evaluated once and then used by every object of the type
+    pub id: usize,
+    pub length: Box<Expression>,
+    /// Whether the length belongs to a typedef name, whose declaration
+    /// evaluates it for every use of the name
+    pub typedef: bool,
+}
+
+/// Variable length arrays declared by the same declarator have the same type
+impl PartialEq for VariableLength {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
 }
 
-#[derive(Debug)]
+impl Type {
+    pub fn is_integer(&self) -> bool {
+        self.integer_rank().is_some()
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self, Type::Float | Type::Double | Type::LongDouble)
+    }
+
+    /// The character types, which may be initialized from a string literal
+    pub fn is_character(&self) -> bool {
+        matches!(self, Type::Char | Type::SignedChar | Type::UnsignedChar)
+    }
+
+    /// Conversion rank of an integer type; signed and unsigned variants of a
+    /// type share the same rank
+    pub fn integer_rank(&self) -> Option<u8> {
+        let rank = match self {
+            Type::Bool => 1,
+            Type::Char | Type::SignedChar | Type::UnsignedChar => 2,
+            Type::Short | Type::UnsignedShort => 3,
+            Type::Int | Type::UnsignedInt => 4,
+            Type::Long | Type::UnsignedLong => 5,
+            Type::LongLong | Type::UnsignedLongLong => 6,
+            Type::Enum(_, underlying) => return underlying.integer_rank(),
+            _ => return None,
+        };
+        Some(rank)
+    }
+
+    /// The unsigned integer type with the same rank
+    pub fn to_unsigned(&self) -> Type {
+        match self {
+            Type::Char | Type::SignedChar => Type::UnsignedChar,
+            Type::Short => Type::UnsignedShort,
+            Type::Int => Type::UnsignedInt,
+            Type::Long => Type::UnsignedLong,
+            Type::LongLong => Type::UnsignedLongLong,
+            other => other.clone(),
+        }
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || self.is_floating()
+    }
+
+    /// Arithmetic and pointer types, the types usable as conditions
+    pub fn is_scalar(&self) -> bool {
+        self.is_arithmetic() || matches!(self, Type::Pointer(_))
+    }
+
+    /// Arrays whose size is only known at run time
+    pub fn is_variable_length(&self) -> bool {
+        match self {
+            Type::VariableArray(..) => true,
+            Type::Array(element, _) | Type::Qualified(element, _) => element.is_variable_length(),
+            _ => false,
+        }
+    }
+
+    /// Variable length arrays and the types derived from them, such as
+    /// pointers to variable length arrays
+    pub fn is_variably_modified(&self) -> bool {
+        match self {
+            Type::VariableArray(..) => true,
+            Type::Array(inner, _) | Type::Pointer(inner) | Type::Qualified(inner, _) => inner.is_variably_modified(),
+            _ => false,
+        }
+    }
+
+    /// Arrays, structs and unions, the types initialized by braced lists
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self.unqualified(), Type::Array(..) | Type::Struct(_) | Type::Union(_))
+    }
+
+    /// The type a pointer points to
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Pointer(pointee) => Some(pointee),
+            _ => None,
+        }
+    }
+
+    /// Add qualifiers to the type
+    pub fn qualified(self, qualifiers: Qualifiers) -> Type {
+        if qualifiers.is_empty() {
+            return self;
+        }
+        match self {
+            Type::Qualified(inner, existing) => Type::Qualified(inner, existing.union(qualifiers)),
+            Type::Array(element, size) => Type::Array(Box::new(element.qualified(qualifiers)), size),
+            Type::VariableArray(element, length) => {
+                Type::VariableArray(Box::new(element.qualified(qualifiers)), length)
+            }
+            other => Type::Qualified(Box::new(other), qualifiers),
+        }
+    }
+
+    /// The top-level qualifiers of the type
+    pub fn qualifiers(&self) -> Qualifiers {
+        match self {
+            Type::Qualified(_, qualifiers) => *qualifiers,
+            _ => Qualifiers::default(),
+        }
+    }
+
+    /// The type without its top-level qualifiers
+    pub fn unqualified(&self) -> &Type {
+        match self {
+            Type::Qualified(inner, _) => inner,
+            other => other,
+        }
+    }
+
+    /// Spell the type around a declarator, e.g. `int (*name)[3]`
+    fn spell(&self, declarator: String) -> String {
+        let base = match self {
+            Type::Void => "void".to_string(),
+            Type::Bool => "_Bool".to_string(),
+            Type::Char => "char".to_string(),
+            Type::SignedChar => "signed char".to_string(),
+            Type::UnsignedChar => "unsigned char".to_string(),
+            Type::Short => "short".to_string(),
+            Type::UnsignedShort => "unsigned short".to_string(),
+            Type::Int => "int".to_string(),
+            Type::UnsignedInt => "unsigned int".to_string(),
+            Type::Long => "long".to_string(),
+            Type::UnsignedLong => "unsigned long".to_string(),
+            Type::LongLong => "long long".to_string(),
+            Type::UnsignedLongLong => "unsigned long long".to_string(),
+            Type::Float => "float".to_string(),
+            Type::Double => "double".to_string(),
+            Type::LongDouble => "long double".to_string(),
+            Type::VaList => "__builtin_va_list".to_string(),
+            // Anonymous types are named like `(anonymous struct #n)`
+            Type::Struct(name) | Type::Union(name) | Type::Enum(name, _) if name.starts_with('(') => name.clone(),
+            Type::Struct(name) => format!("struct {}", tag_spelling(name)),
+            Type::Union(name) => format!("union {}", tag_spelling(name)),
+            Type::Enum(name, _) => format!("enum {}", tag_spelling(name)),
+            Type::Pointer(pointee) => {
+                return pointee.spell(pointer_declarator(pointee, Qualifiers::default(), declarator));
+            }
+            // The qualifiers of a pointer follow its `*`, as in `char *const p`
+            Type::Qualified(inner, qualifiers) => match &**inner {
+                Type::Pointer(pointee) => return pointee.spell(pointer_declarator(pointee, *qualifiers, declarator)),
+                _ => return format!("{} {}", qualifiers, inner.spell(declarator)),
+            },
+            Type::Array(element, size) => {
+                let size = size.map(|size| size.to_string()).unwrap_or_default();
+                return element.spell(format!("{}[{}]", declarator, size));
+            }
+            // The length is not spelled out, as in a prototype
+            Type::VariableArray(element, _) => return element.spell(format!("{}[*]", declarator)),
+            Type::Function(return_type, parameters, is_variadic) => {
+                let mut parameters: Vec<String> = parameters.iter().map(|parameter| parameter.to_string()).collect();
+                if *is_variadic {
+                    parameters.push("...".to_string());
+                }
+                let parameters = if parameters.is_empty() {
+                    "void".to_string()
+                } else {
+                    parameters.join(", ")
+                };
+                return return_type.spell(format!("{}({})", declarator, parameters));
+            }
+        };
+        if declarator.is_empty() {
+            base
+        } else {
+            format!("{} {}", base, declarator)
+        }
+    }
+}
+
+/// The declarator of a pointer to `pointee`, parenthesized where needed
+fn pointer_declarator(pointee: &Type, qualifiers: Qualifiers, declarator: String) -> String {
+    let mut pointer = format!("*{}", qualifiers);
+    if !qualifiers.is_empty() && !declarator.is_empty() {
+        pointer.push(' ');
+    }
+    pointer.push_str(&declarator);
+    match pointee.unqualified() {
+        Type::Array(..) | Type::VariableArray(..) | Type::Function(..) => format!("({})", pointer),
+        _ => pointer,
+    }
+}
+
+/// Types are displayed the way they are spelled in C, e.g. `char *[4]`
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.spell(String::new()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Block {
     pub statements: Vec<Statement>,
 }
 
-#[derive(Debug)]
-pub enum Statement {
-    Declaration(Variable, Option<Expression>),
-    Assignment(Expression, Expression),
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum StatementKind {
+    Declaration(Variable, Option<Initializer>),
+    /// A typedef declared in a block, with the type it names; the lengths of
+    /// its variable length arrays are evaluated where it is declared
+    Typedef(String, Type),
     If(Expression, Block, Option<Block>),
     While(Expression, Block),
+    /// The initializer is a `Block` when it declares several variables; those
+    /// declarations are scoped to the loop rather than to a nested block.
+    /// Omitted clauses are an empty block and a constant `1` condition.
     For(Box<Statement>, Expression, Box<Statement>, Block),
+    DoWhile(Block, Expression),
+    /// Case labels may appear anywhere inside the body, not only directly in it
+    Switch(Expression, Block),
+    /// A statement labeled with `case` and an integer constant expression
+    Case(Expression, Box<Statement>),
+    Default(Box<Statement>),
+    Label(String, Box<Statement>),
+    Goto(String),
+    Break,
+    Continue,
     Return(Option<Expression>),
     Expression(Expression),
     Block(Block),
 }
 
-#[derive(Debug)]
-pub enum Expression {
+#[derive(Debug, Clone, Serialize)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+    /// Filled in by semantic analysis
+    pub expr_type: Option<Type>,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Expression {
+            kind,
+            span,
+            expr_type: None,
+        }
+    }
+
+    /// The type computed by semantic analysis
+    pub fn ty(&self) -> &Type {
+        self.expr_type.as_ref().expect("expression has not been analyzed")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum ExpressionKind {
     Literal(Literal),
     Variable(String),
     BinaryOp(Box<Expression>, BinaryOperator, Box<Expression>),
     UnaryOp(UnaryOperator, Box<Expression>),
-    Call(String, Vec<Expression>),
+    /// A call through a function designator or a pointer to a function;
+    /// semantic analysis decays the former to the latter
+    Call(Box<Expression>, Vec<Expression>),
     Cast(Box<Expression>, Type),
     Index(Box<Expression>, Box<Expression>),
+    /// `a.b`; `a->b` is represented as `(*a).b`
     Member(Box<Expression>, String),
     Pointer(Box<Expression>),
     Address(Box<Expression>),
+    /// `target = value`, whose result is the value stored rather than an lvalue
+    Assignment(Box<Expression>, Box<Expression>),
+    /// `target op= value`, which evaluates `target` once. The type is the one
+    /// the operation is carried out in, filled in by semantic analysis.
+    CompoundAssignment(Box<Expression>, BinaryOperator, Box<Expression>, Option<Type>),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    /// `a, b` evaluates `a` for its side effects only
+    Comma(Box<Expression>, Box<Expression>),
+    /// `sizeof expression`, whose operand is not evaluated
+    SizeOf(Box<Expression>),
+    SizeOfType(Type),
+    AlignOf(Type),
+    /// `_Generic(control, type: value, default: value)`, where `default` has
+    /// no type. Semantic analysis replaces it by the selected value.
+    Generic(Box<Expression>, Vec<(Option<Type>, Expression)>),
+    /// `(type){ ... }`, an unnamed object with the given initializer
+    CompoundLiteral(Type, Box<Initializer>),
+    /// `__builtin_va_start(list, parameter)`, which `va_start` expands to;
+    /// the last named parameter is only there for the reader
+    VaStart(Box<Expression>),
+    /// `__builtin_va_arg(list, type)`
+    VaArg(Box<Expression>, Type),
+    VaEnd(Box<Expression>),
+    /// `__builtin_va_copy(destination, source)`
+    VaCopy(Box<Expression>, Box<Expression>),
+    /// A conversion made explicit by semantic analysis: integer promotions,
+    /// the usual arithmetic conversions, conversion as if by assignment and
+    /// array or function decay to a pointer
+    ImplicitCast(Box<Expression>, Type),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum Initializer {
+    Expression(Expression),
+    /// A brace-enclosed list. Semantic analysis resolves designators and
+    /// brace elision: afterwards every item has exactly one designator, for
+    /// the element or member it initializes, and nested aggregates have
+    /// lists of their own. Positions without an item are zero-initialized.
+    List(Vec<InitializerItem>, Span),
+}
+
+impl Initializer {
+    pub fn span(&self) -> Span {
+        match self {
+            Initializer::Expression(expression) => expression.span,
+            Initializer::List(_, span) => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InitializerItem {
+    pub designators: Vec<Designator>,
+    pub initializer: Initializer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Designator {
+    /// `[index]`
+    Index(usize),
+    /// `.member`
+    Member(String),
 }
 
-#[derive(Debug)]
+/// A constant. Analysis types integer and character constants as written
+/// and folds them to `Int`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Literal {
+    /// An integer value; one the compiler made is typed as the smallest of
+    /// `int`, `long` and `long long` that holds it
     Int(i64),
-    Float(f64),
-    Char(char),
-    String(String),
-    Bool(bool),
+    /// An integer constant as written: its value, its suffix and whether it
+    /// is decimal, which decide its type
+    Integer { value: u64, suffix: IntSuffix, is_decimal: bool },
+    /// A floating constant and the suffix that decides its type. A `long
+    /// double` constant also keeps its digits as written, because `value`
+    /// may not hold it exactly or at all.
+    Float { value: f64, suffix: FloatSuffix, digits: Option<String> },
+    /// The code units of a character constant
+    Char(Encoding, Vec<u32>),
+    /// The code units of a string literal, without the terminating null
+    String(Encoding, Vec<u32>),
     Null,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum BinaryOperator {
     Add,
     Subtract,
@@ -108,8 +593,9 @@ pub enum BinaryOperator {
     RightShift,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum UnaryOperator {
+    Plus,
     Negate,
     LogicalNot,
     BitwiseNot,
@@ -119,48 +605,3249 @@ pub enum UnaryOperator {
     PostDecrement,
 }
 
-/// Parse C code into an AST
-pub fn parse(source_code: &str) -> Result<AST> {
-    debug!("Parsing C code");
-    
-    // TODO: Implement a proper C parser
-    // For now, we'll just create a simple AST for a "Hello, World" program
-    
-    let mut functions = HashMap::new();
-    
-    // Create a main function
-    let main_function = Function {
-        name: "main".to_string(),
-        return_type: Type::Int,
-        parameters: vec![],
-        body: Block {
-            statements: vec![
-                // printf("Hello, World!\n");
-                Statement::Expression(Expression::Call(
-                    "printf".to_string(),
-                    vec![Expression::Literal(Literal::String("Hello, World!\n".to_string()))],
-                )),
-                // return 0;
-                Statement::Return(Some(Expression::Literal(Literal::Int(0)))),
-            ],
-        },
+/// Parse a preprocessed token stream, which must end with an `Eof` token
+pub fn parse(tokens: Vec<Token>, target: &TargetInfo) -> Result<AST, CompileError> {
+    debug!("Parsing {} tokens", tokens.len());
+
+    let ast = Parser::new(tokens, target).parse_translation_unit()?;
+    debug!(
+        "Parsed {} functions, {} global variables and {} structs",
+        ast.functions.len(),
+        ast.global_variables.len(),
+        ast.structs.len()
+    );
+
+    Ok(ast)
+}
+
+/// Binary operators with their precedence; higher binds tighter
+pub fn binary_operator(punct: Punct) -> Option<(BinaryOperator, u8)> {
+    let operator = match punct {
+        Punct::PipePipe => (BinaryOperator::LogicalOr, 1),
+        Punct::AmpAmp => (BinaryOperator::LogicalAnd, 2),
+        Punct::Pipe => (BinaryOperator::BitwiseOr, 3),
+        Punct::Caret => (BinaryOperator::BitwiseXor, 4),
+        Punct::Amp => (BinaryOperator::BitwiseAnd, 5),
+        Punct::EqualEqual => (BinaryOperator::Equal, 6),
+        Punct::BangEqual => (BinaryOperator::NotEqual, 6),
+        Punct::Less => (BinaryOperator::LessThan, 7),
+        Punct::Greater => (BinaryOperator::GreaterThan, 7),
+        Punct::LessEqual => (BinaryOperator::LessThanOrEqual, 7),
+        Punct::GreaterEqual => (BinaryOperator::GreaterThanOrEqual, 7),
+        Punct::LessLess => (BinaryOperator::LeftShift, 8),
+        Punct::GreaterGreater => (BinaryOperator::RightShift, 8),
+        Punct::Plus => (BinaryOperator::Add, 9),
+        Punct::Minus => (BinaryOperator::Subtract, 9),
+        Punct::Star => (BinaryOperator::Multiply, 10),
+        Punct::Slash => (BinaryOperator::Divide, 10),
+        Punct::Percent => (BinaryOperator::Modulo, 10),
+        _ => return None,
     };
-    
-    functions.insert("main".to_string(), main_function);
-    
-    let ast = AST {
-        functions,
-        global_variables: vec![],
-        structs: HashMap::new(),
+    Some(operator)
+}
+
+fn compound_assignment_operator(punct: Punct) -> Option<BinaryOperator> {
+    let operator = match punct {
+        Punct::StarEqual => BinaryOperator::Multiply,
+        Punct::SlashEqual => BinaryOperator::Divide,
+        Punct::PercentEqual => BinaryOperator::Modulo,
+        Punct::PlusEqual => BinaryOperator::Add,
+        Punct::MinusEqual => BinaryOperator::Subtract,
+        Punct::LessLessEqual => BinaryOperator::LeftShift,
+        Punct::GreaterGreaterEqual => BinaryOperator::RightShift,
+        Punct::AmpEqual => BinaryOperator::BitwiseAnd,
+        Punct::CaretEqual => BinaryOperator::BitwiseXor,
+        Punct::PipeEqual => BinaryOperator::BitwiseOr,
+        _ => return None,
     };
-    
-    Ok(ast)
+    Some(operator)
+}
+
+/// Declaration specifiers shared by every declarator of a declaration
+struct Specifiers {
+    /// The type specifiers with their qualifiers
+    base: Type,
+    storage: Option<StorageClass>,
+    is_typedef: bool,
+    is_thread_local: bool,
+    is_inline: bool,
+    /// The strictest alignment the `_Alignas` specifiers ask for
+    alignment: Option<u64>,
+    span: Span,
 }
 
-/// A more complete C parser would use a proper parsing library or implement a recursive descent parser
-/// For a production-quality C compiler, we would use a more sophisticated parsing approach
-/// such as LALR(1) or LL(k) parsing with a grammar specification
+/// Where declaration specifiers appear, which decides the storage classes
+/// and function specifiers they may contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpecifierContext {
+    File,
+    Block,
+    Parameter,
+    Member,
+    TypeName,
+}
+
+impl SpecifierContext {
+    /// Whether a storage class or function specifier keyword is allowed
+    fn allows(self, keyword: Keyword) -> bool {
+        match self {
+            SpecifierContext::File => !matches!(keyword, Keyword::Auto | Keyword::Register),
+            SpecifierContext::Block => true,
+            SpecifierContext::Parameter => keyword == Keyword::Register,
+            SpecifierContext::Member | SpecifierContext::TypeName => false,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            SpecifierContext::File => "at file scope",
+            SpecifierContext::Block => "at block scope",
+            SpecifierContext::Parameter => "on function parameters",
+            SpecifierContext::Member => "on struct members",
+            SpecifierContext::TypeName => "in type names",
+        }
+    }
+}
+
+/// One step of a declarator, e.g. the `*` in `int *p`
+enum Derivation {
+    Pointer(Qualifiers),
+    Array(Option<usize>),
+    VariableArray(VariableLength),
+    /// The parameters and whether they end in `...`
+    Function(Vec<Variable>, bool),
+    /// The parameters of an old-style definition, named in an identifier
+    /// list and declared after the declarator
+    IdentifierList(Vec<Variable>),
+}
+
+/// A parsed declarator; `derivations` are ordered from the name outwards,
+/// so `int *a[3]` yields `[Array(3), Pointer]`
+struct Declarator {
+    name: Option<(String, Span)>,
+    derivations: Vec<Derivation>,
+    span: Span,
+}
+
+impl Declarator {
+    fn build_type(&self, base: &Type) -> Type {
+        self.derivations
+            .iter()
+            .rev()
+            .fold(base.clone(), |inner, derivation| match derivation {
+                Derivation::Pointer(qualifiers) => Type::Pointer(Box::new(inner)).qualified(*qualifiers),
+                Derivation::Array(size) => Type::Array(Box::new(inner), *size),
+                Derivation::VariableArray(length) => Type::VariableArray(Box::new(inner), length.clone()),
+                // Qualifiers on the return type have no effect
+                Derivation::Function(parameters, is_variadic) => Type::Function(
+                    Box::new(inner.unqualified().clone()),
+                    parameters.iter().map(|parameter| parameter.var_type.clone()).collect(),
+                    *is_variadic,
+                ),
+                Derivation::IdentifierList(parameters) => Type::Function(
+                    Box::new(inner.unqualified().clone()),
+                    parameters.iter().map(|parameter| parameter.var_type.clone()).collect(),
+                    false,
+                ),
+            })
+    }
+
+    /// The declared variable, with the top-level qualifiers of its type split
+    /// off into `Variable::qualifiers`
+    fn variable(&self, specifiers: &Specifiers) -> Variable {
+        let (name, span) = self.name.clone().unwrap_or_default();
+        let declared = self.build_type(&specifiers.base);
+        Variable {
+            name,
+            var_type: declared.unqualified().clone(),
+            qualifiers: declared.qualifiers(),
+            storage: specifiers.storage,
+            is_thread_local: specifiers.is_thread_local,
+            alignment: specifiers.alignment,
+            span,
+        }
+    }
+
+    /// The declared parameter, whose array or function type is adjusted to
+    /// a pointer
+    fn parameter(&self, specifiers: &Specifiers) -> Variable {
+        let mut parameter = self.variable(specifiers);
+        parameter.var_type = match parameter.var_type {
+            Type::Array(element, _) | Type::VariableArray(element, _) => Type::Pointer(element),
+            function @ Type::Function(..) => Type::Pointer(Box::new(function)),
+            other => other,
+        };
+        parameter
+    }
+
+    /// The parameters of a function declarator applied directly to the name
+    fn function_parameters(&self) -> Option<&[Variable]> {
+        match self.derivations.first() {
+            Some(Derivation::Function(parameters, _) | Derivation::IdentifierList(parameters)) => Some(parameters),
+            _ => None,
+        }
+    }
+
+    /// Whether the parameters applied directly to the name are an identifier
+    /// list, which only a function definition may have
+    fn has_identifier_list(&self) -> bool {
+        matches!(self.derivations.first(), Some(Derivation::IdentifierList(_)))
+    }
+}
+
+/// What an ordinary identifier in scope names; the parser needs this to
+/// tell typedef names from other identifiers
+#[derive(Debug, Clone)]
+enum Ordinary {
+    /// An object or function with the type it was last declared with
+    Object(Type),
+    Typedef(Type),
+    /// An enumeration constant, which is replaced by its value
+    Enumerator(i64),
+}
+
+/// A struct, union or enum tag in scope
+#[derive(Debug, Clone)]
+struct Tag {
+    kind: Keyword,
+    /// The name of the type: the tag itself at file scope, and `tag.n` in a
+    /// block, where the tag can hide another one
+    name: String,
+    span: Span,
+}
+
+/// The tag a struct, union or enum type was declared with
+fn tag_spelling(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    /// Constant expressions depend on the sizes of types
+    target: &'a TargetInfo,
+    pos: usize,
+    ast: AST,
+    /// Ordinary identifiers by scope, innermost last, with where they were
+    /// first declared; names the compiler provides have no declaration
+    scopes: Vec<HashMap<String, (Ordinary, Option<Span>)>>,
+    /// Struct, union and enum tags by scope, innermost last
+    tags: Vec<HashMap<String, Tag>>,
+    /// Enumerated types by name, with the `enum` keyword of their definition
+    enums: HashMap<String, (Type, Span)>,
+    anonymous_types: usize,
+    /// Tags declared in blocks, which are numbered to tell their types apart
+    local_tags: usize,
+    /// Variable length array declarators seen
+    variable_arrays: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<Token>, target: &'a TargetInfo) -> Self {
+        Parser {
+            tokens,
+            target,
+            pos: 0,
+            ast: AST {
+                functions: HashMap::new(),
+                global_variables: Vec::new(),
+                structs: HashMap::new(),
+            },
+            // `__builtin_va_list` behaves as a typedef the compiler provides
+            scopes: vec![HashMap::from([("__builtin_va_list".to_string(), (Ordinary::Typedef(Type::VaList), None))])],
+            tags: vec![HashMap::new()],
+            enums: HashMap::new(),
+            anonymous_types: 0,
+            local_tags: 0,
+            variable_arrays: 0,
+        }
+    }
+
+    // Token helpers
+
+    fn peek(&self) -> &Token {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> &Token {
+        let index = (self.pos + n).min(self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Span of the most recently consumed token
+    fn previous_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].span
+    }
+
+    fn is_punct(&self, punct: Punct) -> bool {
+        self.peek().kind == TokenKind::Punct(punct)
+    }
+
+    fn is_punct_at(&self, n: usize, punct: Punct) -> bool {
+        self.peek_nth(n).kind == TokenKind::Punct(punct)
+    }
+
+    fn is_keyword(&self, keyword: Keyword) -> bool {
+        self.peek().kind == TokenKind::Keyword(keyword)
+    }
+
+    fn eat_punct(&mut self, punct: Punct) -> bool {
+        if self.is_punct(punct) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+        if self.is_keyword(keyword) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, punct: Punct, context: &str) -> Result<Span, CompileError> {
+        if self.is_punct(punct) {
+            Ok(self.advance().span)
+        } else {
+            Err(self.error_here(format!(
+                "expected `{}` {}, found {}",
+                punct.as_str(),
+                context,
+                self.peek()
+            )))
+        }
+    }
+
+    fn expect_identifier(&mut self, context: &str) -> Result<(String, Span), CompileError> {
+        match self.peek().kind.clone() {
+            TokenKind::Identifier(name) => Ok((name, self.advance().span)),
+            _ => Err(self.error_here(format!("expected identifier {}, found {}", context, self.peek()))),
+        }
+    }
+
+    fn error_here(&self, message: impl Into<String>) -> CompileError {
+        CompileError::new(message, self.peek().span)
+    }
+
+    // Scopes
+
+    fn lookup_ordinary(&self, name: &str) -> Option<&Ordinary> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).map(|(ordinary, _)| ordinary)
+    }
+
+    /// The type named by the token at `n` if it is a typedef name
+    fn typedef_at(&self, n: usize) -> Option<&Type> {
+        match &self.peek_nth(n).kind {
+            TokenKind::Identifier(name) => match self.lookup_ordinary(name) {
+                Some(Ordinary::Typedef(typedef)) => Some(typedef),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Declare an ordinary identifier in the innermost scope; objects and
+    /// functions may be redeclared, which semantic analysis checks further
+    fn declare_ordinary(&mut self, name: &str, ordinary: Ordinary, span: Span) -> Result<(), CompileError> {
+        let scope = self.scopes.last_mut().unwrap();
+        let (error, previous) = match (scope.get(name), &ordinary) {
+            (None, _) | (Some((Ordinary::Object(_), _)), Ordinary::Object(_)) => {
+                scope.insert(name.to_string(), (ordinary, Some(span)));
+                return Ok(());
+            }
+            // A typedef may be repeated with the same type
+            (Some((Ordinary::Typedef(existing), _)), Ordinary::Typedef(typedef)) if existing == typedef => {
+                return Ok(())
+            }
+            (Some((Ordinary::Typedef(existing), previous)), Ordinary::Typedef(typedef)) => (
+                CompileError::new(
+                    format!("typedef redefinition with different types (`{}` vs `{}`)", typedef, existing),
+                    span,
+                ),
+                previous,
+            ),
+            (Some((Ordinary::Enumerator(_), previous)), Ordinary::Enumerator(_)) => {
+                (CompileError::new(format!("redefinition of enumerator `{}`", name), span), previous)
+            }
+            (Some((_, previous)), _) => (
+                CompileError::new(format!("redefinition of `{}` as a different kind of symbol", name), span),
+                previous,
+            ),
+        };
+        Err(match previous {
+            Some(previous) => error.with_label(*previous, "previous definition is here"),
+            None => error,
+        })
+    }
+
+    /// Evaluate an integer constant expression as soon as it is parsed, for
+    /// the declarations whose meaning depends on its value; the objects it
+    /// names have the types they were declared with so far. The inner result
+    /// says why a valid expression is not constant.
+    fn integer_constant(&self, expression: &Expression) -> Result<Result<Integer, NotConstant>, CompileError> {
+        let mut names = Vec::new();
+        referenced_names(expression, &mut names);
+        let objects = names
+            .into_iter()
+            .filter_map(|name| match self.lookup_ordinary(name) {
+                Some(Ordinary::Object(object_type)) => Some((name.to_string(), object_type.clone())),
+                _ => None,
+            })
+            .collect();
+        sema::integer_constant_expression(expression, objects, &self.ast.structs, self.target)
+    }
+
+    /// Parse the rest of a `_Static_assert` declaration after the keyword
+    /// and check it
+    fn parse_static_assert(&mut self) -> Result<(), CompileError> {
+        self.expect_punct(Punct::LeftParen, "after `_Static_assert`")?;
+        let condition = self.parse_conditional()?;
+        let message = if self.eat_punct(Punct::Comma) {
+            if !matches!(self.peek().kind, TokenKind::StringLiteral { .. }) {
+                return Err(self.error_here(format!("expected string literal in static assertion, found {}", self.peek())));
+            }
+            match self.parse_string_literal()?.kind {
+                ExpressionKind::Literal(Literal::String(_, units)) => {
+                    Some(units.into_iter().filter_map(char::from_u32).collect::<String>())
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+        self.expect_punct(Punct::RightParen, "after static assertion")?;
+        self.expect_punct(Punct::Semicolon, "after static assertion")?;
+
+        let value = self.integer_constant(&condition)?.map_err(|not_constant| {
+            not_constant.error("static assertion expression is not an integer constant expression")
+        })?;
+        if value.is_true() {
+            return Ok(());
+        }
+        let message = match message {
+            Some(message) => format!("static assertion failed: {}", message),
+            None => "static assertion failed".to_string(),
+        };
+        Err(CompileError::new(message, condition.span))
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.tags.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.tags.pop();
+    }
+
+    // Declarations
+
+    fn parse_translation_unit(mut self) -> Result<AST, CompileError> {
+        while self.peek().kind != TokenKind::Eof {
+            self.parse_external_declaration()?;
+        }
+        Ok(self.ast)
+    }
+
+    fn parse_external_declaration(&mut self) -> Result<(), CompileError> {
+        if self.eat_keyword(Keyword::StaticAssert) {
+            return self.parse_static_assert();
+        }
+        if !self.is_type_start() {
+            return Err(self.error_here(format!("expected declaration, found {}", self.peek())));
+        }
+
+        let specifiers = self.parse_declaration_specifiers(SpecifierContext::File)?;
+        if self.eat_punct(Punct::Semicolon) {
+            return Ok(());
+        }
+
+        let mut first = true;
+        loop {
+            let declarator = self.parse_declarator(false)?;
+            let (name, name_span) = declarator.name.clone().unwrap_or_default();
+            // An old-style definition declares its parameters before the body
+            let is_definition = first
+                && (self.is_punct(Punct::LeftBrace) || (declarator.has_identifier_list() && self.is_type_start()));
+            if !is_definition {
+                self.reject_identifier_list(&declarator)?;
+            }
+
+            if specifiers.is_typedef {
+                self.typedef_declaration(&declarator, &specifiers)?;
+            } else if let Some(mut function) = self.function_declaration(&declarator, &specifiers)? {
+                if is_definition {
+                    if let Some(previous) = self.ast.functions.get(&name).filter(|f| f.body.is_some()) {
+                        return Err(CompileError::new(
+                            format!("redefinition of function `{}`", name),
+                            name_span,
+                        )
+                        .with_label(previous.span, "previous definition is here"));
+                    }
+                    if declarator.function_parameters().is_none() {
+                        return Err(self.error_here("function definition declared with a typedef function type"));
+                    }
+                    if declarator.has_identifier_list() {
+                        self.parse_parameter_declarations(&mut function.parameters)?;
+                        let function_type = Type::Function(
+                            Box::new(function.return_type.clone()),
+                            function.parameters.iter().map(|parameter| parameter.var_type.clone()).collect(),
+                            false,
+                        );
+                        self.declare_ordinary(&name, Ordinary::Object(function_type), name_span)?;
+                    }
+                    function.body = Some(self.parse_function_body(&function.parameters)?);
+                    return self.declare_function(function, name_span, true);
+                }
+
+                self.declare_function(function, name_span, true)?;
+            } else {
+                let variable = self.variable_declaration(&declarator, &specifiers)?;
+                let initializer = self.parse_initializer()?;
+                self.ast.global_variables.push((variable, initializer));
+            }
+
+            first = false;
+            if !self.eat_punct(Punct::Comma) {
+                break;
+            }
+        }
+
+        self.expect_punct(Punct::Semicolon, "after declaration")?;
+        Ok(())
+    }
+
+    /// Declare the name of a typedef declarator and return the type it names.
+    /// The uses of the name see the lengths of its variable length arrays as
+    /// belonging to the typedef.
+    fn typedef_declaration(&mut self, declarator: &Declarator, specifiers: &Specifiers) -> Result<Type, CompileError> {
+        if self.is_punct(Punct::Equal) {
+            return Err(self.error_here("illegal initializer (only variables can be initialized)"));
+        }
+        if specifiers.alignment.is_some() {
+            return Err(CompileError::new("`_Alignas` cannot be applied to a typedef", specifiers.span));
+        }
+        let (name, span) = declarator.name.clone().unwrap_or_default();
+        let typedef = declarator.build_type(&specifiers.base);
+        let mut named = typedef.clone();
+        if typedef.is_variably_modified() {
+            if self.scopes.len() == 1 {
+                return Err(CompileError::new("variable length array declaration not allowed at file scope", span));
+            }
+            claim_lengths(&mut named);
+        }
+        self.declare_ordinary(&name, Ordinary::Typedef(named), span)?;
+        Ok(typedef)
+    }
+
+    /// The function a declarator declares, or `None` if it declares an object
+    fn function_declaration(
+        &mut self,
+        declarator: &Declarator,
+        specifiers: &Specifiers,
+    ) -> Result<Option<Function>, CompileError> {
+        let (return_type, parameter_types, is_variadic) = match declarator.build_type(&specifiers.base) {
+            Type::Function(return_type, parameter_types, is_variadic) => (*return_type, parameter_types, is_variadic),
+            _ => return Ok(None),
+        };
+        let function_type = Type::Function(Box::new(return_type.clone()), parameter_types.clone(), is_variadic);
+        if specifiers.is_thread_local {
+            return Err(CompileError::new(
+                "`_Thread_local` is only allowed on variable declarations",
+                specifiers.span,
+            ));
+        }
+        if specifiers.alignment.is_some() {
+            return Err(CompileError::new("`_Alignas` cannot be applied to a function", specifiers.span));
+        }
+        let (name, name_span) = declarator.name.clone().unwrap_or_default();
+        self.declare_ordinary(&name, Ordinary::Object(function_type), name_span)?;
+
+        // A function declared with a typedef of a function type has unnamed parameters
+        let parameters = match declarator.function_parameters() {
+            Some(parameters) => parameters.to_vec(),
+            None => parameter_types
+                .into_iter()
+                .map(|var_type| Variable {
+                    name: String::new(),
+                    var_type,
+                    qualifiers: Qualifiers::default(),
+                    storage: None,
+                    is_thread_local: false,
+                    alignment: None,
+                    span: name_span,
+                })
+                .collect(),
+        };
+        Ok(Some(Function {
+            name,
+            return_type,
+            parameters,
+            is_variadic,
+            body: None,
+            storage: specifiers.storage,
+            is_inline: specifiers.is_inline,
+            span: specifiers.span.to(declarator.span),
+        }))
+    }
+
+    /// The variable a declarator declares, whose scope begins here
+    fn variable_declaration(
+        &mut self,
+        declarator: &Declarator,
+        specifiers: &Specifiers,
+    ) -> Result<Variable, CompileError> {
+        if specifiers.is_inline {
+            return Err(CompileError::new("`inline` can only appear on functions", specifiers.span));
+        }
+        if specifiers.alignment.is_some() && specifiers.storage == Some(StorageClass::Register) {
+            return Err(CompileError::new(
+                "`_Alignas` cannot be applied to a variable with `register` storage class",
+                specifiers.span,
+            ));
+        }
+        let variable = declarator.variable(specifiers);
+        self.declare_ordinary(&variable.name, Ordinary::Object(variable.qualified_type()), variable.span)?;
+        Ok(variable)
+    }
+
+    /// Record a function declaration or definition, merging it with earlier
+    /// declarations of the same function; only file-scope declarations take
+    /// part in deciding whether a definition is an inline definition
+    fn declare_function(
+        &mut self,
+        function: Function,
+        name_span: Span,
+        at_file_scope: bool,
+    ) -> Result<(), CompileError> {
+        let existing = match self.ast.functions.get_mut(&function.name) {
+            Some(existing) => existing,
+            None => {
+                self.ast.functions.insert(function.name.clone(), function);
+                return Ok(());
+            }
+        };
+
+        if function.storage == Some(StorageClass::Static) && existing.storage != Some(StorageClass::Static) {
+            return Err(CompileError::new(
+                format!("static declaration of `{}` follows non-static declaration", function.name),
+                name_span,
+            )
+            .with_label(existing.span, "previous declaration is here"));
+        }
+        // A later `extern` keeps the internal linkage of an earlier `static`
+        existing.storage = existing.storage.or(function.storage);
+        if at_file_scope {
+            existing.is_inline &= function.is_inline;
+        }
+        // The definition supplies the parameter names used by the body
+        if function.body.is_some() {
+            existing.return_type = function.return_type;
+            existing.parameters = function.parameters;
+            existing.is_variadic = function.is_variadic;
+            existing.body = function.body;
+            existing.span = function.span;
+        }
+        Ok(())
+    }
+
+    fn parse_initializer(&mut self) -> Result<Option<Initializer>, CompileError> {
+        if !self.eat_punct(Punct::Equal) {
+            return Ok(None);
+        }
+        if self.is_punct(Punct::LeftBrace) {
+            return Ok(Some(self.parse_initializer_list()?));
+        }
+        Ok(Some(Initializer::Expression(self.parse_assignment()?)))
+    }
+
+    /// Whether the next token can begin a declaration
+    fn is_type_start(&self) -> bool {
+        self.is_type_start_at(0)
+    }
+
+    fn is_type_start_at(&self, n: usize) -> bool {
+        self.typedef_at(n).is_some()
+            || matches!(
+                self.peek_nth(n).kind,
+                TokenKind::Keyword(
+                    Keyword::Void
+                        | Keyword::Char
+                        | Keyword::Short
+                        | Keyword::Int
+                        | Keyword::Long
+                        | Keyword::Float
+                        | Keyword::Double
+                        | Keyword::Signed
+                        | Keyword::Unsigned
+                        | Keyword::Bool
+                        | Keyword::Complex
+                        | Keyword::Struct
+                        | Keyword::Union
+                        | Keyword::Enum
+                        | Keyword::Const
+                        | Keyword::Volatile
+                        | Keyword::Restrict
+                        | Keyword::Atomic
+                        | Keyword::Static
+                        | Keyword::Extern
+                        | Keyword::Auto
+                        | Keyword::Register
+                        | Keyword::Typedef
+                        | Keyword::Inline
+                        | Keyword::Noreturn
+                        | Keyword::ThreadLocal
+                        | Keyword::Alignas
+                )
+            )
+    }
+
+    fn parse_declaration_specifiers(&mut self, context: SpecifierContext) -> Result<Specifiers, CompileError> {
+        let start = self.peek().span;
+        let mut type_keywords: Vec<Keyword> = Vec::new();
+        // A type given by a struct, union or enum specifier, `_Atomic(...)`
+        // or a typedef name
+        let mut specified_type = None;
+        let mut qualifiers = Qualifiers::default();
+        // The storage class keyword, counting `typedef` as one
+        let mut storage_keyword: Option<Keyword> = None;
+        let mut is_thread_local = false;
+        let mut is_inline = false;
+        let mut alignment = None;
+
+        loop {
+            let keyword = match self.peek().kind {
+                TokenKind::Keyword(keyword) => keyword,
+                // An identifier is a typedef name only where a type is still expected
+                TokenKind::Identifier(_) if specified_type.is_none() && type_keywords.is_empty() => {
+                    match self.typedef_at(0) {
+                        Some(typedef) => {
+                            specified_type = Some(typedef.clone());
+                            self.advance();
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                _ => break,
+            };
+            match keyword {
+                Keyword::Const => qualifiers.is_const = true,
+                Keyword::Volatile => qualifiers.is_volatile = true,
+                Keyword::Restrict => qualifiers.is_restrict = true,
+                Keyword::Atomic if !self.is_punct_at(1, Punct::LeftParen) => qualifiers.is_atomic = true,
+                // `_Noreturn` has no representation in the AST yet
+                Keyword::Noreturn => {}
+                Keyword::Static
+                | Keyword::Extern
+                | Keyword::Auto
+                | Keyword::Register
+                | Keyword::Typedef
+                | Keyword::Inline
+                | Keyword::ThreadLocal => {
+                    if !context.allows(keyword) {
+                        return Err(self.error_here(format!(
+                            "`{}` is not allowed {}",
+                            keyword.as_str(),
+                            context.description()
+                        )));
+                    }
+                    match keyword {
+                        Keyword::Inline => is_inline = true,
+                        Keyword::ThreadLocal => is_thread_local = true,
+                        _ => {
+                            if let Some(previous) = storage_keyword {
+                                return Err(self.error_here(format!(
+                                    "cannot combine with previous `{}` declaration specifier",
+                                    previous.as_str()
+                                )));
+                            }
+                            storage_keyword = Some(keyword);
+                        }
+                    }
+                }
+                Keyword::Alignas => {
+                    if matches!(context, SpecifierContext::Parameter | SpecifierContext::TypeName) {
+                        return Err(self.error_here(format!("`_Alignas` is not allowed {}", context.description())));
+                    }
+                    self.advance();
+                    alignment = alignment.max(self.parse_alignment_specifier()?);
+                    continue;
+                }
+                Keyword::Struct | Keyword::Union | Keyword::Enum | Keyword::Atomic => {
+                    if specified_type.is_some() || !type_keywords.is_empty() {
+                        return Err(self.error_here("multiple types in declaration"));
+                    }
+                    self.advance();
+                    specified_type = Some(match keyword {
+                        Keyword::Struct => self.parse_struct_specifier(false)?,
+                        Keyword::Union => self.parse_struct_specifier(true)?,
+                        Keyword::Enum => self.parse_enum_specifier()?,
+                        _ => self.parse_atomic_specifier()?,
+                    });
+                    continue;
+                }
+                Keyword::Void
+                | Keyword::Char
+                | Keyword::Short
+                | Keyword::Int
+                | Keyword::Long
+                | Keyword::Float
+                | Keyword::Double
+                | Keyword::Signed
+                | Keyword::Unsigned
+                | Keyword::Bool
+                | Keyword::Complex => {
+                    if specified_type.is_some() {
+                        return Err(self.error_here("multiple types in declaration"));
+                    }
+                    type_keywords.push(keyword);
+                }
+                _ => break,
+            }
+            self.advance();
+        }
+
+        let span = start.to(self.previous_span());
+        let base = match specified_type {
+            Some(specified_type) => specified_type,
+            None => self.resolve_type_keywords(&type_keywords, span)?,
+        };
+        if qualifiers.is_restrict && !matches!(base.unqualified(), Type::Pointer(_)) {
+            return Err(CompileError::new(
+                format!("restrict requires a pointer type (`{}` is invalid)", base),
+                span,
+            ));
+        }
+        let storage = match storage_keyword {
+            Some(Keyword::Static) => Some(StorageClass::Static),
+            Some(Keyword::Extern) => Some(StorageClass::Extern),
+            Some(Keyword::Auto) => Some(StorageClass::Auto),
+            Some(Keyword::Register) => Some(StorageClass::Register),
+            _ => None,
+        };
+        if is_thread_local && context == SpecifierContext::Block && storage.is_none() {
+            return Err(CompileError::new(
+                "`_Thread_local` variables at block scope must also be `static` or `extern`",
+                span,
+            ));
+        }
+
+        Ok(Specifiers {
+            base: base.qualified(qualifiers),
+            storage,
+            is_typedef: storage_keyword == Some(Keyword::Typedef),
+            is_thread_local,
+            is_inline,
+            alignment,
+            span,
+        })
+    }
+
+    /// Parse the rest of an `_Alignas(type-name)` or
+    /// `_Alignas(constant-expression)` specifier after the keyword into the
+    /// alignment it asks for; `_Alignas(0)` asks for none
+    fn parse_alignment_specifier(&mut self) -> Result<Option<u64>, CompileError> {
+        self.expect_punct(Punct::LeftParen, "after `_Alignas`")?;
+        let start = self.peek().span;
+        // A type name asks for the alignment of the type
+        let expression = if self.is_type_start() {
+            let operand = self.parse_type_name()?;
+            Expression::new(ExpressionKind::AlignOf(operand), start.to(self.previous_span()))
+        } else {
+            self.parse_conditional()?
+        };
+        self.expect_punct(Punct::RightParen, "after alignment")?;
+
+        let value = self
+            .integer_constant(&expression)?
+            .map_err(|not_constant| not_constant.error("alignment is not an integer constant expression"))?;
+        if value.is_negative() || !(value.bits == 0 || value.bits.is_power_of_two()) {
+            return Err(CompileError::new("requested alignment is not a power of 2", expression.span));
+        }
+        if value.bits > MAX_ALIGNMENT {
+            return Err(CompileError::new(
+                format!("requested alignment must be {} bytes or smaller", MAX_ALIGNMENT),
+                expression.span,
+            ));
+        }
+        Ok((value.bits != 0).then_some(value.bits))
+    }
+
+    /// Parse the rest of an `_Atomic(type-name)` specifier after the keyword
+    fn parse_atomic_specifier(&mut self) -> Result<Type, CompileError> {
+        self.expect_punct(Punct::LeftParen, "after `_Atomic`")?;
+        let start = self.peek().span;
+        let atomic_type = self.parse_type_name()?;
+        self.expect_punct(Punct::RightParen, "after atomic type name")?;
+        match atomic_type {
+            Type::Array(..) | Type::Function(..) | Type::Qualified(..) => Err(CompileError::new(
+                format!("`_Atomic` cannot be applied to type `{}`", atomic_type),
+                start.to(self.previous_span()),
+            )),
+            atomic_type => Ok(atomic_type.qualified(Qualifiers {
+                is_atomic: true,
+                ..Qualifiers::default()
+            })),
+        }
+    }
+
+    /// Map a list of type specifier keywords such as `signed int` to a type
+    fn resolve_type_keywords(&self, keywords: &[Keyword], span: Span) -> Result<Type, CompileError> {
+        let mut sorted: Vec<&str> = keywords.iter().map(|keyword| keyword.as_str()).collect();
+        sorted.sort_unstable();
+
+        let resolved = match sorted.as_slice() {
+            ["void"] => Type::Void,
+            ["_Bool"] => Type::Bool,
+            ["char"] => Type::Char,
+            ["char", "signed"] => Type::SignedChar,
+            ["char", "unsigned"] => Type::UnsignedChar,
+            ["short"] | ["int", "short"] | ["short", "signed"] | ["int", "short", "signed"] => Type::Short,
+            ["short", "unsigned"] | ["int", "short", "unsigned"] => Type::UnsignedShort,
+            ["int"] | ["signed"] | ["int", "signed"] => Type::Int,
+            ["unsigned"] | ["int", "unsigned"] => Type::UnsignedInt,
+            ["long"] | ["int", "long"] | ["long", "signed"] | ["int", "long", "signed"] => Type::Long,
+            ["long", "unsigned"] | ["int", "long", "unsigned"] => Type::UnsignedLong,
+            ["long", "long"] | ["int", "long", "long"] | ["long", "long", "signed"] | ["int", "long", "long", "signed"] => {
+                Type::LongLong
+            }
+            ["long", "long", "unsigned"] | ["int", "long", "long", "unsigned"] => Type::UnsignedLongLong,
+            ["float"] => Type::Float,
+            ["double"] => Type::Double,
+            ["double", "long"] => Type::LongDouble,
+            [] => {
+                return Err(CompileError::new(
+                    format!("expected type specifier, found {}", self.peek()),
+                    self.peek().span,
+                ))
+            }
+            _ => {
+                let spelling: Vec<&str> = keywords.iter().map(|keyword| keyword.as_str()).collect();
+                let message = if keywords.contains(&Keyword::Complex) {
+                    format!("type `{}` is not supported yet", spelling.join(" "))
+                } else {
+                    format!("invalid combination of type specifiers `{}`", spelling.join(" "))
+                };
+                return Err(CompileError::new(message, span));
+            }
+        };
+        Ok(resolved)
+    }
+
+    /// Parse the tag of a struct, union or enum specifier, if any, returning
+    /// the name of its type; all three kinds share one tag namespace. A
+    /// specifier with a body or followed by `;` declares the tag in the
+    /// innermost scope, hiding any outer one; otherwise it names the
+    /// innermost tag in scope, or declares it if there is none.
+    fn parse_tag(&mut self, kind: Keyword) -> Result<Option<String>, CompileError> {
+        let (tag, span) = match self.peek().kind.clone() {
+            TokenKind::Identifier(tag) => (tag, self.advance().span),
+            _ => return Ok(None),
+        };
+        let declares = self.is_punct(Punct::LeftBrace) || self.is_punct(Punct::Semicolon);
+        let previous = if declares {
+            self.tags.last().unwrap().get(&tag)
+        } else {
+            self.tags.iter().rev().find_map(|scope| scope.get(&tag))
+        };
+        match previous {
+            Some(previous) if previous.kind != kind => Err(CompileError::new(
+                format!("use of `{}` with tag type that does not match previous declaration", tag),
+                span,
+            )
+            .with_label(previous.span, "previous declaration is here")),
+            Some(previous) => Ok(Some(previous.name.clone())),
+            None => {
+                let name = if self.tags.len() == 1 {
+                    tag.clone()
+                } else {
+                    self.local_tags += 1;
+                    format!("{}.{}", tag, self.local_tags)
+                };
+                self.tags.last_mut().unwrap().insert(
+                    tag,
+                    Tag {
+                        kind,
+                        name: name.clone(),
+                        span,
+                    },
+                );
+                Ok(Some(name))
+            }
+        }
+    }
+
+    /// Name an anonymous struct, union or enum, e.g. `(anonymous union #2)`
+    fn anonymous_tag(&mut self, kind: Keyword) -> String {
+        self.anonymous_types += 1;
+        format!("(anonymous {} #{})", kind.as_str(), self.anonymous_types)
+    }
+
+    /// Parse the rest of a `struct` or `union` specifier after the keyword
+    fn parse_struct_specifier(&mut self, is_union: bool) -> Result<Type, CompileError> {
+        let start = self.previous_span();
+        let kind = if is_union { Keyword::Union } else { Keyword::Struct };
+        let tag = self.parse_tag(kind)?;
+        let record_type = |name: String| if is_union { Type::Union(name) } else { Type::Struct(name) };
+
+        if !self.is_punct(Punct::LeftBrace) {
+            return match tag {
+                Some(name) => Ok(record_type(name)),
+                None => Err(self.error_here(format!(
+                    "expected {} name or `{{`, found {}",
+                    kind.as_str(),
+                    self.peek()
+                ))),
+            };
+        }
+
+        let name = match tag {
+            Some(name) => name,
+            None => self.anonymous_tag(kind),
+        };
+        if let Some(previous) = self.ast.structs.get(&name) {
+            return Err(CompileError::new(
+                format!("redefinition of `{}`", record_type(name)),
+                start,
+            )
+            .with_label(previous.span, "previous definition is here"));
+        }
 
-// TODO: Implement lexer for tokenizing C code
-// TODO: Implement parser for constructing AST from tokens
-// TODO: Implement semantic analysis for type checking and validation
\ No newline at end of file
+        self.expect_punct(Punct::LeftBrace, "to begin struct body")?;
+        let mut fields: Vec<Field> = Vec::new();
+        while !self.eat_punct(Punct::RightBrace) {
+            if self.eat_keyword(Keyword::StaticAssert) {
+                self.parse_static_assert()?;
+                continue;
+            }
+            let specifiers = self.parse_declaration_specifiers(SpecifierContext::Member)?;
+            let base = specifiers.base.unqualified().clone();
+
+            // A struct or union without a tag or declarator is an anonymous member
+            if self.is_punct(Punct::Semicolon) {
+                if let Type::Struct(name) | Type::Union(name) = &base {
+                    if name.starts_with('(') {
+                        let field = Field {
+                            name: name.clone(),
+                            var_type: base.clone(),
+                            qualifiers: specifiers.base.qualifiers(),
+                            bit_width: None,
+                            alignment: specifiers.alignment,
+                            span: specifiers.span,
+                        };
+                        self.add_field(&mut fields, field)?;
+                    }
+                }
+                self.advance();
+                continue;
+            }
+
+            loop {
+                let mut field = if self.is_punct(Punct::Colon) {
+                    // An unnamed bit-field, used for padding
+                    Field {
+                        name: String::new(),
+                        var_type: base.clone(),
+                        qualifiers: specifiers.base.qualifiers(),
+                        bit_width: None,
+                        alignment: specifiers.alignment,
+                        span: self.peek().span,
+                    }
+                } else {
+                    let declarator = self.parse_declarator(false)?;
+                    self.reject_identifier_list(&declarator)?;
+                    let variable = declarator.variable(&specifiers);
+                    Field {
+                        name: variable.name,
+                        var_type: variable.var_type,
+                        qualifiers: variable.qualifiers,
+                        bit_width: None,
+                        alignment: variable.alignment,
+                        span: variable.span,
+                    }
+                };
+                if self.eat_punct(Punct::Colon) {
+                    if field.alignment.is_some() {
+                        return Err(CompileError::new("`_Alignas` cannot be applied to a bit-field", field.span));
+                    }
+                    field.bit_width = Some(self.parse_bit_width(&field.name)?);
+                }
+                self.add_field(&mut fields, field)?;
+                if !self.eat_punct(Punct::Comma) {
+                    break;
+                }
+            }
+            self.expect_punct(Punct::Semicolon, "after struct member")?;
+        }
+
+        let span = start.to(self.previous_span());
+        self.ast.structs.insert(
+            name.clone(),
+            Struct {
+                name: name.clone(),
+                is_union,
+                fields,
+                span,
+            },
+        );
+        Ok(record_type(name))
+    }
+
+    /// Add a member to a struct or union, rejecting duplicate names including
+    /// those of the members of anonymous structs and unions
+    fn add_field(&self, fields: &mut Vec<Field>, field: Field) -> Result<(), CompileError> {
+        let existing = self.member_names(fields);
+        for name in self.member_names(std::slice::from_ref(&field)) {
+            if existing.contains(&name) {
+                return Err(CompileError::new(format!("duplicate member `{}`", name), field.span));
+            }
+        }
+        fields.push(field);
+        Ok(())
+    }
+
+    /// The names by which members can be accessed, looking through anonymous
+    /// structs and unions
+    fn member_names(&self, fields: &[Field]) -> Vec<String> {
+        let mut names = Vec::new();
+        for field in fields {
+            if field.is_anonymous() {
+                if let Some(definition) = self.ast.structs.get(&field.name) {
+                    names.extend(self.member_names(&definition.fields));
+                }
+            } else if !field.name.is_empty() {
+                names.push(field.name.clone());
+            }
+        }
+        names
+    }
+
+    /// Parse the width of a bit-field after the `:`
+    fn parse_bit_width(&mut self, name: &str) -> Result<u32, CompileError> {
+        let width = self.parse_conditional()?;
+        let value = self
+            .integer_constant(&width)?
+            .map_err(|not_constant| not_constant.error("bit-field width is not an integer constant expression"))?
+            .value();
+        let field = if name.is_empty() {
+            "anonymous bit-field".to_string()
+        } else {
+            format!("bit-field `{}`", name)
+        };
+        if value < 0 {
+            return Err(CompileError::new(format!("{} has negative width ({})", field, value), width.span));
+        }
+        if value == 0 && !name.is_empty() {
+            return Err(CompileError::new(format!("named {} has zero width", field), width.span));
+        }
+        u32::try_from(value).map_err(|_| CompileError::new(format!("{} is too wide", field), width.span))
+    }
+
+    /// Parse the rest of an `enum` specifier after the keyword, declaring its
+    /// enumeration constants in the current scope
+    fn parse_enum_specifier(&mut self) -> Result<Type, CompileError> {
+        let start = self.previous_span();
+        let tag = self.parse_tag(Keyword::Enum)?;
+
+        if !self.is_punct(Punct::LeftBrace) {
+            return match tag {
+                Some(name) => self.enums.get(&name).map(|(enum_type, _)| enum_type.clone()).ok_or_else(|| {
+                    CompileError::new(
+                        format!("ISO C forbids forward references to `enum {}`", tag_spelling(&name)),
+                        start.to(self.previous_span()),
+                    )
+                }),
+                None => Err(self.error_here(format!("expected enum name or `{{`, found {}", self.peek()))),
+            };
+        }
+
+        let name = match tag {
+            Some(name) => name,
+            None => self.anonymous_tag(Keyword::Enum),
+        };
+        if let Some((_, previous)) = self.enums.get(&name) {
+            return Err(CompileError::new(format!("redefinition of `enum {}`", tag_spelling(&name)), start)
+                .with_label(*previous, "previous definition is here"));
+        }
+
+        self.expect_punct(Punct::LeftBrace, "to begin enumerator list")?;
+        let mut values = Vec::new();
+        let mut next = Some(0i64);
+        while !self.eat_punct(Punct::RightBrace) {
+            let (enumerator, span) = self.expect_identifier("in enumerator list")?;
+            let value = if self.eat_punct(Punct::Equal) {
+                let value = self.parse_conditional()?;
+                self.integer_constant(&value)?
+                    .map_err(|not_constant| not_constant.error("enumerator value is not an integer constant expression"))?
+                    .value()
+            } else {
+                next.ok_or_else(|| CompileError::new("overflow in enumeration value", span))?
+            };
+            self.declare_ordinary(&enumerator, Ordinary::Enumerator(value), span)?;
+            values.push(value);
+            next = value.checked_add(1);
+
+            if !self.eat_punct(Punct::Comma) {
+                self.expect_punct(Punct::RightBrace, "after enumerator list")?;
+                break;
+            }
+        }
+        if values.is_empty() {
+            return Err(CompileError::new("use of empty enum", start.to(self.previous_span())));
+        }
+
+        // Like GCC, an enum without negative values is unsigned, and one
+        // whose values do not fit in an int uses the 64-bit integer types
+        let (min, max) = (*values.iter().min().unwrap(), *values.iter().max().unwrap());
+        let underlying = if min < 0 {
+            if min >= i32::MIN as i64 && max <= i32::MAX as i64 {
+                Type::Int
+            } else {
+                Type::LongLong
+            }
+        } else if max <= u32::MAX as i64 {
+            Type::UnsignedInt
+        } else {
+            Type::UnsignedLongLong
+        };
+        let enum_type = Type::Enum(name.clone(), Box::new(underlying));
+        self.enums.insert(name, (enum_type.clone(), start));
+        Ok(enum_type)
+    }
+
+    /// Parse a declarator; abstract declarators (without a name) are accepted
+    /// when `is_abstract` is set and required to be named otherwise
+    fn parse_declarator(&mut self, is_abstract: bool) -> Result<Declarator, CompileError> {
+        let start = self.peek().span;
+
+        let mut pointers = Vec::new();
+        while self.eat_punct(Punct::Star) {
+            let mut qualifiers = Qualifiers::default();
+            loop {
+                if self.eat_keyword(Keyword::Const) {
+                    qualifiers.is_const = true;
+                } else if self.eat_keyword(Keyword::Volatile) {
+                    qualifiers.is_volatile = true;
+                } else if self.eat_keyword(Keyword::Restrict) {
+                    qualifiers.is_restrict = true;
+                } else if self.eat_keyword(Keyword::Atomic) {
+                    qualifiers.is_atomic = true;
+                } else {
+                    break;
+                }
+            }
+            pointers.push(Derivation::Pointer(qualifiers));
+        }
+
+        let mut name = None;
+        let mut inner = None;
+        let nested = self.is_punct(Punct::LeftParen)
+            && match self.peek_nth(1).kind {
+                TokenKind::Punct(Punct::Star | Punct::LeftParen | Punct::LeftBracket) => true,
+                TokenKind::Identifier(_) => !is_abstract,
+                _ => false,
+            };
+        if nested {
+            self.advance();
+            inner = Some(self.parse_declarator(is_abstract)?);
+            self.expect_punct(Punct::RightParen, "to close declarator")?;
+        } else if let TokenKind::Identifier(identifier) = self.peek().kind.clone() {
+            if is_abstract {
+                return Err(self.error_here(format!("unexpected identifier `{}` in type name", identifier)));
+            }
+            name = Some((identifier, self.advance().span));
+        } else if !is_abstract {
+            return Err(self.error_here(format!("expected identifier in declarator, found {}", self.peek())));
+        }
+
+        let mut suffixes = Vec::new();
+        loop {
+            if self.eat_punct(Punct::LeftBracket) {
+                suffixes.push(self.parse_array_size()?);
+            } else if self.eat_punct(Punct::LeftParen) {
+                suffixes.push(self.parse_parameter_list()?);
+            } else {
+                break;
+            }
+        }
+
+        let mut derivations = Vec::new();
+        if let Some(inner) = inner {
+            name = inner.name;
+            derivations.extend(inner.derivations);
+        }
+        derivations.extend(suffixes);
+        derivations.extend(pointers.into_iter().rev());
+
+        let end = if self.pos > 0 { self.previous_span() } else { start };
+        let declarator = Declarator {
+            name,
+            derivations,
+            span: start.to(end),
+        };
+        // Only the parameters of the function being defined can be named
+        // without their types
+        if declarator.derivations.iter().skip(1).any(|derivation| matches!(derivation, Derivation::IdentifierList(_))) {
+            return Err(identifier_list_error(declarator.span));
+        }
+        Ok(declarator)
+    }
+
+    /// Parse an array size after `[`, including the closing bracket; a size
+    /// that is not an integer constant makes a variable length array
+    fn parse_array_size(&mut self) -> Result<Derivation, CompileError> {
+        if self.eat_punct(Punct::RightBracket) {
+            return Ok(Derivation::Array(None));
+        }
+        let size = self.parse_assignment()?;
+        self.expect_punct(Punct::RightBracket, "after array size")?;
+        // Semantic analysis reports the errors in a length that is not constant
+        let Ok(Ok(value)) = self.integer_constant(&size) else {
+            self.variable_arrays += 1;
+            return Ok(Derivation::VariableArray(VariableLength {
+                id: self.variable_arrays,
+                length: Box::new(size),
+                typedef: false,
+            }));
+        };
+        if value.is_negative() {
+            return Err(CompileError::new("array size is negative", size.span));
+        }
+        let value = usize::try_from(value.value()).map_err(|_| CompileError::new("array is too large", size.span))?;
+        Ok(Derivation::Array(Some(value)))
+    }
+
+    /// Parse a parameter list or identifier list after `(`, including the
+    /// closing parenthesis; the parameters have their own scope
+    fn parse_parameter_list(&mut self) -> Result<Derivation, CompileError> {
+        self.push_scope();
+        let derivation = if matches!(self.peek().kind, TokenKind::Identifier(_)) && self.typedef_at(0).is_none() {
+            self.parse_identifier_list().map(Derivation::IdentifierList)
+        } else {
+            self.parse_parameters().map(|(parameters, is_variadic)| Derivation::Function(parameters, is_variadic))
+        };
+        self.pop_scope();
+        derivation
+    }
+
+    /// Parse the identifier list of an old-style definition such as
+    /// `int f(a, b) int a; char *b; { ... }`. The parameters are `int` until
+    /// the declarations after the declarator give their types.
+    fn parse_identifier_list(&mut self) -> Result<Vec<Variable>, CompileError> {
+        let mut parameters: Vec<Variable> = Vec::new();
+        loop {
+            let (name, span) = self.expect_identifier("in identifier list")?;
+            if parameters.iter().any(|parameter| parameter.name == name) {
+                return Err(CompileError::new(format!("redefinition of parameter `{}`", name), span));
+            }
+            parameters.push(Variable {
+                name,
+                var_type: Type::Int,
+                qualifiers: Qualifiers::default(),
+                storage: None,
+                is_thread_local: false,
+                alignment: None,
+                span,
+            });
+            if !self.eat_punct(Punct::Comma) {
+                break;
+            }
+        }
+        self.expect_punct(Punct::RightParen, "after parameters")?;
+        Ok(parameters)
+    }
+
+    /// Parse the declarations between the declarator of an old-style
+    /// definition and its body, which give the types of the parameters
+    fn parse_parameter_declarations(&mut self, parameters: &mut [Variable]) -> Result<(), CompileError> {
+        self.push_scope();
+        let result = self.parse_parameter_declaration_list(parameters);
+        self.pop_scope();
+        result
+    }
+
+    fn parse_parameter_declaration_list(&mut self, parameters: &mut [Variable]) -> Result<(), CompileError> {
+        let mut declared: Vec<String> = Vec::new();
+        while !self.is_punct(Punct::LeftBrace) {
+            let specifiers = self.parse_declaration_specifiers(SpecifierContext::Parameter)?;
+            loop {
+                let declarator = self.parse_declarator(false)?;
+                self.reject_identifier_list(&declarator)?;
+                let (name, span) = declarator.name.clone().unwrap_or_default();
+                let Some(parameter) = parameters.iter_mut().find(|parameter| parameter.name == name) else {
+                    return Err(CompileError::new(format!("parameter named `{}` is missing", name), span));
+                };
+                if declared.contains(&name) {
+                    return Err(CompileError::new(format!("redefinition of parameter `{}`", name), span));
+                }
+                *parameter = declarator.parameter(&specifiers);
+                self.declare_ordinary(&name, Ordinary::Object(parameter.qualified_type()), span)?;
+                declared.push(name);
+                if !self.eat_punct(Punct::Comma) {
+                    break;
+                }
+            }
+            self.expect_punct(Punct::Semicolon, "after parameter declaration")?;
+        }
+        Ok(())
+    }
+
+    /// Reject a declarator with an identifier list outside a function definition
+    fn reject_identifier_list(&self, declarator: &Declarator) -> Result<(), CompileError> {
+        if declarator.has_identifier_list() {
+            return Err(identifier_list_error(declarator.span));
+        }
+        Ok(())
+    }
+
+    fn parse_parameters(&mut self) -> Result<(Vec<Variable>, bool), CompileError> {
+        let mut parameters = Vec::new();
+        if self.eat_punct(Punct::RightParen) {
+            return Ok((parameters, false));
+        }
+        if self.is_keyword(Keyword::Void) && self.is_punct_at(1, Punct::RightParen) {
+            self.advance();
+            self.advance();
+            return Ok((parameters, false));
+        }
+
+        let mut is_variadic = false;
+        loop {
+            if self.is_punct(Punct::Ellipsis) {
+                if parameters.is_empty() {
+                    return Err(self.error_here("ISO C requires a named parameter before `...`"));
+                }
+                self.advance();
+                is_variadic = true;
+                break;
+            }
+            let specifiers = self.parse_declaration_specifiers(SpecifierContext::Parameter)?;
+            let start = self.peek().span;
+            let declarator = if self.is_punct(Punct::Comma) || self.is_punct(Punct::RightParen) {
+                Declarator {
+                    name: None,
+                    derivations: Vec::new(),
+                    span: start,
+                }
+            } else {
+                self.parse_parameter_declarator()?
+            };
+
+            self.reject_identifier_list(&declarator)?;
+            let mut parameter = declarator.parameter(&specifiers);
+            if declarator.name.is_none() {
+                parameter.span = specifiers.span;
+            } else {
+                self.declare_ordinary(&parameter.name, Ordinary::Object(parameter.qualified_type()), parameter.span)?;
+            }
+            parameters.push(parameter);
+
+            if !self.eat_punct(Punct::Comma) {
+                break;
+            }
+        }
+
+        self.expect_punct(Punct::RightParen, "after parameters")?;
+        Ok((parameters, is_variadic))
+    }
+
+    /// Parameters may be named or abstract
+    fn parse_parameter_declarator(&mut self) -> Result<Declarator, CompileError> {
+        let mut offset = 0;
+        while matches!(
+            self.peek_nth(offset).kind,
+            TokenKind::Punct(Punct::Star | Punct::LeftParen)
+                | TokenKind::Keyword(Keyword::Const | Keyword::Volatile | Keyword::Restrict | Keyword::Atomic)
+        ) {
+            offset += 1;
+        }
+        let is_named = matches!(self.peek_nth(offset).kind, TokenKind::Identifier(_));
+        self.parse_declarator(!is_named)
+    }
+
+    /// Parse a type name as used in casts
+    fn parse_type_name(&mut self) -> Result<Type, CompileError> {
+        let specifiers = self.parse_declaration_specifiers(SpecifierContext::TypeName)?;
+        let declarator = self.parse_declarator(true)?;
+        self.reject_identifier_list(&declarator)?;
+        Ok(declarator.build_type(&specifiers.base))
+    }
+
+    /// Parse a declaration inside a function body into one statement per declarator
+    fn parse_local_declaration(&mut self) -> Result<Vec<Statement>, CompileError> {
+        let specifiers = self.parse_declaration_specifiers(SpecifierContext::Block)?;
+        let mut statements = Vec::new();
+        if self.eat_punct(Punct::Semicolon) {
+            return Ok(statements);
+        }
+
+        loop {
+            let declarator = self.parse_declarator(false)?;
+            self.reject_identifier_list(&declarator)?;
+            let name_span = declarator.name.clone().unwrap_or_default().1;
+
+            if specifiers.is_typedef {
+                let typedef = self.typedef_declaration(&declarator, &specifiers)?;
+                statements.push(Statement {
+                    kind: StatementKind::Typedef(declarator.name.clone().unwrap_or_default().0, typedef),
+                    span: specifiers.span.to(self.previous_span()),
+                });
+            } else if let Some(function) = self.function_declaration(&declarator, &specifiers)? {
+                if let Some(storage) = function.storage.filter(|storage| *storage != StorageClass::Extern) {
+                    return Err(CompileError::new(
+                        format!("function declared in block scope cannot have `{}` storage class", storage.as_str()),
+                        specifiers.span,
+                    ));
+                }
+                self.declare_function(function, name_span, false)?;
+            } else {
+                let variable = self.variable_declaration(&declarator, &specifiers)?;
+                let initializer = self.parse_initializer()?;
+                statements.push(Statement {
+                    kind: StatementKind::Declaration(variable, initializer),
+                    span: specifiers.span.to(self.previous_span()),
+                });
+            }
+
+            if !self.eat_punct(Punct::Comma) {
+                break;
+            }
+        }
+
+        self.expect_punct(Punct::Semicolon, "after declaration")?;
+        Ok(statements)
+    }
+
+    // Statements
+
+    fn parse_block(&mut self) -> Result<Block, CompileError> {
+        self.push_scope();
+        let block = self.parse_block_items();
+        self.pop_scope();
+        block
+    }
+
+    /// Parse a function body, whose outermost block is also the scope of the parameters
+    fn parse_function_body(&mut self, parameters: &[Variable]) -> Result<Block, CompileError> {
+        self.push_scope();
+        let body = parameters
+            .iter()
+            .filter(|parameter| !parameter.name.is_empty())
+            .try_for_each(|parameter| {
+                self.declare_ordinary(&parameter.name, Ordinary::Object(parameter.qualified_type()), parameter.span)
+            })
+            .and_then(|_| self.parse_block_items());
+        self.pop_scope();
+        body
+    }
+
+    fn parse_block_items(&mut self) -> Result<Block, CompileError> {
+        self.expect_punct(Punct::LeftBrace, "to begin block")?;
+        let mut statements = Vec::new();
+        while !self.eat_punct(Punct::RightBrace) {
+            if self.peek().kind == TokenKind::Eof {
+                return Err(self.error_here("expected `}` before end of file"));
+            }
+            if self.eat_keyword(Keyword::StaticAssert) {
+                self.parse_static_assert()?;
+            // A typedef name followed by `:` is a label
+            } else if self.is_type_start() && !self.is_punct_at(1, Punct::Colon) {
+                statements.extend(self.parse_local_declaration()?);
+            } else {
+                statements.push(self.parse_statement()?);
+            }
+        }
+        Ok(Block { statements })
+    }
+
+    /// Parse a statement used as the body of a control statement
+    fn parse_body(&mut self) -> Result<Block, CompileError> {
+        let statement = self.parse_statement()?;
+        Ok(match statement.kind {
+            StatementKind::Block(block) => block,
+            _ => Block {
+                statements: vec![statement],
+            },
+        })
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, CompileError> {
+        let start = self.peek().span;
+        let kind = match self.peek().kind.clone() {
+            TokenKind::Punct(Punct::LeftBrace) => StatementKind::Block(self.parse_block()?),
+            TokenKind::Punct(Punct::Semicolon) => {
+                self.advance();
+                StatementKind::Block(Block { statements: vec![] })
+            }
+            TokenKind::Keyword(Keyword::If) => {
+                self.advance();
+                self.expect_punct(Punct::LeftParen, "after `if`")?;
+                let condition = self.parse_expression()?;
+                self.expect_punct(Punct::RightParen, "after condition")?;
+                let then_block = self.parse_body()?;
+                let else_block = if self.eat_keyword(Keyword::Else) {
+                    Some(self.parse_body()?)
+                } else {
+                    None
+                };
+                StatementKind::If(condition, then_block, else_block)
+            }
+            TokenKind::Keyword(Keyword::While) => {
+                self.advance();
+                self.expect_punct(Punct::LeftParen, "after `while`")?;
+                let condition = self.parse_expression()?;
+                self.expect_punct(Punct::RightParen, "after condition")?;
+                StatementKind::While(condition, self.parse_body()?)
+            }
+            TokenKind::Keyword(Keyword::For) => {
+                self.advance();
+                self.parse_for()?
+            }
+            TokenKind::Keyword(Keyword::Return) => {
+                self.advance();
+                let value = if self.is_punct(Punct::Semicolon) {
+                    None
+                } else {
+                    Some(self.parse_expression()?)
+                };
+                self.expect_punct(Punct::Semicolon, "after return statement")?;
+                StatementKind::Return(value)
+            }
+            TokenKind::Keyword(Keyword::Do) => {
+                self.advance();
+                let body = self.parse_body()?;
+                if !self.eat_keyword(Keyword::While) {
+                    return Err(self.error_here(format!("expected `while` in do/while loop, found {}", self.peek())));
+                }
+                self.expect_punct(Punct::LeftParen, "after `while`")?;
+                let condition = self.parse_expression()?;
+                self.expect_punct(Punct::RightParen, "after condition")?;
+                self.expect_punct(Punct::Semicolon, "after do/while statement")?;
+                StatementKind::DoWhile(body, condition)
+            }
+            TokenKind::Keyword(Keyword::Switch) => {
+                self.advance();
+                self.expect_punct(Punct::LeftParen, "after `switch`")?;
+                let condition = self.parse_expression()?;
+                self.expect_punct(Punct::RightParen, "after condition")?;
+                StatementKind::Switch(condition, self.parse_body()?)
+            }
+            TokenKind::Keyword(Keyword::Case) => {
+                self.advance();
+                let value = self.parse_conditional()?;
+                self.expect_punct(Punct::Colon, "after `case` value")?;
+                StatementKind::Case(value, Box::new(self.parse_statement()?))
+            }
+            TokenKind::Keyword(Keyword::Default) => {
+                self.advance();
+                self.expect_punct(Punct::Colon, "after `default`")?;
+                StatementKind::Default(Box::new(self.parse_statement()?))
+            }
+            TokenKind::Keyword(Keyword::Goto) => {
+                self.advance();
+                let (label, _) = self.expect_identifier("after `goto`")?;
+                self.expect_punct(Punct::Semicolon, "after goto statement")?;
+                StatementKind::Goto(label)
+            }
+            TokenKind::Keyword(keyword @ (Keyword::Break | Keyword::Continue)) => {
+                self.advance();
+                self.expect_punct(Punct::Semicolon, &format!("after {} statement", keyword.as_str()))?;
+                if keyword == Keyword::Break {
+                    StatementKind::Break
+                } else {
+                    StatementKind::Continue
+                }
+            }
+            TokenKind::Identifier(label) if self.is_punct_at(1, Punct::Colon) => {
+                self.advance();
+                self.advance();
+                StatementKind::Label(label, Box::new(self.parse_statement()?))
+            }
+            _ => {
+                let expression = self.parse_expression()?;
+                self.expect_punct(Punct::Semicolon, "after expression")?;
+                StatementKind::Expression(expression)
+            }
+        };
+
+        Ok(Statement {
+            kind,
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    /// Parse a `for` statement after the keyword; a declaration in its
+    /// first clause is scoped to the statement
+    fn parse_for(&mut self) -> Result<StatementKind, CompileError> {
+        self.push_scope();
+        let statement = self.parse_for_clauses();
+        self.pop_scope();
+        statement
+    }
+
+    fn parse_for_clauses(&mut self) -> Result<StatementKind, CompileError> {
+        self.expect_punct(Punct::LeftParen, "after `for`")?;
+
+        let init_span = self.peek().span;
+        let init = if self.is_type_start() {
+            let mut declarations = self.parse_local_declaration()?;
+            if declarations.len() == 1 {
+                declarations.remove(0)
+            } else {
+                Statement {
+                    kind: StatementKind::Block(Block {
+                        statements: declarations,
+                    }),
+                    span: init_span.to(self.previous_span()),
+                }
+            }
+        } else if self.is_punct(Punct::Semicolon) {
+            self.advance();
+            Statement {
+                kind: StatementKind::Block(Block { statements: vec![] }),
+                span: init_span,
+            }
+        } else {
+            let init = self.parse_expression()?;
+            self.expect_punct(Punct::Semicolon, "after for-loop initializer")?;
+            Statement {
+                span: init.span,
+                kind: StatementKind::Expression(init),
+            }
+        };
+
+        let condition = if self.is_punct(Punct::Semicolon) {
+            Expression::new(ExpressionKind::Literal(Literal::Int(1)), self.peek().span)
+        } else {
+            self.parse_expression()?
+        };
+        self.expect_punct(Punct::Semicolon, "after for-loop condition")?;
+
+        let step = if self.is_punct(Punct::RightParen) {
+            Statement {
+                kind: StatementKind::Block(Block { statements: vec![] }),
+                span: self.peek().span,
+            }
+        } else {
+            let step = self.parse_expression()?;
+            Statement {
+                span: step.span,
+                kind: StatementKind::Expression(step),
+            }
+        };
+        self.expect_punct(Punct::RightParen, "after for-loop clauses")?;
+
+        let body = self.parse_body()?;
+        Ok(StatementKind::For(Box::new(init), condition, Box::new(step), body))
+    }
+
+    // Expressions
+
+    /// Parse a full expression, including the comma operator
+    fn parse_expression(&mut self) -> Result<Expression, CompileError> {
+        let mut expression = self.parse_assignment()?;
+        while self.eat_punct(Punct::Comma) {
+            let rhs = self.parse_assignment()?;
+            let span = expression.span.to(rhs.span);
+            expression = Expression::new(ExpressionKind::Comma(Box::new(expression), Box::new(rhs)), span);
+        }
+        Ok(expression)
+    }
+
+    /// Parse an assignment expression, as used for arguments and initializers
+    fn parse_assignment(&mut self) -> Result<Expression, CompileError> {
+        let target = self.parse_conditional()?;
+        let operator = match self.peek().kind {
+            TokenKind::Punct(Punct::Equal) => None,
+            TokenKind::Punct(punct) if compound_assignment_operator(punct).is_some() => {
+                compound_assignment_operator(punct)
+            }
+            _ => return Ok(target),
+        };
+        self.advance();
+        // Assignment is right-associative
+        let value = self.parse_assignment()?;
+        let span = target.span.to(value.span);
+        let kind = match operator {
+            None => ExpressionKind::Assignment(Box::new(target), Box::new(value)),
+            Some(operator) => ExpressionKind::CompoundAssignment(Box::new(target), operator, Box::new(value), None),
+        };
+        Ok(Expression::new(kind, span))
+    }
+
+    /// Parse a conditional expression, the form of constant expressions
+    fn parse_conditional(&mut self) -> Result<Expression, CompileError> {
+        let condition = self.parse_binary(1)?;
+        if !self.eat_punct(Punct::Question) {
+            return Ok(condition);
+        }
+        let then_value = self.parse_expression()?;
+        self.expect_punct(Punct::Colon, "in conditional expression")?;
+        let else_value = self.parse_conditional()?;
+        let span = condition.span.to(else_value.span);
+        Ok(Expression::new(
+            ExpressionKind::Conditional(Box::new(condition), Box::new(then_value), Box::new(else_value)),
+            span,
+        ))
+    }
+
+    /// Precedence climbing over the binary operators
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, CompileError> {
+        let mut lhs = self.parse_cast()?;
+        while let TokenKind::Punct(punct) = self.peek().kind {
+            let (operator, precedence) = match binary_operator(punct) {
+                Some((operator, precedence)) if precedence >= min_precedence => (operator, precedence),
+                _ => break,
+            };
+            self.advance();
+            let rhs = self.parse_binary(precedence + 1)?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expression::new(ExpressionKind::BinaryOp(Box::new(lhs), operator, Box::new(rhs)), span);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_cast(&mut self) -> Result<Expression, CompileError> {
+        if self.is_punct(Punct::LeftParen) && self.is_type_start_at(1) {
+            let start = self.advance().span;
+            let target_type = self.parse_type_name()?;
+            self.expect_punct(Punct::RightParen, "after type name")?;
+            if self.is_punct(Punct::LeftBrace) {
+                let literal = self.parse_compound_literal(target_type, start)?;
+                return self.parse_postfix_operators(literal);
+            }
+            let operand = self.parse_cast()?;
+            let span = start.to(operand.span);
+            return Ok(Expression::new(ExpressionKind::Cast(Box::new(operand), target_type), span));
+        }
+        self.parse_unary()
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, CompileError> {
+        let start = self.peek().span;
+        let operator = match self.peek().kind {
+            TokenKind::Punct(Punct::PlusPlus) => Some(UnaryOperator::PreIncrement),
+            TokenKind::Punct(Punct::MinusMinus) => Some(UnaryOperator::PreDecrement),
+            TokenKind::Punct(Punct::Minus) => Some(UnaryOperator::Negate),
+            TokenKind::Punct(Punct::Bang) => Some(UnaryOperator::LogicalNot),
+            TokenKind::Punct(Punct::Tilde) => Some(UnaryOperator::BitwiseNot),
+            TokenKind::Punct(Punct::Plus) => Some(UnaryOperator::Plus),
+            TokenKind::Punct(Punct::Star | Punct::Amp) => None,
+            TokenKind::Keyword(Keyword::Sizeof) => return self.parse_sizeof(),
+            TokenKind::Keyword(Keyword::Alignof) => {
+                self.advance();
+                self.expect_punct(Punct::LeftParen, "after `_Alignof`")?;
+                let operand = self.parse_type_name()?;
+                self.expect_punct(Punct::RightParen, "after type name")?;
+                return Ok(Expression::new(ExpressionKind::AlignOf(operand), start.to(self.previous_span())));
+            }
+            _ => return self.parse_postfix(),
+        };
+
+        let token = self.advance();
+        let operand = match operator {
+            Some(UnaryOperator::PreIncrement | UnaryOperator::PreDecrement) => self.parse_unary()?,
+            _ => self.parse_cast()?,
+        };
+        let span = start.to(operand.span);
+        let kind = match (operator, token.kind) {
+            (Some(operator), _) => ExpressionKind::UnaryOp(operator, Box::new(operand)),
+            (None, TokenKind::Punct(Punct::Star)) => ExpressionKind::Pointer(Box::new(operand)),
+            _ => ExpressionKind::Address(Box::new(operand)),
+        };
+        Ok(Expression::new(kind, span))
+    }
+
+    /// Parse `sizeof expression` or `sizeof (type-name)`
+    fn parse_sizeof(&mut self) -> Result<Expression, CompileError> {
+        let start = self.advance().span;
+        if self.is_punct(Punct::LeftParen) && self.is_type_start_at(1) {
+            let open = self.advance().span;
+            let operand = self.parse_type_name()?;
+            self.expect_punct(Punct::RightParen, "after type name")?;
+            // `sizeof (type){ ... }` is the size of a compound literal
+            if !self.is_punct(Punct::LeftBrace) {
+                return Ok(Expression::new(ExpressionKind::SizeOfType(operand), start.to(self.previous_span())));
+            }
+            let literal = self.parse_compound_literal(operand, open)?;
+            let operand = self.parse_postfix_operators(literal)?;
+            let span = start.to(operand.span);
+            return Ok(Expression::new(ExpressionKind::SizeOf(Box::new(operand)), span));
+        }
+        let operand = self.parse_unary()?;
+        let span = start.to(operand.span);
+        Ok(Expression::new(ExpressionKind::SizeOf(Box::new(operand)), span))
+    }
+
+    /// Parse the initializer list of a compound literal after its parenthesized type name
+    fn parse_compound_literal(&mut self, literal_type: Type, start: Span) -> Result<Expression, CompileError> {
+        let initializer = self.parse_initializer_list()?;
+        let span = start.to(initializer.span());
+        Ok(Expression::new(ExpressionKind::CompoundLiteral(literal_type, Box::new(initializer)), span))
+    }
+
+    /// Parse a brace-enclosed initializer list, with optional designators
+    fn parse_initializer_list(&mut self) -> Result<Initializer, CompileError> {
+        let start = self.expect_punct(Punct::LeftBrace, "to begin initializer list")?;
+        let mut items = Vec::new();
+        while !self.eat_punct(Punct::RightBrace) {
+            let mut designators = Vec::new();
+            loop {
+                if self.eat_punct(Punct::LeftBracket) {
+                    let index = self.parse_conditional()?;
+                    let value = self
+                        .integer_constant(&index)?
+                        .map_err(|not_constant| not_constant.error("expression is not an integer constant expression"))?
+                        .value();
+                    let value = usize::try_from(value).map_err(|_| {
+                        CompileError::new(format!("array designator value `{}` is negative", value), index.span)
+                    })?;
+                    self.expect_punct(Punct::RightBracket, "after array designator")?;
+                    designators.push(Designator::Index(value));
+                } else if self.eat_punct(Punct::Dot) {
+                    let (member, _) = self.expect_identifier("after `.` in designator")?;
+                    designators.push(Designator::Member(member));
+                } else {
+                    break;
+                }
+            }
+            if !designators.is_empty() {
+                self.expect_punct(Punct::Equal, "after designator")?;
+            }
+            let initializer = if self.is_punct(Punct::LeftBrace) {
+                self.parse_initializer_list()?
+            } else {
+                Initializer::Expression(self.parse_assignment()?)
+            };
+            items.push(InitializerItem {
+                designators,
+                initializer,
+            });
+
+            if !self.eat_punct(Punct::Comma) {
+                self.expect_punct(Punct::RightBrace, "after initializer list")?;
+                break;
+            }
+        }
+        Ok(Initializer::List(items, start.to(self.previous_span())))
+    }
+
+    /// Parse a `_Generic` selection
+    fn parse_generic_selection(&mut self) -> Result<Expression, CompileError> {
+        let start = self.advance().span;
+        self.expect_punct(Punct::LeftParen, "after `_Generic`")?;
+        let control = self.parse_assignment()?;
+        self.expect_punct(Punct::Comma, "after `_Generic` controlling expression")?;
+
+        let mut associations: Vec<(Option<Type>, Expression)> = Vec::new();
+        loop {
+            let association_start = self.peek().span;
+            let association_type = if self.eat_keyword(Keyword::Default) {
+                if associations.iter().any(|(association_type, _)| association_type.is_none()) {
+                    return Err(CompileError::new("duplicate default generic association", association_start));
+                }
+                None
+            } else {
+                Some(self.parse_type_name()?)
+            };
+            self.expect_punct(Punct::Colon, "after generic association type")?;
+            associations.push((association_type, self.parse_assignment()?));
+            if !self.eat_punct(Punct::Comma) {
+                break;
+            }
+        }
+        self.expect_punct(Punct::RightParen, "after generic associations")?;
+
+        Ok(Expression::new(
+            ExpressionKind::Generic(Box::new(control), associations),
+            start.to(self.previous_span()),
+        ))
+    }
+
+    /// Parse a call of one of the builtins behind `stdarg.h`
+    fn parse_va_builtin(&mut self) -> Result<Expression, CompileError> {
+        let token = self.advance().clone();
+        let name = match &token.kind {
+            TokenKind::Identifier(name) => name.clone(),
+            _ => unreachable!("builtin names are identifiers"),
+        };
+        self.expect_punct(Punct::LeftParen, &format!("after `{}`", name))?;
+        let list = Box::new(self.parse_assignment()?);
+        let kind = match name.as_str() {
+            "__builtin_va_start" => {
+                self.expect_punct(Punct::Comma, "after `va_list` argument")?;
+                self.parse_assignment()?;
+                ExpressionKind::VaStart(list)
+            }
+            "__builtin_va_arg" => {
+                self.expect_punct(Punct::Comma, "after `va_list` argument")?;
+                ExpressionKind::VaArg(list, self.parse_type_name()?)
+            }
+            "__builtin_va_end" => ExpressionKind::VaEnd(list),
+            "__builtin_va_copy" => {
+                self.expect_punct(Punct::Comma, "after `va_list` argument")?;
+                ExpressionKind::VaCopy(list, Box::new(self.parse_assignment()?))
+            }
+            _ => return Err(CompileError::new(format!("use of unknown builtin `{}`", name), token.span)),
+        };
+        self.expect_punct(Punct::RightParen, "after builtin arguments")?;
+        Ok(Expression::new(kind, token.span.to(self.previous_span())))
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expression, CompileError> {
+        let expression = self.parse_primary()?;
+        self.parse_postfix_operators(expression)
+    }
+
+    fn parse_postfix_operators(&mut self, mut expression: Expression) -> Result<Expression, CompileError> {
+        loop {
+            let start = expression.span;
+            let kind = match self.peek().kind {
+                TokenKind::Punct(Punct::LeftBracket) => {
+                    self.advance();
+                    let index = self.parse_expression()?;
+                    self.expect_punct(Punct::RightBracket, "after array index")?;
+                    ExpressionKind::Index(Box::new(expression), Box::new(index))
+                }
+                TokenKind::Punct(Punct::LeftParen) => {
+                    self.advance();
+                    let mut arguments = Vec::new();
+                    if !self.is_punct(Punct::RightParen) {
+                        loop {
+                            arguments.push(self.parse_assignment()?);
+                            if !self.eat_punct(Punct::Comma) {
+                                break;
+                            }
+                        }
+                    }
+                    self.expect_punct(Punct::RightParen, "after arguments")?;
+                    ExpressionKind::Call(Box::new(expression), arguments)
+                }
+                TokenKind::Punct(Punct::Dot) => {
+                    self.advance();
+                    let (member, _) = self.expect_identifier("after `.`")?;
+                    ExpressionKind::Member(Box::new(expression), member)
+                }
+                TokenKind::Punct(Punct::Arrow) => {
+                    self.advance();
+                    let (member, _) = self.expect_identifier("after `->`")?;
+                    let span = expression.span;
+                    let target = Expression::new(ExpressionKind::Pointer(Box::new(expression)), span);
+                    ExpressionKind::Member(Box::new(target), member)
+                }
+                TokenKind::Punct(Punct::PlusPlus) => {
+                    self.advance();
+                    ExpressionKind::UnaryOp(UnaryOperator::PostIncrement, Box::new(expression))
+                }
+                TokenKind::Punct(Punct::MinusMinus) => {
+                    self.advance();
+                    ExpressionKind::UnaryOp(UnaryOperator::PostDecrement, Box::new(expression))
+                }
+                _ => break,
+            };
+            expression = Expression::new(kind, start.to(self.previous_span()));
+        }
+        Ok(expression)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, CompileError> {
+        let token = self.peek().clone();
+        let kind = match token.kind {
+            TokenKind::Identifier(ref name)
+                if name.starts_with("__builtin_va_") && self.is_punct_at(1, Punct::LeftParen) =>
+            {
+                return self.parse_va_builtin();
+            }
+            TokenKind::Identifier(name) => {
+                self.advance();
+                match self.lookup_ordinary(&name) {
+                    Some(Ordinary::Enumerator(value)) => ExpressionKind::Literal(Literal::Int(*value)),
+                    _ => ExpressionKind::Variable(name),
+                }
+            }
+            TokenKind::IntLiteral { value, radix, suffix } => {
+                self.advance();
+                ExpressionKind::Literal(Literal::Integer { value, suffix, is_decimal: radix == 10 })
+            }
+            TokenKind::FloatLiteral { value, suffix } => {
+                self.advance();
+                let digits = (suffix == FloatSuffix::Long).then(|| token.text[..token.text.len() - 1].to_string());
+                ExpressionKind::Literal(Literal::Float { value, suffix, digits })
+            }
+            TokenKind::CharLiteral { encoding, value } => {
+                // Only plain constants may hold several characters
+                if value.len() > 1 && encoding != Encoding::Plain {
+                    return Err(CompileError::new(
+                        "character too large for enclosing character literal type",
+                        token.span,
+                    ));
+                }
+                self.advance();
+                ExpressionKind::Literal(Literal::Char(encoding, value))
+            }
+            TokenKind::StringLiteral { .. } => return self.parse_string_literal(),
+            TokenKind::Punct(Punct::LeftParen) => {
+                self.advance();
+                let inner = self.parse_expression()?;
+                self.expect_punct(Punct::RightParen, "to close parenthesized expression")?;
+                return Ok(Expression::new(inner.kind, token.span.to(self.previous_span())));
+            }
+            TokenKind::Keyword(Keyword::Generic) => return self.parse_generic_selection(),
+            _ => return Err(self.error_here(format!("expected expression, found {}", token))),
+        };
+        Ok(Expression::new(kind, token.span))
+    }
+
+    /// Concatenate adjacent string literals. A prefixed piece gives its
+    /// encoding to the whole literal; unprefixed pieces are re-encoded.
+    fn parse_string_literal(&mut self) -> Result<Expression, CompileError> {
+        let mut pieces = Vec::new();
+        let mut encoding = Encoding::Plain;
+        let mut span = self.peek().span;
+        while let TokenKind::StringLiteral { encoding: piece_encoding, value } = self.peek().kind.clone() {
+            let token_span = self.advance().span;
+            if piece_encoding != Encoding::Plain {
+                if encoding != Encoding::Plain && encoding != piece_encoding {
+                    return Err(CompileError::new(
+                        "unsupported non-standard concatenation of string literals",
+                        token_span,
+                    ));
+                }
+                encoding = piece_encoding;
+            }
+            pieces.push((piece_encoding, value));
+            span = span.to(token_span);
+        }
+
+        let mut units = Vec::new();
+        for (piece_encoding, value) in pieces {
+            if piece_encoding == Encoding::Plain && !matches!(encoding, Encoding::Plain | Encoding::Utf8) {
+                units.extend(reencode(&value, encoding));
+            } else {
+                units.extend(value);
+            }
+        }
+        Ok(Expression::new(ExpressionKind::Literal(Literal::String(encoding, units)), span))
+    }
+}
+
+/// The ordinary identifiers an expression names, including those in the
+/// lengths of variable length array types it mentions
+fn referenced_names<'e>(expression: &'e Expression, names: &mut Vec<&'e str>) {
+    match &expression.kind {
+        ExpressionKind::Literal(_) => {}
+        ExpressionKind::Variable(name) => names.push(name),
+        ExpressionKind::BinaryOp(lhs, _, rhs)
+        | ExpressionKind::Index(lhs, rhs)
+        | ExpressionKind::Assignment(lhs, rhs)
+        | ExpressionKind::CompoundAssignment(lhs, _, rhs, _)
+        | ExpressionKind::Comma(lhs, rhs)
+        | ExpressionKind::VaCopy(lhs, rhs) => {
+            referenced_names(lhs, names);
+            referenced_names(rhs, names);
+        }
+        ExpressionKind::UnaryOp(_, operand)
+        | ExpressionKind::Member(operand, _)
+        | ExpressionKind::Pointer(operand)
+        | ExpressionKind::Address(operand)
+        | ExpressionKind::SizeOf(operand)
+        | ExpressionKind::VaStart(operand)
+        | ExpressionKind::VaEnd(operand) => referenced_names(operand, names),
+        ExpressionKind::Call(callee, arguments) => {
+            referenced_names(callee, names);
+            arguments.iter().for_each(|argument| referenced_names(argument, names));
+        }
+        ExpressionKind::Cast(operand, operand_type)
+        | ExpressionKind::ImplicitCast(operand, operand_type)
+        | ExpressionKind::VaArg(operand, operand_type) => {
+            referenced_names(operand, names);
+            type_referenced_names(operand_type, names);
+        }
+        ExpressionKind::Conditional(condition, then_value, else_value) => {
+            referenced_names(condition, names);
+            referenced_names(then_value, names);
+            referenced_names(else_value, names);
+        }
+        ExpressionKind::SizeOfType(operand_type) | ExpressionKind::AlignOf(operand_type) => {
+            type_referenced_names(operand_type, names)
+        }
+        ExpressionKind::Generic(control, associations) => {
+            referenced_names(control, names);
+            for (association_type, value) in associations {
+                if let Some(association_type) = association_type {
+                    type_referenced_names(association_type, names);
+                }
+                referenced_names(value, names);
+            }
+        }
+        ExpressionKind::CompoundLiteral(literal_type, initializer) => {
+            type_referenced_names(literal_type, names);
+            initializer_referenced_names(initializer, names);
+        }
+    }
+}
+
+fn type_referenced_names<'e>(referenced_type: &'e Type, names: &mut Vec<&'e str>) {
+    match referenced_type {
+        Type::VariableArray(element, length) => {
+            type_referenced_names(element, names);
+            referenced_names(&length.length, names);
+        }
+        Type::Pointer(inner) | Type::Array(inner, _) | Type::Qualified(inner, _) => type_referenced_names(inner, names),
+        Type::Function(return_type, parameters, _) => {
+            type_referenced_names(return_type, names);
+            parameters.iter().for_each(|parameter| type_referenced_names(parameter, names));
+        }
+        _ => {}
+    }
+}
+
+/// Mark the variable length arrays of a type named by a typedef as belonging
+/// The error for an identifier list outside the declarator of a function
+/// definition
+fn identifier_list_error(span: Span) -> CompileError {
+    CompileError::new("a parameter list without types is only allowed in a function definition", span)
+}
+
+/// to it
+fn claim_lengths(typedef: &mut Type) {
+    match typedef {
+        Type::VariableArray(element, length) => {
+            length.typedef = true;
+            claim_lengths(element);
+        }
+        Type::Pointer(inner) | Type::Array(inner, _) | Type::Qualified(inner, _) => claim_lengths(inner),
+        _ => {}
+    }
+}
+
+fn initializer_referenced_names<'e>(initializer: &'e Initializer, names: &mut Vec<&'e str>) {
+    match initializer {
+        Initializer::Expression(value) => referenced_names(value, names),
+        Initializer::List(items, _) => {
+            items.iter().for_each(|item| initializer_referenced_names(&item.initializer, names))
+        }
+    }
+}
+
+/// The code units of an unprefixed string literal in a wider encoding; bytes
+/// that are not UTF-8 keep their value
+fn reencode(units: &[u32], encoding: Encoding) -> Vec<u32> {
+    let bytes: Vec<u8> = units.iter().map(|&unit| unit as u8).collect();
+    let mut reencoded = Vec::new();
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            encoding.push_char(&mut reencoded, c);
+        }
+        reencoded.extend(chunk.invalid().iter().map(|&byte| u32::from(byte)));
+    }
+    reencoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer;
+    use crate::compiler::source::FileId;
+    use crate::compiler::target::test_target;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        lexer::tokenize(source, FileId(0)).expect("lexing failed")
+    }
+
+    fn parse_source(source: &str) -> AST {
+        parse(tokens(source), &test_target()).expect("parsing failed")
+    }
+
+    fn parse_error(source: &str) -> CompileError {
+        parse(tokens(source), &test_target()).expect_err("parsing should fail")
+    }
+
+    fn body<'a>(ast: &'a AST, function: &str) -> &'a [Statement] {
+        &ast.functions[function].body.as_ref().expect("function has no body").statements
+    }
+
+    /// Render an expression as an S-expression for compact assertions
+    fn sexpr(expression: &Expression) -> String {
+        match &expression.kind {
+            ExpressionKind::Literal(Literal::Int(value)) => value.to_string(),
+            ExpressionKind::Literal(Literal::Integer { value, .. }) => value.to_string(),
+            ExpressionKind::Literal(Literal::Float { value, .. }) => format!("{:?}", value),
+            ExpressionKind::Literal(Literal::Char(Encoding::Plain, units)) if units.len() == 1 => {
+                format!("{:?}", char::from(units[0] as u8))
+            }
+            ExpressionKind::Literal(Literal::String(Encoding::Plain, units)) => {
+                let bytes: Vec<u8> = units.iter().map(|&unit| unit as u8).collect();
+                format!("{:?}", String::from_utf8_lossy(&bytes))
+            }
+            ExpressionKind::Literal(other) => format!("{:?}", other),
+            ExpressionKind::Variable(name) => name.clone(),
+            ExpressionKind::BinaryOp(lhs, operator, rhs) => {
+                format!("({:?} {} {})", operator, sexpr(lhs), sexpr(rhs))
+            }
+            ExpressionKind::UnaryOp(operator, operand) => format!("({:?} {})", operator, sexpr(operand)),
+            ExpressionKind::Call(callee, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(sexpr).collect();
+                format!("(call {} [{}])", sexpr(callee), arguments.join(" "))
+            }
+            ExpressionKind::Cast(operand, target) => format!("(cast {:?} {})", target, sexpr(operand)),
+            ExpressionKind::Index(base, index) => format!("(index {} {})", sexpr(base), sexpr(index)),
+            ExpressionKind::Member(base, member) => format!("(member {} {})", sexpr(base), member),
+            ExpressionKind::Pointer(operand) => format!("(deref {})", sexpr(operand)),
+            ExpressionKind::Address(operand) => format!("(addr {})", sexpr(operand)),
+            ExpressionKind::ImplicitCast(operand, target) => format!("(implicit {:?} {})", target, sexpr(operand)),
+            ExpressionKind::Assignment(target, value) => format!("(= {} {})", sexpr(target), sexpr(value)),
+            ExpressionKind::CompoundAssignment(target, operator, value, _) => {
+                format!("({:?}= {} {})", operator, sexpr(target), sexpr(value))
+            }
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                format!("(? {} {} {})", sexpr(condition), sexpr(then), sexpr(otherwise))
+            }
+            ExpressionKind::Comma(lhs, rhs) => format!("(, {} {})", sexpr(lhs), sexpr(rhs)),
+            ExpressionKind::SizeOf(operand) => format!("(sizeof {})", sexpr(operand)),
+            ExpressionKind::SizeOfType(operand) => format!("(sizeof {})", operand),
+            ExpressionKind::AlignOf(operand) => format!("(alignof {})", operand),
+            ExpressionKind::Generic(control, associations) => {
+                let associations: Vec<String> = associations
+                    .iter()
+                    .map(|(ty, value)| match ty {
+                        Some(ty) => format!("{}: {}", ty, sexpr(value)),
+                        None => format!("default: {}", sexpr(value)),
+                    })
+                    .collect();
+                format!("(generic {} [{}])", sexpr(control), associations.join(", "))
+            }
+            ExpressionKind::CompoundLiteral(literal_type, initializer) => {
+                format!("(literal {} {})", literal_type, sinit(initializer))
+            }
+            ExpressionKind::VaStart(list) => format!("(va_start {})", sexpr(list)),
+            ExpressionKind::VaArg(list, arg_type) => format!("(va_arg {} {})", sexpr(list), arg_type),
+            ExpressionKind::VaEnd(list) => format!("(va_end {})", sexpr(list)),
+            ExpressionKind::VaCopy(destination, source) => {
+                format!("(va_copy {} {})", sexpr(destination), sexpr(source))
+            }
+        }
+    }
+
+    fn sinit(initializer: &Initializer) -> String {
+        match initializer {
+            Initializer::Expression(value) => sexpr(value),
+            Initializer::List(items, _) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| {
+                        let designators: String = item
+                            .designators
+                            .iter()
+                            .map(|designator| match designator {
+                                Designator::Index(index) => format!("[{}]", index),
+                                Designator::Member(member) => format!(".{}", member),
+                            })
+                            .collect();
+                        match designators.is_empty() {
+                            true => sinit(&item.initializer),
+                            false => format!("{}={}", designators, sinit(&item.initializer)),
+                        }
+                    })
+                    .collect();
+                format!("{{{}}}", items.join(" "))
+            }
+        }
+    }
+
+    /// Parse `expression` as the value of a return statement
+    fn parse_expr(expression: &str) -> String {
+        let ast = parse_source(&format!("int f(void) {{ return {}; }}", expression));
+        match &body(&ast, "f")[0].kind {
+            StatementKind::Return(Some(value)) => sexpr(value),
+            other => panic!("expected return statement, got {:?}", other),
+        }
+    }
+
+    fn global_type(source: &str) -> Type {
+        let ast = parse_source(source);
+        ast.global_variables[0].0.var_type.clone()
+    }
+
+    fn ptr(inner: Type) -> Type {
+        Type::Pointer(Box::new(inner))
+    }
+
+    #[test]
+    fn test_function_definition() {
+        let ast = parse_source("int add(int a, int b) { return a + b; }");
+        let add = &ast.functions["add"];
+        assert_eq!(add.return_type, Type::Int);
+        let parameters: Vec<_> = add.parameters.iter().map(|p| (p.name.as_str(), p.var_type.clone())).collect();
+        assert_eq!(parameters, vec![("a", Type::Int), ("b", Type::Int)]);
+        match &body(&ast, "add")[0].kind {
+            StatementKind::Return(Some(value)) => assert_eq!(sexpr(value), "(Add a b)"),
+            other => panic!("unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_binary_precedence() {
+        assert_eq!(parse_expr("1 + 2 * 3 - 4"), "(Subtract (Add 1 (Multiply 2 3)) 4)");
+        assert_eq!(parse_expr("a || b && c | d ^ e & f"), "(LogicalOr a (LogicalAnd b (BitwiseOr c (BitwiseXor d (BitwiseAnd e f)))))");
+        assert_eq!(parse_expr("a == b < c << d + e"), "(Equal a (LessThan b (LeftShift c (Add d e))))");
+        assert_eq!(parse_expr("(a + b) * c"), "(Multiply (Add a b) c)");
+        assert_eq!(parse_expr("a % b / c"), "(Divide (Modulo a b) c)");
+    }
+
+    #[test]
+    fn test_left_associativity() {
+        assert_eq!(parse_expr("a - b - c"), "(Subtract (Subtract a b) c)");
+        assert_eq!(parse_expr("a / b * c"), "(Multiply (Divide a b) c)");
+        assert_eq!(parse_expr("a >> b >> c"), "(RightShift (RightShift a b) c)");
+        assert_eq!(parse_expr("a != b == c"), "(Equal (NotEqual a b) c)");
+    }
+
+    #[test]
+    fn test_unary_and_postfix() {
+        assert_eq!(parse_expr("-*p++"), "(Negate (deref (PostIncrement p)))");
+        assert_eq!(parse_expr("!~x"), "(LogicalNot (BitwiseNot x))");
+        assert_eq!(parse_expr("++*p"), "(PreIncrement (deref p))");
+        assert_eq!(parse_expr("--i"), "(PreDecrement i)");
+        assert_eq!(parse_expr("&a[1]"), "(addr (index a 1))");
+        assert_eq!(parse_expr("p->x.y"), "(member (member (deref p) x) y)");
+        assert_eq!(parse_expr("a[i][j]"), "(index (index a i) j)");
+        assert_eq!(parse_expr("-x * y"), "(Multiply (Negate x) y)");
+        assert_eq!(parse_expr("f(1, g(x), y + 1)"), "(call f [1 (call g [x]) (Add y 1)])");
+        assert_eq!(parse_expr("f()"), "(call f [])");
+        assert_eq!(parse_expr("(*table[i])(x)"), "(call (deref (index table i)) [x])");
+        assert_eq!(parse_expr("s->handler(s)(1)"), "(call (call (member (deref s) handler) [s]) [1])");
+    }
+
+    #[test]
+    fn test_casts() {
+        assert_eq!(parse_expr("(int)x + 1"), "(Add (cast Int x) 1)");
+        assert_eq!(parse_expr("(char *)p"), "(cast Pointer(Char) p)");
+        assert_eq!(parse_expr("(void)0"), "(cast Void 0)");
+        assert_eq!(parse_expr("(double)(int)f"), "(cast Double (cast Int f))");
+        assert_eq!(parse_expr("(int (*)(int))p"), "(cast Pointer(Function(Int, [Int], false)) p)");
+        assert_eq!(parse_expr("(x)"), "x");
+    }
+
+    #[test]
+    fn test_assignment_conditional_and_comma() {
+        assert_eq!(parse_expr("a = b = c"), "(= a (= b c))");
+        assert_eq!(parse_expr("a += b *= 2"), "(Add= a (Multiply= b 2))");
+        assert_eq!(parse_expr("a <<= 1"), "(LeftShift= a 1)");
+        assert_eq!(parse_expr("a ? b : c ? d : e"), "(? a b (? c d e))");
+        assert_eq!(parse_expr("a || b ? c = 1 : d"), "(? (LogicalOr a b) (= c 1) d)");
+        assert_eq!(parse_expr("x = a ? b : c"), "(= x (? a b c))");
+        assert_eq!(parse_expr("(a = 1, b = 2, c)"), "(, (, (= a 1) (= b 2)) c)");
+        assert_eq!(parse_expr("f((a, b), c)"), "(call f [(, a b) c])");
+        assert_eq!(parse_expr("+x"), "(Plus x)");
+    }
+
+    #[test]
+    fn test_sizeof_alignof_and_generic() {
+        assert_eq!(parse_expr("sizeof x + 1"), "(Add (sizeof x) 1)");
+        assert_eq!(parse_expr("sizeof(int *)"), "(sizeof int *)");
+        assert_eq!(parse_expr("sizeof (x)[0]"), "(sizeof (index x 0))");
+        assert_eq!(parse_expr("sizeof -x"), "(sizeof (Negate x))");
+        assert_eq!(parse_expr("_Alignof(double)"), "(alignof double)");
+        assert_eq!(parse_expr("sizeof (int[]){1, 2}"), "(sizeof (literal int [] {1 2}))");
+        assert_eq!(
+            parse_expr("_Generic(x, int: 1, char *: 2, default: 3)"),
+            "(generic x [int: 1, char *: 2, default: 3])"
+        );
+        assert_eq!(
+            parse_error("int f(int x) { return _Generic(x, default: 1, default: 2); }").message,
+            "duplicate default generic association"
+        );
+    }
+
+    #[test]
+    fn test_compound_literals_and_designators() {
+        assert_eq!(parse_expr("(int){4}"), "(literal int {4})");
+        assert_eq!(parse_expr("(int[3]){1, 2,}[1]"), "(index (literal int [3] {1 2}) 1)");
+        assert_eq!(parse_expr("(struct s){.a = 1, .b.c[2] = 3, {4}}"), "(literal struct s {.a=1 .b.c[2]=3 {4}})");
+        assert_eq!(parse_expr("(struct s){}.a"), "(member (literal struct s {}) a)");
+        assert_eq!(parse_expr("(int[]){[1 + 1] = 5}"), "(literal int [] {[2]=5})");
+        assert_eq!(
+            parse_error("int f(void) { return (int[]){[-1] = 0}[0]; }").message,
+            "array designator value `-1` is negative"
+        );
+        assert_eq!(
+            parse_error("int f(int n) { return (int[]){[n] = 0}[0]; }").message,
+            "expression is not an integer constant expression: read of variable `n`"
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(parse_expr("42"), "42");
+        assert_eq!(parse_expr("0x10"), "16");
+        assert_eq!(parse_expr("2.5"), "2.5");
+        assert_eq!(parse_expr("'A'"), "'A'");
+        assert_eq!(parse_expr("'\\n'"), "'\\n'");
+        assert_eq!(parse_expr("\"Hello, \" \"World!\\n\""), "\"Hello, World!\\n\"");
+
+        let literal = |source: &str| match parse_source(&format!("int f(void) {{ {}; }}", source)).functions["f"]
+            .body
+            .as_ref()
+            .unwrap()
+            .statements[0]
+            .kind
+        {
+            StatementKind::Expression(Expression { kind: ExpressionKind::Literal(ref literal), .. }) => literal.clone(),
+            ref other => panic!("expected a literal, got {:?}", other),
+        };
+        assert_eq!(
+            literal("0x10ULL"),
+            Literal::Integer { value: 16, suffix: IntSuffix::UnsignedLongLong, is_decimal: false }
+        );
+        assert_eq!(literal("10"), Literal::Integer { value: 10, suffix: IntSuffix::None, is_decimal: true });
+        assert_eq!(literal("2.5f"), Literal::Float { value: 2.5, suffix: FloatSuffix::Float, digits: None });
+        assert_eq!(
+            literal("0x1p-2L"),
+            Literal::Float { value: 0.25, suffix: FloatSuffix::Long, digits: Some("0x1p-2".to_string()) }
+        );
+        assert_eq!(
+            literal("1e4000l"),
+            Literal::Float { value: f64::INFINITY, suffix: FloatSuffix::Long, digits: Some("1e4000".to_string()) }
+        );
+        assert_eq!(literal("'ab'"), Literal::Char(Encoding::Plain, vec![97, 98]));
+        assert_eq!(literal("L'\\x100'"), Literal::Char(Encoding::Wide, vec![0x100]));
+        // Embedded nulls are kept and a prefix applies to the whole concatenation
+        assert_eq!(literal("\"a\\0b\""), Literal::String(Encoding::Plain, vec![97, 0, 98]));
+        assert_eq!(literal("\"\u{e9}\" u\"x\""), Literal::String(Encoding::Utf16, vec![0xE9, 120]));
+        assert_eq!(literal("u8\"a\" \"b\""), Literal::String(Encoding::Utf8, vec![97, 98]));
+        assert_eq!(
+            parse_error("int f(void) { u'ab'; }").message,
+            "character too large for enclosing character literal type"
+        );
+        assert_eq!(
+            parse_error("int f(void) { L\"a\" u\"b\"; }").message,
+            "unsupported non-standard concatenation of string literals"
+        );
+    }
+
+    #[test]
+    fn test_declarator_types() {
+        assert_eq!(global_type("int *a[3];"), Type::Array(Box::new(ptr(Type::Int)), Some(3)));
+        assert_eq!(global_type("int (*a)[3];"), ptr(Type::Array(Box::new(Type::Int), Some(3))));
+        assert_eq!(
+            global_type("int m[2][3];"),
+            Type::Array(Box::new(Type::Array(Box::new(Type::Int), Some(3))), Some(2))
+        );
+        // A size that is not an integer constant makes a variable length array
+        match &parse_source("int n; int v[n + 1][2];").global_variables[1].0.var_type {
+            Type::VariableArray(element, length) => {
+                assert_eq!(**element, Type::Array(Box::new(Type::Int), Some(2)));
+                assert_eq!(sexpr(&length.length), "(Add n 1)");
+            }
+            other => panic!("expected a variable length array, got {:?}", other),
+        }
+        assert_eq!(global_type("char **argv;"), ptr(ptr(Type::Char)));
+        assert_eq!(
+            global_type("int (*handler)(int, char *);"),
+            ptr(Type::Function(Box::new(Type::Int), vec![Type::Int, ptr(Type::Char)], false))
+        );
+
+        let ast = parse_source("void (*signal_fn(int))(int);");
+        assert_eq!(
+            ast.functions["signal_fn"].return_type,
+            ptr(Type::Function(Box::new(Type::Void), vec![Type::Int], false))
+        );
+    }
+
+    #[test]
+    fn test_type_specifier_combinations() {
+        let cases = [
+            ("signed char", Type::SignedChar),
+            ("unsigned char", Type::UnsignedChar),
+            ("short int", Type::Short),
+            ("signed short", Type::Short),
+            ("unsigned short int", Type::UnsignedShort),
+            ("signed", Type::Int),
+            ("unsigned", Type::UnsignedInt),
+            ("long", Type::Long),
+            ("int long signed", Type::Long),
+            ("unsigned long", Type::UnsignedLong),
+            ("long long", Type::LongLong),
+            ("long int long", Type::LongLong),
+            ("unsigned long long int", Type::UnsignedLongLong),
+            ("long double", Type::LongDouble),
+        ];
+        for (specifiers, expected) in cases {
+            assert_eq!(global_type(&format!("{} x;", specifiers)), expected, "{}", specifiers);
+        }
+
+        for invalid in ["signed unsigned x;", "long long long x;", "short long x;", "unsigned double x;", "int char x;"] {
+            assert!(
+                parse_error(invalid).message.starts_with("invalid combination of type specifiers"),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_const_applies_to_the_declared_object() {
+        let ast = parse_source("const int a = 1; const char *b; char *const c = 0; int const d[2];");
+        let consts: Vec<_> = ast
+            .global_variables
+            .iter()
+            .map(|(v, _)| (v.name.as_str(), v.qualifiers.is_const))
+            .collect();
+        assert_eq!(consts, vec![("a", true), ("b", false), ("c", true), ("d", false)]);
+        // Qualifying an array type qualifies its elements
+        assert_eq!(ast.global_variables[3].0.var_type.to_string(), "const int [2]");
+    }
+
+    #[test]
+    fn test_qualifiers_at_each_pointer_level() {
+        let cases = [
+            ("const char *const p;", "const char *const"),
+            ("volatile int *restrict p;", "volatile int *restrict"),
+            ("char *const *volatile p;", "char *const *volatile"),
+            ("const volatile unsigned p;", "const volatile unsigned int"),
+            ("int (*const p)[3];", "int (*const)[3]"),
+            ("_Atomic int p;", "_Atomic int"),
+            ("_Atomic(long) *p;", "_Atomic long *"),
+            ("int *_Atomic p;", "int *_Atomic"),
+        ];
+        for (source, expected) in cases {
+            let ast = parse_source(source);
+            assert_eq!(ast.global_variables[0].0.qualified_type().to_string(), expected, "{}", source);
+        }
+
+        let ast = parse_source("const int f(const char *s, int *const end);");
+        let f = &ast.functions["f"];
+        assert_eq!(f.return_type, Type::Int);
+        assert_eq!(f.parameters[0].var_type.to_string(), "const char *");
+        assert_eq!(f.parameters[1].var_type, ptr(Type::Int));
+        assert!(f.parameters[1].qualifiers.is_const);
+    }
+
+    #[test]
+    fn test_storage_classes() {
+        let ast = parse_source(
+            "static int counter;\nextern int shared;\n_Thread_local int slot;\nstatic _Thread_local int hidden;\n\
+             int f(register int n) { static int calls; extern int shared; auto int x = n; return x; }",
+        );
+        let globals: Vec<_> = ast
+            .global_variables
+            .iter()
+            .map(|(v, _)| (v.name.as_str(), v.storage, v.is_thread_local))
+            .collect();
+        assert_eq!(
+            globals,
+            vec![
+                ("counter", Some(StorageClass::Static), false),
+                ("shared", Some(StorageClass::Extern), false),
+                ("slot", None, true),
+                ("hidden", Some(StorageClass::Static), true),
+            ]
+        );
+        assert_eq!(ast.functions["f"].parameters[0].storage, Some(StorageClass::Register));
+        let locals: Vec<_> = body(&ast, "f")
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Declaration(variable, _) => Some((variable.name.as_str(), variable.storage)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            locals,
+            vec![
+                ("calls", Some(StorageClass::Static)),
+                ("shared", Some(StorageClass::Extern)),
+                ("x", Some(StorageClass::Auto)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_linkage_and_inline() {
+        let ast = parse_source(
+            "static int helper(void);\nint helper(void) { return 1; }\n\
+             inline int fast(void) { return 2; }\n\
+             inline int exported(void);\nextern inline int exported(void) { return 3; }\n\
+             int plain(void);\ninline int plain(void) { return 4; }",
+        );
+        let functions: Vec<_> = ["helper", "fast", "exported", "plain"]
+            .iter()
+            .map(|name| (ast.functions[*name].storage, ast.functions[*name].is_inline))
+            .collect();
+        assert_eq!(
+            functions,
+            vec![
+                (Some(StorageClass::Static), false),
+                (None, true),
+                (Some(StorageClass::Extern), true),
+                (None, false),
+            ]
+        );
+        assert!(ast.functions["helper"].body.is_some());
+    }
+
+    #[test]
+    fn test_storage_class_errors() {
+        let cases = [
+            ("static extern int x;", "cannot combine with previous `static` declaration specifier"),
+            ("register int x;", "`register` is not allowed at file scope"),
+            ("int f(static int x);", "`static` is not allowed on function parameters"),
+            ("struct s { extern int x; };", "`extern` is not allowed on struct members"),
+            ("int f(void) { return (static int)1; }", "`static` is not allowed in type names"),
+            ("inline int x;", "`inline` can only appear on functions"),
+            ("_Thread_local int f(void);", "`_Thread_local` is only allowed on variable declarations"),
+            (
+                "void f(void) { _Thread_local int x; }",
+                "`_Thread_local` variables at block scope must also be `static` or `extern`",
+            ),
+            (
+                "void f(void) { static int g(void); }",
+                "function declared in block scope cannot have `static` storage class",
+            ),
+            ("int f(void);\nstatic int f(void) { return 0; }", "static declaration of `f` follows non-static declaration"),
+            ("restrict int x;", "restrict requires a pointer type (`int` is invalid)"),
+            ("_Atomic(int [2]) x;", "`_Atomic` cannot be applied to type `int [2]`"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_global_variables_and_prototypes() {
+        let ast = parse_source(
+            "int counter = 42, *ptr;\nint square(int);\nint square(int x) { return x * x; }\nvoid log_it(char *message, int);",
+        );
+        let globals: Vec<_> = ast
+            .global_variables
+            .iter()
+            .map(|(variable, init)| (variable.name.as_str(), init.as_ref().map(sinit)))
+            .collect();
+        assert_eq!(globals, vec![("counter", Some("42".to_string())), ("ptr", None)]);
+
+        assert_eq!(ast.functions["square"].parameters[0].name, "x");
+        assert!(ast.functions["square"].body.is_some());
+
+        let log_it = &ast.functions["log_it"];
+        assert!(log_it.body.is_none());
+        assert_eq!(log_it.parameters[0].var_type, ptr(Type::Char));
+        assert_eq!(log_it.parameters[1].name, "");
+    }
+
+    #[test]
+    fn test_parameter_adjustment() {
+        let ast = parse_source("int main(int argc, char *argv[]) { return 0; }\nvoid apply(int f(int), int values[10]);");
+        assert_eq!(ast.functions["main"].parameters[1].var_type, ptr(ptr(Type::Char)));
+        let apply = &ast.functions["apply"];
+        assert_eq!(apply.parameters[0].var_type, ptr(Type::Function(Box::new(Type::Int), vec![Type::Int], false)));
+        assert_eq!(apply.parameters[1].var_type, ptr(Type::Int));
+        assert!(parse_source("int f(void);").functions["f"].parameters.is_empty());
+    }
+
+    #[test]
+    fn test_alignment_specifiers() {
+        let ast = parse_source(
+            "_Alignas(16) int a, b;\n_Alignas(8) _Alignas(double) _Alignas(0) char c[3];\n\
+             struct s { char x; _Alignas(32) short y; };",
+        );
+        let alignments: Vec<_> = ast.global_variables.iter().map(|(variable, _)| variable.alignment).collect();
+        assert_eq!(alignments, vec![Some(16), Some(16), Some(8)]);
+        assert_eq!(ast.structs["s"].fields[0].alignment, None);
+        assert_eq!(ast.structs["s"].fields[1].alignment, Some(32));
+
+        let cases = [
+            ("_Alignas(3) int a;", "requested alignment is not a power of 2"),
+            ("_Alignas(-8) int a;", "requested alignment is not a power of 2"),
+            ("_Alignas(1 << 30) int a;", "requested alignment must be 268435456 bytes or smaller"),
+            ("int n; _Alignas(n) int a;", "alignment is not an integer constant expression: read of variable `n`"),
+            ("typedef _Alignas(8) int aligned;", "`_Alignas` cannot be applied to a typedef"),
+            ("_Alignas(8) int f(void);", "`_Alignas` cannot be applied to a function"),
+            ("int f(_Alignas(8) int a);", "`_Alignas` is not allowed on function parameters"),
+            ("int n = sizeof(_Alignas(8) int);", "`_Alignas` is not allowed in type names"),
+            ("struct s { _Alignas(8) int a : 3; };", "`_Alignas` cannot be applied to a bit-field"),
+            (
+                "void f(void) { register _Alignas(8) int a; }",
+                "`_Alignas` cannot be applied to a variable with `register` storage class",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_old_style_definitions() {
+        let ast = parse_source("int g(a, b, c) int a; char *b, c[]; { return a; }\nint h(n) { return n; }");
+        let g = &ast.functions["g"];
+        let parameters: Vec<_> =
+            g.parameters.iter().map(|parameter| (parameter.name.as_str(), &parameter.var_type)).collect();
+        assert_eq!(parameters, vec![("a", &Type::Int), ("b", &ptr(Type::Char)), ("c", &ptr(Type::Char))]);
+        assert!(g.body.is_some());
+        assert_eq!(ast.functions["h"].parameters[0].var_type, Type::Int);
+
+        let cases = [
+            ("int f(a);", "a parameter list without types is only allowed in a function definition"),
+            ("int (*p)(a);", "a parameter list without types is only allowed in a function definition"),
+            (
+                "int x, f(a) int a; { return a; }",
+                "a parameter list without types is only allowed in a function definition",
+            ),
+            ("int f(a, a) { return a; }", "redefinition of parameter `a`"),
+            ("int f(a) int a, a; { return a; }", "redefinition of parameter `a`"),
+            ("int f(a) int b; { return a; }", "parameter named `b` is missing"),
+            ("int f(a) int a = 1; { return a; }", "expected `;` after parameter declaration, found `=`"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_structs() {
+        let ast = parse_source(
+            "struct Point { int x, y; };\nstruct Line { struct Point start; struct Point *end; char name[16]; };\nstruct { int id; } anonymous;",
+        );
+        let point = &ast.structs["Point"];
+        let fields: Vec<_> = point.fields.iter().map(|f| (f.name.as_str(), f.var_type.clone())).collect();
+        assert_eq!(fields, vec![("x", Type::Int), ("y", Type::Int)]);
+
+        let line = &ast.structs["Line"];
+        assert_eq!(line.fields[0].var_type, Type::Struct("Point".to_string()));
+        assert_eq!(line.fields[1].var_type, ptr(Type::Struct("Point".to_string())));
+        assert_eq!(line.fields[2].var_type, Type::Array(Box::new(Type::Char), Some(16)));
+
+        let (variable, _) = &ast.global_variables[0];
+        match &variable.var_type {
+            Type::Struct(name) => assert_eq!(ast.structs[name].fields[0].name, "id"),
+            other => panic!("expected struct type, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        assert!(parse_error("struct S { int a; int a; };").message.contains("duplicate member `a`"));
+        assert!(parse_error("struct S { int a; }; struct S { int b; };").message.contains("redefinition of `struct S`"));
+    }
+
+    #[test]
+    fn test_tag_scopes() {
+        let ast = parse_source(
+            "struct S { int a; } global;\n\
+             int f(void) { struct S { double b; } s; struct S *p = &s; { struct S; struct S *q; } return 0; }\n\
+             int g(void) { enum E { A }; struct T { int t; } t; return A; }\n\
+             int h(void) { enum E { B = 2 }; struct T { long t; } t; struct S outer; return B; }",
+        );
+        assert_eq!(ast.global_variables[0].0.var_type, Type::Struct("S".to_string()));
+        assert_eq!(ast.structs["S"].fields[0].var_type, Type::Int);
+
+        // The inner `struct S` hides the outer one for the rest of its block
+        let declared = |function: &str| -> Vec<Type> {
+            body(&ast, function)
+                .iter()
+                .filter_map(|statement| match &statement.kind {
+                    StatementKind::Declaration(variable, _) => Some(variable.var_type.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let f = declared("f");
+        assert_eq!(f[0], Type::Struct("S.1".to_string()));
+        assert_eq!(f[0].to_string(), "struct S");
+        assert_eq!(f[1], ptr(Type::Struct("S.1".to_string())));
+        assert_eq!(ast.structs["S.1"].fields[0].var_type, Type::Double);
+        match &body(&ast, "f")[2].kind {
+            StatementKind::Block(block) => match &block.statements[0].kind {
+                StatementKind::Declaration(variable, _) => {
+                    assert_eq!(variable.var_type, ptr(Type::Struct("S.2".to_string())))
+                }
+                other => panic!("unexpected statement {:?}", other),
+            },
+            other => panic!("unexpected statement {:?}", other),
+        }
+
+        // Each function has its own `enum E` and `struct T`
+        assert_eq!(declared("g")[0], Type::Struct("T.4".to_string()));
+        assert_eq!(declared("h"), vec![Type::Struct("T.6".to_string()), Type::Struct("S".to_string())]);
+        assert_eq!(ast.structs["T.6"].fields[0].var_type, Type::Long);
+
+        let cases = [
+            ("void f(void) { struct S { int a; }; struct S { int b; }; }", "redefinition of `struct S`"),
+            ("void f(void) { enum E { A }; enum E { B }; }", "redefinition of `enum E`"),
+            ("struct S; void f(void) { union S *p; }", "use of `S` with tag type that does not match previous declaration"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+        assert!(parse_source("struct S; void f(void) { union S { int a; } u; }").structs.contains_key("S.1"));
+    }
+
+    #[test]
+    fn test_unions_and_bit_fields() {
+        let ast = parse_source(
+            "union Value { int i; float f; } v;\n\
+             struct Flags { unsigned ready : 1, : 2, mode : 3; int : 0; union { int x; float y; }; struct { int z; } named; };",
+        );
+        let value = &ast.structs["Value"];
+        assert!(value.is_union);
+        assert_eq!(ast.global_variables[0].0.var_type, Type::Union("Value".to_string()));
+
+        let flags = &ast.structs["Flags"];
+        let fields: Vec<_> = flags.fields.iter().map(|f| (f.name.as_str(), f.bit_width)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("ready", Some(1)),
+                ("", Some(2)),
+                ("mode", Some(3)),
+                ("", Some(0)),
+                ("(anonymous union #1)", None),
+                ("named", None),
+            ]
+        );
+        assert!(flags.fields[4].is_anonymous());
+        assert_eq!(flags.fields[4].var_type, Type::Union("(anonymous union #1)".to_string()));
+        assert!(!flags.fields[5].is_anonymous());
+    }
+
+    #[test]
+    fn test_enums() {
+        let ast = parse_source(
+            "enum Color { RED, GREEN = 5, BLUE, };\n\
+             enum Sign { NEGATIVE = -1, POSITIVE = 1 } sign;\n\
+             enum { HUGE = 0x100000000 } huge;\n\
+             int blue = BLUE;\n\
+             int table[GREEN * 2 + (RED < BLUE)];",
+        );
+        let color = Type::Enum("Color".to_string(), Box::new(Type::UnsignedInt));
+        assert_eq!(color.to_string(), "enum Color");
+        assert_eq!(ast.global_variables[0].0.var_type, Type::Enum("Sign".to_string(), Box::new(Type::Int)));
+        assert_eq!(
+            ast.global_variables[1].0.var_type,
+            Type::Enum("(anonymous enum #1)".to_string(), Box::new(Type::UnsignedLongLong))
+        );
+        assert_eq!(sinit(ast.global_variables[2].1.as_ref().unwrap()), "6");
+        assert_eq!(ast.global_variables[3].0.var_type, Type::Array(Box::new(Type::Int), Some(11)));
+        assert_eq!(global_type("enum Color { A }; enum Color c;"), color);
+    }
+
+    #[test]
+    fn test_typedefs() {
+        let ast = parse_source(
+            "typedef unsigned long size;\n\
+             typedef struct { int id; } Student, *StudentPtr;\n\
+             typedef int handler(int);\n\
+             size n;\n\
+             const StudentPtr p;\n\
+             handler on_event;\n\
+             void f(size size) { size = (size) - 1; }\n\
+             void g(void) { size x = (size)1; { int size; size = 2; } typedef char size; size c; }",
+        );
+        assert_eq!(ast.global_variables[0].0.var_type, Type::UnsignedLong);
+        let student = Type::Struct("(anonymous struct #1)".to_string());
+        assert_eq!(ast.global_variables[1].0.qualified_type(), ptr(student).qualified(Qualifiers {
+            is_const: true,
+            ..Qualifiers::default()
+        }));
+        let on_event = &ast.functions["on_event"];
+        assert_eq!(on_event.parameters.len(), 1);
+        assert_eq!(on_event.parameters[0].var_type, Type::Int);
+
+        // A parameter named `size` hides the typedef, so `(size) - 1` is not a cast
+        match &body(&ast, "f")[0].kind {
+            StatementKind::Expression(value) => assert_eq!(sexpr(value), "(= size (Subtract size 1))"),
+            other => panic!("unexpected statement {:?}", other),
+        }
+        let statements = body(&ast, "g");
+        match &statements[0].kind {
+            StatementKind::Declaration(variable, Some(init)) => {
+                assert_eq!(variable.var_type, Type::UnsignedLong);
+                assert!(matches!(init, Initializer::Expression(e) if matches!(e.kind, ExpressionKind::Cast(..))));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+        // A block-scope typedef is a statement of its own
+        assert!(matches!(&statements[2].kind, StatementKind::Typedef(name, Type::Char) if name == "size"));
+        assert!(matches!(&statements[3].kind, StatementKind::Declaration(v, None) if v.var_type == Type::Char));
+    }
+
+    #[test]
+    fn test_tag_typedef_and_bit_field_errors() {
+        let cases = [
+            ("struct S; union S *p;", "use of `S` with tag type that does not match previous declaration"),
+            ("enum E { A }; struct E *p;", "use of `E` with tag type that does not match previous declaration"),
+            ("enum F f;", "ISO C forbids forward references to `enum F`"),
+            ("enum E { A, A };", "redefinition of enumerator `A`"),
+            ("enum E { A = 1.5 };", "enumerator value is not an integer constant expression: expression has type `double`"),
+            ("enum E { A = 1 / 0 };", "enumerator value is not an integer constant expression: division by zero"),
+            ("int x; enum E { A = x };", "enumerator value is not an integer constant expression: read of variable `x`"),
+            ("enum E {};", "use of empty enum"),
+            ("int x; typedef int x;", "redefinition of `x` as a different kind of symbol"),
+            ("int n; typedef int row[n];", "variable length array declaration not allowed at file scope"),
+            ("typedef int T; typedef long T;", "typedef redefinition with different types (`long` vs `int`)"),
+            ("typedef int T = 1;", "illegal initializer (only variables can be initialized)"),
+            ("typedef int F(void); F g { return 0; }", "function definition declared with a typedef function type"),
+            ("struct S { int a : -1; };", "bit-field `a` has negative width (-1)"),
+            ("struct S { int a : 0; };", "named bit-field `a` has zero width"),
+            ("struct S { int a : 1 + x; };", "use of undeclared identifier `x`"),
+            ("int f(void); struct S { int a : f(); };", "bit-field width is not an integer constant expression: function call"),
+            ("struct S { union { int a; }; int a; };", "duplicate member `a`"),
+            ("int a[1 - 2];", "array size is negative"),
+            ("int a[(int)sizeof(int) - 8];", "array size is negative"),
+            ("int a[sizeof(int) - 8];", "array is too large"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+
+        // Redefinitions point at the definition they clash with
+        let cases = [
+            ("enum E { A };\nenum E { B };", "previous definition is here"),
+            ("enum { A };\nenum { A };", "previous definition is here"),
+            ("typedef int T;\ntypedef long T;", "previous definition is here"),
+            ("int x;\ntypedef int x;", "previous definition is here"),
+            ("struct S { int a; };\nstruct S { int b; };", "previous definition is here"),
+            ("int f(void);\nstatic int f(void);", "previous declaration is here"),
+        ];
+        for (source, message) in cases {
+            let error = parse_error(source);
+            assert_eq!(error.span.start.line, 2, "{}", source);
+            assert_eq!(error.labels.len(), 1, "{}", source);
+            assert_eq!((error.labels[0].message.as_str(), error.labels[0].span.start.line), (message, 1), "{}", source);
+        }
+        // `__builtin_va_list` has no declaration to point at
+        assert!(parse_error("typedef int __builtin_va_list;").labels.is_empty());
+    }
+
+    #[test]
+    fn test_local_declarations_and_assignment() {
+        let ast = parse_source("void f(void) { int a = 10, b; b = a * 2; p->x = *q; }");
+        let statements = body(&ast, "f");
+        assert_eq!(statements.len(), 4);
+        match &statements[0].kind {
+            StatementKind::Declaration(variable, Some(init)) => {
+                assert_eq!((variable.name.as_str(), sinit(init)), ("a", "10".to_string()));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+        assert!(matches!(&statements[1].kind, StatementKind::Declaration(v, None) if v.name == "b"));
+        match &statements[3].kind {
+            StatementKind::Expression(value) => assert_eq!(sexpr(value), "(= (member (deref p) x) (deref q))"),
+            other => panic!("unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_initializer_lists() {
+        let ast = parse_source(
+            "int numbers[5] = {1, 2, 3, 4, 5};\n\
+             struct point { int x, y; } origin = {.x = 0, .y = 0,};\n\
+             void f(void) { int grid[2][2] = {{1}, [1] = {2, 3}}; char s[] = {\"hi\"}; int n = {7}; }",
+        );
+        let globals: Vec<String> = ast
+            .global_variables
+            .iter()
+            .map(|(_, initializer)| sinit(initializer.as_ref().unwrap()))
+            .collect();
+        assert_eq!(globals, vec!["{1 2 3 4 5}", "{.x=0 .y=0}"]);
+        let locals: Vec<String> = body(&ast, "f")
+            .iter()
+            .map(|statement| match &statement.kind {
+                StatementKind::Declaration(_, Some(initializer)) => sinit(initializer),
+                other => panic!("unexpected statement {:?}", other),
+            })
+            .collect();
+        assert_eq!(locals, vec!["{{1} [1]={2 3}}", "{\"hi\"}", "{7}"]);
+    }
+
+    #[test]
+    fn test_if_else_chain() {
+        let ast = parse_source("int f(int x) { if (x > 50) return 2; else if (x > 30) { return 1; } else return 0; }");
+        match &body(&ast, "f")[0].kind {
+            StatementKind::If(condition, then_block, Some(else_block)) => {
+                assert_eq!(sexpr(condition), "(GreaterThan x 50)");
+                assert_eq!(then_block.statements.len(), 1);
+                assert!(matches!(&else_block.statements[0].kind, StatementKind::If(_, _, Some(_))));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_loops() {
+        let ast = parse_source(
+            "void f(int n) { while (n) n--; for (int i = 0; i < n; i++) g(i); for (;;) ; for (n = 0; n < 3; n = n + 1) {} }",
+        );
+        let statements = body(&ast, "f");
+        match &statements[0].kind {
+            StatementKind::While(condition, block) => {
+                assert_eq!(sexpr(condition), "n");
+                assert!(matches!(&block.statements[0].kind, StatementKind::Expression(_)));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+        match &statements[1].kind {
+            StatementKind::For(init, condition, step, block) => {
+                assert!(matches!(&init.kind, StatementKind::Declaration(v, Some(_)) if v.name == "i"));
+                assert_eq!(sexpr(condition), "(LessThan i n)");
+                assert!(matches!(&step.kind, StatementKind::Expression(e) if sexpr(e) == "(PostIncrement i)"));
+                assert_eq!(block.statements.len(), 1);
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+        match &statements[2].kind {
+            StatementKind::For(init, condition, step, _) => {
+                assert!(matches!(&init.kind, StatementKind::Block(b) if b.statements.is_empty()));
+                assert_eq!(sexpr(condition), "1");
+                assert!(matches!(&step.kind, StatementKind::Block(b) if b.statements.is_empty()));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+        match &statements[3].kind {
+            StatementKind::For(init, _, step, _) => {
+                assert!(matches!(&init.kind, StatementKind::Expression(e) if sexpr(e) == "(= n 0)"));
+                assert!(matches!(&step.kind, StatementKind::Expression(e) if sexpr(e) == "(= n (Add n 1))"));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_switch_and_jumps() {
+        let ast = parse_source(
+            "void f(int n) {\n\
+             switch (n) { case 1: case 2 + 1: n++; break; default: { continue; } }\n\
+             do n--; while (n > 0);\n\
+             again: if (n) goto again;\n\
+             }",
+        );
+        let statements = body(&ast, "f");
+        match &statements[0].kind {
+            StatementKind::Switch(condition, block) => {
+                assert_eq!(sexpr(condition), "n");
+                assert_eq!(block.statements.len(), 3);
+                match &block.statements[0].kind {
+                    StatementKind::Case(value, labeled) => {
+                        assert_eq!(sexpr(value), "1");
+                        assert!(matches!(&labeled.kind, StatementKind::Case(value, _) if sexpr(value) == "(Add 2 1)"));
+                    }
+                    other => panic!("unexpected statement {:?}", other),
+                }
+                assert!(matches!(block.statements[1].kind, StatementKind::Break));
+                assert!(matches!(&block.statements[2].kind, StatementKind::Default(labeled)
+                    if matches!(&labeled.kind, StatementKind::Block(b) if matches!(b.statements[0].kind, StatementKind::Continue))));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+        match &statements[1].kind {
+            StatementKind::DoWhile(block, condition) => {
+                assert_eq!(block.statements.len(), 1);
+                assert_eq!(sexpr(condition), "(GreaterThan n 0)");
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+        match &statements[2].kind {
+            StatementKind::Label(label, labeled) => {
+                assert_eq!(label, "again");
+                assert!(matches!(&labeled.kind, StatementKind::If(_, then_block, None)
+                    if matches!(&then_block.statements[0].kind, StatementKind::Goto(target) if target == "again")));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+
+        // A label may share its name with a typedef
+        let ast = parse_source("typedef int T; void f(void) { T: return; }");
+        assert!(matches!(&body(&ast, "f")[0].kind, StatementKind::Label(label, _) if label == "T"));
+
+        let cases = [
+            ("void f(int n) { do n--; (n); }", "expected `while` in do/while loop, found `(`"),
+            ("void f(void) { goto 1; }", "expected identifier after `goto`, found `1`"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_for_with_several_declarations() {
+        let ast = parse_source("void f(void) { for (int i = 0, j = 10; i < j; i++) {} }");
+        match &body(&ast, "f")[0].kind {
+            StatementKind::For(init, ..) => match &init.kind {
+                StatementKind::Block(block) => assert_eq!(block.statements.len(), 2),
+                other => panic!("unexpected initializer {:?}", other),
+            },
+            other => panic!("unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let ast = parse_source("int main(void) {\n    return a +\n        b;\n}");
+        let statement = &body(&ast, "main")[0];
+        assert_eq!((statement.span.start.line, statement.span.start.column), (2, 5));
+        assert_eq!((statement.span.end.line, statement.span.end.column), (3, 11));
+        match &statement.kind {
+            StatementKind::Return(Some(value)) => {
+                assert_eq!((value.span.start.line, value.span.start.column), (2, 12));
+                assert_eq!((value.span.end.line, value.span.end.column), (3, 10));
+            }
+            other => panic!("unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_variadic_functions() {
+        let ast = parse_source(
+            "int printf(const char *, ...);\n\
+             int sum(int count, ...) { __builtin_va_list ap; __builtin_va_start(ap, count); return 0; }",
+        );
+        assert!(ast.functions["printf"].is_variadic);
+        assert!(ast.functions["sum"].is_variadic);
+        assert_eq!(ast.functions["sum"].parameters.len(), 1);
+        let handler = ptr(Type::Function(Box::new(Type::Int), vec![Type::Int], true));
+        assert_eq!(global_type("int (*handler)(int, ...);"), handler);
+        assert_eq!(global_type("__builtin_va_list ap;"), Type::VaList);
+
+        assert_eq!(parse_expr("__builtin_va_arg(ap, double)"), "(va_arg ap double)");
+        assert_eq!(parse_expr("__builtin_va_copy(a, b)"), "(va_copy a b)");
+        assert_eq!(parse_expr("__builtin_va_end(ap)"), "(va_end ap)");
+
+        let error = parse_error("int f(...);");
+        assert_eq!(error.message, "ISO C requires a named parameter before `...`");
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let error = parse_error("int main(void) {\n    return 0\n}");
+        assert_eq!(error.message, "expected `;` after return statement, found `}`");
+        assert_eq!((error.span.start.line, error.span.start.column), (3, 1));
+
+        assert!(parse_error("int f(void) { return (1 + 2; }").message.contains("expected `)`"));
+        assert!(parse_error("int f(void) { return 1 +; }").message.contains("expected expression, found `;`"));
+        assert!(parse_error("int f(void) {").message.contains("end of file"));
+        assert!(parse_error("x = 1;").message.contains("expected declaration"));
+        assert!(parse_error("int f(void) {} int f(void) {}").message.contains("redefinition of function `f`"));
+    }
+
+    #[test]
+    fn test_unsupported_constructs_are_rejected() {
+        let cases = [
+            ("_Complex double z;", "type `_Complex double` is not supported yet"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_error(source).message, expected, "{}", source);
+        }
+    }
+}
@@ -0,0 +1,2002 @@
+//! The C preprocessor: directives, macro expansion and conditional inclusion.
+//!
+//! Tokens are pulled lazily from a stack of lexers, one per open file, so that
+//! lines inside skipped conditional groups never have to be valid tokens.
+//! Macro expansion follows the usual "blue paint" scheme: while a macro's
+//! replacement list is being rescanned the macro is disabled, and any
+//! identifier naming it is marked `no_expand` for good.
+
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::constant::{self, Integer};
+use super::diagnostics::Diagnostics;
+use super::headers;
+use super::lexer::{Encoding, IntSuffix, Lexer, Punct, Token, TokenKind};
+use super::parser::{self, BinaryOperator, Type};
+use super::source::{CompileError, FileId, SourceMap, Span};
+use super::target::{is_host, LongDouble, TargetInfo};
+use crate::utils::CompilerOptions;
+
+/// Deepest allowed `#include` nesting
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Preprocess a file, returning the expanded tokens ending with `Eof`.
+/// Files pulled in by `#include` and macro expansions are added to
+/// `sources`; `#warning` is reported to `diagnostics`.
+pub fn preprocess(
+    sources: &mut SourceMap,
+    file: FileId,
+    options: &CompilerOptions,
+    target: &TargetInfo,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Token>, CompileError> {
+    debug!("Preprocessing {}", sources.path(file).display());
+
+    let mut search_path = options.include_paths.clone();
+    search_path.extend(system_include_dirs(target, is_host(options)));
+    let mut preprocessor = Preprocessor::new(sources, diagnostics, search_path);
+    preprocessor.warnings_as_errors = options.warnings_as_errors;
+    preprocessor.enter_file(file, None);
+    let builtin = preprocessor
+        .sources
+        .add_file(Path::new("<built-in>"), predefined_macros(target));
+    preprocessor.enter_file(builtin, None);
+
+    let mut tokens = Vec::new();
+    loop {
+        let token = preprocessor.next_expanded(None)?;
+        let done = token.kind == TokenKind::Eof;
+        tokens.push(token);
+        if done {
+            break;
+        }
+    }
+
+    debug!("Preprocessed into {} tokens", tokens.len());
+    Ok(tokens)
+}
+
+/// Source of the `<built-in>` file processed before every translation unit.
+/// Operating system and architecture macros describe the target platform.
+fn predefined_macros(target: &TargetInfo) -> String {
+    let mut macros = vec![
+        ("__STDC__", "1"),
+        ("__STDC_VERSION__", "201112L"),
+        ("__STDC_HOSTED__", "1"),
+        ("__STDC_UTF_16__", "1"),
+        ("__STDC_UTF_32__", "1"),
+        ("__STDC_NO_ATOMICS__", "1"),
+        ("__STDC_NO_COMPLEX__", "1"),
+        ("__STDC_NO_THREADS__", "1"),
+        ("__tlstuc__", "1"),
+        ("__CHAR_BIT__", "8"),
+        ("__ORDER_LITTLE_ENDIAN__", "1234"),
+        ("__ORDER_BIG_ENDIAN__", "4321"),
+    ];
+    if target.big_endian {
+        macros.push(("__BYTE_ORDER__", "__ORDER_BIG_ENDIAN__"));
+    } else {
+        macros.push(("__BYTE_ORDER__", "__ORDER_LITTLE_ENDIAN__"));
+    }
+    if target.is_linux() || target.is_apple() {
+        macros.extend([("__unix__", "1"), ("__unix", "1")]);
+    }
+    if target.is_linux() {
+        macros.extend([("__linux__", "1"), ("__linux", "1"), ("__gnu_linux__", "1")]);
+    }
+    if target.is_apple() {
+        macros.push(("__APPLE__", "1"));
+    }
+    if target.is_windows() {
+        macros.push(("_WIN32", "1"));
+        if target.integer_width(&target.size_type()) == 64 {
+            macros.push(("_WIN64", "1"));
+        }
+    }
+    match target.arch() {
+        "x86_64" => macros.extend([("__x86_64__", "1"), ("__x86_64", "1"), ("__amd64__", "1"), ("__amd64", "1")]),
+        "i386" | "i486" | "i586" | "i686" => macros.extend([("__i386__", "1"), ("__i386", "1")]),
+        "aarch64" => macros.push(("__aarch64__", "1")),
+        "riscv32" => macros.extend([("__riscv", "1"), ("__riscv_xlen", "32")]),
+        "riscv64" => macros.extend([("__riscv", "1"), ("__riscv_xlen", "64")]),
+        "wasm32" => macros.extend([("__wasm__", "1"), ("__wasm32__", "1")]),
+        "wasm64" => macros.extend([("__wasm__", "1"), ("__wasm64__", "1")]),
+        arch if arch.starts_with("arm") || arch.starts_with("thumb") => macros.push(("__arm__", "1")),
+        _ => {}
+    }
+
+    macros
+        .iter()
+        .map(|(name, value)| format!("#define {} {}\n", name, value))
+        .chain(
+            type_macros(target)
+                .iter()
+                .map(|(name, value)| format!("#define {} {}\n", name, value)),
+        )
+        .collect()
+}
+
+/// Sizes, types and limits of the target's types, in the form GCC provides
+/// them to headers
+fn type_macros(target: &TargetInfo) -> Vec<(&'static str, String)> {
+    let size = |scalar: &Type| target.scalar_layout(scalar).expect("scalar type").size.to_string();
+    let max = |integer: &Type| {
+        let width = target.integer_width(integer);
+        let max = if target.is_signed(integer) {
+            (1u128 << (width - 1)) - 1
+        } else {
+            (1u128 << width) - 1
+        };
+        let suffix = match integer {
+            Type::UnsignedInt => "U",
+            Type::Long => "L",
+            Type::UnsignedLong => "UL",
+            Type::LongLong => "LL",
+            Type::UnsignedLongLong => "ULL",
+            _ => "",
+        };
+        format!("{}{}", max, suffix)
+    };
+    let size_type = target.size_type();
+    let ptrdiff_type = target.ptrdiff_type();
+    let int64_type = target.int64_type();
+    let intmax_type = target.intmax_type();
+    let wchar_type = target.wchar_type();
+    let wint_type = target.wint_type();
+
+    let mut macros = vec![
+        ("__SIZEOF_SHORT__", size(&Type::Short)),
+        ("__SIZEOF_INT__", size(&Type::Int)),
+        ("__SIZEOF_LONG__", size(&Type::Long)),
+        ("__SIZEOF_LONG_LONG__", size(&Type::LongLong)),
+        ("__SIZEOF_POINTER__", size(&Type::Pointer(Box::new(Type::Void)))),
+        ("__SIZEOF_SIZE_T__", size(&size_type)),
+        ("__SIZEOF_PTRDIFF_T__", size(&ptrdiff_type)),
+        ("__SIZEOF_WCHAR_T__", size(&wchar_type)),
+        ("__SIZEOF_WINT_T__", size(&wint_type)),
+        ("__SIZEOF_FLOAT__", size(&Type::Float)),
+        ("__SIZEOF_DOUBLE__", size(&Type::Double)),
+        ("__SIZEOF_LONG_DOUBLE__", size(&Type::LongDouble)),
+        ("__SIZE_TYPE__", size_type.to_string()),
+        ("__PTRDIFF_TYPE__", ptrdiff_type.to_string()),
+        ("__INTPTR_TYPE__", ptrdiff_type.to_string()),
+        ("__UINTPTR_TYPE__", size_type.to_string()),
+        ("__INTMAX_TYPE__", intmax_type.to_string()),
+        ("__UINTMAX_TYPE__", intmax_type.to_unsigned().to_string()),
+        ("__INT64_TYPE__", int64_type.to_string()),
+        ("__UINT64_TYPE__", int64_type.to_unsigned().to_string()),
+        ("__WCHAR_TYPE__", wchar_type.to_string()),
+        ("__WINT_TYPE__", wint_type.to_string()),
+        ("__CHAR16_TYPE__", Type::UnsignedShort.to_string()),
+        ("__CHAR32_TYPE__", Type::UnsignedInt.to_string()),
+        ("__SCHAR_MAX__", max(&Type::SignedChar)),
+        ("__SHRT_MAX__", max(&Type::Short)),
+        ("__INT_MAX__", max(&Type::Int)),
+        ("__LONG_MAX__", max(&Type::Long)),
+        ("__LONG_LONG_MAX__", max(&Type::LongLong)),
+        ("__WCHAR_MAX__", max(&wchar_type)),
+        ("__SIZE_MAX__", max(&size_type)),
+        ("__PTRDIFF_MAX__", max(&ptrdiff_type)),
+        ("__INTPTR_MAX__", max(&ptrdiff_type)),
+        ("__UINTPTR_MAX__", max(&size_type)),
+        ("__INTMAX_MAX__", max(&intmax_type)),
+        ("__UINTMAX_MAX__", max(&intmax_type.to_unsigned())),
+    ];
+    let wchar_min = if target.is_signed(&wchar_type) {
+        format!("(-{} - 1)", max(&wchar_type))
+    } else {
+        "0".to_string()
+    };
+    macros.push(("__WCHAR_MIN__", wchar_min));
+    let long_double_digits = match target.long_double {
+        LongDouble::X87 => "64",
+        LongDouble::Quad => "113",
+        LongDouble::Double => "53",
+    };
+    macros.push(("__LDBL_MANT_DIG__", long_double_digits.to_string()));
+    if !target.char_signed {
+        macros.push(("__CHAR_UNSIGNED__", "1".to_string()));
+    }
+    if size(&Type::Long) == "8" && size(&Type::Pointer(Box::new(Type::Void))) == "8" {
+        macros.extend([("__LP64__", "1".to_string()), ("_LP64", "1".to_string())]);
+    }
+    macros
+}
+
+/// Directories searched for `<...>` includes after the configured include
+/// paths. Headers for another architecture are found where Debian's
+/// cross-compilation packages install them; without those, only the bundled
+/// headers are available.
+fn system_include_dirs(target: &TargetInfo, host: bool) -> Vec<PathBuf> {
+    if host {
+        if !cfg!(unix) {
+            return Vec::new();
+        }
+        let mut dirs = vec![PathBuf::from("/usr/local/include")];
+        if let Some(multiarch) = target.multiarch() {
+            dirs.push(PathBuf::from(format!("/usr/include/{}", multiarch)));
+        }
+        dirs.push(PathBuf::from("/usr/include"));
+        return dirs;
+    }
+
+    let Some(multiarch) = target.multiarch() else {
+        return Vec::new();
+    };
+    let mut dirs = vec![PathBuf::from(format!("/usr/{}/include", multiarch))];
+    // The architecture-independent headers in /usr/include are only usable
+    // with the target's own `bits/` headers next to them
+    let arch_dir = PathBuf::from(format!("/usr/include/{}", multiarch));
+    if arch_dir.is_dir() {
+        dirs.extend([arch_dir, PathBuf::from("/usr/include")]);
+    }
+    dirs
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum MacroKind {
+    Object,
+    /// A variadic macro's last parameter is `__VA_ARGS__`, or the name given
+    /// before its `...`
+    Function { params: Vec<String>, variadic: bool },
+    Builtin(Builtin),
+}
+
+/// Macros whose expansion is computed by the preprocessor
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Builtin {
+    File,
+    Line,
+    Date,
+    Time,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    kind: MacroKind,
+    body: Vec<Token>,
+    /// The name in the `#define`, which diagnostics on the expansion point at
+    span: Span,
+}
+
+/// A macro replacement list being rescanned
+struct Expansion {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// The macro being expanded, disabled until the expansion is exhausted;
+    /// `None` for a macro argument being expanded on its own
+    name: Option<String>,
+}
+
+/// An `#if`/`#ifdef`/`#ifndef` whose `#endif` has not been seen yet
+struct Conditional {
+    span: Span,
+    /// One of the groups has already been taken
+    taken: bool,
+    seen_else: bool,
+}
+
+/// Tracks whether a file is wrapped in `#ifndef X ... #endif`, so that
+/// including it again can be skipped while `X` stays defined
+enum Guard {
+    Start,
+    Inside(String),
+    After(String),
+    Unguarded,
+}
+
+struct FileState {
+    lexer: Lexer,
+    file: FileId,
+    /// Canonical path, used for `#pragma once` and include guards
+    key: PathBuf,
+    /// The name reported by `__FILE__`, changed by `#line`
+    presumed_name: String,
+    /// Difference between the line reported by `__LINE__` and the physical line
+    line_delta: i64,
+    /// Position in the search path the file was found at, for `#include_next`
+    search_index: Option<usize>,
+    conditionals: Vec<Conditional>,
+    guard: Guard,
+}
+
+struct Preprocessor<'a> {
+    sources: &'a mut SourceMap,
+    diagnostics: &'a mut Diagnostics,
+    search_path: Vec<PathBuf>,
+    macros: HashMap<String, Macro>,
+    /// Open files, the innermost `#include` last
+    files: Vec<FileState>,
+    /// Macro expansions being rescanned, the innermost last
+    contexts: Vec<Expansion>,
+    /// A token read ahead from the current file
+    pending: Option<Token>,
+    pragma_once: HashSet<PathBuf>,
+    include_guards: HashMap<PathBuf, String>,
+    /// Expanding the expression of `#if`/`#elif`, where `defined` is an operator
+    in_condition: bool,
+    /// Whether `#warning` stops compilation like `#error`
+    warnings_as_errors: bool,
+    date: String,
+    time: String,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn new(sources: &'a mut SourceMap, diagnostics: &'a mut Diagnostics, search_path: Vec<PathBuf>) -> Self {
+        let mut macros = HashMap::new();
+        for (name, builtin) in [
+            ("__FILE__", Builtin::File),
+            ("__LINE__", Builtin::Line),
+            ("__DATE__", Builtin::Date),
+            ("__TIME__", Builtin::Time),
+        ] {
+            macros.insert(
+                name.to_string(),
+                Macro {
+                    kind: MacroKind::Builtin(builtin),
+                    body: Vec::new(),
+                    span: Span::default(),
+                },
+            );
+        }
+
+        let (date, time) = current_date_and_time();
+        Preprocessor {
+            sources,
+            diagnostics,
+            search_path,
+            macros,
+            files: Vec::new(),
+            contexts: Vec::new(),
+            pending: None,
+            pragma_once: HashSet::new(),
+            include_guards: HashMap::new(),
+            in_condition: false,
+            warnings_as_errors: false,
+            date,
+            time,
+        }
+    }
+
+    fn current(&self) -> &FileState {
+        self.files.last().expect("no open file")
+    }
+
+    fn current_mut(&mut self) -> &mut FileState {
+        self.files.last_mut().expect("no open file")
+    }
+
+    fn enter_file(&mut self, file: FileId, search_index: Option<usize>) {
+        let path = self.sources.path(file);
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let presumed_name = path.display().to_string();
+        let lexer = Lexer::new(self.sources.text(file), file);
+        self.files.push(FileState {
+            lexer,
+            file,
+            key,
+            presumed_name,
+            line_delta: 0,
+            search_index,
+            conditionals: Vec::new(),
+            guard: Guard::Start,
+        });
+    }
+
+    /// Check the state of a file whose end has been reached
+    fn finish_file(&mut self) -> Result<(), CompileError> {
+        let state = self.current();
+        if let Some(conditional) = state.conditionals.last() {
+            return Err(CompileError::new("unterminated conditional directive", conditional.span));
+        }
+        if let Guard::After(name) = &state.guard {
+            self.include_guards.insert(state.key.clone(), name.clone());
+        }
+        Ok(())
+    }
+
+    /// Read the next token of the open files, processing directives and
+    /// leaving finished `#include`s. With `directives` unset a `#` that starts
+    /// a line is returned as is, so that a caller looking ahead can push it back.
+    fn next_file_token(&mut self, directives: bool) -> Result<Token, CompileError> {
+        loop {
+            let token = match self.pending.take() {
+                Some(token) => token,
+                None => self.current_mut().lexer.next_token()?,
+            };
+
+            if token.kind == TokenKind::Eof {
+                self.finish_file()?;
+                if self.files.len() == 1 {
+                    return Ok(token);
+                }
+                self.files.pop();
+                continue;
+            }
+
+            if token.at_line_start && is_punct(&token, Punct::Hash) {
+                if !directives {
+                    return Ok(token);
+                }
+                self.directive()?;
+                continue;
+            }
+
+            let state = self.current_mut();
+            if matches!(state.guard, Guard::Start | Guard::After(_)) {
+                state.guard = Guard::Unguarded;
+            }
+            return Ok(token);
+        }
+    }
+
+    /// Read the next token without expanding it. With a `floor`, only the
+    /// expansions above that index are read and `Eof` marks their end.
+    fn next_unexpanded(&mut self, floor: Option<usize>) -> Result<Token, CompileError> {
+        let base = floor.unwrap_or(0);
+        loop {
+            if self.contexts.len() > base {
+                let context = self.contexts.last_mut().unwrap();
+                if let Some(token) = context.tokens.get(context.pos) {
+                    let mut token = token.clone();
+                    context.pos += 1;
+                    self.paint(&mut token);
+                    return Ok(token);
+                }
+                if floor.is_some() && self.contexts.len() == base + 1 {
+                    return Ok(eof_token());
+                }
+                self.contexts.pop();
+                continue;
+            }
+            return self.next_file_token(true);
+        }
+    }
+
+    /// Mark an identifier naming a macro that is being expanded so it is never expanded again
+    fn paint(&self, token: &mut Token) {
+        if let Some(name) = identifier_name(token) {
+            if self.contexts.iter().any(|context| context.name.as_deref() == Some(name)) {
+                token.no_expand = true;
+            }
+        }
+    }
+
+    /// Check whether the next token is `(` without consuming it
+    fn next_is_left_paren(&mut self, floor: Option<usize>) -> Result<bool, CompileError> {
+        let base = floor.unwrap_or(0);
+        loop {
+            if self.contexts.len() > base {
+                let context = self.contexts.last().unwrap();
+                if let Some(token) = context.tokens.get(context.pos) {
+                    return Ok(is_punct(token, Punct::LeftParen));
+                }
+                if floor.is_some() && self.contexts.len() == base + 1 {
+                    return Ok(false);
+                }
+                self.contexts.pop();
+                continue;
+            }
+            let token = self.next_file_token(false)?;
+            let is_paren = is_punct(&token, Punct::LeftParen);
+            self.pending = Some(token);
+            return Ok(is_paren);
+        }
+    }
+
+    /// Read the next token with macros expanded
+    fn next_expanded(&mut self, floor: Option<usize>) -> Result<Token, CompileError> {
+        loop {
+            let token = self.next_unexpanded(floor)?;
+            if token.no_expand {
+                return Ok(token);
+            }
+            let name = match identifier_name(&token) {
+                Some(name) => name.to_string(),
+                None => return Ok(token),
+            };
+            if self.in_condition && name == "defined" {
+                return self.defined_operator(&token, floor);
+            }
+            if name == "_Pragma" {
+                self.pragma_operator(&token, floor)?;
+                continue;
+            }
+            let definition = match self.macros.get(&name) {
+                Some(definition) => definition.clone(),
+                None => return Ok(token),
+            };
+
+            match &definition.kind {
+                MacroKind::Object => {
+                    let body = self.substitute(&name, &definition.body, &[], false, &[], token.span)?;
+                    self.push_expansion(name, &token, body);
+                }
+                MacroKind::Function { params, variadic } => {
+                    if !self.next_is_left_paren(floor)? {
+                        return Ok(token);
+                    }
+                    let (args, right_paren) = self.collect_arguments(&token, &definition, params, *variadic, floor)?;
+                    let invocation = token.span.to(right_paren.span);
+                    let body = self.substitute(&name, &definition.body, params, *variadic, &args, invocation)?;
+                    self.push_expansion(name, &token, body);
+                }
+                MacroKind::Builtin(builtin) => return self.expand_builtin(*builtin, &token),
+            }
+        }
+    }
+
+    /// The span of a token that a macro invocation spelled at `invocation` is
+    /// replaced by, which records the token of the body it came from
+    fn expansion_span(&mut self, name: &str, spelling: Span, invocation: Span) -> Span {
+        let expansion = self.sources.add_expansion(name, spelling, invocation);
+        Span {
+            expansion: Some(expansion),
+            ..invocation
+        }
+    }
+
+    fn push_expansion(&mut self, name: String, invocation: &Token, mut tokens: Vec<Token>) {
+        for token in &mut tokens {
+            token.at_line_start = false;
+        }
+        if let Some(first) = tokens.first_mut() {
+            first.leading_space = invocation.leading_space;
+        }
+        self.contexts.push(Expansion {
+            tokens,
+            pos: 0,
+            name: Some(name),
+        });
+    }
+
+    /// Fully macro-expand a list of tokens on its own
+    fn expand_tokens(&mut self, tokens: Vec<Token>) -> Result<Vec<Token>, CompileError> {
+        let floor = self.contexts.len();
+        self.contexts.push(Expansion {
+            tokens,
+            pos: 0,
+            name: None,
+        });
+        let mut expanded = Vec::new();
+        let result = loop {
+            match self.next_expanded(Some(floor)) {
+                Ok(token) if token.kind == TokenKind::Eof => break Ok(expanded),
+                Ok(token) => expanded.push(token),
+                Err(err) => break Err(err),
+            }
+        };
+        self.contexts.truncate(floor);
+        result
+    }
+
+    /// Collect the arguments of a function-like macro invocation, starting at its `(`
+    fn collect_arguments(
+        &mut self,
+        name: &Token,
+        definition: &Macro,
+        params: &[String],
+        variadic: bool,
+        floor: Option<usize>,
+    ) -> Result<(Vec<Vec<Token>>, Token), CompileError> {
+        self.next_unexpanded(floor)?;
+
+        let mut args = vec![Vec::new()];
+        let mut depth = 0;
+        let right_paren = loop {
+            let mut token = self.next_unexpanded(floor)?;
+            token.at_line_start = false;
+            match token.kind {
+                TokenKind::Eof => {
+                    return Err(CompileError::new(
+                        format!("unterminated argument list invoking macro `{}`", name.text),
+                        name.span,
+                    ));
+                }
+                TokenKind::Punct(Punct::LeftParen) => depth += 1,
+                TokenKind::Punct(Punct::RightParen) if depth == 0 => break token,
+                TokenKind::Punct(Punct::RightParen) => depth -= 1,
+                // Commas inside the variable arguments belong to `__VA_ARGS__`
+                TokenKind::Punct(Punct::Comma) if depth == 0 && !(variadic && args.len() == params.len()) => {
+                    args.push(Vec::new());
+                    continue;
+                }
+                _ => {}
+            }
+            args.last_mut().unwrap().push(token);
+        };
+
+        if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+            args.clear();
+        }
+        if variadic && args.len() + 1 == params.len() {
+            args.push(Vec::new());
+        }
+
+        if args.len() != params.len() {
+            let expected = params.len() - variadic as usize;
+            let message = if args.len() < params.len() {
+                format!(
+                    "macro `{}` requires {}{} arguments, but only {} given",
+                    name.text,
+                    if variadic { "at least " } else { "" },
+                    expected,
+                    args.len()
+                )
+            } else {
+                format!(
+                    "macro `{}` passed {} arguments, but takes just {}",
+                    name.text,
+                    args.len(),
+                    expected
+                )
+            };
+            return Err(CompileError::new(message, name.span.to(right_paren.span))
+                .with_label(definition.span, "macro defined here"));
+        }
+
+        Ok((args, right_paren))
+    }
+
+    /// Replace the parameters in the body of the macro `name`, invoked at
+    /// `invocation`, applying `#` and `##`. An object-like macro has no
+    /// parameters, so only `##` is an operator in it.
+    fn substitute(
+        &mut self,
+        name: &str,
+        body: &[Token],
+        params: &[String],
+        variadic: bool,
+        args: &[Vec<Token>],
+        invocation: Span,
+    ) -> Result<Vec<Token>, CompileError> {
+        let param_index = |token: &Token| identifier_name(token).and_then(|name| params.iter().position(|p| p == name));
+        let mut expanded_args: Vec<Option<Vec<Token>>> = vec![None; args.len()];
+        let mut output: Vec<Token> = Vec::new();
+        // The last operand produced no tokens, so a following `##` has nothing to paste onto
+        let mut placemarker = false;
+
+        let mut i = 0;
+        while i < body.len() {
+            let token = &body[i];
+
+            if is_punct(token, Punct::HashHash) {
+                let rhs_index = i + 1;
+                let (rhs, consumed) = self.paste_operand(name, body, rhs_index, &param_index, args, invocation)?;
+                i = rhs_index + consumed;
+
+                // `, ## __VA_ARGS__` drops the comma when there are no variable
+                // arguments and is a plain concatenation otherwise (GNU extension)
+                let comma_before_va_args = variadic
+                    && !placemarker
+                    && params.last().is_some_and(|last| body[rhs_index].text == *last)
+                    && output.last().is_some_and(|last| is_punct(last, Punct::Comma));
+                if comma_before_va_args {
+                    if rhs.is_empty() {
+                        output.pop();
+                    } else {
+                        output.extend(rhs);
+                    }
+                    continue;
+                }
+
+                if rhs.is_empty() {
+                    continue;
+                }
+                if placemarker {
+                    output.extend(rhs);
+                    placemarker = false;
+                    continue;
+                }
+                let lhs = output.pop().expect("`##` cannot start a macro body");
+                let mut rhs = rhs.into_iter();
+                output.push(paste(&lhs, &rhs.next().unwrap())?);
+                output.extend(rhs);
+                continue;
+            }
+
+            let followed_by_paste = |next: usize| body.get(next).is_some_and(|t| is_punct(t, Punct::HashHash));
+            let stringified = body.get(i + 1).and_then(param_index).filter(|_| is_punct(token, Punct::Hash));
+            let (tokens, consumed) = if let Some(index) = stringified {
+                let mut origin = token.clone();
+                origin.span = self.expansion_span(name, token.span, invocation);
+                (vec![stringify(&args[index], &origin)?], 2)
+            } else if let Some(index) = param_index(token) {
+                let mut tokens = if followed_by_paste(i + 1) {
+                    args[index].clone()
+                } else {
+                    if expanded_args[index].is_none() {
+                        expanded_args[index] = Some(self.expand_tokens(args[index].clone())?);
+                    }
+                    expanded_args[index].clone().unwrap()
+                };
+                if let Some(first) = tokens.first_mut() {
+                    first.leading_space = token.leading_space;
+                }
+                (tokens, 1)
+            } else {
+                let mut token = token.clone();
+                token.span = self.expansion_span(name, token.span, invocation);
+                (vec![token], 1)
+            };
+
+            placemarker = tokens.is_empty();
+            output.extend(tokens);
+            i += consumed;
+        }
+
+        Ok(output)
+    }
+
+    /// The right operand of `##`: an unexpanded argument, a stringified
+    /// argument or a single token. Returns the tokens and how many body tokens were used.
+    fn paste_operand(
+        &mut self,
+        name: &str,
+        body: &[Token],
+        index: usize,
+        param_index: &dyn Fn(&Token) -> Option<usize>,
+        args: &[Vec<Token>],
+        invocation: Span,
+    ) -> Result<(Vec<Token>, usize), CompileError> {
+        let token = &body[index];
+        if is_punct(token, Punct::Hash) {
+            if let Some(param) = body.get(index + 1).and_then(param_index) {
+                let mut origin = token.clone();
+                origin.span = self.expansion_span(name, token.span, invocation);
+                return Ok((vec![stringify(&args[param], &origin)?], 2));
+            }
+        }
+        if let Some(param) = param_index(token) {
+            return Ok((args[param].clone(), 1));
+        }
+        let mut token = token.clone();
+        token.span = self.expansion_span(name, token.span, invocation);
+        Ok((vec![token], 1))
+    }
+
+    fn expand_builtin(&mut self, builtin: Builtin, token: &Token) -> Result<Token, CompileError> {
+        let text = match builtin {
+            Builtin::File => quote(&self.current().presumed_name),
+            Builtin::Line => (token.span.start.line as i64 + self.current().line_delta).to_string(),
+            Builtin::Date => self.date.clone(),
+            Builtin::Time => self.time.clone(),
+        };
+        let mut expanded = relex(&text, token).expect("built-in macros expand to a single token");
+        expanded.no_expand = true;
+        Ok(expanded)
+    }
+
+    /// `defined X` or `defined(X)` inside `#if`
+    fn defined_operator(&mut self, token: &Token, floor: Option<usize>) -> Result<Token, CompileError> {
+        let mut operand = self.next_unexpanded(floor)?;
+        let parenthesized = is_punct(&operand, Punct::LeftParen);
+        if parenthesized {
+            operand = self.next_unexpanded(floor)?;
+        }
+        let name = match identifier_name(&operand) {
+            Some(name) => name.to_string(),
+            None => return Err(CompileError::new("macro name must be an identifier", operand.span)),
+        };
+        if parenthesized {
+            let close = self.next_unexpanded(floor)?;
+            if !is_punct(&close, Punct::RightParen) {
+                return Err(CompileError::new(
+                    format!("expected `)` after `defined({}`, found {}", name, close),
+                    close.span,
+                ));
+            }
+        }
+        Ok(int_token(self.macros.contains_key(&name) as u64, token))
+    }
+
+    /// `_Pragma("...")`, which behaves like a `#pragma` line
+    fn pragma_operator(&mut self, token: &Token, floor: Option<usize>) -> Result<(), CompileError> {
+        let open = self.next_unexpanded(floor)?;
+        let literal = self.next_unexpanded(floor)?;
+        let close = self.next_unexpanded(floor)?;
+        let text = match &literal.kind {
+            TokenKind::StringLiteral { .. }
+                if is_punct(&open, Punct::LeftParen) && is_punct(&close, Punct::RightParen) =>
+            {
+                literal.text[literal.text.find('"').unwrap() + 1..literal.text.len() - 1]
+                    .replace("\\\"", "\"")
+                    .replace("\\\\", "\\")
+            }
+            _ => return Err(CompileError::new("_Pragma takes a parenthesized string literal", token.span)),
+        };
+        if text.trim() == "once" {
+            let key = self.current().key.clone();
+            self.pragma_once.insert(key);
+        }
+        Ok(())
+    }
+
+    /// Read the tokens left on the current directive line
+    fn read_line(&mut self) -> Result<Vec<Token>, CompileError> {
+        let lexer = &mut self.current_mut().lexer;
+        let mut tokens = Vec::new();
+        while !lexer.at_line_end()? {
+            tokens.push(lexer.next_token()?);
+        }
+        Ok(tokens)
+    }
+
+    /// Handle a directive line whose `#` has just been read
+    fn directive(&mut self) -> Result<(), CompileError> {
+        if self.current_mut().lexer.at_line_end()? {
+            // The null directive
+            return Ok(());
+        }
+        let name = self.current_mut().lexer.next_token()?;
+        self.process_directive(name)
+    }
+
+    fn process_directive(&mut self, name: Token) -> Result<(), CompileError> {
+        let header = match name.text.as_str() {
+            "include" | "include_next" => self.current_mut().lexer.header_name()?,
+            _ => None,
+        };
+        let line = self.read_line()?;
+        self.update_guard(&name.text, &line);
+
+        match name.text.as_str() {
+            "define" => self.define(&name, line),
+            "undef" => {
+                let macro_name = macro_name(&name, &line)?;
+                self.macros.remove(macro_name);
+                Ok(())
+            }
+            "include" | "include_next" => self.include(&name, header, line),
+            "if" => {
+                let taken = self.evaluate(&name, line)?;
+                self.push_conditional(name.span, taken)
+            }
+            "ifdef" | "ifndef" => {
+                let defined = self.macros.contains_key(macro_name(&name, &line)?);
+                self.push_conditional(name.span, defined == (name.text == "ifdef"))
+            }
+            "elif" => {
+                let conditional = self.current_conditional(&name)?;
+                if conditional.seen_else {
+                    return Err(CompileError::new("#elif after #else", name.span));
+                }
+                if conditional.taken || !self.evaluate(&name, line)? {
+                    return self.skip_group();
+                }
+                self.current_conditional(&name)?.taken = true;
+                Ok(())
+            }
+            "else" => {
+                let conditional = self.current_conditional(&name)?;
+                if conditional.seen_else {
+                    return Err(CompileError::new("#else after #else", name.span));
+                }
+                conditional.seen_else = true;
+                if conditional.taken {
+                    return self.skip_group();
+                }
+                conditional.taken = true;
+                Ok(())
+            }
+            "endif" => {
+                self.current_conditional(&name)?;
+                self.current_mut().conditionals.pop();
+                Ok(())
+            }
+            "line" => self.line_directive(&name, line),
+            "error" => Err(CompileError::new(format!("#error {}", spell(&line)), name.span)),
+            "warning" if self.warnings_as_errors => {
+                Err(CompileError::new(format!("#warning {}", spell(&line)), name.span)
+                    .with_note("warnings are treated as errors"))
+            }
+            "warning" => {
+                let warning = CompileError::new(format!("#warning {}", spell(&line)), name.span);
+                self.diagnostics.warning(&warning, self.sources);
+                Ok(())
+            }
+            "pragma" => {
+                if matches!(line.as_slice(), [token] if token.text == "once") {
+                    let key = self.current().key.clone();
+                    self.pragma_once.insert(key);
+                }
+                Ok(())
+            }
+            // GNU line markers: `# 42 "file.c"`
+            _ if matches!(name.kind, TokenKind::IntLiteral { .. }) => {
+                let mut line = line;
+                line.insert(0, name.clone());
+                self.line_directive(&name, line)
+            }
+            _ => Err(CompileError::new(
+                format!("invalid preprocessing directive `#{}`", name.text),
+                name.span,
+            )),
+        }
+    }
+
+    fn update_guard(&mut self, directive: &str, line: &[Token]) {
+        let state = self.current_mut();
+        let depth = state.conditionals.len();
+        state.guard = match std::mem::replace(&mut state.guard, Guard::Unguarded) {
+            Guard::Start if directive == "ifndef" && line.len() == 1 => Guard::Inside(line[0].text.clone()),
+            Guard::Inside(name) if depth == 1 && directive == "endif" => Guard::After(name),
+            Guard::Inside(_) if depth == 1 && matches!(directive, "else" | "elif") => Guard::Unguarded,
+            Guard::Inside(name) => Guard::Inside(name),
+            _ => Guard::Unguarded,
+        };
+    }
+
+    fn current_conditional(&mut self, directive: &Token) -> Result<&mut Conditional, CompileError> {
+        self.current_mut()
+            .conditionals
+            .last_mut()
+            .ok_or_else(|| CompileError::new(format!("#{} without #if", directive.text), directive.span))
+    }
+
+    fn push_conditional(&mut self, span: Span, taken: bool) -> Result<(), CompileError> {
+        self.current_mut().conditionals.push(Conditional {
+            span,
+            taken,
+            seen_else: false,
+        });
+        if !taken {
+            self.skip_group()?;
+        }
+        Ok(())
+    }
+
+    /// Skip the lines of a group that is not taken, up to the `#elif`,
+    /// `#else` or `#endif` belonging to the same conditional
+    fn skip_group(&mut self) -> Result<(), CompileError> {
+        let mut depth = 0;
+        loop {
+            let lexer = &mut self.current_mut().lexer;
+            if lexer.at_directive()? {
+                lexer.next_token()?;
+                if !lexer.at_line_end()? {
+                    if let Ok(name) = lexer.next_token() {
+                        match name.text.as_str() {
+                            "if" | "ifdef" | "ifndef" => depth += 1,
+                            "endif" if depth > 0 => depth -= 1,
+                            "elif" | "else" | "endif" if depth == 0 => return self.process_directive(name),
+                            _ => {}
+                        }
+                    }
+                }
+            } else if lexer.at_eof() {
+                // Reported as an unterminated conditional once `Eof` is read
+                return Ok(());
+            }
+            self.current_mut().lexer.skip_line()?;
+        }
+    }
+
+    fn define(&mut self, directive: &Token, line: Vec<Token>) -> Result<(), CompileError> {
+        let name = macro_name(directive, &line)?.to_string();
+        if name == "defined" {
+            return Err(CompileError::new("`defined` cannot be used as a macro name", line[0].span));
+        }
+
+        // A function-like macro has its `(` directly after the name
+        let (kind, body_start) = match line.get(1) {
+            Some(paren) if is_punct(paren, Punct::LeftParen) && !paren.leading_space => {
+                let (params, variadic, end) = parse_macro_parameters(&line)?;
+                (MacroKind::Function { params, variadic }, end)
+            }
+            _ => (MacroKind::Object, 1),
+        };
+        let mut body = line[body_start..].to_vec();
+        for token in &mut body {
+            token.at_line_start = false;
+        }
+
+        let va_args = matches!(
+            &kind,
+            MacroKind::Function { params, variadic: true } if params.last().unwrap() == "__VA_ARGS__"
+        );
+        for (i, token) in body.iter().enumerate() {
+            if token.text == "__VA_ARGS__" && !va_args {
+                return Err(CompileError::new(
+                    "`__VA_ARGS__` can only appear in the expansion of a variadic macro",
+                    token.span,
+                ));
+            }
+            if is_punct(token, Punct::HashHash) && (i == 0 || i == body.len() - 1) {
+                return Err(CompileError::new(
+                    "`##` cannot appear at either end of a macro expansion",
+                    token.span,
+                ));
+            }
+            if let MacroKind::Function { params, .. } = &kind {
+                let names_param = |next: &Token| identifier_name(next).is_some_and(|n| params.iter().any(|p| p == n));
+                if is_punct(token, Punct::Hash) && !body.get(i + 1).is_some_and(names_param) {
+                    return Err(CompileError::new("`#` is not followed by a macro parameter", token.span));
+                }
+            }
+        }
+
+        if let Some(MacroKind::Builtin(_)) = self.macros.get(&name).map(|existing| &existing.kind) {
+            return Err(CompileError::new(
+                format!("cannot redefine built-in macro `{}`", name),
+                line[0].span,
+            ));
+        }
+        self.macros.insert(name, Macro { kind, body, span: line[0].span });
+        Ok(())
+    }
+
+    fn include(
+        &mut self,
+        directive: &Token,
+        header: Option<(String, bool, Span)>,
+        line: Vec<Token>,
+    ) -> Result<(), CompileError> {
+        let (name, quoted, span) = match header {
+            Some((name, quoted, span)) => (name, quoted, directive.span.to(span)),
+            // Otherwise the header name has to come from expanding macros
+            None => {
+                let line = self.expand_tokens(line)?;
+                let (name, quoted) = header_name(&line).ok_or_else(|| {
+                    CompileError::new(
+                        format!("#{} expects \"FILENAME\" or <FILENAME>", directive.text),
+                        directive.span,
+                    )
+                })?;
+                let span = directive.span.to(line.last().map_or(directive.span, |token| token.span));
+                (name, quoted, span)
+            }
+        };
+        let next = directive.text == "include_next";
+
+        let search_start = if next {
+            self.current().search_index.map_or(0, |index| index + 1)
+        } else {
+            0
+        };
+        let (path, search_index) = self
+            .find_include(&name, quoted && !next, search_start)
+            .ok_or_else(|| {
+                CompileError::new(format!("`{}` file not found", name), span)
+                    .with_help("add the directory that contains it to the search path with `-I`")
+            })?;
+
+        let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if self.pragma_once.contains(&key) {
+            return Ok(());
+        }
+        if let Some(guard) = self.include_guards.get(&key) {
+            if self.macros.contains_key(guard) {
+                debug!("Skipping {}, guarded by {}", path.display(), guard);
+                return Ok(());
+            }
+        }
+        if self.files.len() >= MAX_INCLUDE_DEPTH {
+            return Err(CompileError::new("#include nested too deeply", span));
+        }
+
+        let bundled = path
+            .strip_prefix(headers::BUNDLED_DIR)
+            .ok()
+            .and_then(|name| headers::find(name.to_str()?));
+        let text = match bundled {
+            Some(text) => text.to_string(),
+            None => std::fs::read_to_string(&path)
+                .map_err(|err| CompileError::new(format!("cannot read `{}`: {}", path.display(), err), span))?,
+        };
+        let file = self.sources.add_file(&path, text);
+        self.enter_file(file, search_index);
+        Ok(())
+    }
+
+    /// Locate an included file: next to the including file for `"..."`, then
+    /// along the search path starting at `search_start`, and finally among the
+    /// bundled headers, which sit after the last search path entry
+    fn find_include(&self, name: &str, quoted: bool, search_start: usize) -> Option<(PathBuf, Option<usize>)> {
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return path.is_file().then(|| (path.to_path_buf(), None));
+        }
+        if quoted {
+            let including = self.sources.path(self.current().file);
+            let candidate = including.parent().unwrap_or(Path::new("")).join(path);
+            if candidate.is_file() {
+                return Some((candidate, None));
+            }
+        }
+        self.search_path
+            .iter()
+            .enumerate()
+            .skip(search_start)
+            .map(|(index, dir)| (dir.join(path), Some(index)))
+            .find(|(candidate, _)| candidate.is_file())
+            .or_else(|| {
+                let bundled = search_start <= self.search_path.len() && headers::find(name).is_some();
+                bundled.then(|| (Path::new(headers::BUNDLED_DIR).join(path), Some(self.search_path.len())))
+            })
+    }
+
+    fn line_directive(&mut self, directive: &Token, line: Vec<Token>) -> Result<(), CompileError> {
+        let line = self.expand_tokens(line)?;
+        let number = match line.first().map(|token| &token.kind) {
+            Some(TokenKind::IntLiteral { value, .. }) => *value as i64,
+            _ => {
+                return Err(CompileError::new(
+                    "#line directive requires a positive integer argument",
+                    directive.span,
+                ))
+            }
+        };
+        // The number applies to the line following the directive
+        let physical_line = directive.span.start.line as i64 + 1;
+        let state = self.current_mut();
+        state.line_delta = number - physical_line;
+        if let Some(file_name) = line.get(1) {
+            if matches!(file_name.kind, TokenKind::StringLiteral { encoding: Encoding::Plain, .. }) {
+                state.presumed_name = file_name.text[1..file_name.text.len() - 1].to_string();
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate the expression of `#if` or `#elif`
+    fn evaluate(&mut self, directive: &Token, line: Vec<Token>) -> Result<bool, CompileError> {
+        self.in_condition = true;
+        let tokens = self.expand_tokens(line);
+        self.in_condition = false;
+        let tokens = tokens?;
+
+        if tokens.is_empty() {
+            return Err(CompileError::new(
+                format!("#{} with no expression", directive.text),
+                directive.span,
+            ));
+        }
+        let mut condition = Condition {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let value = condition.conditional(true)?;
+        if let Some(token) = condition.peek() {
+            return Err(CompileError::new(
+                format!("expected end of line in preprocessor expression, found {}", token),
+                token.span,
+            ));
+        }
+        Ok(value.is_true())
+    }
+}
+
+/// The name of a token that can be defined as a macro; keywords can be too
+fn identifier_name(token: &Token) -> Option<&str> {
+    match &token.kind {
+        TokenKind::Identifier(name) => Some(name),
+        TokenKind::Keyword(_) => Some(&token.text),
+        _ => None,
+    }
+}
+
+fn is_punct(token: &Token, punct: Punct) -> bool {
+    token.kind == TokenKind::Punct(punct)
+}
+
+/// The macro name operand of `#define`, `#undef`, `#ifdef` and `#ifndef`
+fn macro_name<'t>(directive: &Token, line: &'t [Token]) -> Result<&'t str, CompileError> {
+    match line.first() {
+        Some(token) => identifier_name(token)
+            .ok_or_else(|| CompileError::new("macro names must be identifiers", token.span)),
+        None => Err(CompileError::new(
+            format!("macro name missing in #{}", directive.text),
+            directive.span,
+        )),
+    }
+}
+
+/// Parse the parameter list of a function-like `#define` starting at `line[1]`,
+/// returning the parameters, whether the macro is variadic and where the body starts
+fn parse_macro_parameters(line: &[Token]) -> Result<(Vec<String>, bool, usize), CompileError> {
+    let mut params: Vec<String> = Vec::new();
+    let mut variadic = false;
+    let mut i = 2;
+    if line.get(i).is_some_and(|token| is_punct(token, Punct::RightParen)) {
+        return Ok((params, false, i + 1));
+    }
+
+    loop {
+        let token = line
+            .get(i)
+            .ok_or_else(|| CompileError::new("missing `)` in macro parameter list", line[i - 1].span))?;
+        if is_punct(token, Punct::Ellipsis) {
+            variadic = true;
+            params.push("__VA_ARGS__".to_string());
+        } else if let Some(name) = identifier_name(token) {
+            // `name...` names the variable arguments (GNU extension)
+            if line.get(i + 1).is_some_and(|next| is_punct(next, Punct::Ellipsis)) {
+                variadic = true;
+                i += 1;
+            }
+            if name == "__VA_ARGS__" {
+                return Err(CompileError::new(
+                    "`__VA_ARGS__` can only appear in the expansion of a variadic macro",
+                    token.span,
+                ));
+            }
+            if params.iter().any(|param| param == name) {
+                return Err(CompileError::new(format!("duplicate macro parameter `{}`", name), token.span));
+            }
+            params.push(name.to_string());
+        } else {
+            return Err(CompileError::new(
+                format!("expected parameter name, found {}", token),
+                token.span,
+            ));
+        }
+        i += 1;
+
+        match line.get(i) {
+            Some(token) if is_punct(token, Punct::RightParen) => return Ok((params, variadic, i + 1)),
+            Some(token) if is_punct(token, Punct::Comma) && !variadic => i += 1,
+            Some(token) => {
+                return Err(CompileError::new(
+                    format!("expected `,` or `)` in macro parameter list, found {}", token),
+                    token.span,
+                ))
+            }
+            None => return Err(CompileError::new("missing `)` in macro parameter list", line[i - 1].span)),
+        }
+    }
+}
+
+/// The file named by an `#include` line and whether it was written in quotes
+fn header_name(line: &[Token]) -> Option<(String, bool)> {
+    let first = line.first()?;
+    match first.kind {
+        TokenKind::StringLiteral {
+            encoding: Encoding::Plain,
+            ..
+        } => Some((first.text[1..first.text.len() - 1].to_string(), true)),
+        TokenKind::Punct(Punct::Less) => {
+            let end = line.iter().position(|token| is_punct(token, Punct::Greater))?;
+            Some((spell(&line[1..end]), false))
+        }
+        _ => None,
+    }
+}
+
+/// Spell tokens back out, keeping a single space where there was whitespace
+fn spell(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && token.leading_space {
+            text.push(' ');
+        }
+        text.push_str(&token.text);
+    }
+    text
+}
+
+/// Quote text as a string literal
+fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+/// Escape `"` and `\\` for use inside a string literal
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Apply the `#` operator to a macro argument
+fn stringify(arg: &[Token], origin: &Token) -> Result<Token, CompileError> {
+    let mut text = String::new();
+    for (i, token) in arg.iter().enumerate() {
+        if i > 0 && token.leading_space {
+            text.push(' ');
+        }
+        match token.kind {
+            TokenKind::StringLiteral { .. } | TokenKind::CharLiteral { .. } => text.push_str(&escape(&token.text)),
+            _ => text.push_str(&token.text),
+        }
+    }
+    let literal = format!("\"{}\"", text);
+    relex(&literal, origin)
+        .ok_or_else(|| CompileError::new(format!("stringification formed the invalid literal {}", literal), origin.span))
+}
+
+/// Apply the `##` operator
+fn paste(lhs: &Token, rhs: &Token) -> Result<Token, CompileError> {
+    let text = format!("{}{}", lhs.text, rhs.text);
+    relex(&text, lhs).ok_or_else(|| {
+        CompileError::new(
+            format!("pasting formed `{}`, an invalid preprocessing token", text),
+            lhs.span,
+        )
+    })
+}
+
+/// Lex text that must form exactly one token, giving it the position of `origin`
+fn relex(text: &str, origin: &Token) -> Option<Token> {
+    let mut lexer = Lexer::new(text, origin.span.file);
+    let mut token = lexer.next_token().ok()?;
+    if token.kind == TokenKind::Eof || lexer.next_token().ok()?.kind != TokenKind::Eof {
+        return None;
+    }
+    token.span = origin.span;
+    token.leading_space = origin.leading_space;
+    token.at_line_start = false;
+    Some(token)
+}
+
+fn int_token(value: u64, origin: &Token) -> Token {
+    Token {
+        kind: TokenKind::IntLiteral {
+            value,
+            radix: 10,
+            suffix: IntSuffix::None,
+        },
+        span: origin.span,
+        text: value.to_string(),
+        at_line_start: false,
+        leading_space: origin.leading_space,
+        no_expand: false,
+    }
+}
+
+fn eof_token() -> Token {
+    Token {
+        kind: TokenKind::Eof,
+        span: Span::default(),
+        text: String::new(),
+        at_line_start: false,
+        leading_space: false,
+        no_expand: false,
+    }
+}
+
+/// `__DATE__` and `__TIME__` for the current UTC time, as string literals
+fn current_date_and_time() -> (String, String) {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+    let (days, time_of_day) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    // Convert days since the epoch to a civil date (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    let date = format!("\"{} {:2} {}\"", MONTHS[month as usize - 1], day, year);
+    let time = format!(
+        "\"{:02}:{:02}:{:02}\"",
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60
+    );
+    (date, time)
+}
+
+/// Evaluates the macro-expanded tokens of an `#if` expression, whose values
+/// have type `intmax_t` or `uintmax_t`. Operands of `&&`, `||` and `?:` that
+/// are not evaluated are still parsed, with `live` unset so that e.g.
+/// division by zero is not reported.
+struct Condition<'t> {
+    tokens: &'t [Token],
+    pos: usize,
+}
+
+impl Condition<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<&Token, CompileError> {
+        let span = self.tokens[self.tokens.len() - 1].span;
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| CompileError::new("expected value in preprocessor expression", span))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, punct: Punct) -> Result<(), CompileError> {
+        let token = self.next()?;
+        if !is_punct(token, punct) {
+            return Err(CompileError::new(
+                format!("expected `{}` in preprocessor expression, found {}", punct.as_str(), token),
+                token.span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn conditional(&mut self, live: bool) -> Result<Integer, CompileError> {
+        let condition = self.binary(1, live)?;
+        if !self.peek().is_some_and(|token| is_punct(token, Punct::Question)) {
+            return Ok(condition);
+        }
+        self.pos += 1;
+        let taken = condition.is_true();
+        let then_value = self.conditional(live && taken)?;
+        self.expect(Punct::Colon)?;
+        let else_value = self.conditional(live && !taken)?;
+        Ok(Integer {
+            bits: if taken { then_value.bits } else { else_value.bits },
+            unsigned: then_value.unsigned || else_value.unsigned,
+        })
+    }
+
+    fn binary(&mut self, min_precedence: u8, live: bool) -> Result<Integer, CompileError> {
+        let mut lhs = self.unary(live)?;
+        while let Some(token) = self.peek() {
+            let (operator, precedence) = match token.kind {
+                TokenKind::Punct(punct) => match parser::binary_operator(punct) {
+                    Some((operator, precedence)) if precedence >= min_precedence => (operator, precedence),
+                    _ => break,
+                },
+                _ => break,
+            };
+            let span = token.span;
+            self.pos += 1;
+            let rhs_live = match operator {
+                BinaryOperator::LogicalAnd => live && lhs.is_true(),
+                BinaryOperator::LogicalOr => live && !lhs.is_true(),
+                _ => live,
+            };
+            let rhs = self.binary(precedence + 1, rhs_live)?;
+            lhs = apply_binary(operator, lhs, rhs, live, span)?;
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self, live: bool) -> Result<Integer, CompileError> {
+        let token = self.next()?;
+        match &token.kind {
+            TokenKind::Punct(Punct::Plus) => self.unary(live),
+            TokenKind::Punct(Punct::Minus) => {
+                let value = self.unary(live)?;
+                Ok(Integer {
+                    bits: value.bits.wrapping_neg(),
+                    ..value
+                })
+            }
+            TokenKind::Punct(Punct::Tilde) => {
+                let value = self.unary(live)?;
+                Ok(Integer {
+                    bits: !value.bits,
+                    ..value
+                })
+            }
+            TokenKind::Punct(Punct::Bang) => {
+                let value = self.unary(live)?;
+                Ok(Integer::signed(!value.is_true() as i64))
+            }
+            TokenKind::Punct(Punct::LeftParen) => {
+                let value = self.conditional(live)?;
+                self.expect(Punct::RightParen)?;
+                Ok(value)
+            }
+            TokenKind::IntLiteral { value, suffix, .. } => Ok(Integer {
+                bits: *value,
+                unsigned: *value > i64::MAX as u64
+                    || matches!(
+                        suffix,
+                        IntSuffix::Unsigned | IntSuffix::UnsignedLong | IntSuffix::UnsignedLongLong
+                    ),
+            }),
+            TokenKind::CharLiteral { encoding, value } => Ok(Integer::signed(encoding.char_value(value))),
+            // Identifiers that are not macros evaluate to 0, keywords included
+            TokenKind::Identifier(_) | TokenKind::Keyword(_) => Ok(Integer::signed(0)),
+            TokenKind::FloatLiteral { .. } => Err(CompileError::new(
+                "floating constant in preprocessor expression",
+                token.span,
+            )),
+            _ => Err(CompileError::new(
+                format!("invalid token {} in preprocessor expression", token),
+                token.span,
+            )),
+        }
+    }
+}
+
+fn apply_binary(
+    operator: BinaryOperator,
+    lhs: Integer,
+    rhs: Integer,
+    live: bool,
+    span: Span,
+) -> Result<Integer, CompileError> {
+    match constant::binary(operator, lhs, rhs) {
+        Some(value) => Ok(value),
+        None if live => Err(CompileError::new("division by zero in preprocessor expression", span)),
+        None => Ok(Integer {
+            bits: 0,
+            unsigned: lhs.unsigned || rhs.unsigned,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer;
+    use crate::compiler::target::{create_target_machine_for, test_target};
+    use std::fs;
+
+    fn options_with(include_paths: Vec<PathBuf>) -> CompilerOptions {
+        CompilerOptions { include_paths, ..CompilerOptions::default() }
+    }
+
+    fn run(source: &str, include_paths: &[PathBuf]) -> Result<Vec<Token>, CompileError> {
+        run_with(source, &options_with(include_paths.to_vec()))
+    }
+
+    fn run_with(source: &str, options: &CompilerOptions) -> Result<Vec<Token>, CompileError> {
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(Path::new("test.c"), source.to_string());
+        preprocess(&mut sources, file, options, &test_target(), &mut Diagnostics::captured())
+    }
+
+    /// Token spellings separated by single spaces
+    fn spelled(tokens: &[Token]) -> String {
+        let texts: Vec<&str> = tokens
+            .iter()
+            .filter(|token| token.kind != TokenKind::Eof)
+            .map(|token| token.text.as_str())
+            .collect();
+        texts.join(" ")
+    }
+
+    fn expand(source: &str) -> String {
+        spelled(&run(source, &[]).expect("preprocessing failed"))
+    }
+
+    /// Lex `expected` the same way so that spacing does not matter
+    fn normalized(expected: &str) -> String {
+        spelled(&lexer::tokenize(expected, FileId(0)).expect("lexing failed"))
+    }
+
+    fn expand_error(source: &str) -> CompileError {
+        run(source, &[]).expect_err("preprocessing should fail")
+    }
+
+    #[test]
+    fn test_object_and_function_like_macros() {
+        let source = "#define MAX_SIZE 100\n#define MIN(a, b) ((a) < (b) ? (a) : (b))\n#define SQUARE(x) ((x) * (x))\nint a[MAX_SIZE];\nint m = MIN(1 + 2, f(3, 4));\nint s = SQUARE(SQUARE(2));\n";
+        assert_eq!(
+            expand(source),
+            normalized(
+                "int a[100]; int m = ((1 + 2) < (f(3, 4)) ? (1 + 2) : (f(3, 4))); int s = ((((2) * (2))) * (((2) * (2))));"
+            )
+        );
+    }
+
+    #[test]
+    fn test_function_like_macro_without_arguments_is_not_expanded() {
+        assert_eq!(expand("#define f(x) [x]\nint f; f (1) f\n(2)"), normalized("int f; [1] [2]"));
+        assert_eq!(expand("#define f() 42\nf() f( ) f"), "42 42 f");
+        assert_eq!(expand("#define EMPTY\n#define f(x) <x>\nf() EMPTY f(EMPTY)"), "< > < >");
+    }
+
+    #[test]
+    fn test_recursive_macros_are_not_expanded_again() {
+        assert_eq!(expand("#define foo foo + 1\nfoo"), "foo + 1");
+        assert_eq!(expand("#define a b\n#define b a\na b"), "a b");
+        assert_eq!(expand("#define f(x) x + f(x)\nf(f(1))"), normalized("1 + f(1) + f(1 + f(1))"));
+    }
+
+    // C11 6.10.3.5, example 3
+    #[test]
+    fn test_standard_rescanning_example() {
+        let source = r#"#define x 3
+#define f(a) f(x * (a))
+#undef x
+#define x 2
+#define g f
+#define z z[0]
+#define h g(~
+#define m(a) a(w)
+#define w 0,1
+#define t(a) a
+#define p() int
+#define q(x) x
+#define r(x,y) x ## y
+#define str(x) # x
+f(y+1) + f(f(z)) % t(t(g)(0) + t)(1);
+g(x+(3,4)-w) | h 5) & m
+(f)^m(m);
+p() i[q()] = { q(1), r(2,3), r(4,), r(,5), r(,) };
+char c[2][6] = { str(hello), str() };
+"#;
+        let expected = r#"f(2 * (y+1)) + f(2 * (f(2 * (z[0])))) % f(2 * (0)) + t(1);
+f(2 * (2+(3,4)-0,1)) | f(2 * (~ 5)) & f(2 * (0,1))^m(0,1);
+int i[] = { 1, 23, 4, 5, };
+char c[2][6] = { "hello", "" };
+"#;
+        assert_eq!(expand(source), normalized(expected));
+    }
+
+    // C11 6.10.3.5, example 4
+    #[test]
+    fn test_standard_stringify_and_paste_example() {
+        let source = r#"#define str(s) # s
+#define xstr(s) str(s)
+#define debug(s, t) printf("x" # s "= %d, x" # t "= %s", \
+ x ## s, x ## t)
+#define INCFILE(n) vers ## n
+#define glue(a, b) a ## b
+#define xglue(a, b) glue(a, b)
+#define HIGHLOW "hello"
+#define LOW LOW ", world"
+debug(1, 2);
+fputs(str(strncmp("abc\0d", "abc", '\4') // this goes away
+ == 0), s);
+xstr(INCFILE(2).h)
+glue(HIGH, LOW);
+xglue(HIGH, LOW)
+"#;
+        let expected = r#"printf("x" "1" "= %d, x" "2" "= %s", x1, x2);
+fputs("strncmp(\"abc\\0d\", \"abc\", '\\4') == 0", s);
+"vers2.h"
+"hello";
+"hello" ", world"
+"#;
+        assert_eq!(expand(source), normalized(expected));
+
+        // C11 6.10.3.3, example: `##` in an object-like macro, whose result is
+        // not an operator
+        let source = r#"#define hash_hash # ## #
+#define mkstr(a) # a
+#define in_between(a) mkstr(a)
+#define join(c, d) in_between(c hash_hash d)
+#define AB x ## y
+char p[] = join(x, y);
+AB
+"#;
+        assert_eq!(expand(source), normalized("char p[] = \"x ## y\";\nxy\n"));
+    }
+
+    // C11 6.10.3.5, example 7
+    #[test]
+    fn test_standard_variadic_example() {
+        let source = r#"#define debug(...) fprintf(stderr, __VA_ARGS__)
+#define showlist(...) puts(#__VA_ARGS__)
+#define report(test, ...) ((test)?puts(#test): printf(__VA_ARGS__))
+debug("Flag");
+debug("X = %d\n", x);
+showlist(The first, second, and third items.);
+report(x>y, "x is %d but y is %d", x, y);
+"#;
+        let expected = r#"fprintf(stderr, "Flag");
+fprintf(stderr, "X = %d\n", x);
+puts("The first, second, and third items.");
+((x>y)?puts("x>y"): printf("x is %d but y is %d", x, y));
+"#;
+        assert_eq!(expand(source), normalized(expected));
+    }
+
+    #[test]
+    fn test_comma_paste_before_empty_va_args() {
+        let source = "#define log(format, ...) printf(format, ## __VA_ARGS__)\nlog(\"a\"); log(\"b\", 1, 2);";
+        assert_eq!(expand(source), normalized("printf(\"a\"); printf(\"b\", 1, 2);"));
+    }
+
+    #[test]
+    fn test_named_variadic_parameter() {
+        let source = "#define log(format, args...) printf(format, ## args)\n#define call(f, rest...) f(rest)\nlog(\"a\"); log(\"b\", 1, 2); call(g, x, y);";
+        assert_eq!(expand(source), normalized("printf(\"a\"); printf(\"b\", 1, 2); g(x, y);"));
+        assert!(expand_error("#define f(args...) __VA_ARGS__\n")
+            .message
+            .contains("`__VA_ARGS__` can only appear"));
+    }
+
+    #[test]
+    fn test_keywords_can_be_macros() {
+        assert_eq!(expand("#define inline\n#define bool _Bool\ninline bool x;"), "_Bool x ;");
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let source = r#"#define A 2
+#if A == 1
+one
+#elif A == 2
+two
+#  if defined(B) || !defined A
+nested
+#  else
+not_nested
+#  endif
+#else
+other
+#endif
+#ifdef A
+a_defined
+#endif
+#ifndef A
+a_not_defined
+#endif
+"#;
+        assert_eq!(expand(source), "two not_nested a_defined");
+    }
+
+    #[test]
+    fn test_skipped_groups_need_not_be_valid_tokens() {
+        let source = "#if 0\nit's @ not C ` \"unterminated\n#bogus directive\n#if 1\n#error not reached\n#endif\n#else\nkept\n#endif\n";
+        assert_eq!(expand(source), "kept");
+    }
+
+    #[test]
+    fn test_condition_arithmetic() {
+        let cases = [
+            ("1 + 2 * 3 == 7", true),
+            ("(1 + 2) * 3 == 7", false),
+            ("-1 < 0", true),
+            ("-1 > 0u", true),
+            ("0xFFFFFFFFFFFFFFFF == -1", true),
+            ("10 / 3 == 3 && 10 % 3 == 1", true),
+            ("-7 / 2 == -3", true),
+            ("1 << 62 > 0", true),
+            ("~0 == -1", true),
+            ("0 && 1 / 0", true == false),
+            ("1 || 1 / 0", true),
+            ("1 ? 2 : 1 / 0", true),
+            ("0 ? 1 / 0 : 0", false),
+            ("'A' == 65 && '\\377' < 0", true),
+            ("UNDEFINED_NAME == 0 && true == 0", true),
+            ("__STDC_VERSION__ >= 201112L", true),
+            ("defined __STDC_NO_THREADS__ && !defined __STDC_NO_VLA__", true),
+        ];
+        for (expression, expected) in cases {
+            let source = format!("#if {}\nyes\n#else\nno\n#endif\n", expression);
+            assert_eq!(expand(&source), if expected { "yes" } else { "no" }, "{}", expression);
+        }
+    }
+
+    #[test]
+    fn test_defined_through_macros() {
+        let source = "#define FOO\n#define HAVE_FOO defined(FOO) && !defined BAR\n#if HAVE_FOO\nok\n#endif\n";
+        assert_eq!(expand(source), "ok");
+    }
+
+    #[test]
+    fn test_undef_and_redefinition() {
+        assert_eq!(expand("#define X 1\nX\n#undef X\nX\n#define X 2\nX"), "1 X 2");
+    }
+
+    #[test]
+    fn test_builtin_macros() {
+        assert_eq!(expand("__LINE__\n\n__LINE__ __FILE__"), "1 3 \"test.c\"");
+        assert_eq!(expand("#line 100 \"other.c\"\n__LINE__ __FILE__\n__LINE__"), "100 \"other.c\" 101");
+        assert_eq!(expand("#define L __LINE__\n\nL"), "3");
+        let date = expand("__DATE__");
+        assert_eq!(date.len(), "\"Jan  1 2024\"".len(), "{}", date);
+        assert_eq!(expand("__TIME__").len(), "\"12:34:56\"".len());
+    }
+
+    #[test]
+    fn test_platform_macros_describe_the_target() {
+        let platform = |triple: &str| {
+            let mut sources = SourceMap::new();
+            let source = "__x86_64__ __aarch64__ __riscv_xlen __wasm32__ __linux__ _WIN64 __BYTE_ORDER__";
+            let file = sources.add_file(Path::new("test.c"), source.to_string());
+            let target = TargetInfo::new(&create_target_machine_for(triple));
+            let options = CompilerOptions { target: Some(triple.to_string()), ..CompilerOptions::default() };
+            spelled(&preprocess(&mut sources, file, &options, &target, &mut Diagnostics::captured()).expect("preprocessing failed"))
+        };
+        assert_eq!(platform("x86_64-unknown-linux-gnu"), "1 __aarch64__ __riscv_xlen __wasm32__ 1 _WIN64 1234");
+        assert_eq!(platform("aarch64-unknown-linux-gnu"), "__x86_64__ 1 __riscv_xlen __wasm32__ 1 _WIN64 1234");
+        assert_eq!(platform("riscv64-unknown-linux-gnu"), "__x86_64__ __aarch64__ 64 __wasm32__ 1 _WIN64 1234");
+        assert_eq!(platform("wasm32-unknown-unknown"), "__x86_64__ __aarch64__ __riscv_xlen 1 __linux__ _WIN64 1234");
+        assert_eq!(platform("x86_64-pc-windows-msvc"), "1 __aarch64__ __riscv_xlen __wasm32__ __linux__ 1 1234");
+        assert_eq!(platform("powerpc64-unknown-linux-gnu"), "__x86_64__ __aarch64__ __riscv_xlen __wasm32__ 1 _WIN64 4321");
+    }
+
+    #[test]
+    fn test_directives_are_only_recognised_at_line_start() {
+        assert_eq!(expand("#define H #\n#define E H define X 1\nE\n"), "# define X 1");
+        assert_eq!(expand("a # define X"), "a # define X");
+        assert_eq!(expand("#\n# /* null directive */\nok"), "ok");
+    }
+
+    #[test]
+    fn test_pragma_operator_is_removed() {
+        assert_eq!(expand("a _Pragma(\"pack(1)\") b\n#pragma whatever\nc"), "a b c");
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = expand_error("int x;\n#error \"not supported\" here\n");
+        assert_eq!(error.message, "#error \"not supported\" here");
+        assert_eq!((error.span.start.line, error.span.start.column), (2, 2));
+
+        let source = "#warning \"deprecated\"\nint x;\n";
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(Path::new("test.c"), source.to_string());
+        let mut diagnostics = Diagnostics::captured();
+        let tokens = preprocess(&mut sources, file, &CompilerOptions::default(), &test_target(), &mut diagnostics)
+            .expect("a warning is not an error");
+        assert_eq!(spelled(&tokens), "int x ;");
+        assert!(diagnostics.output().starts_with("warning: #warning \"deprecated\"\n --> test.c:1:2\n"));
+        let options = CompilerOptions { warnings_as_errors: true, ..CompilerOptions::default() };
+        assert_eq!(run_with(source, &options).unwrap_err().message, "#warning \"deprecated\"");
+
+        let cases = [
+            ("#if 1\nx\n", "unterminated conditional directive"),
+            ("#if 0\nx\n", "unterminated conditional directive"),
+            ("#endif\n", "#endif without #if"),
+            ("#else\n", "#else without #if"),
+            ("#if 1\n#else\n#else\n#endif\n", "#else after #else"),
+            ("#if 1\n#else\n#elif 1\n#endif\n", "#elif after #else"),
+            ("#if\n#endif\n", "#if with no expression"),
+            ("#if 1 / 0\n#endif\n", "division by zero in preprocessor expression"),
+            ("#if 1.5\n#endif\n", "floating constant in preprocessor expression"),
+            ("#if (1\n#endif\n", "expected value in preprocessor expression"),
+            ("#if 1 2\n#endif\n", "expected end of line in preprocessor expression, found `2`"),
+            ("#frobnicate\n", "invalid preprocessing directive `#frobnicate`"),
+            ("#define\n", "macro name missing in #define"),
+            ("#define 3 x\n", "macro names must be identifiers"),
+            ("#define defined\n", "`defined` cannot be used as a macro name"),
+            ("#define f(x, x) x\n", "duplicate macro parameter `x`"),
+            ("#define f(x x\n", "expected `,` or `)` in macro parameter list, found `x`"),
+            ("#define f(x) #y\n", "`#` is not followed by a macro parameter"),
+            ("#define f(x) ## x\n", "`##` cannot appear at either end of a macro expansion"),
+            ("#define X __VA_ARGS__\n", "`__VA_ARGS__` can only appear in the expansion of a variadic macro"),
+            ("#define f(a, b) a\nf(1)\n", "macro `f` requires 2 arguments, but only 1 given"),
+            ("#define f(a) a\nf(1, 2)\n", "macro `f` passed 2 arguments, but takes just 1"),
+            ("#define f(a, b, ...) a\nf(1)\n", "macro `f` requires at least 2 arguments, but only 1 given"),
+            ("#define f(a) a\nf(1, (2)\n", "unterminated argument list invoking macro `f`"),
+            ("#define cat(a, b) a ## b\ncat(+, /)\n", "pasting formed `+/`, an invalid preprocessing token"),
+            ("#include <definitely/not/a/header.h>\n", "`definitely/not/a/header.h` file not found"),
+            ("#include\n", "#include expects \"FILENAME\" or <FILENAME>"),
+            ("#define __LINE__ 1\n", "cannot redefine built-in macro `__LINE__`"),
+        ];
+        for (source, expected) in cases {
+            let message = run(source, &[]).err().map(|error| error.message);
+            assert_eq!(message.as_deref(), Some(expected), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let include_dir = dir.path().join("include");
+        fs::create_dir(&include_dir).unwrap();
+        fs::write(dir.path().join("local.h"), "local_decl\n#include \"nested.h\"\n").unwrap();
+        fs::write(dir.path().join("nested.h"), "nested_decl __FILE__\n").unwrap();
+        fs::write(include_dir.join("lib-64.h"), "#define LIB_VALUE 7\nlib_decl\n").unwrap();
+        fs::write(
+            dir.path().join("main.c"),
+            "#include \"local.h\"\n#include <lib-64.h>\n#define HEADER \"nested.h\"\n#include HEADER\nint x = LIB_VALUE;\n",
+        )
+        .unwrap();
+
+        let mut sources = SourceMap::new();
+        let main = dir.path().join("main.c");
+        let file = sources.add_file(&main, fs::read_to_string(&main).unwrap());
+        let tokens = preprocess(&mut sources, file, &options_with(vec![include_dir]), &test_target(), &mut Diagnostics::captured()).unwrap();
+        let nested = dir.path().join("nested.h");
+        let nested_name = quote(&nested.display().to_string());
+        assert_eq!(
+            spelled(&tokens),
+            format!(
+                "local_decl nested_decl {} lib_decl nested_decl {} int x = 7 ;",
+                nested_name, nested_name
+            )
+        );
+
+        let nested_token = tokens.iter().find(|token| token.text == "nested_decl").unwrap();
+        assert_eq!(sources.path(nested_token.span.file), nested);
+    }
+
+    #[test]
+    fn test_bundled_headers_come_after_the_search_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("iso646.h"), "wrapped\n#include_next <iso646.h>\n").unwrap();
+        let tokens = run(
+            "#include <iso646.h>\n#include <stdnoreturn.h>\nnoreturn int x = a and not b;\n",
+            &[dir.path().to_path_buf()],
+        )
+        .unwrap();
+        assert_eq!(spelled(&tokens), "wrapped _Noreturn int x = a && ! b ;");
+
+        let error = run("#include <no-such-header.h>\n", &[]).unwrap_err();
+        assert_eq!(error.message, "`no-such-header.h` file not found");
+    }
+
+    #[test]
+    fn test_include_guards_and_pragma_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("guarded.h"),
+            "// comment before the guard\n#ifndef GUARDED_H\n#define GUARDED_H\nguarded\n#endif\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("once.h"), "#pragma once\nonce\n").unwrap();
+        fs::write(dir.path().join("plain.h"), "plain\n").unwrap();
+        fs::write(
+            dir.path().join("main.c"),
+            "#include \"guarded.h\"\n#include \"guarded.h\"\n#include \"once.h\"\n#include \"once.h\"\n#include \"plain.h\"\n#include \"plain.h\"\n#undef GUARDED_H\n#include \"guarded.h\"\n",
+        )
+        .unwrap();
+
+        let mut sources = SourceMap::new();
+        let main = dir.path().join("main.c");
+        let file = sources.add_file(&main, fs::read_to_string(&main).unwrap());
+        let tokens = preprocess(&mut sources, file, &CompilerOptions::default(), &test_target(), &mut Diagnostics::captured()).unwrap();
+        assert_eq!(spelled(&tokens), "guarded once plain plain guarded");
+    }
+
+    #[test]
+    fn test_include_errors_point_into_the_included_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("broken.h"), "ok\n#if 1\n").unwrap();
+        fs::write(dir.path().join("main.c"), "#include \"broken.h\"\n").unwrap();
+
+        let mut sources = SourceMap::new();
+        let main = dir.path().join("main.c");
+        let file = sources.add_file(&main, fs::read_to_string(&main).unwrap());
+        let error = preprocess(&mut sources, file, &CompilerOptions::default(), &test_target(), &mut Diagnostics::captured()).unwrap_err();
+        assert_eq!(error.message, "unterminated conditional directive");
+        assert_eq!(sources.path(error.span.file), dir.path().join("broken.h"));
+        assert_eq!(error.span.start.line, 2);
+    }
+
+    #[test]
+    fn test_expanded_tokens_point_at_the_invocation() {
+        let tokens = run("#define TWICE(x) (x + x)\n\nint y = TWICE(value);", &[]).unwrap();
+        let plus = tokens.iter().find(|token| token.text == "+").unwrap();
+        assert_eq!((plus.span.start.line, plus.span.start.column), (3, 9));
+        let value = tokens.iter().find(|token| token.text == "value").unwrap();
+        assert_eq!((value.span.start.line, value.span.start.column), (3, 15));
+    }
+
+    #[test]
+    fn test_expansions_are_recorded() {
+        let source = "#define ONE 1\n#define TWO (ONE + ONE)\nint y = TWO;\n";
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(Path::new("test.c"), source.to_string());
+        let tokens =
+            preprocess(&mut sources, file, &CompilerOptions::default(), &test_target(), &mut Diagnostics::captured())
+                .unwrap();
+
+        // The `1` of `ONE` came through the second `ONE` in `TWO`, the
+        // innermost expansion first
+        let one = tokens.iter().filter(|token| token.text == "1").nth(1).unwrap();
+        assert_eq!((one.span.start.line, one.span.start.column), (3, 9));
+        let mut spellings = Vec::new();
+        let mut expansion = one.span.expansion;
+        while let Some(id) = expansion {
+            let macro_expansion = sources.expansion(id);
+            let spelling = macro_expansion.spelling.start;
+            spellings.push((macro_expansion.name.as_str(), spelling.line, spelling.column));
+            expansion = macro_expansion.parent;
+        }
+        assert_eq!(spellings, vec![("ONE", 1, 13), ("TWO", 2, 20)]);
+        let plus = tokens.iter().find(|token| token.text == "+").unwrap();
+        assert_eq!(sources.expansion(plus.span.expansion.unwrap()).spelling.start.column, 18);
+        let y = tokens.iter().find(|token| token.text == "y").unwrap();
+        assert_eq!(y.span.expansion, None);
+
+        let error = expand_error("#define f(a) a\nf(1, 2)\n");
+        assert_eq!(error.labels[0].message, "macro defined here");
+        assert_eq!((error.labels[0].span.start.line, error.labels[0].span.start.column), (1, 9));
+    }
+}
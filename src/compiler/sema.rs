@@ -0,0 +1,3290 @@
+//! Semantic analysis: name resolution and type checking.
+//!
+//! The analyzer walks the AST with a stack of scopes, checks declarations,
+//! statements and expressions against C's typing rules and stores the type of
+//! every expression in `Expression::expr_type`. Implicit conversions are made
+//! explicit as `ExpressionKind::ImplicitCast` nodes, so code generation can
+//! lower the tree without deriving any types itself.
+
+use log::debug;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::constant::{Constant, Evaluator, Integer, NotConstant};
+use super::lexer::{Encoding, FloatSuffix, IntSuffix};
+use super::parser::{
+    BinaryOperator, Block, Designator, Expression, ExpressionKind, Field, Function, Initializer, InitializerItem,
+    Literal, Statement, StatementKind, StorageClass, Struct, Type, UnaryOperator, Variable, AST,
+};
+use super::source::{CompileError, Span};
+use super::target::TargetInfo;
+
+/// Resolve names and compute the type of every expression in `ast`. Analysis
+/// carries on past a declaration or statement in error, so every error found
+/// is returned, in source order.
+pub fn analyze(ast: &mut AST, target: &TargetInfo) -> Result<(), Vec<CompileError>> {
+    debug!(
+        "Analyzing {} functions and {} global variables",
+        ast.functions.len(),
+        ast.global_variables.len()
+    );
+
+    let mut analyzer = Analyzer::new(&ast.structs, target);
+
+    let mut structs: Vec<&Struct> = ast.structs.values().collect();
+    structs.sort_by_key(|definition| (definition.span.file.0, definition.span.start));
+    for definition in structs {
+        let result = analyzer.check_fields(definition);
+        analyzer.recover(result);
+    }
+
+    // Every function is visible from every body, whatever the source order
+    let mut functions: Vec<&mut Function> = ast.functions.values_mut().collect();
+    functions.sort_by_key(|function| (function.span.file.0, function.span.start));
+    for function in &functions {
+        let result = analyzer.declare_function(function);
+        analyzer.recover(result);
+    }
+
+    let mut initialized = HashMap::new();
+    for (variable, initializer) in ast.global_variables.iter_mut() {
+        let result = analyzer.global_variable(variable, initializer.as_mut(), &mut initialized);
+        analyzer.recover(result);
+    }
+
+    // Arrays in parameter types may be sized by earlier parameters or by
+    // file-scope variables
+    for function in &mut functions {
+        let result = analyzer.parameter_lengths(function);
+        analyzer.recover(result);
+        // Unless the name was taken by an object that the function conflicts with
+        let symbol = analyzer.scopes[0].get_mut(&function.name);
+        if let Some(symbol @ Symbol { symbol_type: Type::Function(..), .. }) = symbol {
+            symbol.symbol_type = function_type(function);
+        }
+    }
+
+    for function in functions {
+        if function.body.is_some() {
+            let result = analyzer.function(function);
+            analyzer.recover(result);
+        }
+    }
+
+    if analyzer.errors.is_empty() {
+        return Ok(());
+    }
+    analyzer.errors.sort_by_key(|error| (error.span.file.0, error.span.start));
+    Err(analyzer.errors)
+}
+
+/// Evaluate an integer constant expression the parser needs the value of
+/// before the translation unit is analyzed: an enumerator, a bit-field
+/// width, an array length, a designator or a static assertion. `objects`
+/// gives the declared types of the objects and functions it names.
+pub fn integer_constant_expression(
+    expression: &Expression,
+    objects: HashMap<String, Type>,
+    structs: &HashMap<String, Struct>,
+    target: &TargetInfo,
+) -> Result<Result<Integer, NotConstant>, CompileError> {
+    let mut analyzer = Analyzer::new(structs, target);
+    analyzer.scopes[0] = objects
+        .into_iter()
+        .map(|(name, symbol_type)| {
+            // The expression cannot redeclare them, so where they were
+            // declared does not matter
+            let symbol = Symbol {
+                symbol_type,
+                storage: None,
+                is_thread_local: false,
+                span: Span::default(),
+            };
+            (name, symbol)
+        })
+        .collect();
+    let mut expression = expression.clone();
+    analyzer.rvalue(&mut expression)?;
+    Ok(analyzer.integer_constant(&expression))
+}
+
+/// Something a name in scope refers to
+#[derive(Debug, Clone)]
+struct Symbol {
+    /// The type including the qualifiers of the object
+    symbol_type: Type,
+    storage: Option<StorageClass>,
+    is_thread_local: bool,
+    /// The declaration that brought the name into scope
+    span: Span,
+}
+
+impl Symbol {
+    fn variable(variable: &Variable) -> Self {
+        Symbol {
+            symbol_type: variable.qualified_type(),
+            storage: variable.storage,
+            is_thread_local: variable.is_thread_local,
+            span: variable.span,
+        }
+    }
+}
+
+/// How a value is being converted to another type as if by assignment
+#[derive(Debug, Clone, Copy)]
+enum Conversion {
+    Assignment,
+    Initialization,
+    Argument,
+    Return,
+}
+
+/// Part of an initializer list being resolved, by element or member position
+enum Slot {
+    Initializer(Initializer),
+    /// A subaggregate whose elements are initialized one by one, through
+    /// brace elision or designators
+    Elements(BTreeMap<usize, Slot>),
+}
+
+/// A `switch` statement whose body is being analyzed
+struct Switch {
+    /// The promoted type of the controlling expression
+    controlling_type: Type,
+    /// Case values converted to the controlling type, and their labels
+    cases: HashMap<i64, Span>,
+    default: Option<Span>,
+    /// How many variably modified objects were in scope at the `switch`
+    variably_modified: usize,
+}
+
+struct Analyzer<'a> {
+    structs: &'a HashMap<String, Struct>,
+    target: &'a TargetInfo,
+    /// Innermost scope last; the first scope holds the file-scope names
+    scopes: Vec<HashMap<String, Symbol>>,
+    /// Name and return type of the function being analyzed
+    function: Option<(String, Type)>,
+    /// Number of loops around the current statement
+    loops: usize,
+    /// Switch statements around the current statement, innermost last
+    switches: Vec<Switch>,
+    /// Labels defined in the current function, with the variably modified
+    /// objects in scope there
+    labels: HashMap<String, (Span, Vec<VariablyModified>)>,
+    /// Targets of the `goto` statements in the current function, with the
+    /// variably modified objects in scope there; checked once the whole body
+    /// has been seen
+    gotos: Vec<(String, Span, Vec<VariablyModified>)>,
+    /// Variably modified objects in scope, outermost first. A jump must not
+    /// enter the scope of one (C11 6.8.6.1p1, 6.8.4.2p2).
+    variably_modified: Vec<VariablyModified>,
+    /// The analyzed type of each variable length array, by
+    /// `VariableLength::id`; the uses of a typedef name take theirs from here
+    analyzed_lengths: HashMap<usize, Type>,
+    /// Errors in the declarations and statements analyzed so far
+    errors: Vec<CompileError>,
+}
+
+/// The declaration of an object whose size is only known at run time
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VariablyModified {
+    span: Span,
+    is_array: bool,
+}
+
+impl VariablyModified {
+    /// Point a jump into the scope of the object at its declaration
+    fn bypassed_by(self, jump: CompileError) -> CompileError {
+        let what = if self.is_array { "variable length array" } else { "variably modified type" };
+        jump.with_label(self.span, format!("jump bypasses initialization of {}", what))
+    }
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(structs: &'a HashMap<String, Struct>, target: &'a TargetInfo) -> Self {
+        Analyzer {
+            structs,
+            target,
+            scopes: vec![HashMap::new()],
+            function: None,
+            loops: 0,
+            switches: Vec::new(),
+            labels: HashMap::new(),
+            gotos: Vec::new(),
+            variably_modified: Vec::new(),
+            analyzed_lengths: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Record the error of a declaration or statement and carry on with the
+    /// next one
+    fn recover(&mut self, result: Result<(), CompileError>) {
+        if let Err(error) = result {
+            self.errors.push(error);
+        }
+    }
+
+    // Declarations
+
+    /// Make a function visible from every body
+    fn declare_function(&mut self, function: &Function) -> Result<(), CompileError> {
+        if function.name == "main" && function.is_inline {
+            return Err(CompileError::new("`main` is not allowed to be declared inline", function.span));
+        }
+        let symbol = Symbol {
+            symbol_type: function_type(function),
+            storage: function.storage,
+            is_thread_local: false,
+            span: function.span,
+        };
+        self.declare(&function.name, symbol, function.span)
+    }
+
+    /// Declare a file-scope variable and fold its initializer; `initialized`
+    /// holds the variables defined with an initializer so far
+    fn global_variable(
+        &mut self,
+        variable: &mut Variable,
+        initializer: Option<&mut Initializer>,
+        initialized: &mut HashMap<String, Span>,
+    ) -> Result<(), CompileError> {
+        self.object_lengths(variable)?;
+        self.check_alignment(variable.alignment, &variable.var_type, variable.span)?;
+        self.declare_global(variable)?;
+        let Some(initializer) = initializer else {
+            return Ok(());
+        };
+        if let Some(previous) = initialized.insert(variable.name.clone(), variable.span) {
+            return Err(CompileError::new(format!("redefinition of `{}`", variable.name), variable.span)
+                .with_label(previous, "previous definition is here"));
+        }
+        self.initializer(variable, initializer)?;
+        self.constant_initializer(initializer)?;
+        self.scopes[0].get_mut(&variable.name).unwrap().symbol_type = variable.qualified_type();
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Add a name to the innermost scope, which must not declare it already
+    fn declare(&mut self, name: &str, symbol: Symbol, span: Span) -> Result<(), CompileError> {
+        let scope = self.scopes.last_mut().unwrap();
+        if let Some(previous) = scope.get(name) {
+            return Err(CompileError::new(format!("redefinition of `{}`", name), span)
+                .with_label(previous.span, "previous definition is here"));
+        }
+        scope.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    /// File-scope variables may be declared repeatedly as long as the types
+    /// and linkage agree
+    fn declare_global(&mut self, variable: &Variable) -> Result<(), CompileError> {
+        if variable.storage != Some(StorageClass::Extern) {
+            self.check_complete(&variable.var_type, variable.span, true)?;
+        }
+        let existing = match self.scopes[0].get(&variable.name) {
+            Some(existing) => existing,
+            None => return self.declare(&variable.name, Symbol::variable(variable), variable.span),
+        };
+        check_redeclaration(existing, variable)?;
+
+        let is_static = variable.storage == Some(StorageClass::Static);
+        let was_static = existing.storage == Some(StorageClass::Static);
+        let (declaration, previous) = if is_static && !was_static {
+            ("static", "non-static")
+        } else if was_static && variable.storage.is_none() {
+            // `extern` takes the linkage of the earlier declaration instead
+            ("non-static", "static")
+        } else if variable.is_thread_local != existing.is_thread_local && variable.is_thread_local {
+            ("thread-local", "non-thread-local")
+        } else if variable.is_thread_local != existing.is_thread_local {
+            ("non-thread-local", "thread-local")
+        } else {
+            return Ok(());
+        };
+        Err(CompileError::new(
+            format!(
+                "{} declaration of `{}` follows {} declaration",
+                declaration, variable.name, previous
+            ),
+            variable.span,
+        )
+        .with_label(existing.span, "previous declaration is here"))
+    }
+
+    /// Declare a variable at block scope; `extern` declarations refer to the
+    /// file-scope object of the same name
+    fn declare_local(&mut self, variable: &Variable, initializer: Option<&Initializer>) -> Result<(), CompileError> {
+        if variable.storage == Some(StorageClass::Extern) {
+            if let Some(initializer) = initializer {
+                return Err(CompileError::new(
+                    "declaration of block scope identifier with linkage cannot have an initializer",
+                    initializer.span(),
+                ));
+            }
+            if let Some(global) = self.scopes[0].get(&variable.name) {
+                check_redeclaration(global, variable)?;
+            }
+        } else {
+            self.check_complete(&variable.var_type, variable.span, initializer.is_some())?;
+        }
+        self.declare(&variable.name, Symbol::variable(variable), variable.span)
+    }
+
+    /// Check that an object of type `object_type` can be defined; arrays of
+    /// unknown size are only accepted where `allow_unsized` is set
+    fn check_complete(&self, object_type: &Type, span: Span, allow_unsized: bool) -> Result<(), CompileError> {
+        match object_type {
+            Type::Void => Err(CompileError::new("variable has incomplete type `void`", span)),
+            Type::Struct(name) | Type::Union(name) if !self.structs.contains_key(name) => Err(CompileError::new(
+                format!("variable has incomplete type `{}`", object_type),
+                span,
+            )),
+            Type::Array(_, None) if !allow_unsized => Err(CompileError::new(
+                "definition of variable with array type needs an explicit size or an initializer",
+                span,
+            )),
+            Type::Array(element, _) | Type::VariableArray(element, _) => match element.unqualified() {
+                Type::Void | Type::Function(..) | Type::Array(_, None) => Err(CompileError::new(
+                    format!("array has incomplete element type `{}`", element),
+                    span,
+                )),
+                Type::Struct(name) | Type::Union(name) if !self.structs.contains_key(name) => Err(CompileError::new(
+                    format!("array has incomplete element type `{}`", element),
+                    span,
+                )),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the arrays and records a type is built from are no larger
+    /// than the target allows
+    fn check_size(&self, object_type: &Type, span: Span) -> Result<(), CompileError> {
+        match object_type {
+            Type::Pointer(inner) | Type::Qualified(inner, _) | Type::VariableArray(inner, _) | Type::Array(inner, None) => {
+                self.check_size(inner, span)
+            }
+            Type::Array(element, Some(_)) => {
+                self.check_size(element, span)?;
+                let complete = self.target.layout(element, self.structs).is_some();
+                if complete && self.target.layout(object_type, self.structs).is_none() {
+                    return Err(CompileError::new("size of array is too large", span));
+                }
+                Ok(())
+            }
+            Type::Struct(name) | Type::Union(name) => {
+                // A record that contains itself is reported with its definition
+                let Some(definition) = self.structs.get(name) else {
+                    return Ok(());
+                };
+                if self.contains_itself(object_type, &mut Vec::new()) {
+                    return Ok(());
+                }
+                // Members that are incomplete are reported with the
+                // definition, and only make the record incomplete
+                let complete = definition.fields.iter().all(|field| {
+                    let field_type = match &field.var_type {
+                        Type::Array(element, None) => element,
+                        field_type => field_type,
+                    };
+                    self.target.layout(field_type, self.structs).is_some()
+                        || self.check_size(field_type, field.span).is_err()
+                });
+                if complete && self.target.record_layout(definition, self.structs).is_none() {
+                    return Err(CompileError::new(format!("size of `{}` is too large", object_type), span));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that `_Alignas` does not ask for an object to be aligned less
+    /// strictly than its type
+    fn check_alignment(&self, alignment: Option<u64>, object_type: &Type, span: Span) -> Result<(), CompileError> {
+        let (Some(alignment), Some(layout)) = (alignment, self.target.layout(object_type, self.structs)) else {
+            return Ok(());
+        };
+        if alignment < layout.align {
+            return Err(CompileError::new(
+                format!(
+                    "requested alignment is less than minimum alignment of {} for type `{}`",
+                    layout.align, object_type
+                ),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether a record in a type contains itself, directly or through its
+    /// members; `records` holds the records the type is a member of
+    fn contains_itself(&self, object_type: &Type, records: &mut Vec<String>) -> bool {
+        match object_type {
+            Type::Array(element, _) | Type::Qualified(element, _) => self.contains_itself(element, records),
+            Type::Struct(name) | Type::Union(name) => {
+                if records.contains(name) {
+                    return true;
+                }
+                let Some(definition) = self.structs.get(name) else {
+                    return false;
+                };
+                records.push(name.clone());
+                let contains = definition.fields.iter().any(|field| self.contains_itself(&field.var_type, records));
+                records.pop();
+                contains
+            }
+            _ => false,
+        }
+    }
+
+    /// Analyze the lengths of the variable length arrays in a type, turning
+    /// those that turn out to be constant into arrays of fixed size
+    fn variable_lengths(&mut self, object_type: &mut Type) -> Result<(), CompileError> {
+        let (element, id, length) = match object_type {
+            Type::Pointer(inner) | Type::Array(inner, _) | Type::Qualified(inner, _) => {
+                return self.variable_lengths(inner)
+            }
+            // The length was analyzed where the typedef was declared
+            Type::VariableArray(_, length) if length.typedef => {
+                if let Some(analyzed) = self.analyzed_lengths.get(&length.id) {
+                    *object_type = analyzed.clone();
+                }
+                return Ok(());
+            }
+            Type::VariableArray(element, length) => (element, length.id, &mut length.length),
+            _ => return Ok(()),
+        };
+        self.rvalue(length)?;
+        if !length.ty().is_integer() {
+            return Err(CompileError::new(
+                format!("size of array has non-integer type `{}`", length.ty()),
+                length.span,
+            ));
+        }
+        let constant = match self.integer_constant(length) {
+            Ok(constant) => Some(constant),
+            Err(overflow) if overflow.overflowed => return Err(overflow.error("size of array")),
+            Err(_) => None,
+        };
+        if constant.is_none() {
+            implicit_cast(length, self.target.size_type());
+        }
+        self.variable_lengths(element)?;
+        if let Some(size) = constant {
+            if size.is_negative() {
+                return Err(CompileError::new("array size is negative", length.span));
+            }
+            let size = usize::try_from(size.value()).map_err(|_| CompileError::new("array is too large", length.span))?;
+            let element = element.clone();
+            *object_type = Type::Array(element, Some(size));
+        }
+        let mut analyzed = object_type.clone();
+        if let Type::VariableArray(_, length) = &mut analyzed {
+            length.typedef = true;
+        }
+        self.analyzed_lengths.insert(id, analyzed);
+        Ok(())
+    }
+
+    /// Analyze the array lengths in the type of a declared object, which may
+    /// only be variably modified at block scope and without linkage or
+    /// static storage
+    fn object_lengths(&mut self, variable: &mut Variable) -> Result<(), CompileError> {
+        self.variable_lengths(&mut variable.var_type)?;
+        self.check_size(&variable.var_type, variable.span)?;
+        if !variable.var_type.is_variably_modified() {
+            return Ok(());
+        }
+        let message = match variable.storage {
+            _ if self.function.is_none() => "variable length array declaration not allowed at file scope",
+            Some(StorageClass::Static) => "variable length array declaration cannot have `static` storage duration",
+            Some(StorageClass::Extern) => "variable length array declaration cannot have `extern` linkage",
+            _ => return Ok(()),
+        };
+        Err(CompileError::new(message, variable.span))
+    }
+
+    /// Analyze the array lengths in the parameter types of a function, which
+    /// may refer to the parameters before them
+    fn parameter_lengths(&mut self, function: &mut Function) -> Result<(), CompileError> {
+        self.scopes.push(HashMap::new());
+        let result = function.parameters.iter_mut().try_for_each(|parameter| {
+            self.variable_lengths(&mut parameter.var_type)?;
+            self.check_size(&parameter.var_type, parameter.span)?;
+            let scope = self.scopes.last_mut().unwrap();
+            scope.insert(parameter.name.clone(), Symbol::variable(parameter));
+            Ok(())
+        });
+        self.scopes.pop();
+        result
+    }
+
+    fn check_fields(&self, definition: &Struct) -> Result<(), CompileError> {
+        for (index, field) in definition.fields.iter().enumerate() {
+            match field.var_type {
+                Type::Function(..) => {
+                    return Err(CompileError::new(
+                        format!("field `{}` declared as a function", field.name),
+                        field.span,
+                    ))
+                }
+                ref field_type if self.contains_itself(field_type, &mut vec![definition.name.clone()]) => {
+                    return Err(CompileError::new(
+                        format!("field has incomplete type `{}`", field.var_type),
+                        field.span,
+                    ))
+                }
+                ref field_type if field_type.is_variably_modified() => {
+                    return Err(CompileError::new("fields must have a constant size", field.span))
+                }
+                Type::Array(_, None) if definition.is_union => {
+                    return Err(CompileError::new(
+                        format!("flexible array member `{}` in a union is not allowed", field.name),
+                        field.span,
+                    ))
+                }
+                Type::Array(_, None) if index + 1 < definition.fields.len() => {
+                    return Err(CompileError::new(
+                        format!("flexible array member `{}` is not at the end of the struct", field.name),
+                        field.span,
+                    ))
+                }
+                _ => {}
+            }
+            self.check_complete(&field.var_type, field.span, true)
+                .map_err(|err| CompileError::new(err.message.replacen("variable", "field", 1), err.span))?;
+            self.check_size(&field.var_type, field.span)?;
+            self.check_alignment(field.alignment, &field.var_type, field.span)?;
+            if let Some(width) = field.bit_width {
+                self.check_bit_field(field, width)?;
+            }
+        }
+        let record_type = if definition.is_union {
+            Type::Union(definition.name.clone())
+        } else {
+            Type::Struct(definition.name.clone())
+        };
+        self.check_size(&record_type, definition.span)
+    }
+
+    fn check_bit_field(&self, field: &Field, width: u32) -> Result<(), CompileError> {
+        let description = if field.name.is_empty() {
+            "anonymous bit-field".to_string()
+        } else {
+            format!("bit-field `{}`", field.name)
+        };
+        if !field.var_type.is_integer() {
+            return Err(CompileError::new(
+                format!("{} has non-integral type `{}`", description, field.var_type),
+                field.span,
+            ));
+        }
+        let type_width = self.target.integer_width(&field.var_type);
+        if width > type_width {
+            return Err(CompileError::new(
+                format!(
+                    "width of {} ({} bits) exceeds the width of its type ({} bits)",
+                    description, width, type_width
+                ),
+                field.span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, function: &mut Function) -> Result<(), CompileError> {
+        if matches!(function.return_type, Type::Array(..) | Type::VariableArray(..) | Type::Function(..)) {
+            return Err(CompileError::new(
+                format!("function cannot return `{}`", function.return_type),
+                function.span,
+            ));
+        }
+
+        // Parameters share the scope of the outermost block of the body
+        self.scopes.push(HashMap::new());
+        self.function = Some((function.name.clone(), function.return_type.clone()));
+        let result = self.function_body(function);
+        self.function = None;
+        self.scopes.pop();
+        result
+    }
+
+    fn function_body(&mut self, function: &mut Function) -> Result<(), CompileError> {
+        for parameter in &function.parameters {
+            if parameter.name.is_empty() {
+                return Err(CompileError::new("parameter name omitted", parameter.span));
+            }
+            self.check_complete(&parameter.var_type, parameter.span, false)?;
+            self.declare(&parameter.name, Symbol::variable(parameter), parameter.span)?;
+        }
+        let body = function.body.as_mut().expect("function has a body");
+        self.labels.clear();
+        self.gotos.clear();
+        self.variably_modified.clear();
+        self.statements(&mut body.statements);
+        for (label, span, in_scope) in &self.gotos {
+            let Some((_, at_label)) = self.labels.get(label) else {
+                self.errors.push(CompileError::new(format!("use of undeclared label `{}`", label), *span));
+                continue;
+            };
+            if let Some(bypassed) = at_label.iter().find(|object| !in_scope.contains(object)) {
+                let jump = CompileError::new("cannot jump from this goto statement to its label", *span);
+                self.errors.push(bypassed.bypassed_by(jump));
+            }
+        }
+        Ok(())
+    }
+
+    /// Analyze the initializer of a variable, completing an array type of
+    /// unknown size from it
+    fn initializer(&mut self, variable: &mut Variable, initializer: &mut Initializer) -> Result<(), CompileError> {
+        self.initialize(&mut variable.var_type, initializer)
+    }
+
+    /// Fold the analyzed initializer of an object with static storage
+    /// duration to constants; pointers may instead hold the address of a
+    /// static object or function, plus or minus a constant
+    fn constant_initializer(&self, initializer: &mut Initializer) -> Result<(), CompileError> {
+        let value = match initializer {
+            Initializer::Expression(value) => value,
+            Initializer::List(items, _) => {
+                return items.iter_mut().try_for_each(|item| self.constant_initializer(&mut item.initializer))
+            }
+        };
+        // A string literal initializing a character array
+        if let Type::Array(..) = value.ty() {
+            return Ok(());
+        }
+        let constant = self
+            .evaluate(value)
+            .map_err(|not_constant| {
+                let overflowed = not_constant.overflowed;
+                let error = not_constant.error("initializer element is not a compile-time constant");
+                if overflowed {
+                    return error;
+                }
+                error.with_note("objects with static storage duration are initialized before the program runs")
+            })?;
+        value.kind = match constant {
+            Constant::Integer(value) => ExpressionKind::Literal(Literal::Int(value.value())),
+            Constant::Float(constant) => {
+                let (suffix, digits) = match value.ty().unqualified() {
+                    Type::Float => (FloatSuffix::Float, None),
+                    Type::LongDouble => (FloatSuffix::Long, long_double_digits(value)),
+                    _ => (FloatSuffix::None, None),
+                };
+                ExpressionKind::Literal(Literal::Float { value: constant, suffix, digits })
+            }
+            Constant::Address => return Ok(()),
+        };
+        Ok(())
+    }
+
+    /// Evaluate an analyzed constant expression
+    fn evaluate(&self, expression: &Expression) -> Result<Constant, NotConstant> {
+        let is_static = |name: &str| self.is_static_variable(name);
+        Evaluator::new(self.target, &is_static, self.function.is_none()).evaluate(expression)
+    }
+
+    /// Evaluate an analyzed integer constant expression
+    fn integer_constant(&self, expression: &Expression) -> Result<Integer, NotConstant> {
+        let is_static = |name: &str| self.is_static_variable(name);
+        Evaluator::new(self.target, &is_static, self.function.is_none()).integer(expression)
+    }
+
+    /// Whether a variable names a function or an object with static storage
+    /// duration
+    fn is_static_variable(&self, name: &str) -> bool {
+        match self.scopes.iter().rposition(|scope| scope.contains_key(name)) {
+            Some(0) => true,
+            Some(scope) => {
+                let symbol = &self.scopes[scope][name];
+                matches!(symbol.symbol_type, Type::Function(..))
+                    || matches!(symbol.storage, Some(StorageClass::Static | StorageClass::Extern))
+            }
+            None => false,
+        }
+    }
+
+    /// An integer constant expression with value zero, optionally cast to
+    /// `void *`
+    fn is_null_pointer_constant(&self, expression: &Expression) -> bool {
+        match &expression.kind {
+            ExpressionKind::Literal(Literal::Null) => true,
+            ExpressionKind::Cast(operand, Type::Pointer(pointee)) if *pointee.unqualified() == Type::Void => {
+                operand.ty().is_integer() && self.is_null_pointer_constant(operand)
+            }
+            _ => expression.ty().is_integer() && self.integer_constant(expression).is_ok_and(|value| value.bits == 0),
+        }
+    }
+
+    /// Analyze an initializer for an object of type `object_type`, completing
+    /// it if it is an array of unknown size
+    fn initialize(&mut self, object_type: &mut Type, initializer: &mut Initializer) -> Result<(), CompileError> {
+        let (items, span) = match initializer {
+            Initializer::Expression(value) => return self.initialize_expression(object_type, value),
+            Initializer::List(items, span) => (items, *span),
+        };
+
+        // A scalar, or a character array from a string literal, may be in braces
+        let unbraced = match (object_type.unqualified(), items.as_slice()) {
+            (Type::Array(element, _), [item])
+                if item.designators.is_empty()
+                    && matches!(&item.initializer, Initializer::Expression(value)
+                        if self.initializes_array(element, value)) =>
+            {
+                true
+            }
+            _ if object_type.is_aggregate() => false,
+            (_, []) => return Err(CompileError::new("scalar initializer cannot be empty", span)),
+            (_, [item]) if item.designators.is_empty() => true,
+            (_, [item]) => {
+                return Err(CompileError::new(
+                    format!("designator in initializer for scalar type `{}`", object_type),
+                    item.initializer.span(),
+                ))
+            }
+            (_, [_, excess, ..]) => {
+                return Err(CompileError::new("excess elements in scalar initializer", excess.initializer.span()))
+            }
+        };
+        if unbraced {
+            *initializer = items.remove(0).initializer;
+            return self.initialize(object_type, initializer);
+        }
+
+        let mut queue: VecDeque<InitializerItem> = std::mem::take(items).into();
+        let mut slots = BTreeMap::new();
+        let length = self.fill(object_type, &mut queue, &mut slots, true, false)?;
+        if let Some(excess) = queue.front() {
+            let kind = match object_type.unqualified() {
+                Type::Array(..) => "array",
+                Type::Union(_) => "union",
+                _ => "struct",
+            };
+            return Err(CompileError::new(
+                format!("excess elements in {} initializer", kind),
+                excess.initializer.span(),
+            ));
+        }
+        if let Type::Array(element, None) = object_type {
+            *object_type = Type::Array(element.clone(), Some(length));
+        }
+        *initializer = self.assemble(object_type, slots, span);
+        Ok(())
+    }
+
+    /// Initialize the elements or members of the aggregate `object_type` from
+    /// the front of `items`, recording what initializes each position in
+    /// `slots`. Designators refer to the innermost braced list, so an aggregate
+    /// whose braces were elided stops at one, unless it is `designated`: the
+    /// target of the designators remaining on the front item. Returns the
+    /// number of positions covered, which is the length of an array.
+    fn fill(
+        &mut self,
+        object_type: &Type,
+        items: &mut VecDeque<InitializerItem>,
+        slots: &mut BTreeMap<usize, Slot>,
+        braced: bool,
+        mut designated: bool,
+    ) -> Result<usize, CompileError> {
+        let (capacity, definition) = match object_type.unqualified() {
+            Type::Array(_, size) => (*size, None),
+            Type::Struct(name) | Type::Union(name) => {
+                let definition = &self.structs[name];
+                (Some(definition.fields.len()), Some(definition))
+            }
+            _ => unreachable!("not an aggregate"),
+        };
+        // Unnamed bit-fields are skipped
+        let skip_unnamed = |position: usize| match definition {
+            Some(definition) => (position..definition.fields.len())
+                .find(|&position| !definition.fields[position].name.is_empty())
+                .unwrap_or(definition.fields.len()),
+            None => position,
+        };
+
+        let mut next = skip_unnamed(0);
+        let mut length = 0;
+        while let Some(item) = items.front_mut() {
+            if !item.designators.is_empty() {
+                if !braced && !designated {
+                    break;
+                }
+                designated = false;
+                let designator = item.designators.remove(0);
+                next = self.designate(object_type, designator, item)?;
+            } else if capacity.is_some_and(|capacity| next >= capacity) {
+                break;
+            }
+            let span = item.initializer.span();
+            if definition.is_some_and(|definition| matches!(definition.fields[next].var_type, Type::Array(_, None))) {
+                return Err(CompileError::new("initialization of flexible array member is not allowed", span));
+            }
+
+            let element_type = self.element_type(object_type, next);
+            if !item.designators.is_empty() {
+                // The designation goes on into a subaggregate
+                if !element_type.is_aggregate() {
+                    let designator = item.designators.remove(0);
+                    self.designate(&element_type, designator, item)?;
+                }
+                let mut elements = self.reopen(&element_type, slots.remove(&next));
+                self.fill(&element_type, items, &mut elements, false, true)?;
+                slots.insert(next, Slot::Elements(elements));
+            } else {
+                self.fill_element(&element_type, items, slots, next)?;
+            }
+
+            if definition.is_some_and(|definition| definition.is_union) {
+                // Only the last member initialized in a union is kept
+                slots.retain(|position, _| *position == next);
+                next = capacity.unwrap();
+            } else {
+                next = skip_unnamed(next + 1);
+            }
+            length = length.max(next);
+        }
+        Ok(length)
+    }
+
+    /// Initialize one element or member from the front of `items`
+    fn fill_element(
+        &mut self,
+        element_type: &Type,
+        items: &mut VecDeque<InitializerItem>,
+        slots: &mut BTreeMap<usize, Slot>,
+        position: usize,
+    ) -> Result<(), CompileError> {
+        let whole = match &items.front().unwrap().initializer {
+            Initializer::List(..) => true,
+            Initializer::Expression(value) => match element_type.unqualified() {
+                Type::Array(element, _) => self.initializes_array(element, value),
+                // A struct or union may be initialized by a value of its type
+                record @ (Type::Struct(_) | Type::Union(_)) => {
+                    let mut probe = value.clone();
+                    self.rvalue(&mut probe)?;
+                    compatible(probe.ty(), record)
+                }
+                _ => true,
+            },
+        };
+        if whole {
+            let mut item = items.pop_front().unwrap();
+            self.initialize(&mut element_type.clone(), &mut item.initializer)?;
+            slots.insert(position, Slot::Initializer(item.initializer));
+        } else {
+            // Brace elision: the subaggregate takes as many items as it needs
+            let mut elements = self.reopen(element_type, slots.remove(&position));
+            self.fill(element_type, items, &mut elements, false, false)?;
+            slots.insert(position, Slot::Elements(elements));
+        }
+        Ok(())
+    }
+
+    /// The position a designator selects in an aggregate; designating a member
+    /// of an anonymous struct or union designates that member first
+    fn designate(
+        &self,
+        object_type: &Type,
+        designator: Designator,
+        item: &mut InitializerItem,
+    ) -> Result<usize, CompileError> {
+        let span = item.initializer.span();
+        match (object_type.unqualified(), designator) {
+            (Type::Array(_, size), Designator::Index(index)) => match size {
+                Some(size) if index >= *size => Err(CompileError::new(
+                    format!("array designator index ({}) exceeds array bound ({})", index, size),
+                    span,
+                )),
+                _ => Ok(index),
+            },
+            (Type::Struct(name) | Type::Union(name), Designator::Member(member)) => {
+                let definition = &self.structs[name];
+                let path = find_member(self.structs, definition, &member).ok_or_else(|| {
+                    CompileError::new(
+                        format!("field designator `{}` does not refer to any field in type `{}`", member, object_type),
+                        span,
+                    )
+                })?;
+                for field in path[1..].iter().rev() {
+                    item.designators.insert(0, Designator::Member(field.name.clone()));
+                }
+                Ok(definition.fields.iter().position(|field| field.name == path[0].name).unwrap())
+            }
+            (_, Designator::Index(_)) => Err(CompileError::new(
+                format!("array designator cannot initialize non-array type `{}`", object_type),
+                span,
+            )),
+            (_, Designator::Member(_)) => Err(CompileError::new(
+                format!("field designator cannot initialize a non-struct, non-union type `{}`", object_type),
+                span,
+            )),
+        }
+    }
+
+    /// The type of the element or member at `position` of an aggregate
+    fn element_type(&self, aggregate: &Type, position: usize) -> Type {
+        match aggregate.unqualified() {
+            Type::Array(element, _) => (**element).clone(),
+            Type::Struct(name) | Type::Union(name) => self.structs[name].fields[position].qualified_type(),
+            _ => unreachable!("not an aggregate"),
+        }
+    }
+
+    /// Turn what already initializes a subaggregate back into slots, so that
+    /// a later designator can override part of it
+    fn reopen(&self, aggregate: &Type, slot: Option<Slot>) -> BTreeMap<usize, Slot> {
+        match slot {
+            Some(Slot::Elements(elements)) => elements,
+            Some(Slot::Initializer(Initializer::List(items, _))) => items
+                .into_iter()
+                .map(|item| {
+                    let position = match (&item.designators[0], aggregate.unqualified()) {
+                        (Designator::Index(index), _) => *index,
+                        (Designator::Member(member), Type::Struct(name) | Type::Union(name)) => {
+                            self.structs[name].fields.iter().position(|field| field.name == *member).unwrap()
+                        }
+                        _ => unreachable!("designator does not match the aggregate"),
+                    };
+                    (position, Slot::Initializer(item.initializer))
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Build the resolved initializer list of an aggregate from its slots
+    fn assemble(&self, aggregate: &Type, slots: BTreeMap<usize, Slot>, span: Span) -> Initializer {
+        let items = slots
+            .into_iter()
+            .map(|(position, slot)| {
+                let designator = match aggregate.unqualified() {
+                    Type::Struct(name) | Type::Union(name) => {
+                        Designator::Member(self.structs[name].fields[position].name.clone())
+                    }
+                    _ => Designator::Index(position),
+                };
+                let initializer = match slot {
+                    Slot::Initializer(initializer) => initializer,
+                    Slot::Elements(elements) => self.assemble(&self.element_type(aggregate, position), elements, span),
+                };
+                InitializerItem {
+                    designators: vec![designator],
+                    initializer,
+                }
+            })
+            .collect();
+        Initializer::List(items, span)
+    }
+
+    /// Analyze an expression initializing an object of type `object_type`
+    fn initialize_expression(
+        &mut self,
+        object_type: &mut Type,
+        initializer: &mut Expression,
+    ) -> Result<(), CompileError> {
+        if let Type::Array(element, size) = &*object_type {
+            let length = match &initializer.kind {
+                ExpressionKind::Literal(Literal::String(_, units)) if self.initializes_array(element, initializer) => {
+                    units.len()
+                }
+                ExpressionKind::Literal(Literal::String(encoding, _)) if element.is_integer() => {
+                    let message = if element.unqualified().is_character() {
+                        "initializing char array with wide string literal"
+                    } else if matches!(encoding, Encoding::Plain | Encoding::Utf8) {
+                        "initializing wide char array with non-wide string literal"
+                    } else {
+                        "initializing wide char array with incompatible wide string literal"
+                    };
+                    return Err(CompileError::new(message, initializer.span));
+                }
+                _ => {
+                    return Err(CompileError::new(
+                        "array initializer must be an initializer list or string literal",
+                        initializer.span,
+                    ))
+                }
+            };
+            match size {
+                // The terminating null character is dropped when it does not fit
+                Some(size) if length > *size => {
+                    return Err(CompileError::new(
+                        "initializer-string for char array is too long",
+                        initializer.span,
+                    ))
+                }
+                Some(_) => {}
+                None => *object_type = Type::Array(element.clone(), Some(length + 1)),
+            }
+            return self.expression(initializer);
+        }
+
+        self.rvalue(initializer)?;
+        self.convert(initializer, object_type.unqualified(), Conversion::Initialization)
+    }
+
+    // Statements
+
+    /// Analyze the statements of a block in a scope of their own
+    fn block(&mut self, block: &mut Block) {
+        self.scopes.push(HashMap::new());
+        let variably_modified = self.variably_modified.len();
+        self.statements(&mut block.statements);
+        self.variably_modified.truncate(variably_modified);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &mut [Statement]) {
+        for statement in statements {
+            let result = self.statement(statement);
+            self.recover(result);
+        }
+    }
+
+    /// Analyze a statement. Blocks in it record their own errors, so an
+    /// error in a condition does not hide the errors in the body.
+    fn statement(&mut self, statement: &mut Statement) -> Result<(), CompileError> {
+        match &mut statement.kind {
+            StatementKind::Declaration(variable, initializer) => {
+                let result = self.local_declaration(variable, initializer.as_mut());
+                // A declaration in error still declares its name, so that
+                // its uses are not reported as well
+                let scope = self.scopes.last_mut().unwrap();
+                if result.is_err() && !scope.contains_key(&variable.name) {
+                    scope.insert(variable.name.clone(), Symbol::variable(variable));
+                }
+                result
+            }
+            StatementKind::Typedef(_, typedef) => {
+                self.variable_lengths(typedef)?;
+                self.check_size(typedef, statement.span)?;
+                if typedef.is_variably_modified() {
+                    self.variably_modified.push(VariablyModified { span: statement.span, is_array: false });
+                }
+                Ok(())
+            }
+            StatementKind::If(condition, then_block, else_block) => {
+                let result = self.condition(condition);
+                self.block(then_block);
+                if let Some(else_block) = else_block {
+                    self.block(else_block);
+                }
+                result
+            }
+            StatementKind::While(condition, body) => {
+                let result = self.condition(condition);
+                self.loop_body(body);
+                result
+            }
+            StatementKind::DoWhile(body, condition) => {
+                self.loop_body(body);
+                self.condition(condition)
+            }
+            StatementKind::For(init, condition, step, body) => {
+                self.scopes.push(HashMap::new());
+                let variably_modified = self.variably_modified.len();
+                let result = self.for_clauses(init, condition, step, body);
+                self.variably_modified.truncate(variably_modified);
+                self.scopes.pop();
+                result
+            }
+            StatementKind::Switch(condition, body) => self.switch_statement(condition, body),
+            StatementKind::Case(value, labeled) => {
+                let result = self.case_label(value, statement.span);
+                self.recover(result);
+                self.statement(labeled)
+            }
+            StatementKind::Default(labeled) => {
+                let result = self.default_label(statement.span);
+                self.recover(result);
+                self.statement(labeled)
+            }
+            StatementKind::Label(label, labeled) => {
+                let in_scope = self.variably_modified.clone();
+                if let Some((previous, _)) = self.labels.insert(label.clone(), (statement.span, in_scope)) {
+                    let error = CompileError::new(format!("redefinition of label `{}`", label), statement.span)
+                        .with_label(previous, "previous definition is here");
+                    self.errors.push(error);
+                }
+                self.statement(labeled)
+            }
+            StatementKind::Goto(label) => {
+                self.gotos.push((label.clone(), statement.span, self.variably_modified.clone()));
+                Ok(())
+            }
+            StatementKind::Break if self.loops == 0 && self.switches.is_empty() => Err(CompileError::new(
+                "`break` statement not in loop or switch statement",
+                statement.span,
+            )),
+            StatementKind::Continue if self.loops == 0 => {
+                Err(CompileError::new("`continue` statement not in loop statement", statement.span))
+            }
+            StatementKind::Break | StatementKind::Continue => Ok(()),
+            StatementKind::Return(value) => self.return_statement(value.as_mut(), statement.span),
+            StatementKind::Expression(expression) => self.rvalue(expression),
+            StatementKind::Block(block) => {
+                self.block(block);
+                Ok(())
+            }
+        }
+    }
+
+    fn local_declaration(
+        &mut self,
+        variable: &mut Variable,
+        initializer: Option<&mut Initializer>,
+    ) -> Result<(), CompileError> {
+        self.object_lengths(variable)?;
+        self.check_alignment(variable.alignment, &variable.var_type, variable.span)?;
+        if let (true, Some(initializer)) = (variable.var_type.is_variable_length(), &initializer) {
+            return Err(CompileError::new("variable-sized object may not be initialized", initializer.span()));
+        }
+        // The scope of a variable starts right after its declarator
+        self.declare_local(variable, initializer.as_deref())?;
+        if variable.var_type.is_variably_modified() {
+            self.variably_modified.push(VariablyModified {
+                span: variable.span,
+                is_array: variable.var_type.is_variable_length(),
+            });
+        }
+        if let Some(initializer) = initializer {
+            self.initializer(variable, initializer)?;
+            if variable.storage == Some(StorageClass::Static) {
+                self.constant_initializer(initializer)?;
+            }
+            let scope = self.scopes.last_mut().unwrap();
+            scope.get_mut(&variable.name).unwrap().symbol_type = variable.qualified_type();
+        }
+        Ok(())
+    }
+
+    fn for_clauses(
+        &mut self,
+        init: &mut Statement,
+        condition: &mut Expression,
+        step: &mut Statement,
+        body: &mut Block,
+    ) -> Result<(), CompileError> {
+        // Several declarations in the initializer belong to the loop's scope
+        match &mut init.kind {
+            StatementKind::Block(declarations) => self.statements(&mut declarations.statements),
+            _ => {
+                let result = self.statement(init);
+                self.recover(result);
+            }
+        }
+        let result = self.condition(condition).and_then(|()| self.statement(step));
+        self.loop_body(body);
+        result
+    }
+
+    fn loop_body(&mut self, body: &mut Block) {
+        self.loops += 1;
+        self.block(body);
+        self.loops -= 1;
+    }
+
+    fn switch_statement(&mut self, condition: &mut Expression, body: &mut Block) -> Result<(), CompileError> {
+        self.rvalue(condition)?;
+        if !condition.ty().is_integer() {
+            return Err(CompileError::new(
+                format!("statement requires expression of integer type (`{}` invalid)", condition.ty()),
+                condition.span,
+            ));
+        }
+        promote(self.target, condition);
+
+        self.switches.push(Switch {
+            controlling_type: condition.ty().clone(),
+            cases: HashMap::new(),
+            default: None,
+            variably_modified: self.variably_modified.len(),
+        });
+        self.block(body);
+        self.switches.pop();
+        Ok(())
+    }
+
+    /// Check a `default` label
+    fn default_label(&mut self, span: Span) -> Result<(), CompileError> {
+        match self.switches.last_mut() {
+            None => return Err(CompileError::new("`default` statement not in switch statement", span)),
+            Some(Switch { default: Some(previous), .. }) => {
+                return Err(CompileError::new("multiple default labels in one switch", span)
+                    .with_label(*previous, "previous case defined here"))
+            }
+            Some(switch) => switch.default = Some(span),
+        }
+        self.check_switch_jump(span)
+    }
+
+    /// Check a case label, folding its value converted to the controlling type
+    fn case_label(&mut self, value: &mut Expression, span: Span) -> Result<(), CompileError> {
+        self.rvalue(value)?;
+        let constant = self
+            .integer_constant(value)
+            .map_err(|not_constant| not_constant.error("expression is not an integer constant expression"))?;
+        let controlling_type = match self.switches.last() {
+            Some(switch) => switch.controlling_type.clone(),
+            None => return Err(CompileError::new("`case` statement not in switch statement", span)),
+        };
+        let constant = self.target.wrap_integer(constant.value(), &controlling_type);
+        implicit_cast(value, controlling_type);
+        value.kind = ExpressionKind::Literal(Literal::Int(constant));
+
+        let switch = self.switches.last_mut().unwrap();
+        if let Some(previous) = switch.cases.insert(constant, value.span) {
+            return Err(CompileError::new(format!("duplicate case value `{}`", constant), value.span)
+                .with_label(previous, "previous case defined here"));
+        }
+        self.check_switch_jump(span)
+    }
+
+    /// Check that the `case` or `default` label at `span` is not in the scope
+    /// of a variably modified object declared in the body of the switch
+    fn check_switch_jump(&self, span: Span) -> Result<(), CompileError> {
+        let switch = self.switches.last().expect("label checked to be in a switch");
+        match self.variably_modified.get(switch.variably_modified) {
+            Some(bypassed) => Err(bypassed.bypassed_by(CompileError::new(
+                "cannot jump from switch statement to this case label",
+                span,
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn return_statement(&mut self, value: Option<&mut Expression>, span: Span) -> Result<(), CompileError> {
+        let (name, return_type) = self.function.clone().expect("return outside of a function");
+        match value {
+            Some(value) if return_type == Type::Void => Err(CompileError::new(
+                format!("void function `{}` should not return a value", name),
+                value.span,
+            )),
+            Some(value) => {
+                self.rvalue(value)?;
+                self.convert(value, &return_type, Conversion::Return)
+            }
+            None if return_type != Type::Void => Err(CompileError::new(
+                format!("non-void function `{}` should return a value", name),
+                span,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn condition(&mut self, condition: &mut Expression) -> Result<(), CompileError> {
+        self.rvalue(condition)?;
+        if !condition.ty().is_scalar() {
+            return Err(CompileError::new(
+                format!("statement requires expression of scalar type (`{}` invalid)", condition.ty()),
+                condition.span,
+            ));
+        }
+        Ok(())
+    }
+
+    // Expressions
+
+    /// Analyze an expression whose value is used, decaying arrays and
+    /// functions to pointers and dropping the qualifiers of lvalues
+    fn rvalue(&mut self, expression: &mut Expression) -> Result<(), CompileError> {
+        self.expression(expression)?;
+        let converted = match expression.ty() {
+            Type::Array(element, _) | Type::VariableArray(element, _) => Type::Pointer(element.clone()),
+            function @ Type::Function(..) => Type::Pointer(Box::new(function.clone())),
+            Type::Qualified(unqualified, _) => (**unqualified).clone(),
+            _ => return Ok(()),
+        };
+        implicit_cast(expression, converted);
+        Ok(())
+    }
+
+    /// The value and type of an integer or character constant as written.
+    /// An integer constant has the first type in C11's list for its suffix
+    /// and base that holds it; a character constant has the type of its
+    /// encoding and the value of its code units in that type.
+    fn constant(&self, literal: &Literal) -> (i64, Type) {
+        match literal {
+            Literal::Integer { value, suffix, is_decimal } => {
+                let candidates: &[Type] = match (suffix, is_decimal) {
+                    (IntSuffix::None, true) => &[Type::Int, Type::Long, Type::LongLong],
+                    (IntSuffix::None, false) => &[
+                        Type::Int,
+                        Type::UnsignedInt,
+                        Type::Long,
+                        Type::UnsignedLong,
+                        Type::LongLong,
+                        Type::UnsignedLongLong,
+                    ],
+                    (IntSuffix::Unsigned, _) => &[Type::UnsignedInt, Type::UnsignedLong, Type::UnsignedLongLong],
+                    (IntSuffix::Long, true) => &[Type::Long, Type::LongLong],
+                    (IntSuffix::Long, false) => {
+                        &[Type::Long, Type::UnsignedLong, Type::LongLong, Type::UnsignedLongLong]
+                    }
+                    (IntSuffix::UnsignedLong, _) => &[Type::UnsignedLong, Type::UnsignedLongLong],
+                    (IntSuffix::LongLong, true) => &[Type::LongLong],
+                    (IntSuffix::LongLong, false) => &[Type::LongLong, Type::UnsignedLongLong],
+                    (IntSuffix::UnsignedLongLong, _) => &[Type::UnsignedLongLong],
+                };
+                // A decimal constant too large for `long long` is taken as
+                // unsigned, as other compilers do
+                let constant_type = candidates
+                    .iter()
+                    .find(|candidate| {
+                        let width = self.target.integer_width(candidate);
+                        let bits = if self.target.is_signed(candidate) { width - 1 } else { width };
+                        bits >= 64 || *value < 1 << bits
+                    })
+                    .cloned()
+                    .unwrap_or(Type::UnsignedLongLong);
+                (*value as i64, constant_type)
+            }
+            Literal::Char(encoding, units) => {
+                let constant_type = match encoding {
+                    Encoding::Plain => Type::Int,
+                    Encoding::Utf8 => Type::UnsignedChar,
+                    Encoding::Utf16 => Type::UnsignedShort,
+                    Encoding::Utf32 => Type::UnsignedInt,
+                    Encoding::Wide => self.target.wchar_type(),
+                };
+                let value = match (encoding, units.as_slice()) {
+                    // A single character has the value of a `char` holding it
+                    (Encoding::Plain, [unit]) if !self.target.char_signed => i64::from(*unit),
+                    _ => encoding.char_value(units),
+                };
+                (self.target.wrap_integer(value, &constant_type), constant_type)
+            }
+            other => unreachable!("{:?} is not an integer constant", other),
+        }
+    }
+
+    /// The element type of a string literal with `encoding`
+    fn string_element(&self, encoding: Encoding) -> Type {
+        match encoding {
+            Encoding::Plain | Encoding::Utf8 => Type::Char,
+            Encoding::Utf16 => Type::UnsignedShort,
+            Encoding::Utf32 => Type::UnsignedInt,
+            Encoding::Wide => self.target.wchar_type(),
+        }
+    }
+
+    /// Whether `value` is a string literal that can initialize an array of
+    /// `element`: a character array from a plain or UTF-8 string, any other
+    /// from a wide string of its element type
+    fn initializes_array(&self, element: &Type, value: &Expression) -> bool {
+        let ExpressionKind::Literal(Literal::String(encoding, _)) = &value.kind else {
+            return false;
+        };
+        let string_element = self.string_element(*encoding);
+        if element.unqualified().is_character() {
+            string_element == Type::Char
+        } else {
+            compatible(element.unqualified(), &string_element)
+        }
+    }
+
+    /// Analyze an expression and record its type
+    fn expression(&mut self, expression: &mut Expression) -> Result<(), CompileError> {
+        if let ExpressionKind::Generic(..) = expression.kind {
+            // A generic selection is replaced by the expression it selects
+            *expression = self.generic_selection(expression)?;
+            return Ok(());
+        }
+        if matches!(
+            expression.kind,
+            ExpressionKind::SizeOf(_) | ExpressionKind::SizeOfType(_) | ExpressionKind::AlignOf(_)
+        ) {
+            // The operand is not evaluated and the result is an integer
+            // constant, unless the operand is a variable length array
+            if let Some(value) = self.size_or_alignment(expression)? {
+                expression.kind = ExpressionKind::Literal(Literal::Int(value as i64));
+            }
+            expression.expr_type = Some(self.target.size_type());
+            return Ok(());
+        }
+
+        if let ExpressionKind::Literal(literal @ (Literal::Integer { .. } | Literal::Char(..))) = &expression.kind {
+            let (value, constant_type) = self.constant(literal);
+            expression.kind = ExpressionKind::Literal(Literal::Int(value));
+            expression.expr_type = Some(constant_type);
+            return Ok(());
+        }
+
+        let span = expression.span;
+        let expr_type = match &mut expression.kind {
+            ExpressionKind::Literal(literal) => match literal {
+                Literal::Int(value) => self.target.literal_type(*value),
+                Literal::Integer { .. } | Literal::Char(..) => unreachable!("constants as written are folded above"),
+                Literal::Float { suffix: FloatSuffix::None, .. } => Type::Double,
+                Literal::Float { suffix: FloatSuffix::Float, .. } => Type::Float,
+                Literal::Float { suffix: FloatSuffix::Long, .. } => Type::LongDouble,
+                Literal::String(encoding, units) => {
+                    Type::Array(Box::new(self.string_element(*encoding)), Some(units.len() + 1))
+                }
+                Literal::Null => Type::Pointer(Box::new(Type::Void)),
+            },
+            ExpressionKind::Variable(name) => match self.lookup(name) {
+                Some(symbol) => symbol.symbol_type.clone(),
+                None => {
+                    return Err(CompileError::new(
+                        format!("use of undeclared identifier `{}`", name),
+                        span,
+                    ))
+                }
+            },
+            ExpressionKind::BinaryOp(lhs, operator, rhs) => {
+                self.rvalue(lhs)?;
+                self.rvalue(rhs)?;
+                self.binary_type(lhs, *operator, rhs, span)?
+            }
+            ExpressionKind::UnaryOp(operator, operand) => self.unary_type(*operator, operand)?,
+            ExpressionKind::Call(callee, arguments) => self.call_type(callee, arguments, span)?,
+            ExpressionKind::Cast(operand, target) => {
+                self.variable_lengths(target)?;
+                self.check_size(target, span)?;
+                self.rvalue(operand)?;
+                // A cast yields an rvalue, which is never qualified
+                let target = target.unqualified();
+                let source = operand.ty();
+                let valid = *target == Type::Void
+                    || (target.is_scalar()
+                        && source.is_scalar()
+                        && !(target.is_floating() && source.pointee().is_some())
+                        && !(source.is_floating() && target.pointee().is_some()));
+                if !valid {
+                    return Err(CompileError::new(
+                        format!("cannot cast from `{}` to `{}`", source, target),
+                        span,
+                    ));
+                }
+                target.clone()
+            }
+            ExpressionKind::Index(base, index) => {
+                self.rvalue(base)?;
+                self.rvalue(index)?;
+                // `a[i]` and `i[a]` are both `*(a + i)`
+                let (pointer, offset) = if base.ty().pointee().is_some() {
+                    (base, index)
+                } else {
+                    (index, base)
+                };
+                let element = match pointer.ty() {
+                    Type::Pointer(element) => (**element).clone(),
+                    _ => {
+                        return Err(CompileError::new(
+                            "subscripted value is not an array or pointer",
+                            pointer.span,
+                        ))
+                    }
+                };
+                if !offset.ty().is_integer() {
+                    return Err(CompileError::new("array subscript is not an integer", offset.span));
+                }
+                check_pointer_arithmetic(&element, pointer.span)?;
+                promote(self.target, offset);
+                element
+            }
+            ExpressionKind::Member(base, member) => {
+                self.expression(base)?;
+                let mut path = self.member_path(base.ty().unqualified(), member, span)?;
+                let field = path.pop().unwrap();
+                // Members of anonymous structs and unions are reached through them
+                for anonymous in path {
+                    let qualifiers = base.ty().qualifiers();
+                    let placeholder = Expression::new(ExpressionKind::Literal(Literal::Null), span);
+                    let outer = std::mem::replace(&mut **base, placeholder);
+                    let member = ExpressionKind::Member(Box::new(outer), anonymous.name.clone());
+                    let mut inner = Expression::new(member, span);
+                    inner.expr_type = Some(anonymous.qualified_type().qualified(qualifiers));
+                    **base = inner;
+                }
+                // Members of a qualified struct have its qualifiers too
+                field.qualified_type().qualified(base.ty().qualifiers())
+            }
+            ExpressionKind::Pointer(operand) => {
+                self.rvalue(operand)?;
+                match operand.ty() {
+                    Type::Pointer(pointee) => (**pointee).clone(),
+                    other => {
+                        return Err(CompileError::new(
+                            format!("indirection requires pointer operand (`{}` invalid)", other),
+                            span,
+                        ))
+                    }
+                }
+            }
+            ExpressionKind::Address(operand) => {
+                self.expression(operand)?;
+                if !is_lvalue(operand) && !matches!(operand.ty(), Type::Function(..)) {
+                    return Err(CompileError::new(
+                        format!("cannot take the address of an rvalue of type `{}`", operand.ty()),
+                        span,
+                    ));
+                }
+                if self.bit_field(operand).is_some() {
+                    return Err(CompileError::new("address of bit-field requested", span));
+                }
+                if let ExpressionKind::Variable(name) = &operand.kind {
+                    if self.lookup(name).is_some_and(|symbol| symbol.storage == Some(StorageClass::Register)) {
+                        return Err(CompileError::new(
+                            format!("address of register variable `{}` requested", name),
+                            span,
+                        ));
+                    }
+                }
+                Type::Pointer(Box::new(operand.ty().clone()))
+            }
+            ExpressionKind::Assignment(target, value) => {
+                self.expression(target)?;
+                self.check_assignable(target, "assign to")?;
+                self.rvalue(value)?;
+                let target_type = target.ty().unqualified().clone();
+                self.convert(value, &target_type, Conversion::Assignment)?;
+                target_type
+            }
+            ExpressionKind::CompoundAssignment(target, operator, value, computation) => {
+                self.expression(target)?;
+                self.check_assignable(target, "assign to")?;
+                self.rvalue(value)?;
+                // The operation is done as in `target = target op value`, on a
+                // copy of the target that records the computation type
+                let target_type = target.ty().unqualified().clone();
+                let mut probe = (**target).clone();
+                implicit_cast(&mut probe, target_type.clone());
+                let result = self.binary_type(&mut probe, *operator, value, span)?;
+                let valid = if target_type.is_arithmetic() {
+                    result.is_arithmetic()
+                } else {
+                    result == target_type
+                };
+                if !valid {
+                    return Err(CompileError::new(
+                        format!("invalid operands to binary expression (`{}` and `{}`)", target_type, value.ty()),
+                        span,
+                    ));
+                }
+                *computation = Some(probe.ty().clone());
+                target_type
+            }
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                self.condition(condition)?;
+                self.rvalue(then)?;
+                self.rvalue(otherwise)?;
+                self.conditional_type(then, otherwise, span)?
+            }
+            ExpressionKind::Comma(lhs, rhs) => {
+                self.rvalue(lhs)?;
+                self.rvalue(rhs)?;
+                rhs.ty().clone()
+            }
+            ExpressionKind::SizeOf(_) | ExpressionKind::SizeOfType(_) | ExpressionKind::AlignOf(_) => {
+                unreachable!("`sizeof` and `_Alignof` are analyzed separately")
+            }
+            ExpressionKind::CompoundLiteral(literal_type, initializer) => {
+                self.variable_lengths(literal_type)?;
+                self.check_size(literal_type, span)?;
+                if literal_type.is_variable_length() {
+                    return Err(CompileError::new("compound literal has variable-length array type", span));
+                }
+                let unsized_array = matches!(literal_type, Type::Array(_, None));
+                if !unsized_array && self.target.layout(literal_type, self.structs).is_none() {
+                    return Err(CompileError::new(
+                        format!("compound literal has incomplete type `{}`", literal_type),
+                        span,
+                    ));
+                }
+                self.initialize(literal_type, initializer)?;
+                literal_type.clone()
+            }
+            ExpressionKind::VaStart(list) => {
+                // A parameter may hide the function's own name
+                let is_variadic = match &self.function {
+                    Some((name, _)) => matches!(
+                        self.scopes[0].get(name),
+                        Some(Symbol { symbol_type: Type::Function(_, _, true), .. })
+                    ),
+                    None => false,
+                };
+                if !is_variadic {
+                    return Err(CompileError::new("`va_start` used in function with fixed parameters", span));
+                }
+                self.va_list(list)?;
+                Type::Void
+            }
+            ExpressionKind::VaArg(list, arg_type) => {
+                self.va_list(list)?;
+                self.check_size(arg_type, span)?;
+                if self.target.layout(arg_type, self.structs).is_none() {
+                    return Err(CompileError::new(
+                        format!("second argument to `va_arg` is of incomplete type `{}`", arg_type),
+                        span,
+                    ));
+                }
+                arg_type.unqualified().clone()
+            }
+            ExpressionKind::VaEnd(list) => {
+                self.va_list(list)?;
+                Type::Void
+            }
+            ExpressionKind::VaCopy(destination, source) => {
+                self.va_list(destination)?;
+                self.va_list(source)?;
+                Type::Void
+            }
+            ExpressionKind::Generic(..) => unreachable!("generic selections are replaced"),
+            ExpressionKind::ImplicitCast(_, target) => target.clone(),
+        };
+        expression.expr_type = Some(expr_type);
+        Ok(())
+    }
+
+    /// Check the `va_list` operand of a `stdarg.h` builtin, which is used in place
+    fn va_list(&mut self, list: &mut Expression) -> Result<(), CompileError> {
+        self.expression(list)?;
+        if *list.ty().unqualified() != Type::VaList || !is_lvalue(list) {
+            return Err(CompileError::new(
+                format!("expected an object of type `va_list`, found `{}`", list.ty()),
+                list.span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The type of `c ? then : otherwise`, converting the operands to it
+    fn conditional_type(
+        &self,
+        then: &mut Expression,
+        otherwise: &mut Expression,
+        span: Span,
+    ) -> Result<Type, CompileError> {
+        if then.ty().is_arithmetic() && otherwise.ty().is_arithmetic() {
+            return Ok(arithmetic_conversions(self.target, then, otherwise));
+        }
+        match (then.ty().pointee().cloned(), otherwise.ty().pointee().cloned()) {
+            (Some(left), Some(right)) => {
+                // The result points to a type with the qualifiers of both
+                let qualifiers = left.qualifiers().union(right.qualifiers());
+                let pointee = if *left.unqualified() == Type::Void || *right.unqualified() == Type::Void {
+                    Type::Void
+                } else if compatible(left.unqualified(), right.unqualified()) {
+                    left.unqualified().clone()
+                } else {
+                    return Err(CompileError::new(
+                        format!("pointer type mismatch (`{}` and `{}`)", then.ty(), otherwise.ty()),
+                        span,
+                    ));
+                };
+                let result = Type::Pointer(Box::new(pointee.qualified(qualifiers)));
+                if *then.ty() != result {
+                    implicit_cast(then, result.clone());
+                }
+                if *otherwise.ty() != result {
+                    implicit_cast(otherwise, result.clone());
+                }
+                Ok(result)
+            }
+            (Some(_), None) if self.is_null_pointer_constant(otherwise) => {
+                implicit_cast(otherwise, then.ty().clone());
+                Ok(then.ty().clone())
+            }
+            (None, Some(_)) if self.is_null_pointer_constant(then) => {
+                implicit_cast(then, otherwise.ty().clone());
+                Ok(otherwise.ty().clone())
+            }
+            _ if then.ty() == otherwise.ty() && !then.ty().is_scalar() => Ok(then.ty().clone()),
+            _ => Err(CompileError::new(
+                format!("incompatible operand types (`{}` and `{}`)", then.ty(), otherwise.ty()),
+                span,
+            )),
+        }
+    }
+
+    /// The value of a `sizeof` or `_Alignof` expression, or `None` for the
+    /// size of a variable length array, which is computed at run time
+    fn size_or_alignment(&mut self, expression: &mut Expression) -> Result<Option<u64>, CompileError> {
+        let span = expression.span;
+        let (operand, operator) = match &mut expression.kind {
+            ExpressionKind::SizeOf(operand) => {
+                self.expression(operand)?;
+                if self.bit_field(operand).is_some() {
+                    return Err(CompileError::new("invalid application of `sizeof` to bit-field", span));
+                }
+                (operand.ty(), "sizeof")
+            }
+            ExpressionKind::SizeOfType(operand) => {
+                self.variable_lengths(operand)?;
+                self.check_size(operand, span)?;
+                (&*operand, "sizeof")
+            }
+            ExpressionKind::AlignOf(operand) => {
+                self.variable_lengths(operand)?;
+                self.check_size(operand, span)?;
+                (&*operand, "_Alignof")
+            }
+            _ => unreachable!("not `sizeof` or `_Alignof`"),
+        };
+        if let Type::Function(..) = operand {
+            return Err(CompileError::new(
+                format!("invalid application of `{}` to a function type", operator),
+                span,
+            ));
+        }
+        if operand.is_variable_length() && operator == "sizeof" {
+            return Ok(None);
+        }
+        // A variable length array is aligned like its elements
+        let mut operand = operand;
+        while let Type::Array(element, _) | Type::VariableArray(element, _) = operand {
+            if !operand.is_variable_length() {
+                break;
+            }
+            operand = element;
+        }
+        match self.target.layout(operand, self.structs) {
+            Some(layout) if operator == "sizeof" => Ok(Some(layout.size)),
+            Some(layout) => Ok(Some(layout.align)),
+            None => Err(CompileError::new(
+                format!("invalid application of `{}` to an incomplete type `{}`", operator, operand),
+                span,
+            )),
+        }
+    }
+
+    /// Select the association of a `_Generic` expression whose type is
+    /// compatible with the type of its controlling expression
+    fn generic_selection(&mut self, expression: &mut Expression) -> Result<Expression, CompileError> {
+        let span = expression.span;
+        let (control, associations) = match &mut expression.kind {
+            ExpressionKind::Generic(control, associations) => (control, associations),
+            _ => unreachable!("not a generic selection"),
+        };
+        // The controlling expression is converted as an rvalue but not evaluated
+        self.rvalue(control)?;
+
+        let mut seen: Vec<&Type> = Vec::new();
+        let mut selected = None;
+        let mut default = None;
+        for (index, (association_type, value)) in associations.iter_mut().enumerate() {
+            self.expression(value)?;
+            let association_type = match association_type {
+                Some(association_type) => association_type,
+                None => {
+                    default = Some(index);
+                    continue;
+                }
+            };
+            self.check_size(association_type, value.span)?;
+            if matches!(association_type, Type::Function(..))
+                || self.target.layout(association_type, self.structs).is_none()
+            {
+                return Err(CompileError::new(
+                    format!("type `{}` in generic association incomplete", association_type),
+                    value.span,
+                ));
+            }
+            if let Some(previous) = seen.iter().find(|previous| compatible(previous, association_type)) {
+                return Err(CompileError::new(
+                    format!(
+                        "type `{}` in generic association compatible with previously specified type `{}`",
+                        association_type, previous
+                    ),
+                    value.span,
+                ));
+            }
+            if compatible(control.ty(), association_type) {
+                selected = Some(index);
+            }
+            seen.push(association_type);
+        }
+
+        match selected.or(default) {
+            Some(index) => Ok(associations.swap_remove(index).1),
+            None => Err(CompileError::new(
+                format!(
+                    "controlling expression type `{}` not compatible with any generic association type",
+                    control.ty()
+                ),
+                span,
+            )),
+        }
+    }
+
+    fn unary_type(&mut self, operator: UnaryOperator, operand: &mut Expression) -> Result<Type, CompileError> {
+        match operator {
+            UnaryOperator::PreIncrement
+            | UnaryOperator::PostIncrement
+            | UnaryOperator::PreDecrement
+            | UnaryOperator::PostDecrement => {
+                self.expression(operand)?;
+                let action = match operator {
+                    UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => "increment",
+                    _ => "decrement",
+                };
+                self.check_assignable(operand, action)?;
+                let operand_type = operand.ty().unqualified().clone();
+                match &operand_type {
+                    Type::Pointer(pointee) => check_pointer_arithmetic(pointee, operand.span)?,
+                    other if other.is_arithmetic() => {}
+                    other => {
+                        return Err(CompileError::new(
+                            format!("cannot {} value of type `{}`", action, other),
+                            operand.span,
+                        ))
+                    }
+                }
+                Ok(operand_type)
+            }
+            UnaryOperator::LogicalNot => {
+                self.rvalue(operand)?;
+                if !operand.ty().is_scalar() {
+                    return Err(invalid_unary_operand(operand));
+                }
+                Ok(Type::Int)
+            }
+            UnaryOperator::Plus | UnaryOperator::Negate | UnaryOperator::BitwiseNot => {
+                self.rvalue(operand)?;
+                let valid = match operator {
+                    UnaryOperator::Plus | UnaryOperator::Negate => operand.ty().is_arithmetic(),
+                    _ => operand.ty().is_integer(),
+                };
+                if !valid {
+                    return Err(invalid_unary_operand(operand));
+                }
+                promote(self.target, operand);
+                Ok(operand.ty().clone())
+            }
+        }
+    }
+
+    fn call_type(
+        &mut self,
+        callee: &mut Expression,
+        arguments: &mut [Expression],
+        span: Span,
+    ) -> Result<Type, CompileError> {
+        if let ExpressionKind::Variable(name) = &callee.kind {
+            if self.lookup(name).is_none() {
+                return Err(CompileError::new(format!("call to undeclared function `{}`", name), span));
+            }
+        }
+        // A function designator decays to a pointer to the function
+        self.rvalue(callee)?;
+        let (return_type, parameters, is_variadic) = match callee.ty().pointee().map(Type::unqualified) {
+            Some(Type::Function(return_type, parameters, is_variadic)) => {
+                ((**return_type).clone(), parameters.clone(), *is_variadic)
+            }
+            _ => {
+                let callee_type = match &callee.kind {
+                    ExpressionKind::ImplicitCast(operand, _) => operand.ty(),
+                    _ => callee.ty(),
+                };
+                return Err(CompileError::new(
+                    format!("called object type `{}` is not a function or function pointer", callee_type),
+                    span,
+                ));
+            }
+        };
+
+        if arguments.len() < parameters.len() || (arguments.len() > parameters.len() && !is_variadic) {
+            let amount = if arguments.len() > parameters.len() { "many" } else { "few" };
+            return Err(CompileError::new(
+                format!(
+                    "too {} arguments to function call, expected {}{}, have {}",
+                    amount,
+                    if is_variadic { "at least " } else { "" },
+                    parameters.len(),
+                    arguments.len()
+                ),
+                span,
+            ));
+        }
+        for (argument, parameter) in arguments.iter_mut().zip(&parameters) {
+            self.rvalue(argument)?;
+            self.convert(argument, parameter, Conversion::Argument)?;
+        }
+        // Arguments matching `...` undergo the default argument promotions
+        for argument in arguments.iter_mut().skip(parameters.len()) {
+            self.rvalue(argument)?;
+            if *argument.ty() == Type::Void {
+                return Err(CompileError::new("argument may not have `void` type", argument.span));
+            }
+            if argument.ty().is_arithmetic() {
+                promote(self.target, argument);
+                if *argument.ty() == Type::Float {
+                    implicit_cast(argument, Type::Double);
+                }
+            }
+        }
+        Ok(return_type)
+    }
+
+    /// The members leading to `member` of a struct or union, looking inside
+    /// anonymous members; the last one is `member` itself
+    fn member_path(&self, base: &Type, member: &str, span: Span) -> Result<Vec<&'a Field>, CompileError> {
+        let name = match base {
+            Type::Struct(name) | Type::Union(name) => name,
+            other => {
+                return Err(CompileError::new(
+                    format!("member reference base type `{}` is not a structure or union", other),
+                    span,
+                ))
+            }
+        };
+        let definition = self.structs.get(name).ok_or_else(|| {
+            CompileError::new(format!("member access into incomplete type `{}`", base), span)
+        })?;
+        find_member(self.structs, definition, member)
+            .ok_or_else(|| CompileError::new(format!("no member named `{}` in `{}`", member, base), span))
+    }
+
+    /// The bit-field a member expression refers to, if any
+    fn bit_field(&self, expression: &Expression) -> Option<&'a Field> {
+        match &expression.kind {
+            ExpressionKind::Member(base, member) => {
+                let field = *self.member_path(base.ty().unqualified(), member, expression.span).ok()?.last()?;
+                field.bit_width.map(|_| field)
+            }
+            _ => None,
+        }
+    }
+
+    /// Check that `target` designates an object that may be modified
+    fn check_assignable(&self, target: &Expression, action: &str) -> Result<(), CompileError> {
+        if !is_lvalue(target) || matches!(target.ty(), Type::Function(..)) {
+            return Err(CompileError::new("expression is not assignable", target.span));
+        }
+        if let Type::Array(..) | Type::VariableArray(..) = target.ty() {
+            return Err(CompileError::new(
+                format!("array type `{}` is not assignable", target.ty()),
+                target.span,
+            ));
+        }
+        if let Some(description) = self.read_only(target) {
+            return Err(CompileError::new(
+                format!("cannot {} read-only {}", action, description),
+                target.span,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Describe the const-qualified object an lvalue refers to, if any
+    fn read_only(&self, target: &Expression) -> Option<String> {
+        if !target.ty().qualifiers().is_const {
+            return None;
+        }
+        Some(match &target.kind {
+            ExpressionKind::Variable(name) => format!("variable `{}`", name),
+            // A member of a const struct is read-only because of the struct
+            ExpressionKind::Member(base, _) if base.ty().qualifiers().is_const => return self.read_only(base),
+            ExpressionKind::Member(_, member) => format!("member `{}`", member),
+            _ => "location".to_string(),
+        })
+    }
+
+    /// Convert `value` to `target` as if by assignment
+    fn convert(&self, value: &mut Expression, target: &Type, conversion: Conversion) -> Result<(), CompileError> {
+        let source = value.ty();
+        let valid = (target.is_arithmetic() && source.is_arithmetic())
+            || (*target == Type::Bool && source.pointee().is_some())
+            || compatible(target, source)
+            || match (target.pointee(), source.pointee()) {
+                // Qualifiers of the pointed-to types may differ
+                (Some(to), Some(from)) => {
+                    let (to, from) = (to.unqualified(), from.unqualified());
+                    compatible(to, from)
+                        || (*to == Type::Void && !matches!(from, Type::Function(..)))
+                        || (*from == Type::Void && !matches!(to, Type::Function(..)))
+                }
+                (Some(_), None) => self.is_null_pointer_constant(value),
+                _ => false,
+            };
+        if !valid {
+            let message = match conversion {
+                Conversion::Assignment => format!("assigning to `{}` from incompatible type `{}`", target, source),
+                Conversion::Initialization => format!(
+                    "initializing `{}` with an expression of incompatible type `{}`",
+                    target, source
+                ),
+                Conversion::Argument => format!("passing `{}` to parameter of incompatible type `{}`", source, target),
+                Conversion::Return => format!(
+                    "returning `{}` from a function with incompatible result type `{}`",
+                    source, target
+                ),
+            };
+            return Err(CompileError::new(message, value.span));
+        }
+        implicit_cast(value, target.clone());
+        Ok(())
+    }
+
+    /// Compute the type of a binary operation on analyzed operands, inserting
+    /// the conversions the operator applies to them
+    fn binary_type(
+        &self,
+        lhs: &mut Expression,
+        operator: BinaryOperator,
+        rhs: &mut Expression,
+        span: Span,
+    ) -> Result<Type, CompileError> {
+        let invalid = |lhs: &Expression, rhs: &Expression| {
+            CompileError::new(
+                format!("invalid operands to binary expression (`{}` and `{}`)", lhs.ty(), rhs.ty()),
+                span,
+            )
+        };
+
+        match operator {
+            BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                if !lhs.ty().is_scalar() || !rhs.ty().is_scalar() {
+                    return Err(invalid(lhs, rhs));
+                }
+                Ok(Type::Int)
+            }
+            BinaryOperator::Multiply | BinaryOperator::Divide => {
+                if !lhs.ty().is_arithmetic() || !rhs.ty().is_arithmetic() {
+                    return Err(invalid(lhs, rhs));
+                }
+                Ok(arithmetic_conversions(self.target, lhs, rhs))
+            }
+            BinaryOperator::Modulo | BinaryOperator::BitwiseAnd | BinaryOperator::BitwiseOr | BinaryOperator::BitwiseXor => {
+                if !lhs.ty().is_integer() || !rhs.ty().is_integer() {
+                    return Err(invalid(lhs, rhs));
+                }
+                Ok(arithmetic_conversions(self.target, lhs, rhs))
+            }
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                if !lhs.ty().is_integer() || !rhs.ty().is_integer() {
+                    return Err(invalid(lhs, rhs));
+                }
+                // The operands are promoted separately and the result has the type of the left one
+                promote(self.target, lhs);
+                promote(self.target, rhs);
+                Ok(lhs.ty().clone())
+            }
+            BinaryOperator::Add | BinaryOperator::Subtract => {
+                if lhs.ty().is_arithmetic() && rhs.ty().is_arithmetic() {
+                    return Ok(arithmetic_conversions(self.target, lhs, rhs));
+                }
+                match (lhs.ty().clone(), rhs.ty().clone()) {
+                    (Type::Pointer(pointee), offset) if offset.is_integer() => {
+                        check_pointer_arithmetic(&pointee, lhs.span)?;
+                        promote(self.target, rhs);
+                        Ok(Type::Pointer(pointee))
+                    }
+                    (offset, Type::Pointer(pointee)) if offset.is_integer() && operator == BinaryOperator::Add => {
+                        check_pointer_arithmetic(&pointee, rhs.span)?;
+                        promote(self.target, lhs);
+                        Ok(Type::Pointer(pointee))
+                    }
+                    (Type::Pointer(left), Type::Pointer(right)) if operator == BinaryOperator::Subtract => {
+                        if !compatible(left.unqualified(), right.unqualified()) {
+                            return Err(invalid(lhs, rhs));
+                        }
+                        check_pointer_arithmetic(&left, lhs.span)?;
+                        Ok(self.target.ptrdiff_type())
+                    }
+                    _ => Err(invalid(lhs, rhs)),
+                }
+            }
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::LessThan
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::LessThanOrEqual
+            | BinaryOperator::GreaterThanOrEqual => {
+                if lhs.ty().is_arithmetic() && rhs.ty().is_arithmetic() {
+                    arithmetic_conversions(self.target, lhs, rhs);
+                    return Ok(Type::Int);
+                }
+                let equality = matches!(operator, BinaryOperator::Equal | BinaryOperator::NotEqual);
+                let pointees = |expression: &Expression| {
+                    expression.ty().pointee().map(|pointee| pointee.unqualified().clone())
+                };
+                match (pointees(lhs), pointees(rhs)) {
+                    (Some(left), Some(right)) => {
+                        let comparable = compatible(&left, &right)
+                            || (equality && (left == Type::Void || right == Type::Void));
+                        if !comparable {
+                            return Err(CompileError::new(
+                                format!(
+                                    "comparison of distinct pointer types (`{}` and `{}`)",
+                                    lhs.ty(),
+                                    rhs.ty()
+                                ),
+                                span,
+                            ));
+                        }
+                        if left == Type::Void {
+                            implicit_cast(rhs, lhs.ty().clone());
+                        } else if right == Type::Void {
+                            implicit_cast(lhs, rhs.ty().clone());
+                        }
+                    }
+                    (Some(_), None) if equality && self.is_null_pointer_constant(rhs) => {
+                        implicit_cast(rhs, lhs.ty().clone());
+                    }
+                    (None, Some(_)) if equality && self.is_null_pointer_constant(lhs) => {
+                        implicit_cast(lhs, rhs.ty().clone());
+                    }
+                    (Some(_), _) | (_, Some(_)) => {
+                        return Err(CompileError::new(
+                            format!("comparison between `{}` and `{}`", lhs.ty(), rhs.ty()),
+                            span,
+                        ))
+                    }
+                    (None, None) => return Err(invalid(lhs, rhs)),
+                }
+                Ok(Type::Int)
+            }
+        }
+    }
+}
+
+/// Check that a redeclaration of a variable agrees with the type of an
+/// earlier declaration
+fn check_redeclaration(existing: &Symbol, variable: &Variable) -> Result<(), CompileError> {
+    if let Type::Function(..) = existing.symbol_type {
+        return Err(CompileError::new(
+            format!("redefinition of `{}` as a different kind of symbol", variable.name),
+            variable.span,
+        )
+        .with_label(existing.span, "previous definition is here"));
+    }
+    let variable_type = variable.qualified_type();
+    if !compatible(&existing.symbol_type, &variable_type) {
+        return Err(CompileError::new(
+            format!(
+                "conflicting types for `{}` (`{}` and `{}`)",
+                variable.name, existing.symbol_type, variable_type
+            ),
+            variable.span,
+        )
+        .with_label(existing.span, "previous declaration is here"));
+    }
+    Ok(())
+}
+
+/// Pointer arithmetic needs the size of the pointed-to type
+/// The type of a function as seen by its callers
+fn function_type(function: &Function) -> Type {
+    let parameters = function.parameters.iter().map(|parameter| parameter.var_type.clone()).collect();
+    Type::Function(Box::new(function.return_type.clone()), parameters, function.is_variadic)
+}
+
+fn check_pointer_arithmetic(pointee: &Type, span: Span) -> Result<(), CompileError> {
+    match pointee.unqualified() {
+        Type::Void | Type::Function(..) => Err(CompileError::new(
+            format!("arithmetic on a pointer to `{}`", pointee),
+            span,
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn invalid_unary_operand(operand: &Expression) -> CompileError {
+    CompileError::new(
+        format!("invalid argument type `{}` to unary expression", operand.ty()),
+        operand.span,
+    )
+}
+
+/// Apply the integer promotions to an analyzed operand
+fn promote(target: &TargetInfo, operand: &mut Expression) {
+    let promoted = target.promote(operand.ty());
+    implicit_cast(operand, promoted);
+}
+
+/// Apply the usual arithmetic conversions to two arithmetic operands and
+/// return their common type
+fn arithmetic_conversions(target: &TargetInfo, lhs: &mut Expression, rhs: &mut Expression) -> Type {
+    let common = target.common_type(lhs.ty(), rhs.ty());
+    implicit_cast(lhs, common.clone());
+    implicit_cast(rhs, common.clone());
+    common
+}
+
+/// Wrap an analyzed expression in a conversion to `target` unless it already
+/// has that type
+fn implicit_cast(expression: &mut Expression, target: Type) {
+    if *expression.ty() == target {
+        return;
+    }
+    let span = expression.span;
+    let operand = std::mem::replace(expression, Expression::new(ExpressionKind::Literal(Literal::Null), span));
+    *expression = Expression {
+        kind: ExpressionKind::ImplicitCast(Box::new(operand), target.clone()),
+        span,
+        expr_type: Some(target),
+    };
+}
+
+/// The digits of a `long double` initializer that is a constant as written,
+/// possibly signed, so folding it does not round it to `double`
+fn long_double_digits(expression: &Expression) -> Option<String> {
+    match &expression.kind {
+        ExpressionKind::Literal(Literal::Float { digits, .. }) => digits.clone(),
+        ExpressionKind::UnaryOp(UnaryOperator::Plus, operand) => long_double_digits(operand),
+        ExpressionKind::UnaryOp(UnaryOperator::Negate, operand) => match long_double_digits(operand)? {
+            digits if digits.starts_with('-') => Some(digits[1..].to_string()),
+            digits => Some(format!("-{}", digits)),
+        },
+        _ => None,
+    }
+}
+
+/// Find `member` in a struct or union definition, searching anonymous
+/// members depth-first
+fn find_member<'a>(
+    structs: &'a HashMap<String, Struct>,
+    definition: &'a Struct,
+    member: &str,
+) -> Option<Vec<&'a Field>> {
+    for field in &definition.fields {
+        if field.is_anonymous() {
+            let inner = structs.get(&field.name)?;
+            if let Some(mut path) = find_member(structs, inner, member) {
+                path.insert(0, field);
+                return Some(path);
+            }
+        } else if field.name == member {
+            return Some(vec![field]);
+        }
+    }
+    None
+}
+
+/// Whether an expression designates an object
+fn is_lvalue(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::Variable(_) => !matches!(expression.ty(), Type::Function(..)),
+        ExpressionKind::Pointer(_) | ExpressionKind::Index(..) => true,
+        ExpressionKind::Literal(Literal::String(..)) | ExpressionKind::CompoundLiteral(..) => true,
+        ExpressionKind::Member(base, _) => is_lvalue(base),
+        _ => false,
+    }
+}
+
+/// Type compatibility; arrays of unknown size are compatible with any size
+fn compatible(a: &Type, b: &Type) -> bool {
+    match (a, b) {
+        (Type::Pointer(a), Type::Pointer(b)) => compatible(a, b),
+        (Type::Qualified(a, a_qualifiers), Type::Qualified(b, b_qualifiers)) => {
+            a_qualifiers == b_qualifiers && compatible(a, b)
+        }
+        // An enumerated type is compatible with its underlying integer type
+        (Type::Enum(_, underlying), other) | (other, Type::Enum(_, underlying)) if !matches!(other, Type::Enum(..)) => {
+            compatible(underlying, other)
+        }
+        (Type::Array(a, a_size), Type::Array(b, b_size)) => {
+            compatible(a, b) && (a_size.is_none() || b_size.is_none() || a_size == b_size)
+        }
+        // The length of a variable length array is only known at run time
+        (Type::VariableArray(a, _), Type::Array(b, _) | Type::VariableArray(b, _))
+        | (Type::Array(a, _), Type::VariableArray(b, _)) => compatible(a, b),
+        (Type::Function(a_return, a_parameters, a_variadic), Type::Function(b_return, b_parameters, b_variadic)) => {
+            a_variadic == b_variadic
+                && compatible(a_return, b_return)
+                && a_parameters.len() == b_parameters.len()
+                && a_parameters.iter().zip(b_parameters).all(|(a, b)| compatible(a, b))
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer;
+    use crate::compiler::parser;
+    use crate::compiler::source::FileId;
+    use crate::compiler::target::test_target;
+
+    fn analyze_all(source: &str) -> Result<AST, Vec<CompileError>> {
+        let tokens = lexer::tokenize(source, FileId(0)).expect("lexing failed");
+        let mut ast = parser::parse(tokens, &test_target()).expect("parsing failed");
+        analyze(&mut ast, &test_target())?;
+        Ok(ast)
+    }
+
+    /// Analyze `source`, keeping only the first error
+    fn analyze_source(source: &str) -> Result<AST, CompileError> {
+        analyze_all(source).map_err(|errors| errors.into_iter().next().unwrap())
+    }
+
+    fn analyze_error(source: &str) -> String {
+        analyze_source(source).expect_err("analysis should fail").message
+    }
+
+    fn analyze_errors(source: &str) -> Vec<String> {
+        let errors = analyze_all(source).expect_err("analysis should fail");
+        errors.into_iter().map(|error| error.message).collect()
+    }
+
+    /// Render an analyzed expression with the type of every node
+    fn typed(expression: &Expression) -> String {
+        let inner = match &expression.kind {
+            ExpressionKind::Literal(Literal::Int(value)) => value.to_string(),
+            ExpressionKind::Literal(Literal::Float { value, .. }) => format!("Float({:?})", value),
+            ExpressionKind::Literal(Literal::String(Encoding::Plain, units)) => {
+                let bytes: Vec<u8> = units.iter().map(|&unit| unit as u8).collect();
+                format!("String({:?})", String::from_utf8_lossy(&bytes))
+            }
+            ExpressionKind::Literal(other) => format!("{:?}", other),
+            ExpressionKind::Variable(name) => name.clone(),
+            ExpressionKind::BinaryOp(lhs, operator, rhs) => format!("{:?} {} {}", operator, typed(lhs), typed(rhs)),
+            ExpressionKind::UnaryOp(operator, operand) => format!("{:?} {}", operator, typed(operand)),
+            ExpressionKind::Call(callee, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(typed).collect();
+                // A function called by name is shown by its name
+                let callee = match &callee.kind {
+                    ExpressionKind::ImplicitCast(operand, _) => match &operand.kind {
+                        ExpressionKind::Variable(name) => name.clone(),
+                        _ => typed(callee),
+                    },
+                    _ => typed(callee),
+                };
+                format!("call {} {}", callee, arguments.join(" "))
+            }
+            ExpressionKind::Cast(operand, _) => format!("cast {}", typed(operand)),
+            ExpressionKind::Index(base, index) => format!("index {} {}", typed(base), typed(index)),
+            ExpressionKind::Member(base, member) => format!("member {} {}", typed(base), member),
+            ExpressionKind::Pointer(operand) => format!("deref {}", typed(operand)),
+            ExpressionKind::Address(operand) => format!("addr {}", typed(operand)),
+            ExpressionKind::ImplicitCast(operand, _) => format!("implicit {}", typed(operand)),
+            ExpressionKind::Assignment(target, value) => format!("= {} {}", typed(target), typed(value)),
+            ExpressionKind::CompoundAssignment(target, operator, value, computation) => format!(
+                "{:?}= {} {} in {}",
+                operator,
+                typed(target),
+                typed(value),
+                computation.as_ref().unwrap()
+            ),
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                format!("? {} {} {}", typed(condition), typed(then), typed(otherwise))
+            }
+            ExpressionKind::Comma(lhs, rhs) => format!(", {} {}", typed(lhs), typed(rhs)),
+            // Only the size of a variable length array is left to compute
+            ExpressionKind::SizeOf(operand) => format!("sizeof {}", typed(operand)),
+            ExpressionKind::SizeOfType(operand) => format!("sizeof {}", operand),
+            ExpressionKind::AlignOf(_) => unreachable!("`_Alignof` is folded"),
+            ExpressionKind::Generic(..) => unreachable!("generic selections are replaced"),
+            ExpressionKind::CompoundLiteral(_, initializer) => format!("literal {}", typed_initializer(initializer)),
+            ExpressionKind::VaStart(list) => format!("va_start {}", typed(list)),
+            ExpressionKind::VaArg(list, _) => format!("va_arg {}", typed(list)),
+            ExpressionKind::VaEnd(list) => format!("va_end {}", typed(list)),
+            ExpressionKind::VaCopy(destination, source) => format!("va_copy {} {}", typed(destination), typed(source)),
+        };
+        format!("({}: {})", inner, expression.ty())
+    }
+
+    /// Render a resolved initializer, whose items have one designator each
+    fn typed_initializer(initializer: &Initializer) -> String {
+        match initializer {
+            Initializer::Expression(value) => typed(value),
+            Initializer::List(items, _) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| {
+                        let designator = match &item.designators[..] {
+                            [Designator::Index(index)] => format!("[{}]", index),
+                            [Designator::Member(member)] => format!(".{}", member),
+                            other => panic!("unresolved designators {:?}", other),
+                        };
+                        format!("{}={}", designator, typed_initializer(&item.initializer))
+                    })
+                    .collect();
+                format!("{{{}}}", items.join(" "))
+            }
+        }
+    }
+
+    const PRELUDE: &str = "struct point { int x; const int y; };\n\
+        int add(int a, int b);\n\
+        void f(int i, char c, double d, int *p, char *s, void *v, struct point pt, struct point *pp) {\n\
+        unsigned u = 1; long l = 2; unsigned char uc = 3; long double ld = 4;\n\
+        int a[3];\n";
+
+    /// Analyze `expression` as an expression statement inside `f`
+    fn expression_type(expression: &str) -> String {
+        let ast = analyze_source(&format!("{}{};\n}}", PRELUDE, expression)).expect("analysis failed");
+        let statements = &ast.functions["f"].body.as_ref().unwrap().statements;
+        match &statements.last().unwrap().kind {
+            StatementKind::Expression(expression) => typed(expression),
+            other => panic!("expected expression statement, got {:?}", other),
+        }
+    }
+
+    fn expression_error(statement: &str) -> String {
+        analyze_error(&format!("{}{};\n}}", PRELUDE, statement))
+    }
+
+    #[test]
+    fn test_literal_types() {
+        // Integer constants take the first type in C11's list that holds them
+        assert_eq!(expression_type("2147483647"), "(2147483647: int)");
+        assert_eq!(expression_type("2147483648"), "(2147483648: long)");
+        assert_eq!(expression_type("0x7FFFFFFF"), "(2147483647: int)");
+        assert_eq!(expression_type("0xFFFFFFFF"), "(4294967295: unsigned int)");
+        assert_eq!(expression_type("0b101u"), "(5: unsigned int)");
+        assert_eq!(expression_type("10l"), "(10: long)");
+        assert_eq!(expression_type("0xFFFFFFFFFFFFFFFFl"), "(-1: unsigned long)");
+        assert_eq!(expression_type("10ULL"), "(10: unsigned long long)");
+        assert_eq!(expression_type("18446744073709551615"), "(-1: unsigned long long)");
+        assert_eq!(expression_type("1.5f"), "(Float(1.5): float)");
+        assert_eq!(expression_type("1.5L"), "(Float(1.5): long double)");
+        // Character constants are folded to their value in their type
+        assert_eq!(expression_type("'ab'"), "(24930: int)");
+        assert_eq!(expression_type("'\\xff'"), "(-1: int)");
+        assert_eq!(expression_type("L'x'"), "(120: int)");
+        assert_eq!(expression_type("u'\\xffff'"), "(65535: unsigned short)");
+        assert_eq!(expression_type("U'x'"), "(120: unsigned int)");
+        // Strings are arrays of their encoding's code units
+        assert_eq!(
+            expression_type("u\"ab\""),
+            "(implicit (String(Utf16, [97, 98]): unsigned short [3]): unsigned short *)"
+        );
+        assert_eq!(
+            expression_type("\"a\\0b\""),
+            "(implicit (String(\"a\\0b\"): char [4]): char *)"
+        );
+
+        let ast = analyze_source("int w[] = L\"abc\"; unsigned short u[5] = {u\"ab\"};").expect("analysis failed");
+        let types: Vec<String> = ast.global_variables.iter().map(|(variable, _)| variable.var_type.to_string()).collect();
+        assert_eq!(types, ["int [4]", "unsigned short [5]"]);
+        for (source, message) in [
+            ("char s[] = L\"a\";", "initializing char array with wide string literal"),
+            ("int s[] = \"a\";", "initializing wide char array with non-wide string literal"),
+            ("int s[] = u\"a\";", "initializing wide char array with incompatible wide string literal"),
+            ("int s[2] = L\"ab\\0\";", "initializer-string for char array is too long"),
+        ] {
+            assert_eq!(analyze_error(source), message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_usual_arithmetic_conversions() {
+        assert_eq!(
+            expression_type("c + c"),
+            "(Add (implicit (c: char): int) (implicit (c: char): int): int)"
+        );
+        assert_eq!(
+            expression_type("i * d"),
+            "(Multiply (implicit (i: int): double) (d: double): double)"
+        );
+        assert_eq!(expression_type("c << i"), "(LeftShift (implicit (c: char): int) (i: int): int)");
+        assert_eq!(expression_type("-c"), "(Negate (implicit (c: char): int): int)");
+        assert_eq!(expression_type("d < i"), "(LessThan (d: double) (implicit (i: int): double): int)");
+        assert_eq!(expression_type("'a'"), "(97: int)");
+        assert_eq!(expression_type("1.5"), "(Float(1.5): double)");
+    }
+
+    #[test]
+    fn test_signedness_and_width_conversions() {
+        assert_eq!(
+            expression_type("u + i"),
+            "(Add (u: unsigned int) (implicit (i: int): unsigned int): unsigned int)"
+        );
+        assert_eq!(expression_type("l * u"), "(Multiply (l: long) (implicit (u: unsigned int): long): long)");
+        assert_eq!(expression_type("uc - 1"), "(Subtract (implicit (uc: unsigned char): int) (1: int): int)");
+        assert_eq!(expression_type("u >> l"), "(RightShift (u: unsigned int) (l: long): unsigned int)");
+        assert_eq!(
+            expression_type("ld / l"),
+            "(Divide (ld: long double) (implicit (l: long): long double): long double)"
+        );
+        assert_eq!(expression_type("5000000000"), "(5000000000: long)");
+        assert_eq!(
+            expression_type("u == -1"),
+            "(Equal (u: unsigned int) (implicit (Negate (1: int): int): unsigned int): int)"
+        );
+    }
+
+    #[test]
+    fn test_pointers_and_arrays() {
+        assert_eq!(expression_type("p + 1"), "(Add (p: int *) (1: int): int *)");
+        assert_eq!(expression_type("1 + p"), "(Add (1: int) (p: int *): int *)");
+        assert_eq!(expression_type("p - p"), "(Subtract (p: int *) (p: int *): long)");
+        assert_eq!(
+            expression_type("a[c]"),
+            "(index (implicit (a: int [3]): int *) (implicit (c: char): int): int)"
+        );
+        assert_eq!(expression_type("*a"), "(deref (implicit (a: int [3]): int *): int)");
+        assert_eq!(expression_type("&a"), "(addr (a: int [3]): int (*)[3])");
+        assert_eq!(expression_type("p == 0"), "(Equal (p: int *) (implicit (0: int): int *): int)");
+        assert_eq!(expression_type("v != p"), "(NotEqual (v: void *) (implicit (p: int *): void *): int)");
+        assert_eq!(
+            expression_type("\"hi\""),
+            "(implicit (String(\"hi\"): char [3]): char *)"
+        );
+    }
+
+    #[test]
+    fn test_variable_length_arrays() {
+        let ast = analyze_source(
+            "int f(int n, int m[][n]) {\n\
+                 int v[n][2], fixed[sizeof(int) * 2];\n\
+                 v[1][0];\n\
+                 m[1];\n\
+                 sizeof v;\n\
+                 sizeof(char[n + 1]);\n\
+                 return sizeof fixed + _Alignof(double[n]);\n\
+             }",
+        )
+        .expect("analysis failed");
+        let function = &ast.functions["f"];
+        assert_eq!(function.parameters[1].var_type.to_string(), "int (*)[*]");
+        let statements = &function.body.as_ref().unwrap().statements;
+        let typed_statement = |index: usize| match &statements[index].kind {
+            StatementKind::Expression(expression) | StatementKind::Return(Some(expression)) => typed(expression),
+            other => panic!("expected expression statement, got {:?}", other),
+        };
+        let declared: Vec<String> = statements[..2]
+            .iter()
+            .map(|declaration| match &declaration.kind {
+                StatementKind::Declaration(variable, _) => variable.var_type.to_string(),
+                other => panic!("expected declaration, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(declared, ["int [*][2]", "int [8]"]);
+        assert_eq!(
+            typed_statement(2),
+            "(index (implicit (index (implicit (v: int [*][2]): int (*)[2]) (1: int): int [2]): int *) (0: int): int)"
+        );
+        assert_eq!(typed_statement(3), "(implicit (index (m: int (*)[*]) (1: int): int [*]): int *)");
+        assert_eq!(typed_statement(4), "(sizeof (v: int [*][2]): unsigned long)");
+        assert_eq!(typed_statement(5), "(sizeof char [*]: unsigned long)");
+        assert_eq!(typed_statement(6), "(implicit (Add (32: unsigned long) (8: unsigned long): unsigned long): int)");
+
+        let cases = [
+            ("int n; int a[n];", "variable length array declaration not allowed at file scope"),
+            ("int n; int (*p)[n];", "variable length array declaration not allowed at file scope"),
+            (
+                "void f(int n) { static int a[n]; }",
+                "variable length array declaration cannot have `static` storage duration",
+            ),
+            (
+                "void f(int n) { extern int a[n]; }",
+                "variable length array declaration cannot have `extern` linkage",
+            ),
+            ("void f(int n) { int a[n] = {1}; }", "variable-sized object may not be initialized"),
+            ("void f(double d) { int a[d]; }", "size of array has non-integer type `double`"),
+            ("void f(int n) { struct s { int a[n]; }; }", "fields must have a constant size"),
+            ("void f(int n) { int *p = (int[n]){1}; }", "compound literal has variable-length array type"),
+            ("void f(int n) { int a[n]; a = 0; }", "array type `int [*]` is not assignable"),
+            (
+                "void f(int n) { int b[3]; char (*p)[n] = &b; }",
+                "initializing `char (*)[*]` with an expression of incompatible type `int (*)[3]`",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_members_and_calls() {
+        assert_eq!(expression_type("pt.x"), "(member (pt: struct point) x: int)");
+        assert_eq!(
+            expression_type("pp->y"),
+            "(implicit (member (deref (pp: struct point *): struct point) y: const int): int)"
+        );
+        assert_eq!(
+            expression_type("add(c, d)"),
+            "(call add (implicit (c: char): int) (implicit (d: double): int): int)"
+        );
+        assert_eq!(expression_type("i++"), "(PostIncrement (i: int): int)");
+        assert_eq!(expression_type("(char *)v"), "(cast (v: void *): char *)");
+    }
+
+    #[test]
+    fn test_calls_through_function_pointers() {
+        let ast = analyze_source(
+            "struct ops { int (*apply)(int); };\n\
+             int twice(int x) { return 2 * x; }\n\
+             int f(struct ops *ops, int (*table[2])(int)) {\n\
+                 int (*fp)(int) = twice;\n\
+                 fp(1);\n\
+                 (*fp)(2);\n\
+                 ops->apply(3);\n\
+                 table[1](4);\n\
+                 return (&twice)(5);\n\
+             }",
+        )
+        .expect("analysis failed");
+        let statements = &ast.functions["f"].body.as_ref().unwrap().statements;
+        let typed_statement = |index: usize| match &statements[index].kind {
+            StatementKind::Expression(expression) | StatementKind::Return(Some(expression)) => typed(expression),
+            other => panic!("expected expression statement, got {:?}", other),
+        };
+        assert_eq!(typed_statement(1), "(call (fp: int (*)(int)) (1: int): int)");
+        assert_eq!(
+            typed_statement(2),
+            "(call (implicit (deref (fp: int (*)(int)): int (int)): int (*)(int)) (2: int): int)"
+        );
+        assert_eq!(
+            typed_statement(3),
+            "(call (member (deref (ops: struct ops *): struct ops) apply: int (*)(int)) (3: int): int)"
+        );
+        assert_eq!(
+            typed_statement(4),
+            "(call (index (table: int (**)(int)) (1: int): int (*)(int)) (4: int): int)"
+        );
+        assert_eq!(typed_statement(5), "(call (addr (twice: int (int)): int (*)(int)) (5: int): int)");
+
+        let cases = [
+            ("void *v; void f(void) { v(); }", "called object type `void *` is not a function or function pointer"),
+            (
+                "int (*fp)(int); void f(void) { fp(); }",
+                "too few arguments to function call, expected 1, have 0",
+            ),
+            (
+                "int (*fp)(int *); void f(void) { fp(1.5); }",
+                "passing `double` to parameter of incompatible type `int *`",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_unions_enums_and_bit_fields() {
+        let ast = analyze_source(
+            "struct flags { unsigned ready : 1; union { int x; const float y; }; };\n\
+             enum color { RED, GREEN };\n\
+             int f(struct flags *fl, enum color c) { unsigned *u = &c; return fl->x + c + fl->ready; }",
+        )
+        .expect("analysis failed");
+        let statements = &ast.functions["f"].body.as_ref().unwrap().statements;
+        match &statements[1].kind {
+            StatementKind::Return(Some(value)) => assert_eq!(
+                typed(value),
+                "(implicit (Add (Add (implicit (member (member (deref (fl: struct flags *): struct flags) \
+                 (anonymous union #1): (anonymous union #1)) x: int): unsigned int) (implicit (c: enum color): \
+                 unsigned int): unsigned int) (member (deref (fl: struct flags *): struct flags) ready: unsigned int): \
+                 unsigned int): int)"
+            ),
+            other => panic!("expected return statement, got {:?}", other),
+        }
+
+        let cases = [
+            ("struct s { float f : 1; };", "bit-field `f` has non-integral type `float`"),
+            ("struct s { int x : 40; };", "width of bit-field `x` (40 bits) exceeds the width of its type (32 bits)"),
+            ("struct s { _Bool b : 2; };", "width of bit-field `b` (2 bits) exceeds the width of its type (1 bits)"),
+            ("union u { int n; int a[]; };", "flexible array member `a` in a union is not allowed"),
+            ("union u v;", "variable has incomplete type `union u`"),
+            ("struct s { int b : 1; }; int *f(struct s *p) { return &p->b; }", "address of bit-field requested"),
+            ("union u { int a; }; int f(union u v) { return v.b; }", "no member named `b` in `union u`"),
+            (
+                "struct s { union { const int a; }; }; void f(struct s *p) { p->a = 1; }",
+                "cannot assign to read-only member `a`",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_assignment_and_conditional_expressions() {
+        assert_eq!(
+            expression_type("i = c = d"),
+            "(= (i: int) (implicit (= (c: char) (implicit (d: double): char): char): int): int)"
+        );
+        assert_eq!(expression_type("c += d"), "(Add= (c: char) (d: double) in double: char)");
+        assert_eq!(expression_type("p -= i"), "(Subtract= (p: int *) (i: int) in int *: int *)");
+        assert_eq!(
+            expression_type("u <<= c"),
+            "(LeftShift= (u: unsigned int) (implicit (c: char): int) in unsigned int: unsigned int)"
+        );
+        assert_eq!(expression_type("i ? p : 0"), "(? (i: int) (p: int *) (implicit (0: int): int *): int *)");
+        assert_eq!(expression_type("i ? v : p"), "(? (i: int) (v: void *) (implicit (p: int *): void *): void *)");
+        assert_eq!(expression_type("i ? c : d"), "(? (i: int) (implicit (c: char): double) (d: double): double)");
+        assert_eq!(expression_type("i, pt"), "(, (i: int) (pt: struct point): struct point)");
+        assert_eq!(expression_type("+c"), "(Plus (implicit (c: char): int): int)");
+    }
+
+    #[test]
+    fn test_sizeof_alignof_and_generic() {
+        assert_eq!(expression_type("sizeof a"), "(12: unsigned long)");
+        assert_eq!(expression_type("sizeof(struct point)"), "(8: unsigned long)");
+        assert_eq!(expression_type("_Alignof(double)"), "(8: unsigned long)");
+        assert_eq!(expression_type("_Generic(c, int: 1, char: p, default: d)"), "(p: int *)");
+        assert_eq!(expression_type("_Generic(\"s\", char *: 1, default: 2)"), "(1: int)");
+        assert_eq!(expression_type("_Generic(pt, int: 1, default: 2)"), "(2: int)");
+    }
+
+    #[test]
+    fn test_compound_literals_and_initializer_lists() {
+        assert_eq!(expression_type("(int){c}"), "(literal (implicit (c: char): int): int)");
+        assert_eq!(
+            expression_type("(int[]){1, [4] = 2, 3}"),
+            "(implicit (literal {[0]=(1: int) [4]=(2: int) [5]=(3: int)}: int [6]): int *)"
+        );
+        assert_eq!(
+            expression_type("(struct point){.y = 2, .x = 1}"),
+            "(literal {.x=(1: int) .y=(2: int)}: struct point)"
+        );
+        // Braces may be elided, and a designator continues from where it points
+        assert_eq!(
+            expression_type("(struct point[]){1, 2, {3}, [3].y = 4, 5}"),
+            "(implicit (literal {[0]={.x=(1: int) .y=(2: int)} [1]={.x=(3: int)} [3]={.y=(4: int)} [4]={.x=(5: int)}}: struct point [5]): struct point *)"
+        );
+        assert_eq!(
+            expression_type("(char[2][3]){\"ab\", {'c'}}"),
+            "(implicit (literal {[0]=(String(\"ab\"): char [3]) [1]={[0]=(implicit (99: int): char)}}: char [2][3]): char (*)[3])"
+        );
+        assert_eq!(
+            expression_type("(struct point[2]){[1] = pt, [0].x = 1}"),
+            "(implicit (literal {[0]={.x=(1: int)} [1]=(pt: struct point)}: struct point [2]): struct point *)"
+        );
+        assert_eq!(
+            expression_type("(struct point[1]){[0] = pt, [0].y = 9}"),
+            "(implicit (literal {[0]={.y=(9: int)}}: struct point [1]): struct point *)"
+        );
+
+        let ast = analyze_source(
+            "struct s { int n; union { char c; float f; }; int : 3; int last; };
+             void f(void) { (struct s){1, 2, 3}; (struct s){.f = 1, .c = 2, 4}; }",
+        )
+        .expect("analysis failed");
+        let rendered: Vec<String> = ast.functions["f"].body.as_ref().unwrap().statements
+            .iter()
+            .map(|statement| match &statement.kind {
+                StatementKind::Expression(literal) => typed(literal),
+                other => panic!("unexpected statement {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "(literal {.n=(1: int) .(anonymous union #1)={.c=(implicit (2: int): char)} .last=(3: int)}: struct s)",
+                "(literal {.(anonymous union #1)={.c=(implicit (2: int): char)} .last=(4: int)}: struct s)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_initializer_and_operand_errors() {
+        let cases = [
+            ("(int){}", "scalar initializer cannot be empty"),
+            ("(int){1, 2}", "excess elements in scalar initializer"),
+            ("(int){.x = 1}", "designator in initializer for scalar type `int`"),
+            ("(int[2]){1, 2, 3}", "excess elements in array initializer"),
+            ("(struct point){1, 2, 3}", "excess elements in struct initializer"),
+            ("(int[2]){[2] = 1}", "array designator index (2) exceeds array bound (2)"),
+            ("(struct point){.z = 1}", "field designator `z` does not refer to any field in type `struct point`"),
+            ("(int[1]){.x = 1}", "field designator cannot initialize a non-struct, non-union type `int [1]`"),
+            ("(struct point){[0] = 1}", "array designator cannot initialize non-array type `struct point`"),
+            ("(struct point){pt}", "initializing `int` with an expression of incompatible type `struct point`"),
+            ("(struct nope){1}", "compound literal has incomplete type `struct nope`"),
+            ("sizeof(void)", "invalid application of `sizeof` to an incomplete type `void`"),
+            ("sizeof add", "invalid application of `sizeof` to a function type"),
+            ("_Alignof(struct nope)", "invalid application of `_Alignof` to an incomplete type `struct nope`"),
+            (
+                "_Generic(d, int: 1, char: 2)",
+                "controlling expression type `double` not compatible with any generic association type",
+            ),
+            (
+                "_Generic(i, int: 1, signed: 2)",
+                "type `int` in generic association compatible with previously specified type `int`",
+            ),
+            ("_Generic(i, void: 1)", "type `void` in generic association incomplete"),
+            ("i ? p : s", "pointer type mismatch (`int *` and `char *`)"),
+            ("i ? pt : 1", "incompatible operand types (`struct point` and `int`)"),
+            ("i ? p : 1", "incompatible operand types (`int *` and `int`)"),
+            ("pt += 1", "invalid operands to binary expression (`struct point` and `int`)"),
+            ("p *= 2", "invalid operands to binary expression (`int *` and `int`)"),
+            ("a += 1", "array type `int [3]` is not assignable"),
+        ];
+        for (statement, expected) in cases {
+            assert_eq!(expression_error(statement), expected, "for {}", statement);
+        }
+        assert_eq!(
+            analyze_error("struct b { int f : 3; };\nunsigned long f(struct b x) { return sizeof x.f; }"),
+            "invalid application of `sizeof` to bit-field"
+        );
+        assert_eq!(
+            analyze_error("struct v { int n; int d[]; };\nvoid f(void) { (struct v){1, {2}}; }"),
+            "initialization of flexible array member is not allowed"
+        );
+    }
+
+    #[test]
+    fn test_static_initializers_are_folded() {
+        let ast = analyze_source(
+            "struct point { int x, y; };\n\
+             int g;\n\
+             int numbers[] = {1, 2, 3, [7] = 4};\n\
+             char names[][4] = {\"ab\", {'c', 'd'}};\n\
+             struct point points[2] = {{1}, [1].y = -1};\n\
+             unsigned char byte = 300;\n\
+             float ratio = 1 / 3.0;\n\
+             int truncated = 2.5;\n\
+             long size = sizeof numbers;\n\
+             int *p = &g;\n\
+             int *q = numbers + 2;\n\
+             int *r = &numbers[1];\n\
+             int main(void) { static int count = 1 + 1; int local[3] = {g, g + 1}; return 0; }",
+        )
+        .expect("analysis failed");
+        let globals: Vec<String> = ast
+            .global_variables
+            .iter()
+            .skip(1)
+            .map(|(variable, initializer)| {
+                format!("{} {}", variable.var_type, typed_initializer(initializer.as_ref().unwrap()))
+            })
+            .collect();
+        assert_eq!(
+            globals,
+            vec![
+                "int [8] {[0]=(1: int) [1]=(2: int) [2]=(3: int) [7]=(4: int)}",
+                "char [2][4] {[0]=(String(\"ab\"): char [3]) [1]={[0]=(99: char) [1]=(100: char)}}",
+                "struct point [2] {[0]={.x=(1: int)} [1]={.y=(-1: int)}}",
+                "unsigned char (44: unsigned char)",
+                "float (Float(0.3333333432674408): float)",
+                "int (2: int)",
+                "long (32: long)",
+                "int * (addr (g: int): int *)",
+                "int * (Add (implicit (numbers: int [8]): int *) (2: int): int *)",
+                "int * (addr (index (implicit (numbers: int [8]): int *) (1: int): int): int *)",
+            ]
+        );
+
+        let rendered: Vec<String> = ast.functions["main"].body.as_ref().unwrap().statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Declaration(_, Some(initializer)) => Some(typed_initializer(initializer)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rendered, vec!["(2: int)", "{[0]=(g: int) [1]=(Add (g: int) (1: int): int)}"]);
+
+        let cases = [
+            ("int g; int x = g;", "read of variable `g`"),
+            ("int f(void); int x = f();", "function call"),
+            ("int g; int *p = &g + (long)&g;", "arithmetic on an address"),
+            ("void f(int a) { static int x = a; }", "read of variable `a`"),
+            ("void f(void) { int a; static int *p = &a; }", "address of `a`, which does not have static storage duration"),
+            ("int x = (1, 2);", "comma operator"),
+            ("int x = 1 / 0;", "division by zero"),
+            ("struct s { int a; } v; struct s w = v;", "read of variable `v`"),
+        ];
+        for (source, reason) in cases {
+            assert_eq!(
+                analyze_error(source),
+                format!("initializer element is not a compile-time constant: {}", reason),
+                "for {}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_signed_overflow_in_constants() {
+        analyze_source("unsigned u = 4294967295u + 1; int top = 1 << 31; long l = 2147483647 + 1L;")
+            .expect("analysis failed");
+        let cases = [
+            ("int x = 2147483647 + 1;", "value 2147483648 is outside the range of `int`"),
+            ("int x = (-2147483647 - 1) / -1;", "value 2147483648 is outside the range of `int`"),
+            ("int x = -(-2147483647 - 1);", "value 2147483648 is outside the range of `int`"),
+            ("long x = 9223372036854775807L * -2;", "value -18446744073709551614 is outside the range of `long`"),
+            ("void f(int n) { switch (n) { case 65536 * 65536: ; } }", "value 4294967296 is outside the range of `int`"),
+            ("void f(void) { char a[2147483647 + 2]; }", "value 2147483649 is outside the range of `int`"),
+        ];
+        for (source, reason) in cases {
+            assert_eq!(
+                analyze_error(source),
+                format!("integer overflow in constant expression: {}", reason),
+                "for {}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_conversion_as_if_by_assignment() {
+        let ast = analyze_source(
+            "double g = 1;\nint *h(void *v) { int *p = v; char c = 300; c = p == 0; return v; }",
+        )
+        .expect("analysis failed");
+        assert_eq!(typed_initializer(ast.global_variables[0].1.as_ref().unwrap()), "(Float(1.0): double)");
+
+        let statements = &ast.functions["h"].body.as_ref().unwrap().statements;
+        let rendered: Vec<String> = statements
+            .iter()
+            .map(|statement| match &statement.kind {
+                StatementKind::Declaration(_, Some(value)) => typed_initializer(value),
+                StatementKind::Return(Some(value)) => typed(value),
+                StatementKind::Expression(assignment) => match &assignment.kind {
+                    ExpressionKind::Assignment(_, value) => typed(value),
+                    other => panic!("unexpected expression {:?}", other),
+                },
+                other => panic!("unexpected statement {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "(implicit (v: void *): int *)",
+                "(implicit (300: int): char)",
+                "(implicit (Equal (p: int *) (implicit (0: int): int *): int): char)",
+                "(implicit (v: void *): int *)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        analyze_source(
+            "int x;\nint f(int y) {\n  int z = x + y;\n  { double x = z; z = x; }\n  for (int i = 0, j = i; i < j; i++) { int i = 2; }\n  return later(z);\n}\nint later(int v) { return v; }",
+        )
+        .expect("analysis failed");
+
+        assert_eq!(
+            analyze_error("int f(void) { { int inner = 1; } return inner; }"),
+            "use of undeclared identifier `inner`"
+        );
+        assert_eq!(
+            analyze_error("int f(void) { for (int i = 0; i < 3; i++) {} return i; }"),
+            "use of undeclared identifier `i`"
+        );
+        assert_eq!(analyze_error("int f(int a) { int a; return a; }"), "redefinition of `a`");
+        assert_eq!(analyze_error("int f(int a, int a) { return a; }"), "redefinition of `a`");
+    }
+
+    #[test]
+    fn test_qualified_lvalues() {
+        let source = "struct point { int x; };\n\
+            void f(const char *s, char *const t, const struct point *cp, volatile int *vp) {\n";
+        let typed_statement = |statement: &str| {
+            let ast = analyze_source(&format!("{}{};\n}}", source, statement)).expect("analysis failed");
+            match &ast.functions["f"].body.as_ref().unwrap().statements[0].kind {
+                StatementKind::Expression(expression) => typed(expression),
+                other => panic!("expected expression statement, got {:?}", other),
+            }
+        };
+        assert_eq!(typed_statement("*s"), "(implicit (deref (s: const char *): const char): char)");
+        assert_eq!(typed_statement("t"), "(implicit (t: char *const): char *)");
+        assert_eq!(
+            typed_statement("&cp->x"),
+            "(addr (member (deref (cp: const struct point *): const struct point) x: const int): const int *)"
+        );
+        assert_eq!(
+            typed_statement("*vp + 1"),
+            "(Add (implicit (deref (vp: volatile int *): volatile int): int) (1: int): int)"
+        );
+
+        // Assigning a pointer to a less qualified one is accepted
+        analyze_source(&format!("{}char *c = s; void *v = vp; s = t; s[1] == t[1];\n}}", source))
+            .expect("analysis failed");
+
+        let error = |statement: &str| analyze_error(&format!("{}{};\n}}", source, statement));
+        assert_eq!(error("*s = 'a'"), "cannot assign to read-only location");
+        assert_eq!(error("s[0]++"), "cannot increment read-only location");
+        assert_eq!(error("t = s"), "cannot assign to read-only variable `t`");
+        assert_eq!(error("cp->x = 1"), "cannot assign to read-only location");
+        assert_eq!(
+            analyze_error("struct point { int x; };\nconst struct point origin;\nvoid f(void) { origin.x = 1; }"),
+            "cannot assign to read-only variable `origin`"
+        );
+    }
+
+    #[test]
+    fn test_storage_and_linkage() {
+        analyze_source(
+            "static int counter;\nextern int counter;\nextern int missing[];\nextern struct opaque handle;\n\
+             _Thread_local int slot;\nextern _Thread_local int slot;\n\
+             int f(register int n) { static int calls; extern int counter; register int r = n; return calls + counter + r; }",
+        )
+        .expect("analysis failed");
+
+        let cases = [
+            ("int x;\nstatic int x;", "static declaration of `x` follows non-static declaration"),
+            ("static int x;\nint x;", "non-static declaration of `x` follows static declaration"),
+            ("int x;\n_Thread_local int x;", "thread-local declaration of `x` follows non-thread-local declaration"),
+            ("_Thread_local int x;\nextern int x;", "non-thread-local declaration of `x` follows thread-local declaration"),
+            ("const int x;\nint x;", "conflicting types for `x` (`const int` and `int`)"),
+            ("int x;\nvoid f(void) { extern double x; }", "conflicting types for `x` (`int` and `double`)"),
+            ("void f(void) { extern int x = 1; }", "declaration of block scope identifier with linkage cannot have an initializer"),
+            ("int f(register int n) { return *&n; }", "address of register variable `n` requested"),
+            ("inline int main(void) { return 0; }", "`main` is not allowed to be declared inline"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_global_declarations() {
+        let ast = analyze_source("int x;\nint x = 2;\nchar name[] = \"tlstuc\";\nint x;").expect("analysis failed");
+        assert_eq!(ast.global_variables[2].0.var_type.to_string(), "char [7]");
+
+        assert_eq!(analyze_error("int x = 1;\nint x = 2;"), "redefinition of `x`");
+        assert_eq!(analyze_error("int x;\ndouble x;"), "conflicting types for `x` (`int` and `double`)");
+        assert_eq!(
+            analyze_error("int f(void);\nint f = 1;"),
+            "redefinition of `f` as a different kind of symbol"
+        );
+    }
+
+    #[test]
+    fn test_redeclarations_point_at_the_previous_one() {
+        let cases = [
+            ("int x = 1;\nint x = 2;", "previous definition is here"),
+            ("int x;\ndouble x;", "previous declaration is here"),
+            ("static int x;\nint x;", "previous declaration is here"),
+            ("int f(void);\nint f = 1;", "previous definition is here"),
+            ("void f(void) { int a;\nint a; }", "previous definition is here"),
+            ("void f(void) { a: ;\na: ; }", "previous definition is here"),
+            ("void f(int n) { switch (n) { case 1:\ncase 2 - 1: ; } }", "previous case defined here"),
+            ("void f(int n) { switch (n) { default:\ndefault: ; } }", "previous case defined here"),
+        ];
+        for (source, message) in cases {
+            let error = analyze_source(source).expect_err(source);
+            assert_eq!(error.span.start.line, 2, "for {}", source);
+            assert_eq!(error.labels.len(), 1, "for {}", source);
+            assert_eq!(error.labels[0].message, message, "for {}", source);
+            assert_eq!(error.labels[0].span.start.line, 1, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_analysis_goes_on_after_an_error() {
+        let source = "int g = h;\n\
+            int f(void) { undeclared(); int a[] ; if (\"\" * 2) { break; } return a[0]; }\n\
+            void s(int n) { switch (n) { default: default: continue; } goto out; }";
+        assert_eq!(
+            analyze_errors(source),
+            [
+                "use of undeclared identifier `h`",
+                "call to undeclared function `undeclared`",
+                "definition of variable with array type needs an explicit size or an initializer",
+                "invalid operands to binary expression (`char *` and `int`)",
+                "`break` statement not in loop or switch statement",
+                "multiple default labels in one switch",
+                "`continue` statement not in loop statement",
+                "use of undeclared label `out`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_declaration_errors() {
+        let cases = [
+            ("void v;", "variable has incomplete type `void`"),
+            ("struct missing m;", "variable has incomplete type `struct missing`"),
+            (
+                "int f(void) { int a[]; return 0; }",
+                "definition of variable with array type needs an explicit size or an initializer",
+            ),
+            ("char s[2] = \"abc\";", "initializer-string for char array is too long"),
+            ("int a[2] = 5;", "array initializer must be an initializer list or string literal"),
+            ("struct s { struct s inner; };", "field has incomplete type `struct s`"),
+            ("_Alignas(2) int a;", "requested alignment is less than minimum alignment of 4 for type `int`"),
+            (
+                "struct s { _Alignas(1) double d; };",
+                "requested alignment is less than minimum alignment of 8 for type `double`",
+            ),
+            ("struct b; struct a { struct b x; }; struct b { struct a y; };", "field has incomplete type `struct b`"),
+            ("struct s { int a[]; int b; };", "flexible array member `a` is not at the end of the struct"),
+            ("int f(int) { return 0; }", "parameter name omitted"),
+            ("int f(void)[3] { return 0; }", "function cannot return `int [3]`"),
+            ("int *f(int *p) { return (int (*)[3])p; }", "returning `int (*)[3]` from a function with incompatible result type `int *`"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_object_size_errors() {
+        let cases = [
+            ("int a[0x7fffffffffffffff];", "size of array is too large"),
+            ("int a[0x1fffffffffffffff];", "size of array is too large"),
+            ("unsigned long n = sizeof(int[0x4000000000000000]);", "size of array is too large"),
+            ("int f(int (*p)[0x4000000000000000]) { return 0; }", "size of array is too large"),
+            ("void f(void) { typedef char huge[0x2000000000000000]; }", "size of array is too large"),
+            ("struct s { char a[0x7fffffffffffffff]; char b[0x7fffffffffffffff]; };", "size of array is too large"),
+            ("struct s { char a[0x1000000000000000]; char b[0x1000000000000000]; };", "size of `struct s` is too large"),
+            ("struct s { char a[0x1000000000000000]; }; struct s b[2];", "size of array is too large"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+        assert!(analyze_source("char a[0x1fffffffffffffff]; int b[0x7ffffffffffffff];").is_ok());
+        assert_eq!(
+            analyze_errors("struct s { char a[0x7fffffffffffffff]; }; unsigned long n = sizeof(struct s);"),
+            ["size of array is too large", "size of `struct s` is too large"]
+        );
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        let cases = [
+            ("i = undefined", "use of undeclared identifier `undefined`"),
+            ("missing(1)", "call to undeclared function `missing`"),
+            ("i(1)", "called object type `int` is not a function or function pointer"),
+            ("add(1)", "too few arguments to function call, expected 2, have 1"),
+            ("add(1, 2, 3)", "too many arguments to function call, expected 2, have 3"),
+            ("add(1, p)", "passing `int *` to parameter of incompatible type `int`"),
+            ("i = pt", "assigning to `int` from incompatible type `struct point`"),
+            ("p = i", "assigning to `int *` from incompatible type `int`"),
+            ("p = s", "assigning to `int *` from incompatible type `char *`"),
+            ("int bad = pt", "initializing `int` with an expression of incompatible type `struct point`"),
+            ("a = p", "array type `int [3]` is not assignable"),
+            ("i + 1 = 2", "expression is not assignable"),
+            ("pt.y = 1", "cannot assign to read-only member `y`"),
+            ("pp->y++", "cannot increment read-only member `y`"),
+            ("pt.z", "no member named `z` in `struct point`"),
+            ("i.x", "member reference base type `int` is not a structure or union"),
+            ("*i", "indirection requires pointer operand (`int` invalid)"),
+            ("&(i + 1)", "cannot take the address of an rvalue of type `int`"),
+            ("pt + 1", "invalid operands to binary expression (`struct point` and `int`)"),
+            ("d % 2", "invalid operands to binary expression (`double` and `int`)"),
+            ("p < 0", "comparison between `int *` and `int`"),
+            ("p == s", "comparison of distinct pointer types (`int *` and `char *`)"),
+            ("v + 1", "arithmetic on a pointer to `void`"),
+            ("~d", "invalid argument type `double` to unary expression"),
+            ("(double)p", "cannot cast from `int *` to `double`"),
+            ("(struct point)i", "cannot cast from `int` to `struct point`"),
+            ("i[i]", "subscripted value is not an array or pointer"),
+            ("a[d]", "array subscript is not an integer"),
+            ("if (pt) {}", "statement requires expression of scalar type (`struct point` invalid)"),
+        ];
+        for (statement, expected) in cases {
+            assert_eq!(expression_error(statement), expected, "for {}", statement);
+        }
+    }
+
+    #[test]
+    fn test_variadic_calls_and_va_builtins() {
+        let ast = analyze_source(
+            "int printf(const char *format, ...);\n\
+             double sum(int count, ...) {\n\
+                 __builtin_va_list ap, copy;\n\
+                 __builtin_va_start(ap, count);\n\
+                 __builtin_va_copy(copy, ap);\n\
+                 double total = __builtin_va_arg(ap, double);\n\
+                 __builtin_va_end(ap);\n\
+                 char c = 'x'; float f = 1;\n\
+                 printf(\"%c %f\", c, f);\n\
+                 return total;\n\
+             }",
+        )
+        .expect("analysis failed");
+        let statements = &ast.functions["sum"].body.as_ref().unwrap().statements;
+        let typed_statement = |index: usize| match &statements[index].kind {
+            StatementKind::Expression(expression) => typed(expression),
+            other => panic!("expected expression statement, got {:?}", other),
+        };
+        assert_eq!(typed_statement(2), "(va_start (ap: __builtin_va_list): void)");
+        assert_eq!(typed_statement(3), "(va_copy (copy: __builtin_va_list) (ap: __builtin_va_list): void)");
+        assert_eq!(
+            typed_statement(8),
+            "(call printf (implicit (implicit (String(\"%c %f\"): char [6]): char *): const char *) \
+             (implicit (c: char): int) (implicit (f: float): double): int)"
+        );
+
+        let cases = [
+            (
+                "int f(int n, ...); void g(void) { f(); }",
+                "too few arguments to function call, expected at least 1, have 0",
+            ),
+            ("int f(int n, ...); void g(void) { f(1, g()); }", "argument may not have `void` type"),
+            (
+                "void f(int n) { __builtin_va_list ap; __builtin_va_start(ap, n); }",
+                "`va_start` used in function with fixed parameters",
+            ),
+            (
+                "void f(int n, ...) { int ap; __builtin_va_start(ap, n); }",
+                "expected an object of type `va_list`, found `int`",
+            ),
+            (
+                "void f(int n, ...) { __builtin_va_list ap; __builtin_va_arg(ap, struct s); }",
+                "second argument to `va_arg` is of incomplete type `struct s`",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_switch_and_jumps() {
+        let ast = analyze_source(
+            "int f(char c, int n) {\n\
+             switch (c) { case 'a': case 1 + 1: return 1; default: break; }\n\
+             while (n) { switch (n) { case 0: continue; } }\n\
+             goto done;\n\
+             done: return 0;\n\
+             }",
+        )
+        .expect("analysis failed");
+        match &ast.functions["f"].body.as_ref().unwrap().statements[0].kind {
+            StatementKind::Switch(condition, block) => {
+                assert_eq!(typed(condition), "(implicit (c: char): int)");
+                match &block.statements[0].kind {
+                    StatementKind::Case(value, _) => assert_eq!(typed(value), "(97: int)"),
+                    other => panic!("expected case label, got {:?}", other),
+                }
+            }
+            other => panic!("expected switch statement, got {:?}", other),
+        }
+
+        // Jumps that leave the scope of a variable length array, or stay
+        // inside it, are fine
+        analyze_source(
+            "void f(int n) {\n\
+             again: { int a[n]; a[0] = n; if (n--) goto again; goto inside; inside: ; }\n\
+             switch (n) { case 0: { int b[n]; b[0] = 0; } case 1: break; }\n\
+             }",
+        )
+        .expect("analysis failed");
+
+        let cases = [
+            ("void f(int n) { break; }", "`break` statement not in loop or switch statement"),
+            ("void f(int n) { switch (n) { default: continue; } }", "`continue` statement not in loop statement"),
+            ("void f(int n) { case 1: ; }", "`case` statement not in switch statement"),
+            ("void f(int n) { default: ; }", "`default` statement not in switch statement"),
+            ("void f(int n) { switch (n) { default: ; default: ; } }", "multiple default labels in one switch"),
+            ("void f(int n) { switch (n) { case 1: case 2 - 1: ; } }", "duplicate case value `1`"),
+            ("void f(unsigned char n) { switch (n) { case 1: case 4294967297: ; } }", "duplicate case value `1`"),
+            ("void f(int n) { switch (n) { case n: ; } }", "expression is not an integer constant expression: read of variable `n`"),
+            ("void f(int n) { switch (n) { case 1.0: ; } }", "expression is not an integer constant expression: expression has type `double`"),
+            ("void f(double d) { switch (d) {} }", "statement requires expression of integer type (`double` invalid)"),
+            ("void f(void) { goto out; }", "use of undeclared label `out`"),
+            ("void f(void) { a: ; a: ; }", "redefinition of label `a`"),
+            ("void f(int n) { goto l; int a[n]; l: ; }", "cannot jump from this goto statement to its label"),
+            ("void f(int n) { goto l; { int (*p)[n]; l: ; } }", "cannot jump from this goto statement to its label"),
+            ("void f(int n) { goto l; typedef int row[n]; l: ; }", "cannot jump from this goto statement to its label"),
+            ("void f(int n) { switch (n) { int a[n]; case 1: ; } }", "cannot jump from switch statement to this case label"),
+            ("void f(int n) { switch (n) { int a[n]; default: ; } }", "cannot jump from switch statement to this case label"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(analyze_error(source), expected, "for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_return_checks() {
+        assert_eq!(
+            analyze_error("void f(void) { return 1; }"),
+            "void function `f` should not return a value"
+        );
+        assert_eq!(analyze_error("int f(void) { return; }"), "non-void function `f` should return a value");
+        assert_eq!(
+            analyze_error("struct s { int a; };\nint f(struct s v) { return v; }"),
+            "returning `struct s` from a function with incompatible result type `int`"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Identifies a file registered in a `SourceMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct FileId(pub u32);
+
+/// A 1-based line/column position in a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Location {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location { line: 1, column: 1 }
+    }
+}
+
+/// Identifies a macro expansion recorded in a `SourceMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExpansionId(u32);
+
+/// A range of source text; `end` points just past the last character.
+/// Tokens produced by a macro expansion have the span of the invocation,
+/// along with the expansion they came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct Span {
+    pub file: FileId,
+    pub start: Location,
+    pub end: Location,
+    #[serde(skip)]
+    pub expansion: Option<ExpansionId>,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: Location, end: Location) -> Self {
+        Span {
+            file,
+            start,
+            end,
+            expansion: None,
+        }
+    }
+
+    /// Create a span from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        if self.file != other.file {
+            return self;
+        }
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+/// A secondary location shown with a diagnostic, such as an earlier
+/// declaration
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// An error attached to a location in the source, with related locations
+/// and explanations to show along with it
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message}")]
+pub struct CompileError {
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub remarks: Vec<Remark>,
+}
+
+/// Text shown after the excerpt of a diagnostic, in the order it was added
+#[derive(Debug, Clone)]
+pub enum Remark {
+    Note(String),
+    /// How to fix the error
+    Help(String),
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        CompileError {
+            message: message.into(),
+            span,
+            labels: Vec::new(),
+            remarks: Vec::new(),
+        }
+    }
+
+    /// Point at another location that explains the error
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.remarks.push(Remark::Note(note.into()));
+        self
+    }
+
+    /// Suggest how to fix the error
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.remarks.push(Remark::Help(help.into()));
+        self
+    }
+}
+
+#[derive(Debug)]
+struct SourceFile {
+    path: PathBuf,
+    text: String,
+}
+
+/// Where a token produced by a macro invocation came from
+#[derive(Debug)]
+pub struct MacroExpansion {
+    pub name: String,
+    /// The token of the macro's body it was replaced by: the token itself,
+    /// or the `#` that stringified it or the left operand of the `##` that
+    /// pasted it
+    pub spelling: Span,
+    /// The expansion the invocation itself came from, if any
+    pub parent: Option<ExpansionId>,
+}
+
+/// Owns the text of every file taking part in a compilation, and the macro
+/// expansions its tokens came from
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    expansions: Vec<MacroExpansion>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// Register a file and return its id
+    pub fn add_file(&mut self, path: &Path, text: String) -> FileId {
+        self.files.push(SourceFile {
+            path: path.to_path_buf(),
+            text,
+        });
+        FileId(self.files.len() as u32 - 1)
+    }
+
+    pub fn path(&self, file: FileId) -> &Path {
+        &self.files[file.0 as usize].path
+    }
+
+    pub fn text(&self, file: FileId) -> &str {
+        &self.files[file.0 as usize].text
+    }
+
+    /// The text of a 1-based line, without its line break
+    pub fn line(&self, file: FileId, line: u32) -> Option<&str> {
+        self.text(file).lines().nth(line.checked_sub(1)? as usize)
+    }
+
+    /// Record a token of the macro `name`'s body, spelled at `spelling`, that
+    /// an invocation spelled at `invocation` was replaced by
+    pub fn add_expansion(&mut self, name: &str, spelling: Span, invocation: Span) -> ExpansionId {
+        self.expansions.push(MacroExpansion {
+            name: name.to_string(),
+            spelling,
+            parent: invocation.expansion,
+        });
+        ExpansionId(self.expansions.len() as u32 - 1)
+    }
+
+    pub fn expansion(&self, id: ExpansionId) -> &MacroExpansion {
+        &self.expansions[id.0 as usize]
+    }
+}
@@ -0,0 +1,862 @@
+//! Properties of the compilation target that C's semantics depend on.
+//!
+//! Sizes and alignments come from the data layout of the LLVM target machine.
+//! The C-level choices a data layout does not record, such as the width of
+//! `long` or the signedness of plain `char`, follow the platform ABI named by
+//! the target triple.
+
+use anyhow::{anyhow, Context, Result};
+use inkwell::context::Context as LLVMContext;
+use inkwell::targets::{ByteOrdering, CodeModel, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple};
+use inkwell::types::AnyType;
+use inkwell::{AddressSpace, OptimizationLevel};
+use log::debug;
+use std::collections::HashMap;
+
+use super::parser::{Struct, Type};
+use crate::utils::CompilerOptions;
+
+/// Size and alignment of a type in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u64,
+    pub align: u64,
+}
+
+/// Size and alignment of a struct or union in bytes, and where each of its
+/// members is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordLayout {
+    pub size: u64,
+    pub align: u64,
+    /// Offset in bits of each field from the start of the record, in
+    /// declaration order; bit-fields need not start on a byte boundary
+    pub field_offsets: Vec<u64>,
+}
+
+/// How `long double` is represented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LongDouble {
+    /// The x87 80-bit extended format
+    X87,
+    /// IEEE binary128
+    Quad,
+    /// The same format as `double`
+    Double,
+}
+
+/// How `va_list` is represented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaList {
+    /// A pointer to the next argument on the stack
+    Pointer,
+    /// The System V AMD64 array of one `struct { unsigned gp_offset,
+    /// fp_offset; void *overflow_arg_area, *reg_save_area; }`
+    SysV,
+    /// The AAPCS64 `struct { void *stack, *gr_top, *vr_top; int gr_offs,
+    /// vr_offs; }`
+    Aapcs64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TargetInfo {
+    pub triple: String,
+    /// Whether plain `char` is signed
+    pub char_signed: bool,
+    pub big_endian: bool,
+    pub long_double: LongDouble,
+    pub va_list: VaList,
+    bool_layout: Layout,
+    char_layout: Layout,
+    short_layout: Layout,
+    int_layout: Layout,
+    long_layout: Layout,
+    long_long_layout: Layout,
+    float_layout: Layout,
+    double_layout: Layout,
+    long_double_layout: Layout,
+    pointer_layout: Layout,
+}
+
+/// The normalized triple of the platform `options` compile for
+pub fn target_triple(options: &CompilerOptions) -> TargetTriple {
+    match &options.target {
+        Some(triple) => TargetMachine::normalize_triple(&TargetTriple::create(triple)),
+        None => TargetMachine::get_default_triple(),
+    }
+}
+
+/// Whether programs compiled with `options` run on this machine. The vendor
+/// does not matter: `x86_64-unknown-linux-gnu` is `x86_64-pc-linux-gnu`.
+pub fn is_host(options: &CompilerOptions) -> bool {
+    let without_vendor = |triple: TargetTriple| {
+        let mut parts: Vec<String> = triple.as_str().to_string_lossy().split('-').map(str::to_string).collect();
+        if parts.len() > 1 {
+            parts.remove(1);
+        }
+        parts
+    };
+    without_vendor(target_triple(options)) == without_vendor(TargetMachine::get_default_triple())
+}
+
+/// Create a target machine for the target, processor and features in
+/// `options`, generating code at `optimization`
+pub fn create_target_machine(options: &CompilerOptions, optimization: OptimizationLevel) -> Result<TargetMachine> {
+    Target::initialize_all(&InitializationConfig::default());
+
+    let triple = target_triple(options);
+    let triple_name = triple.as_str().to_string_lossy().into_owned();
+    debug!("Target triple: {}", triple_name);
+
+    let target = Target::from_triple(&triple)
+        .map_err(|err| anyhow!(err.to_string()))
+        .with_context(|| format!("Unknown target '{}'", triple_name))?;
+
+    for feature in options.target_features.iter().flat_map(|features| features.split(',')) {
+        if !feature.starts_with(['+', '-']) {
+            anyhow::bail!("Target feature '{}' must start with '+' to enable it or '-' to disable it", feature);
+        }
+    }
+
+    let mut features = options.target_features.clone();
+    let cpu = match options.cpu.as_deref() {
+        Some("native") => {
+            // The host's own features come first so that those given
+            // explicitly override them
+            features.insert(0, TargetMachine::get_host_cpu_features().to_string());
+            TargetMachine::get_host_cpu_name().to_string()
+        }
+        Some(cpu) => cpu.to_string(),
+        // Each backend's baseline processor; not every one calls it `generic`
+        None => String::new(),
+    };
+
+    // Distributions link position-independent executables by default;
+    // WebAssembly has no dynamic linking to be position-independent for
+    let reloc_mode = if triple_name.starts_with("wasm") { RelocMode::Static } else { RelocMode::PIC };
+
+    // LLVM checks the processor and features against the target's tables
+    // as the machine is created, but only warns about those it does not
+    // know and ignores them. Code generation for what is left can then
+    // abort, so they are errors here.
+    let (machine, complaints) = capture_stderr(|| {
+        target.create_target_machine(&triple, &cpu, &features.join(","), optimization, reloc_mode, CodeModel::Default)
+    })?;
+    for complaint in complaints.lines() {
+        if let Some(cpu) = unrecognized(complaint, "processor") {
+            anyhow::bail!("Unknown processor '{}' for target '{}'", cpu, triple_name);
+        }
+        match unrecognized(complaint, "feature") {
+            // The host's own features are not the user's to fix
+            Some(feature) if options.target_features.iter().any(|given| given.split(',').any(|f| f == feature)) => {
+                anyhow::bail!("Unknown target feature '{}' for target '{}'", feature, triple_name);
+            }
+            _ => eprintln!("{}", complaint),
+        }
+    }
+    machine.with_context(|| format!("Failed to create target machine for '{}'", triple_name))
+}
+
+/// The name in LLVM's warning that `'name' is not a recognized <kind> for
+/// this target`
+fn unrecognized<'a>(complaint: &'a str, kind: &str) -> Option<&'a str> {
+    let rest = complaint.strip_prefix('\'')?;
+    let (name, rest) = rest.split_once('\'')?;
+    rest.starts_with(&format!(" is not a recognized {} for this target", kind))
+        .then_some(name)
+}
+
+/// Run `f` with standard error redirected, returning what was written to it
+/// as well. LLVM writes its warnings straight to the file descriptor.
+#[cfg(unix)]
+fn capture_stderr<T>(f: impl FnOnce() -> T) -> Result<(T, String)> {
+    use std::io::{Read, Seek, Write};
+    use std::os::fd::AsRawFd;
+
+    let mut captured = tempfile::tempfile().context("Failed to create temporary file")?;
+    std::io::stderr().flush().ok();
+    // SAFETY: only file descriptors owned here and standard error are
+    // touched, and standard error is restored before returning
+    let saved = unsafe { libc::dup(libc::STDERR_FILENO) };
+    if saved < 0 || unsafe { libc::dup2(captured.as_raw_fd(), libc::STDERR_FILENO) } < 0 {
+        if saved >= 0 {
+            unsafe { libc::close(saved) };
+        }
+        return Ok((f(), String::new()));
+    }
+    let result = f();
+    unsafe {
+        libc::dup2(saved, libc::STDERR_FILENO);
+        libc::close(saved);
+    }
+
+    let mut output = String::new();
+    captured.rewind().and_then(|_| captured.read_to_string(&mut output)).context("Failed to read LLVM's warnings")?;
+    Ok((result, output))
+}
+
+#[cfg(not(unix))]
+fn capture_stderr<T>(f: impl FnOnce() -> T) -> Result<(T, String)> {
+    Ok((f(), String::new()))
+}
+
+impl TargetInfo {
+    pub fn new(machine: &TargetMachine) -> Self {
+        let triple = machine.get_triple().as_str().to_string_lossy().into_owned();
+        let target_data = machine.get_target_data();
+        let context = LLVMContext::create();
+        let layout = |llvm_type: &dyn AnyType| Layout {
+            size: target_data.get_abi_size(llvm_type),
+            align: target_data.get_abi_alignment(llvm_type) as u64,
+        };
+
+        let arch = triple.split('-').next().unwrap_or_default();
+        let is_windows = triple.contains("windows");
+        let is_apple = triple.contains("apple") || triple.contains("darwin");
+        let pointer_size = target_data.get_pointer_byte_size(None) as u64;
+
+        // LP64 everywhere except on 64-bit Windows, which is LLP64
+        let long_type = if pointer_size == 8 && !is_windows {
+            context.i64_type()
+        } else {
+            context.i32_type()
+        };
+        let char_signed = !(matches!(arch, "aarch64" | "arm" | "armv7" | "riscv32" | "riscv64" | "powerpc64le" | "s390x")
+            && !is_apple
+            && !is_windows);
+        let long_double = match arch {
+            "x86_64" | "i386" | "i686" if !is_windows => LongDouble::X87,
+            "aarch64" | "riscv64" | "s390x" if !is_apple && !is_windows => LongDouble::Quad,
+            _ => LongDouble::Double,
+        };
+        let va_list = match arch {
+            "x86_64" if !is_windows => VaList::SysV,
+            "aarch64" if !is_apple && !is_windows => VaList::Aapcs64,
+            _ => VaList::Pointer,
+        };
+        let long_double_layout = match long_double {
+            LongDouble::X87 => layout(&context.x86_f80_type()),
+            LongDouble::Quad => layout(&context.f128_type()),
+            LongDouble::Double => layout(&context.f64_type()),
+        };
+
+        TargetInfo {
+            char_signed,
+            big_endian: target_data.get_byte_ordering() == ByteOrdering::BigEndian,
+            long_double,
+            va_list,
+            bool_layout: layout(&context.i8_type()),
+            char_layout: layout(&context.i8_type()),
+            short_layout: layout(&context.i16_type()),
+            int_layout: layout(&context.i32_type()),
+            long_layout: layout(&long_type),
+            long_long_layout: layout(&context.i64_type()),
+            float_layout: layout(&context.f32_type()),
+            double_layout: layout(&context.f64_type()),
+            long_double_layout,
+            pointer_layout: layout(&context.i8_type().ptr_type(AddressSpace::default())),
+            triple,
+        }
+    }
+
+    /// Size and alignment of an arithmetic or pointer type
+    pub fn scalar_layout(&self, scalar: &Type) -> Option<Layout> {
+        let layout = match scalar {
+            Type::Bool => self.bool_layout,
+            Type::Char | Type::SignedChar | Type::UnsignedChar => self.char_layout,
+            Type::Short | Type::UnsignedShort => self.short_layout,
+            Type::Int | Type::UnsignedInt => self.int_layout,
+            Type::Long | Type::UnsignedLong => self.long_layout,
+            Type::LongLong | Type::UnsignedLongLong => self.long_long_layout,
+            Type::Float => self.float_layout,
+            Type::Double => self.double_layout,
+            Type::LongDouble => self.long_double_layout,
+            Type::Pointer(_) => self.pointer_layout,
+            Type::VaList => match self.va_list {
+                VaList::Pointer => self.pointer_layout,
+                VaList::SysV => Layout { size: 24, align: 8 },
+                VaList::Aapcs64 => Layout { size: 32, align: 8 },
+            },
+            Type::Qualified(unqualified, _) | Type::Enum(_, unqualified) => return self.scalar_layout(unqualified),
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    /// Size and alignment of a complete object type, or `None` for an
+    /// incomplete type or one larger than `max_object_size`
+    pub fn layout(&self, object_type: &Type, structs: &HashMap<String, Struct>) -> Option<Layout> {
+        match object_type {
+            Type::Array(element, Some(length)) => {
+                let element = self.layout(element, structs)?;
+                let size = element.size.checked_mul(*length as u64)?;
+                (size <= self.max_object_size()).then_some(Layout {
+                    size,
+                    align: element.align,
+                })
+            }
+            Type::Struct(name) | Type::Union(name) => {
+                let record = self.record_layout(structs.get(name)?, structs)?;
+                Some(Layout {
+                    size: record.size,
+                    align: record.align,
+                })
+            }
+            Type::Qualified(unqualified, _) => self.layout(unqualified, structs),
+            _ => self.scalar_layout(object_type),
+        }
+    }
+
+    /// Lay out the members of a struct or union following the platform ABI:
+    /// the Itanium C++ ABI rules GCC and Clang use on SysV and AAPCS targets,
+    /// or Microsoft's rules on Windows. `None` if a member is incomplete or
+    /// the record is larger than `max_object_size`.
+    pub fn record_layout(&self, definition: &Struct, structs: &HashMap<String, Struct>) -> Option<RecordLayout> {
+        let mut field_offsets = Vec::with_capacity(definition.fields.len());
+        // The next free bit, and the end of the furthest member
+        let mut offset: u64 = 0;
+        let mut size = 0;
+        let mut align = 1;
+        // Microsoft packs bit-fields into a storage unit of their declared
+        // type, given here by its start and width in bits
+        let mut unit: Option<(u64, u64)> = None;
+
+        for field in &definition.fields {
+            let layout = match &field.var_type {
+                // A flexible array member takes up no space
+                Type::Array(element, None) => Layout {
+                    size: 0,
+                    align: self.layout(element, structs)?.align,
+                },
+                field_type => self.layout(field_type, structs)?,
+            };
+            // `_Alignas` can make a member more strictly aligned than its type
+            let layout = Layout {
+                align: layout.align.max(field.alignment.unwrap_or(1)),
+                ..layout
+            };
+            let (type_bits, align_bits) = (layout.size * 8, layout.align * 8);
+            if definition.is_union {
+                offset = 0;
+                unit = None;
+            }
+            // Give up before a record too large for the target overflows the offsets in bits
+            if round_up(offset.div_ceil(8), layout.align) + layout.size > self.max_object_size() {
+                return None;
+            }
+
+            let start = match field.bit_width {
+                None => {
+                    if let Some((unit_start, unit_bits)) = unit.take() {
+                        offset = unit_start + unit_bits;
+                    }
+                    align = align.max(layout.align);
+                    let start = round_up(offset, align_bits);
+                    offset = start + type_bits;
+                    start
+                }
+                Some(width) if self.is_windows() => {
+                    let fits = matches!(unit, Some((unit_start, unit_bits))
+                        if width > 0 && unit_bits == type_bits && offset + width as u64 <= unit_start + unit_bits);
+                    let start = if fits {
+                        offset
+                    } else {
+                        if let Some((unit_start, unit_bits)) = unit.take() {
+                            offset = unit_start + unit_bits;
+                        }
+                        // A zero-width bit-field only ends a run of bit-fields
+                        if width == 0 {
+                            field_offsets.push(offset);
+                            continue;
+                        }
+                        align = align.max(layout.align);
+                        let unit_start = round_up(offset, align_bits);
+                        unit = Some((unit_start, type_bits));
+                        size = size.max(unit_start + type_bits);
+                        unit_start
+                    };
+                    offset = start + width as u64;
+                    start
+                }
+                Some(0) => {
+                    // A zero-width bit-field aligns whatever follows it; on
+                    // ARM it also counts towards the alignment of the record
+                    if self.is_aapcs() {
+                        align = align.max(layout.align);
+                    }
+                    offset = round_up(offset, align_bits);
+                    offset
+                }
+                Some(width) => {
+                    // A bit-field may not straddle a boundary of its type's alignment
+                    let width = width as u64;
+                    let start = if offset % align_bits + width > type_bits {
+                        round_up(offset, align_bits)
+                    } else {
+                        offset
+                    };
+                    // Unnamed bit-fields are padding and do not affect alignment
+                    if !field.name.is_empty() {
+                        align = align.max(layout.align);
+                    }
+                    offset = start + width;
+                    start
+                }
+            };
+            field_offsets.push(start);
+            size = size.max(offset);
+        }
+
+        let size = round_up(size.div_ceil(8), align);
+        if size > self.max_object_size() {
+            return None;
+        }
+        Some(RecordLayout {
+            size,
+            align,
+            field_offsets,
+        })
+    }
+
+    /// The size in bytes of the largest object: its size must fit in
+    /// `ptrdiff_t`, and its size in bits in a `u64`
+    pub fn max_object_size(&self) -> u64 {
+        let ptrdiff_max = (1 << (self.pointer_layout.size * 8 - 1)) - 1;
+        ptrdiff_max.min(u64::MAX / 8)
+    }
+
+    /// Width in bits of an integer type; `_Bool` holds a single bit
+    pub fn integer_width(&self, integer: &Type) -> u32 {
+        match integer {
+            Type::Bool => 1,
+            _ => self.scalar_layout(integer).expect("not an integer type").size as u32 * 8,
+        }
+    }
+
+    pub fn is_signed(&self, integer: &Type) -> bool {
+        match integer {
+            Type::Char => self.char_signed,
+            Type::Enum(_, underlying) => self.is_signed(underlying),
+            Type::SignedChar | Type::Short | Type::Int | Type::Long | Type::LongLong => true,
+            _ => false,
+        }
+    }
+
+    /// Convert an integer value to an integer type, wrapping it modulo the
+    /// width of the type
+    pub fn wrap_integer(&self, value: i64, integer: &Type) -> i64 {
+        let width = self.integer_width(integer);
+        if *integer == Type::Bool {
+            (value != 0) as i64
+        } else if width >= 64 {
+            value
+        } else if self.is_signed(integer) {
+            value << (64 - width) >> (64 - width)
+        } else {
+            value & ((1 << width) - 1)
+        }
+    }
+
+    /// Apply the integer promotions to an arithmetic type
+    pub fn promote(&self, arithmetic: &Type) -> Type {
+        // An enumerated type behaves as its compatible integer type
+        if let Type::Enum(_, underlying) = arithmetic {
+            return self.promote(underlying);
+        }
+        let rank = match arithmetic.integer_rank() {
+            Some(rank) => rank,
+            None => return arithmetic.clone(),
+        };
+        if rank >= Type::Int.integer_rank().unwrap() {
+            return arithmetic.clone();
+        }
+        // Everything below `int` promotes to `int` if it can represent every value
+        let width = self.integer_width(arithmetic);
+        let int_width = self.integer_width(&Type::Int);
+        if width < int_width || (width == int_width && self.is_signed(arithmetic)) {
+            Type::Int
+        } else {
+            Type::UnsignedInt
+        }
+    }
+
+    /// The common type of the usual arithmetic conversions
+    pub fn common_type(&self, a: &Type, b: &Type) -> Type {
+        for floating in [Type::LongDouble, Type::Double, Type::Float] {
+            if *a == floating || *b == floating {
+                return floating;
+            }
+        }
+
+        let (a, b) = (self.promote(a), self.promote(b));
+        if a == b {
+            return a;
+        }
+        let (a_rank, b_rank) = (a.integer_rank().unwrap(), b.integer_rank().unwrap());
+        if self.is_signed(&a) == self.is_signed(&b) {
+            return if a_rank >= b_rank { a } else { b };
+        }
+        let (signed, unsigned) = if self.is_signed(&a) { (a, b) } else { (b, a) };
+        if unsigned.integer_rank() >= signed.integer_rank() {
+            unsigned
+        } else if self.integer_width(&signed) > self.integer_width(&unsigned) {
+            signed
+        } else {
+            signed.to_unsigned()
+        }
+    }
+
+    /// The signed integer type of pointer differences, `ptrdiff_t`
+    pub fn ptrdiff_type(&self) -> Type {
+        [Type::Int, Type::Long, Type::LongLong]
+            .into_iter()
+            .find(|candidate| self.scalar_layout(candidate) == Some(self.pointer_layout))
+            .unwrap_or(Type::LongLong)
+    }
+
+    /// The unsigned integer type of `sizeof`, `size_t`
+    pub fn size_type(&self) -> Type {
+        self.ptrdiff_type().to_unsigned()
+    }
+
+    /// The type of `int64_t`, which Apple platforms define as `long long` even
+    /// where `long` is 64 bits wide
+    pub fn int64_type(&self) -> Type {
+        if self.integer_width(&Type::Long) == 64 && !self.is_apple() {
+            Type::Long
+        } else {
+            Type::LongLong
+        }
+    }
+
+    /// The type of `intmax_t`
+    pub fn intmax_type(&self) -> Type {
+        if self.integer_width(&Type::Long) == 64 {
+            Type::Long
+        } else {
+            Type::LongLong
+        }
+    }
+
+    /// The type of `wchar_t`
+    pub fn wchar_type(&self) -> Type {
+        let arch = self.triple.split('-').next().unwrap_or_default();
+        if self.is_windows() {
+            Type::UnsignedShort
+        } else if arch.starts_with("aarch64") || arch.starts_with("arm") {
+            if self.is_apple() {
+                Type::Int
+            } else {
+                Type::UnsignedInt
+            }
+        } else {
+            Type::Int
+        }
+    }
+
+    /// The type of `wint_t`
+    pub fn wint_type(&self) -> Type {
+        if self.is_windows() {
+            Type::UnsignedShort
+        } else {
+            Type::UnsignedInt
+        }
+    }
+
+    /// The architecture named by the target triple, e.g. `x86_64`
+    pub fn arch(&self) -> &str {
+        self.triple.split('-').next().unwrap_or_default()
+    }
+
+    pub fn is_windows(&self) -> bool {
+        self.triple.contains("windows")
+    }
+
+    pub fn is_apple(&self) -> bool {
+        self.triple.contains("apple") || self.triple.contains("darwin")
+    }
+
+    pub fn is_linux(&self) -> bool {
+        self.triple.contains("linux")
+    }
+
+    pub fn is_wasm(&self) -> bool {
+        self.arch().starts_with("wasm")
+    }
+
+    /// The Debian multiarch name of a Linux target, e.g. `aarch64-linux-gnu`,
+    /// which its headers and libraries are installed under
+    pub fn multiarch(&self) -> Option<String> {
+        if !self.is_linux() {
+            return None;
+        }
+        let arch = self.arch();
+        let multiarch = if arch.starts_with("arm") || arch.starts_with("thumb") {
+            if self.triple.ends_with("hf") {
+                "arm-linux-gnueabihf".to_string()
+            } else {
+                "arm-linux-gnueabi".to_string()
+            }
+        } else if matches!(arch, "i386" | "i486" | "i586" | "i686") {
+            "i386-linux-gnu".to_string()
+        } else {
+            format!("{}-linux-gnu", arch)
+        };
+        Some(multiarch)
+    }
+
+    /// Whether the target follows the ARM procedure call standard, where
+    /// Apple platforms deviate from it
+    fn is_aapcs(&self) -> bool {
+        (self.arch().starts_with("aarch64") || self.arch().starts_with("arm")) && !self.is_apple()
+    }
+
+    /// The smallest of `int`, `long` and `long long` that holds `value`, the
+    /// type of an unsuffixed decimal constant
+    pub fn literal_type(&self, value: i64) -> Type {
+        [Type::Int, Type::Long]
+            .into_iter()
+            .find(|candidate| {
+                let width = self.integer_width(candidate);
+                width >= 64 || (value >= -(1 << (width - 1)) && value < 1 << (width - 1))
+            })
+            .unwrap_or(Type::LongLong)
+    }
+}
+
+fn round_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+/// A target machine for `triple`, for checking other platforms' rules
+#[cfg(test)]
+pub fn create_target_machine_for(triple: &str) -> TargetMachine {
+    Target::initialize_all(&InitializationConfig::default());
+    let triple = inkwell::targets::TargetTriple::create(triple);
+    let target = Target::from_triple(&triple).expect("unknown target");
+    target
+        .create_target_machine(
+            &triple,
+            "",
+            "",
+            OptimizationLevel::Default,
+            RelocMode::Default,
+            CodeModel::Default,
+        )
+        .expect("failed to create target machine")
+}
+
+/// The target unit tests are written against
+#[cfg(test)]
+pub fn test_target() -> TargetInfo {
+    TargetInfo::new(&create_target_machine_for("x86_64-unknown-linux-gnu"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::source::FileId;
+    use crate::compiler::{lexer, parser};
+
+    fn target(triple: &str) -> TargetInfo {
+        TargetInfo::new(&create_target_machine_for(triple))
+    }
+
+    fn layout(size: u64, align: u64) -> Layout {
+        Layout { size, align }
+    }
+
+    #[test]
+    fn test_data_models() {
+        let linux = target("x86_64-unknown-linux-gnu");
+        assert_eq!(linux.scalar_layout(&Type::Long), Some(layout(8, 8)));
+        assert_eq!(linux.scalar_layout(&Type::LongDouble), Some(layout(16, 16)));
+        assert_eq!(linux.scalar_layout(&Type::Pointer(Box::new(Type::Void))), Some(layout(8, 8)));
+        assert!(linux.char_signed);
+        assert_eq!(linux.ptrdiff_type(), Type::Long);
+        assert_eq!(linux.size_type(), Type::UnsignedLong);
+        assert_eq!(linux.va_list, VaList::SysV);
+        assert_eq!(linux.layout(&Type::VaList, &HashMap::new()), Some(layout(24, 8)));
+
+        let windows = target("x86_64-pc-windows-msvc");
+        assert_eq!(windows.scalar_layout(&Type::Long), Some(layout(4, 4)));
+        assert_eq!(windows.scalar_layout(&Type::LongDouble), Some(layout(8, 8)));
+        assert_eq!(windows.ptrdiff_type(), Type::LongLong);
+        assert_eq!(windows.layout(&Type::VaList, &HashMap::new()), Some(layout(8, 8)));
+
+        let arm = target("aarch64-unknown-linux-gnu");
+        assert!(!arm.char_signed);
+        assert_eq!(arm.long_double, LongDouble::Quad);
+        assert_eq!(arm.scalar_layout(&Type::LongDouble), Some(layout(16, 16)));
+        assert_eq!(arm.layout(&Type::VaList, &HashMap::new()), Some(layout(32, 8)));
+        assert_eq!(target("aarch64-apple-darwin").va_list, VaList::Pointer);
+
+        let x86 = target("i686-unknown-linux-gnu");
+        assert_eq!(x86.scalar_layout(&Type::Long), Some(layout(4, 4)));
+        assert_eq!(x86.scalar_layout(&Type::LongLong), Some(layout(8, 4)));
+        assert_eq!(x86.scalar_layout(&Type::LongDouble), Some(layout(12, 4)));
+        assert_eq!(x86.ptrdiff_type(), Type::Int);
+
+        let wasm = target("wasm32-unknown-unknown");
+        assert_eq!(wasm.scalar_layout(&Type::Long), Some(layout(4, 4)));
+        assert_eq!(wasm.scalar_layout(&Type::Pointer(Box::new(Type::Void))), Some(layout(4, 4)));
+        assert_eq!(wasm.size_type(), Type::UnsignedInt);
+    }
+
+    #[test]
+    fn test_target_options() {
+        let options = |triple: &str| CompilerOptions { target: Some(triple.to_string()), ..CompilerOptions::default() };
+        for (triple, normalized) in [
+            ("aarch64-linux-gnu", "aarch64-unknown-linux-gnu"),
+            ("riscv64-unknown-linux-gnu", "riscv64-unknown-linux-gnu"),
+            ("armv7-unknown-linux-gnueabihf", "armv7-unknown-linux-gnueabihf"),
+            ("wasm32-unknown-unknown", "wasm32-unknown-unknown"),
+        ] {
+            let machine = create_target_machine(&options(triple), OptimizationLevel::None).expect(triple);
+            assert_eq!(TargetInfo::new(&machine).triple, normalized);
+        }
+        assert_eq!(target("aarch64-unknown-linux-gnu").multiarch().as_deref(), Some("aarch64-linux-gnu"));
+        assert_eq!(target("armv7-unknown-linux-gnueabihf").multiarch().as_deref(), Some("arm-linux-gnueabihf"));
+        assert_eq!(target("wasm32-unknown-unknown").multiarch(), None);
+
+        let with_cpu = CompilerOptions {
+            cpu: Some("cortex-a72".to_string()),
+            target_features: vec!["+crc".to_string()],
+            ..options("aarch64-linux-gnu")
+        };
+        let machine = create_target_machine(&with_cpu, OptimizationLevel::None).unwrap();
+        assert_eq!(machine.get_cpu().to_string(), "cortex-a72");
+        assert_eq!(machine.get_feature_string().to_string_lossy(), "+crc");
+        assert!(create_target_machine(&options("nonsense-thing"), OptimizationLevel::None).is_err());
+
+        // The vendor does not make another platform
+        assert!(is_host(&CompilerOptions::default()));
+        let host = TargetMachine::get_default_triple().as_str().to_string_lossy().into_owned();
+        let mut parts: Vec<&str> = host.split('-').collect();
+        parts[1] = "someone";
+        assert!(is_host(&options(&parts.join("-"))));
+        let other = if host.starts_with("aarch64") { "x86_64-unknown-linux-gnu" } else { "aarch64-unknown-linux-gnu" };
+        assert!(!is_host(&options(other)));
+    }
+
+    #[test]
+    fn test_integer_promotions() {
+        let linux = target("x86_64-unknown-linux-gnu");
+        for small in [Type::Bool, Type::Char, Type::SignedChar, Type::UnsignedChar, Type::Short, Type::UnsignedShort] {
+            assert_eq!(linux.promote(&small), Type::Int, "promoting {}", small);
+        }
+        assert_eq!(linux.promote(&Type::UnsignedInt), Type::UnsignedInt);
+        assert_eq!(linux.promote(&Type::Long), Type::Long);
+        assert_eq!(linux.promote(&Type::Float), Type::Float);
+    }
+
+    #[test]
+    fn test_usual_arithmetic_conversions() {
+        let linux = target("x86_64-unknown-linux-gnu");
+        let windows = target("x86_64-pc-windows-msvc");
+        let cases = [
+            (Type::Char, Type::UnsignedChar, Type::Int, Type::Int),
+            (Type::Int, Type::UnsignedInt, Type::UnsignedInt, Type::UnsignedInt),
+            (Type::Long, Type::UnsignedInt, Type::Long, Type::UnsignedLong),
+            (Type::LongLong, Type::UnsignedLong, Type::UnsignedLongLong, Type::LongLong),
+            (Type::Long, Type::LongLong, Type::LongLong, Type::LongLong),
+            (Type::UnsignedLong, Type::Int, Type::UnsignedLong, Type::UnsignedLong),
+            (Type::Short, Type::Float, Type::Float, Type::Float),
+            (Type::Double, Type::LongDouble, Type::LongDouble, Type::LongDouble),
+        ];
+        for (a, b, on_linux, on_windows) in cases {
+            assert_eq!(linux.common_type(&a, &b), on_linux, "{} and {} on Linux", a, b);
+            assert_eq!(linux.common_type(&b, &a), on_linux, "{} and {} on Linux", b, a);
+            assert_eq!(windows.common_type(&a, &b), on_windows, "{} and {} on Windows", a, b);
+        }
+    }
+
+    fn record(target: &TargetInfo, source: &str, name: &str) -> RecordLayout {
+        let tokens = lexer::tokenize(source, FileId(0)).expect("lexing failed");
+        let structs = parser::parse(tokens, target).expect("parsing failed").structs;
+        target.record_layout(&structs[name], &structs).expect("incomplete record")
+    }
+
+    fn record_of(size: u64, align: u64, field_offsets: &[u64]) -> RecordLayout {
+        RecordLayout {
+            size,
+            align,
+            field_offsets: field_offsets.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_record_layouts() {
+        let linux = target("x86_64-unknown-linux-gnu");
+        let x86 = target("i686-unknown-linux-gnu");
+        let cases = [
+            ("struct s { char c; int i; short s; };", record_of(12, 4, &[0, 32, 64])),
+            ("union s { char c; int i; double d; };", record_of(8, 8, &[0, 0, 0])),
+            ("struct s { int n; union { short s; char c; }; double d[]; };", record_of(8, 8, &[0, 32, 64])),
+            ("struct s { char c; long long x; };", record_of(16, 8, &[0, 64])),
+            ("struct s { char c; _Alignas(16) int i; };", record_of(32, 16, &[0, 128])),
+            ("union s { char c; _Alignas(8) char d; };", record_of(8, 8, &[0, 0])),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(record(&linux, source, "s"), expected, "{}", source);
+        }
+        assert_eq!(record(&x86, "struct s { char c; long long x; };", "s"), record_of(12, 4, &[0, 32]));
+    }
+
+    #[test]
+    fn test_object_size_limits() {
+        let linux = target("x86_64-unknown-linux-gnu");
+        let x86 = target("i686-unknown-linux-gnu");
+        assert_eq!(linux.max_object_size(), (1 << 61) - 1);
+        assert_eq!(x86.max_object_size(), i32::MAX as u64);
+        let array = |element: Type, length| Type::Array(Box::new(element), Some(length));
+        let structs = HashMap::new();
+        assert_eq!(linux.layout(&array(Type::Char, (1 << 61) - 1), &structs), Some(layout((1 << 61) - 1, 1)));
+        assert_eq!(linux.layout(&array(Type::Char, 1 << 61), &structs), None);
+        assert_eq!(linux.layout(&array(Type::Int, 0x4000000000000000), &structs), None);
+        assert_eq!(x86.layout(&array(Type::Int, 0x20000000), &structs), None);
+
+        let source = "struct s { char a[0x1000000000000000]; char b[0xfffffffffffffff]; };";
+        assert_eq!(record(&linux, source, "s").size, (1 << 61) - 1);
+        let source = "struct s { char a[0x1000000000000000]; char b[0x1000000000000000]; };";
+        let ast = parser::parse(lexer::tokenize(source, FileId(0)).unwrap(), &linux).unwrap();
+        assert!(linux.record_layout(&ast.structs["s"], &ast.structs).is_none());
+    }
+
+    #[test]
+    fn test_bit_field_layouts() {
+        let linux = target("x86_64-unknown-linux-gnu");
+        let windows = target("x86_64-pc-windows-msvc");
+        let arm = target("aarch64-unknown-linux-gnu");
+
+        // Bit-fields of different types share storage except on Windows
+        let mixed = "struct s { char a : 4; char b : 4; int c : 8; };";
+        assert_eq!(record(&linux, mixed, "s"), record_of(4, 4, &[0, 4, 8]));
+        assert_eq!(record(&windows, mixed, "s"), record_of(8, 4, &[0, 4, 32]));
+
+        // A bit-field that would straddle an `int` boundary starts a new one
+        let straddling = "struct s { int a : 30; int b : 4; };";
+        assert_eq!(record(&linux, straddling, "s"), record_of(8, 4, &[0, 32]));
+
+        // A zero-width bit-field aligns the next member, and the record too on ARM
+        let zero = "struct s { char a; int : 0; char b; };";
+        assert_eq!(record(&linux, zero, "s"), record_of(5, 1, &[0, 32, 32]));
+        assert_eq!(record(&arm, zero, "s"), record_of(8, 4, &[0, 32, 32]));
+        assert_eq!(record(&windows, zero, "s"), record_of(2, 1, &[0, 8, 8]));
+
+        // Unnamed bit-fields are padding that does not affect alignment
+        let unnamed = "struct s { char a; int : 4; };";
+        assert_eq!(record(&linux, unnamed, "s"), record_of(2, 1, &[0, 8]));
+    }
+
+    #[test]
+    fn test_literal_types() {
+        let linux = target("x86_64-unknown-linux-gnu");
+        assert_eq!(linux.literal_type(2147483647), Type::Int);
+        assert_eq!(linux.literal_type(2147483648), Type::Long);
+        assert_eq!(linux.literal_type(-2147483648), Type::Int);
+
+        let windows = target("x86_64-pc-windows-msvc");
+        assert_eq!(windows.literal_type(2147483648), Type::LongLong);
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use log::{debug, info};
 use std::path::{Path, PathBuf};
 use std::process;
@@ -9,6 +9,8 @@ mod runtime;
 mod update;
 mod utils;
 
+use utils::{CompilerOptions, EmitKind, OptimizationLevel};
+
 #[derive(Parser)]
 #[command(name = "tc")]
 #[command(about = "tlstuc - A modern C language runtime", long_about = None)]
@@ -16,13 +18,90 @@ struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// C file to compile and run
+    /// C files to compile together and run
     #[arg(value_name = "FILE")]
-    file: Option<PathBuf>,
+    files: Vec<PathBuf>,
 
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
+
+    #[command(flatten)]
+    options: OptionFlags,
+}
+
+/// Flags that override the compiler options from tc.toml
+#[derive(Args)]
+struct OptionFlags {
+    /// Optimization level: 0 to 3, or s or z to optimize for size
+    #[arg(short = 'O', value_name = "LEVEL", global = true, value_parser = parse_optimization_level)]
+    optimization_level: Option<OptimizationLevel>,
+
+    /// Print each optimization pass as it runs
+    #[arg(long, global = true)]
+    print_passes: bool,
+
+    /// Report the time spent in each optimization pass
+    #[arg(long, global = true)]
+    time_passes: bool,
+
+    /// Compile for another platform, given by its target triple
+    #[arg(long, value_name = "TRIPLE", global = true)]
+    target: Option<String>,
+
+    /// Generate code for a processor, or `native` for this machine's
+    #[arg(long, value_name = "CPU", global = true)]
+    cpu: Option<String>,
+
+    /// Enable or disable a target feature, such as +neon or -avx
+    #[arg(long = "target-feature", value_name = "FEATURE", global = true, allow_hyphen_values = true)]
+    target_features: Vec<String>,
+
+    /// Stop compilation at the first warning
+    #[arg(long, global = true)]
+    warnings_as_errors: bool,
+
+    /// Search a directory for included headers
+    #[arg(short = 'I', value_name = "DIR", global = true)]
+    include_paths: Vec<PathBuf>,
+
+    /// Search a directory for libraries
+    #[arg(short = 'L', value_name = "DIR", global = true)]
+    library_paths: Vec<PathBuf>,
+
+    /// Link with a library
+    #[arg(short = 'l', value_name = "LIBRARY", global = true)]
+    libraries: Vec<String>,
+}
+
+impl OptionFlags {
+    fn apply(&self, options: &mut CompilerOptions) {
+        if let Some(level) = self.optimization_level {
+            options.optimization_level = level;
+        }
+        if let Some(target) = &self.target {
+            options.target = Some(target.clone());
+        }
+        if let Some(cpu) = &self.cpu {
+            options.cpu = Some(cpu.clone());
+        }
+        options.target_features.extend(self.target_features.iter().cloned());
+        options.warnings_as_errors |= self.warnings_as_errors;
+        options.print_passes = self.print_passes;
+        options.time_passes = self.time_passes;
+        // Directories given on the command line are searched first
+        options.include_paths.splice(0..0, self.include_paths.iter().cloned());
+        options.library_paths.splice(0..0, self.library_paths.iter().cloned());
+        options.libraries.extend(self.libraries.iter().cloned());
+    }
+}
+
+fn parse_emit_kind(flag: &str) -> Result<EmitKind, String> {
+    EmitKind::from_flag(flag).ok_or_else(|| format!("unknown output kind '{}'", flag))
+}
+
+fn parse_optimization_level(flag: &str) -> Result<OptimizationLevel, String> {
+    OptimizationLevel::from_flag(flag).ok_or_else(|| format!("unknown optimization level '{}'", flag))
 }
 
 #[derive(Subcommand)]
@@ -30,6 +109,26 @@ enum Commands {
     /// Initialize a new C project
     Init,
     
+    /// Compile every C file in a project into one executable
+    Build {
+        /// Project directory to search for C files
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+        
+        /// Where to write the executable, `build/<name>` in the project by default
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        
+        /// Stages whose output to write: tokens, ast, llvm-ir, llvm-bc, asm,
+        /// obj or exe
+        #[arg(long, value_name = "KINDS", value_delimiter = ',', value_parser = parse_emit_kind)]
+        emit: Vec<EmitKind>,
+        
+        /// Where to write the output of --emit, next to each C file by default
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    
     /// Check for updates to tlstuc
     Update,
     
@@ -51,6 +150,9 @@ fn main() -> Result<()> {
         Some(Commands::Init) => {
             init_project()?;
         }
+        Some(Commands::Build { dir, output, emit, out_dir }) => {
+            build_project(dir, output.as_deref(), emit, out_dir.as_deref(), &cli.options)?;
+        }
         Some(Commands::Update) => {
             update::check_and_update()?;
         }
@@ -58,9 +160,9 @@ fn main() -> Result<()> {
             println!("tlstuc version {}", env!("CARGO_PKG_VERSION"));
         }
         None => {
-            // If no subcommand is provided but a file is, compile and run it
-            if let Some(file) = &cli.file {
-                compile_and_run(file)?;
+            // If no subcommand is provided but files are, compile and run them
+            if !cli.files.is_empty() {
+                compile_and_run(&cli.files, &cli.options)?;
             } else {
                 println!("No command or file specified. Use 'tc --help' for usage information.");
                 process::exit(1);
@@ -71,7 +173,7 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-/// Initialize a new C project with a template file
+/// Initialize a new C project with a template file and a tc.toml
 fn init_project() -> Result<()> {
     let current_dir = std::env::current_dir()?;
     let tc_file_path = current_dir.join("tc.c");
@@ -81,6 +183,16 @@ fn init_project() -> Result<()> {
         return Ok(());
     }
     
+    // The project is named after its directory
+    if !current_dir.join("tc.toml").exists() {
+        let mut config = utils::Config::default();
+        if let Some(name) = current_dir.file_name().and_then(|name| name.to_str()) {
+            config.name = name.to_string();
+        }
+        utils::save_config(&config, &current_dir)?;
+        println!("Created tc.toml in {}", current_dir.display());
+    }
+    
     let template = r#"#include <stdio.h>
 
 int main() {
@@ -98,22 +210,77 @@ int main() {
     Ok(())
 }
 
-/// Compile and run a C file
-fn compile_and_run(file_path: &Path) -> Result<()> {
-    info!("Compiling and running {}", file_path.display());
+/// Compile every C file under a project directory into one executable, or
+/// into the outputs `emit` asks for
+fn build_project(
+    dir: &Path,
+    output: Option<&Path>,
+    emit: &[EmitKind],
+    out_dir: Option<&Path>,
+    flags: &OptionFlags,
+) -> Result<()> {
+    info!("Building {}", dir.display());
+    
+    if !dir.is_dir() {
+        anyhow::bail!("Directory '{}' does not exist", dir.display());
+    }
     
-    // Check if the file exists
-    if !file_path.exists() {
-        anyhow::bail!("File '{}' does not exist", file_path.display());
+    // Compile the units in a stable order
+    let mut files = utils::find_c_files(dir)?;
+    files.sort();
+    if files.is_empty() {
+        anyhow::bail!("No C files found in '{}'", dir.display());
     }
     
-    // Check if it's a C file
-    if file_path.extension().and_then(|ext| ext.to_str()) != Some("c") {
-        anyhow::bail!("File '{}' is not a C file", file_path.display());
+    let mut config = utils::load_config(dir)?;
+    flags.apply(&mut config.compiler_options);
+    config.compiler_options.emit = emit.to_vec();
+    config.compiler_options.emit_dir = out_dir.map(Path::to_path_buf);
+    let output_path = match output {
+        Some(output) => output.to_path_buf(),
+        None => compiler::executable_path(&dir.join("build").join(&config.name), &config.compiler_options),
+    };
+    let parent = output_path.parent().filter(|parent| !parent.as_os_str().is_empty());
+    if let Some(parent) = parent.filter(|_| config.compiler_options.emits(EmitKind::Exe)) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
     }
     
-    // Compile the file
-    let executable = compiler::compile(file_path)?;
+    compiler::build(&files, &output_path, &config.compiler_options)?;
+    
+    if config.compiler_options.emits(EmitKind::Exe) {
+        println!("Built {} from {} C files", output_path.display(), files.len());
+    } else {
+        println!("Compiled {} C files", files.len());
+    }
+    
+    Ok(())
+}
+
+/// Compile C files together and run the program
+fn compile_and_run(files: &[PathBuf], flags: &OptionFlags) -> Result<()> {
+    info!("Compiling and running {}", files[0].display());
+    
+    for file_path in files {
+        // Check if the file exists
+        if !file_path.exists() {
+            anyhow::bail!("File '{}' does not exist", file_path.display());
+        }
+        
+        // Check if it's a C file
+        if file_path.extension().and_then(|ext| ext.to_str()) != Some("c") {
+            anyhow::bail!("File '{}' is not a C file", file_path.display());
+        }
+    }
+    
+    // Compile the files with the project's options from tc.toml, if any,
+    // and the command line
+    let mut config = utils::load_config(&std::env::current_dir()?)?;
+    flags.apply(&mut config.compiler_options);
+    if !compiler::is_host(&config.compiler_options) {
+        anyhow::bail!("Programs compiled for another platform cannot be run here; use 'tc build' instead");
+    }
+    let executable = compiler::compile(files, &config.compiler_options)?;
     
     // Run the executable
     runtime::run(&executable)?;
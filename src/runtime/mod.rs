@@ -21,8 +21,8 @@ pub fn run(executable_path: &Path) -> Result<()> {
         std::fs::set_permissions(executable_path, perms)?;
     }
     
-    // Run the executable
-    let status = Command::new(executable_path)
+    // Run the executable; a bare file name would be looked up on the PATH
+    let status = Command::new(Path::new(".").join(executable_path))
         .status()
         .context("Failed to execute program")?;
     
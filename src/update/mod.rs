@@ -88,9 +88,9 @@ pub fn add_to_path() -> Result<()> {
             .unwrap_or_else(|_| "/bin/bash".to_string());
         
         let rc_file = if shell.contains("zsh") {
-            format!("{}/{}/.zshrc", home_dir)
+            format!("{}/.zshrc", home_dir)
         } else {
-            format!("{}/{}/.bashrc", home_dir)
+            format!("{}/.bashrc", home_dir)
         };
         
         println!("To add tlstuc to your PATH, add the following line to {}:", rc_file);
@@ -3,8 +3,10 @@ use log::debug;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-/// Project configuration
+/// Project configuration, read from `tc.toml`. Keys left out of the file
+/// keep their default values.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub name: String,
     pub version: String,
@@ -12,21 +14,104 @@ pub struct Config {
     pub compiler_options: CompilerOptions,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CompilerOptions {
     pub optimization_level: OptimizationLevel,
     pub warnings_as_errors: bool,
     pub include_paths: Vec<PathBuf>,
     pub library_paths: Vec<PathBuf>,
     pub libraries: Vec<String>,
+    /// Triple of the platform to compile for, the host by default
+    pub target: Option<String>,
+    /// Processor to generate code for, the target's baseline by default;
+    /// `native` is the host's processor
+    pub cpu: Option<String>,
+    /// LLVM target features to enable or disable, such as `+neon` or `-sse4.2`
+    pub target_features: Vec<String>,
+    /// Print each optimization pass as it runs; only set on the command line
+    #[serde(skip)]
+    pub print_passes: bool,
+    /// Report the time spent in each optimization pass; only set on the
+    /// command line
+    #[serde(skip)]
+    pub time_passes: bool,
+    /// The stages whose output to write, as `--emit` asks; an executable
+    /// alone when empty
+    #[serde(skip)]
+    pub emit: Vec<EmitKind>,
+    /// Where to write the output of `emit`, next to each C file by default
+    #[serde(skip)]
+    pub emit_dir: Option<PathBuf>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OptimizationLevel {
     None,
     Less,
+    #[default]
     Default,
     Aggressive,
+    /// Optimize for size, as `-Os`
+    Size,
+    /// Optimize for size above all, as `-Oz`
+    MinSize,
+}
+
+impl OptimizationLevel {
+    /// The level of a `-O` flag: `0` to `3`, `s` or `z`
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "0" => Some(OptimizationLevel::None),
+            "1" => Some(OptimizationLevel::Less),
+            "2" => Some(OptimizationLevel::Default),
+            "3" => Some(OptimizationLevel::Aggressive),
+            "s" => Some(OptimizationLevel::Size),
+            "z" => Some(OptimizationLevel::MinSize),
+            _ => None,
+        }
+    }
+}
+
+/// A compilation stage whose output `--emit` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitKind {
+    /// The preprocessed tokens, one per line
+    Tokens,
+    /// The analyzed AST as JSON
+    Ast,
+    LlvmIr,
+    LlvmBc,
+    Asm,
+    Obj,
+    Exe,
+}
+
+impl EmitKind {
+    /// The kind named in an `--emit` list
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "tokens" => Some(EmitKind::Tokens),
+            "ast" => Some(EmitKind::Ast),
+            "llvm-ir" => Some(EmitKind::LlvmIr),
+            "llvm-bc" => Some(EmitKind::LlvmBc),
+            "asm" => Some(EmitKind::Asm),
+            "obj" => Some(EmitKind::Obj),
+            "exe" => Some(EmitKind::Exe),
+            _ => None,
+        }
+    }
+}
+
+impl CompilerOptions {
+    /// Whether to write the output of `kind`
+    pub fn emits(&self, kind: EmitKind) -> bool {
+        if self.emit.is_empty() {
+            kind == EmitKind::Exe
+        } else {
+            self.emit.contains(&kind)
+        }
+    }
 }
 
 impl Default for Config {
@@ -35,20 +120,15 @@ impl Default for Config {
             name: "tlstuc_project".to_string(),
             version: "0.1.0".to_string(),
             author: None,
-            compiler_options: CompilerOptions {
-                optimization_level: OptimizationLevel::Default,
-                warnings_as_errors: false,
-                include_paths: vec![],
-                library_paths: vec![],
-                libraries: vec![],
-            },
+            compiler_options: CompilerOptions::default(),
         }
     }
 }
 
-/// Load project configuration from tc.toml
-pub fn load_config() -> Result<Config> {
-    let config_path = std::env::current_dir()?.join("tc.toml");
+/// Load project configuration from the tc.toml in `dir`. Relative include
+/// and library paths in it are taken relative to `dir`.
+pub fn load_config(dir: &Path) -> Result<Config> {
+    let config_path = dir.join("tc.toml");
     
     if !config_path.exists() {
         debug!("No tc.toml found, using default configuration");
@@ -60,15 +140,23 @@ pub fn load_config() -> Result<Config> {
     let config_str = std::fs::read_to_string(&config_path)
         .context("Failed to read tc.toml")?;
     
-    let config: Config = toml::from_str(&config_str)
+    let mut config: Config = toml::from_str(&config_str)
         .context("Failed to parse tc.toml")?;
     
+    // tc may be run from outside the project
+    let options = &mut config.compiler_options;
+    for path in options.include_paths.iter_mut().chain(options.library_paths.iter_mut()) {
+        if path.is_relative() {
+            *path = dir.join(&*path);
+        }
+    }
+    
     Ok(config)
 }
 
-/// Save project configuration to tc.toml
-pub fn save_config(config: &Config) -> Result<()> {
-    let config_path = std::env::current_dir()?.join("tc.toml");
+/// Save project configuration to the tc.toml in `dir`
+pub fn save_config(config: &Config, dir: &Path) -> Result<()> {
+    let config_path = dir.join("tc.toml");
     
     debug!("Saving configuration to {}", config_path.display());
     
@@ -108,33 +196,6 @@ pub fn find_c_files(dir: &Path) -> Result<Vec<PathBuf>> {
     Ok(c_files)
 }
 
-/// Create a formatted error message
-pub fn format_error(message: &str, file: Option<&Path>, line: Option<usize>, column: Option<usize>) -> String {
-    let location = if let Some(file) = file {
-        let file_name = file.file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("<unknown>");
-        
-        if let Some(line) = line {
-            if let Some(column) = column {
-                format!("{} ({}:{})", file_name, line, column)
-            } else {
-                format!("{} (line {})", file_name, line)
-            }
-        } else {
-            file_name.to_string()
-        }
-    } else {
-        "".to_string()
-    };
-    
-    if location.is_empty() {
-        format!("error: {}", message)
-    } else {
-        format!("error: {} in {}", message, location)
-    }
-}
-
 /// Get the absolute path for a file
 pub fn get_absolute_path(path: &Path) -> Result<PathBuf> {
     if path.is_absolute() {
@@ -131,4 +131,747 @@ fn test_init_command() {
     // Check that the file contains the expected content
     let content = std::fs::read_to_string(&tc_file_path).expect("Failed to read tc.c file");
     assert!(content.contains("Hello from tlstuc"), "tc.c file does not contain expected content");
-}
\ No newline at end of file
+    
+    // Check that a tc.toml named after the directory was created
+    let config = std::fs::read_to_string(temp_dir.path().join("tc.toml")).expect("Failed to read tc.toml");
+    let name = temp_dir.path().file_name().unwrap().to_str().unwrap();
+    assert!(config.contains(&format!("name = \"{}\"", name)), "tc.toml does not name the project: {}", config);
+}
+
+#[test]
+fn test_link_with_configured_libraries() {
+    // Create a temporary directory for the test
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let file_path = temp_dir.path().join("root.c");
+    
+    // Write a program that needs the math library, declaring what it uses
+    // itself so that it does not depend on the system headers
+    let program = r#"int printf(const char *format, ...);
+double sqrt(double x);
+
+int main(int argc, char **argv) {
+    volatile double x = 2.0;
+    printf("Square root: %.3f\n", sqrt(x * 8));
+    return 0;
+}
+"#;
+    
+    std::fs::write(&file_path, program).expect("Failed to write test file");
+    
+    // Ask for the math library in the project configuration
+    let config = r#"name = "root"
+version = "0.1.0"
+
+[compiler_options]
+optimization_level = "Default"
+warnings_as_errors = false
+include_paths = []
+library_paths = []
+libraries = ["m"]
+"#;
+    
+    std::fs::write(temp_dir.path().join("tc.toml"), config).expect("Failed to write tc.toml");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Compile, link and run the program from the project directory
+    let output = Command::new(&tc_path)
+        .arg(&file_path)
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc command");
+    
+    // Check that the command succeeded
+    assert!(output.status.success(), "tc command failed: {:?}", output);
+    
+    // Check that the program ran
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Square root: 4.000"), "Output does not contain the square root: {}", stdout);
+}
+
+#[test]
+fn test_command_line_options_override_tc_toml() {
+    // Create a temporary directory for the test
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let file_path = temp_dir.path().join("root.c");
+    
+    // Write a program that needs the math library and a header from an
+    // include directory given on the command line
+    let program = r#"#include "config.h"
+int printf(const char *format, ...);
+double sqrt(double x);
+
+int main(int argc, char **argv) {
+    volatile double x = SQUARE;
+    printf("Square root: %.3f\n", sqrt(x));
+    return 0;
+}
+"#;
+    
+    std::fs::write(&file_path, program).expect("Failed to write test file");
+    std::fs::create_dir(temp_dir.path().join("include")).expect("Failed to create include directory");
+    std::fs::write(temp_dir.path().join("include/config.h"), "#define SQUARE 9.0\n")
+        .expect("Failed to write config.h");
+    
+    // A partial tc.toml; the options it leaves out take their defaults
+    std::fs::write(temp_dir.path().join("tc.toml"), "[compiler_options]\noptimization_level = \"None\"\n")
+        .expect("Failed to write tc.toml");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Compile, link and run the program with the options on the command line
+    let output = Command::new(&tc_path)
+        .args(["-O3", "-I", "include", "-lm"])
+        .arg(&file_path)
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc command");
+    
+    // Check that the command succeeded
+    assert!(output.status.success(), "tc command failed: {:?}", output);
+    
+    // Check that the program ran
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Square root: 3.000"), "Output does not contain the square root: {}", stdout);
+    
+    // An unknown optimization level is rejected
+    let output = Command::new(&tc_path)
+        .args(["-O9"])
+        .arg(&file_path)
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc command");
+    assert!(!output.status.success(), "tc accepted -O9");
+}
+
+#[test]
+fn test_build_project_from_another_directory() {
+    // Create a project whose tc.toml names an include directory relative to
+    // it, and a directory of the same name around it
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let project_dir = temp_dir.path().join("proj");
+    std::fs::create_dir_all(project_dir.join("include")).expect("Failed to create include directory");
+    std::fs::create_dir(temp_dir.path().join("include")).expect("Failed to create include directory");
+    
+    let program = r#"#include "h.h"
+int printf(const char *format, ...);
+
+int main() {
+    printf("Value: %d\n", VALUE);
+    return 0;
+}
+"#;
+    
+    std::fs::write(project_dir.join("main.c"), program).expect("Failed to write main.c");
+    std::fs::write(project_dir.join("include/h.h"), "#define VALUE 42\n").expect("Failed to write h.h");
+    std::fs::write(temp_dir.path().join("include/h.h"), "#define VALUE 0\n").expect("Failed to write h.h");
+    std::fs::write(project_dir.join("tc.toml"), "[compiler_options]\ninclude_paths = [\"include\"]\n")
+        .expect("Failed to write tc.toml");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Run the tc build command from the project's parent directory
+    let output = Command::new(&tc_path)
+        .args(["build", "proj"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc build command");
+    
+    // Check that the command succeeded
+    assert!(output.status.success(), "tc build command failed: {:?}", output);
+    
+    // The header came from the project's include directory
+    let mut executable = project_dir.join("build/tlstuc_project");
+    if cfg!(windows) {
+        executable.set_extension("exe");
+    }
+    let output = Command::new(&executable).output().expect("Failed to run the built executable");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Value: 42"), "Output does not contain the value: {}", stdout);
+}
+
+#[test]
+fn test_build_project_with_several_units() {
+    // Create a project whose units share an external variable and function,
+    // and each have a static function of the same name
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    std::fs::create_dir(temp_dir.path().join("lib")).expect("Failed to create lib directory");
+    
+    let main_program = r#"int printf(const char *format, ...);
+extern int counter;
+int next(void);
+
+static int helper(void) { return 100; }
+
+int main() {
+    next();
+    next();
+    printf("Counter: %d %d\n", counter, helper());
+    return 0;
+}
+"#;
+    let counter_program = r#"int counter;
+
+static int helper(void) { return 1; }
+
+int next(void) {
+    counter += helper();
+    return counter;
+}
+"#;
+    
+    std::fs::write(temp_dir.path().join("main.c"), main_program).expect("Failed to write main.c");
+    std::fs::write(temp_dir.path().join("lib/counter.c"), counter_program).expect("Failed to write counter.c");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Run the tc build command in the project
+    let output = Command::new(&tc_path)
+        .arg("build")
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc build command");
+    
+    // Check that the command succeeded
+    assert!(output.status.success(), "tc build command failed: {:?}", output);
+    
+    // Run the executable, which is named after the default project
+    let mut executable = temp_dir.path().join("build/tlstuc_project");
+    if cfg!(windows) {
+        executable.set_extension("exe");
+    }
+    let output = Command::new(&executable).output().expect("Failed to run the built executable");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Counter: 2 100"), "Output does not contain the counter: {}", stdout);
+    
+    // Compiling and running the units directly links them the same way
+    let output = Command::new(&tc_path)
+        .arg("main.c")
+        .arg("lib/counter.c")
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc command");
+    assert!(output.status.success(), "tc command failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Counter: 2 100"), "Output does not contain the counter: {}", stdout);
+}
+
+#[test]
+fn test_build_emits_intermediate_artifacts() {
+    // Create a project with a single unit
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let program = r#"int printf(const char *format, ...);
+
+struct point { int x; int y; };
+
+int main() {
+    struct point p = { 1, 2 };
+    printf("Sum: %d\n", p.x + p.y);
+    return 0;
+}
+"#;
+    
+    std::fs::write(temp_dir.path().join("main.c"), program).expect("Failed to write main.c");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Write every stage's output to a separate directory
+    let output = Command::new(&tc_path)
+        .args(["build", "--emit=tokens,ast,llvm-ir,llvm-bc,asm,obj", "--out-dir", "out"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc build command");
+    assert!(output.status.success(), "tc build command failed: {:?}", output);
+    
+    let out_dir = temp_dir.path().join("out");
+    let object = if cfg!(windows) { "main.obj" } else { "main.o" };
+    for name in ["main.tokens", "main.ast.json", "main.ll", "main.bc", "main.s", object] {
+        assert!(out_dir.join(name).is_file(), "{} was not written", name);
+    }
+    
+    let tokens = std::fs::read_to_string(out_dir.join("main.tokens")).expect("Failed to read tokens");
+    assert!(tokens.contains("main.c:1:5\tIdentifier(\"printf\")\tprintf"), "Unexpected tokens: {}", tokens);
+    
+    let ast = std::fs::read_to_string(out_dir.join("main.ast.json")).expect("Failed to read AST");
+    let ast: serde_json::Value = serde_json::from_str(&ast).expect("The AST is not JSON");
+    assert!(ast["functions"]["main"].is_object(), "The AST has no main function: {}", ast);
+    assert!(ast["structs"]["point"].is_object(), "The AST has no point struct: {}", ast);
+    
+    let llvm_ir = std::fs::read_to_string(out_dir.join("main.ll")).expect("Failed to read LLVM IR");
+    assert!(llvm_ir.contains("define i32 @main"), "Unexpected LLVM IR: {}", llvm_ir);
+    
+    // Without exe in the list the units are not linked
+    assert!(!temp_dir.path().join("build").exists(), "tc build linked an executable");
+    
+    // An unknown output kind is rejected
+    let output = Command::new(&tc_path)
+        .args(["build", "--emit=tokens,binary"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc build command");
+    assert!(!output.status.success(), "tc accepted --emit=binary");
+}
+
+#[test]
+fn test_build_units_with_the_same_file_name() {
+    // Create a project with two units called util.c in different directories
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    std::fs::create_dir(temp_dir.path().join("a")).expect("Failed to create a directory");
+    std::fs::create_dir(temp_dir.path().join("b")).expect("Failed to create b directory");
+    
+    let main_program = r#"int printf(const char *format, ...);
+int fa(void);
+int fb(void);
+
+int main() {
+    printf("Units: %d %d\n", fa(), fb());
+    return 0;
+}
+"#;
+    
+    std::fs::write(temp_dir.path().join("main.c"), main_program).expect("Failed to write main.c");
+    std::fs::write(temp_dir.path().join("a/util.c"), "int fa(void) { return 1; }\n").expect("Failed to write a/util.c");
+    std::fs::write(temp_dir.path().join("b/util.c"), "int fb(void) { return 2; }\n").expect("Failed to write b/util.c");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Keep the objects in a separate directory and link them
+    let output = Command::new(&tc_path)
+        .args(["build", "--emit=obj,exe", "--out-dir", "out"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc build command");
+    assert!(output.status.success(), "tc build command failed: {:?}", output);
+    
+    // Each unit keeps its directory inside the output directory
+    let extension = if cfg!(windows) { "obj" } else { "o" };
+    for unit in ["main", "a/util", "b/util"] {
+        let object = temp_dir.path().join("out").join(unit).with_extension(extension);
+        assert!(object.is_file(), "{} was not written", object.display());
+    }
+    
+    let mut executable = temp_dir.path().join("build/tlstuc_project");
+    if cfg!(windows) {
+        executable.set_extension("exe");
+    }
+    let output = Command::new(&executable).output().expect("Failed to run the built executable");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Units: 1 2"), "Output does not contain both units: {}", stdout);
+}
+
+#[test]
+fn test_build_objects_for_other_targets() {
+    // Create a project that does not need a C library
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let program = r#"long scale(long value) {
+    return value * (long)sizeof(void *);
+}
+"#;
+    
+    std::fs::write(temp_dir.path().join("scale.c"), program).expect("Failed to write scale.c");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // The ELF machine of each object, at offset 18 of its header
+    let targets = [("aarch64-linux-gnu", 0xb7), ("riscv64-unknown-linux-gnu", 0xf3), ("armv7-unknown-linux-gnueabihf", 0x28)];
+    for (triple, machine) in targets {
+        let output = Command::new(&tc_path)
+            .args(["build", "--target", triple, "--emit=obj", "--out-dir", triple])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to execute tc build command");
+        assert!(output.status.success(), "tc build command failed for {}: {:?}", triple, output);
+        
+        let object = std::fs::read(temp_dir.path().join(triple).join("scale.o")).expect("Failed to read object file");
+        assert_eq!(&object[..4], b"\x7fELF", "{} did not produce an ELF object", triple);
+        assert_eq!(object[18], machine, "{} produced an object for another machine", triple);
+    }
+    
+    // The target may also come from tc.toml
+    std::fs::write(temp_dir.path().join("tc.toml"), "[compiler_options]\ntarget = \"wasm32-unknown-unknown\"\n")
+        .expect("Failed to write tc.toml");
+    let output = Command::new(&tc_path)
+        .args(["build", "--emit=obj,llvm-ir", "--out-dir", "wasm"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc build command");
+    assert!(output.status.success(), "tc build command failed for wasm32: {:?}", output);
+    let object = std::fs::read(temp_dir.path().join("wasm/scale.o")).expect("Failed to read object file");
+    assert_eq!(&object[..4], b"\0asm", "wasm32 did not produce a WebAssembly object");
+    // Pointers are 4 bytes wide there, and so is long
+    let llvm_ir = std::fs::read_to_string(temp_dir.path().join("wasm/scale.ll")).expect("Failed to read LLVM IR");
+    assert!(llvm_ir.contains("define i32 @scale(i32 %value)"), "Unexpected LLVM IR: {}", llvm_ir);
+    
+    // A program for another platform cannot be run
+    let output = Command::new(&tc_path)
+        .arg("scale.c")
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc command");
+    assert!(!output.status.success(), "tc ran a program built for wasm32");
+}
+
+// LLVM's warnings about them are only captured on Unix
+#[cfg(unix)]
+#[test]
+fn test_build_rejects_unknown_processors_and_features() {
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    std::fs::write(temp_dir.path().join("main.c"), "int main(void) { return 0; }\n").expect("Failed to write main.c");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Each is reported as an error rather than left for LLVM to abort on
+    let cases = [
+        (vec!["--cpu", "bogus"], "Unknown processor 'bogus'"),
+        (vec!["--target-feature", "+bogus"], "Unknown target feature '+bogus'"),
+        (vec!["--target-feature", "bogus"], "Target feature 'bogus' must start with '+' to enable it or '-' to disable it"),
+    ];
+    for (flags, expected) in cases {
+        let output = Command::new(&tc_path)
+            .arg("build")
+            .args(&flags)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to execute tc build command");
+        assert_eq!(output.status.code(), Some(1), "tc build {:?} did not fail cleanly: {:?}", flags, output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(expected), "Output does not contain the error for {:?}: {}", flags, stderr);
+    }
+}
+
+#[test]
+fn test_compile_and_run_with_function_pointers() {
+    // Create a temporary directory for the test
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let file_path = temp_dir.path().join("pointers.c");
+    
+    // Write a C program that calls functions through pointers: a library
+    // callback, a table of handlers, a returned pointer and a variadic one
+    let program = r#"int printf(const char *format, ...);
+void qsort(void *base, unsigned long count, unsigned long size, int (*compare)(const void *, const void *));
+
+static int descending(const void *a, const void *b) {
+    return *(const int *)b - *(const int *)a;
+}
+
+static int add(int a, int b) { return a + b; }
+static int multiply(int a, int b) { return a * b; }
+
+struct calculator {
+    const char *name;
+    int (*apply)(int, int);
+};
+
+static int (*pick(int which))(int, int) {
+    return which ? multiply : add;
+}
+
+int main() {
+    int values[] = {3, 1, 4, 1, 5};
+    qsort(values, 5, sizeof values[0], descending);
+    printf("Sorted: %d %d %d %d %d\n", values[0], values[1], values[2], values[3], values[4]);
+    
+    struct calculator calculators[] = {{"add", add}, {"multiply", &multiply}};
+    struct calculator *last = &calculators[1];
+    printf("%s: %d, %s: %d\n", calculators[0].name, calculators[0].apply(6, 7), last->name, (*last->apply)(6, 7));
+    printf("Picked: %d\n", pick(1)(2, 3) + pick(0)(2, 3));
+    
+    int (*print)(const char *, ...) = printf;
+    print("Variadic: %d %s\n", 42, "done");
+    return 0;
+}
+"#;
+    
+    std::fs::write(&file_path, program).expect("Failed to write test file");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Run the tc command on the file
+    let output = Command::new(&tc_path)
+        .arg(&file_path)
+        .output()
+        .expect("Failed to execute tc command");
+    
+    // Check that the command succeeded
+    assert!(output.status.success(), "tc command failed: {:?}", output);
+    
+    // Check that every call reached the right function
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Sorted: 5 4 3 1 1"), "Output does not contain the sorted values: {}", stdout);
+    assert!(stdout.contains("add: 13, multiply: 42"), "Output does not contain the handlers: {}", stdout);
+    assert!(stdout.contains("Picked: 11"), "Output does not contain the picked results: {}", stdout);
+    assert!(stdout.contains("Variadic: 42 done"), "Output does not contain the variadic call: {}", stdout);
+}
+
+#[test]
+fn test_compile_and_run_with_extern_inline_definitions() {
+    // Create a temporary directory for the test
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let file_path = temp_dir.path().join("inline.c");
+    
+    // Write a C program whose inline functions are also external
+    // definitions, through `extern inline` or a separate `extern` declaration
+    let program = r#"int printf(const char *format, ...);
+
+extern inline int twice(int x) { return 2 * x; }
+
+inline int square(int x) { return x * x; }
+extern int square(int x);
+
+int main() {
+    printf("Results: %d %d\n", twice(21), square(5));
+    return 0;
+}
+"#;
+    
+    std::fs::write(&file_path, program).expect("Failed to write test file");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Run the tc command on the file without optimization, so that the calls
+    // are not inlined and have to be linked
+    let output = Command::new(&tc_path)
+        .arg("-O0")
+        .arg(&file_path)
+        .output()
+        .expect("Failed to execute tc command");
+    
+    // Check that the command succeeded
+    assert!(output.status.success(), "tc command failed: {:?}", output);
+    
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Results: 42 25"), "Output does not contain the results: {}", stdout);
+}
+
+#[test]
+fn test_compile_and_run_with_alignas_and_old_style_definitions() {
+    // Create a temporary directory for the test
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    let file_path = temp_dir.path().join("alignas.c");
+    
+    // Write a C program using `alignas` from <stdalign.h> and a function
+    // defined with an identifier list
+    let program = r#"#include <stdalign.h>
+#include <stdio.h>
+
+alignas(32) static char buffer[10];
+
+struct slot { char tag; alignas(16) int value; };
+
+int add(a, b) int a; char *b; { return a + b[0] - '0'; }
+
+int main() {
+    alignas(64) int local = add(40, "2");
+    printf("Aligned: %d %d %d\n", (int)((unsigned long)buffer % 32), (int)((unsigned long)&local % 64),
+           (int)(sizeof(struct slot) + alignof(struct slot)));
+    printf("Result: %d\n", local);
+    return 0;
+}
+"#;
+    
+    std::fs::write(&file_path, program).expect("Failed to write test file");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Run the tc command on the file
+    let output = Command::new(&tc_path)
+        .arg(&file_path)
+        .output()
+        .expect("Failed to execute tc command");
+    
+    // Check that the command succeeded
+    assert!(output.status.success(), "tc command failed: {:?}", output);
+    
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Aligned: 0 0 48"), "Output does not contain the alignments: {}", stdout);
+    assert!(stdout.contains("Result: 42"), "Output does not contain the result: {}", stdout);
+}
+
+#[test]
+fn test_build_reports_diagnostics_with_source_excerpts() {
+    // Create a project with a warning and an error in each of two units
+    let temp_dir = tempdir().expect("Failed to create temporary directory");
+    
+    let main_program = r#"#warning "unfinished"
+struct point { int x; };
+struct point { int y; };
+
+int main() {
+    return 0;
+}
+"#;
+    let other_program = r#"int value = undefined;
+"#;
+    
+    std::fs::write(temp_dir.path().join("main.c"), main_program).expect("Failed to write main.c");
+    std::fs::write(temp_dir.path().join("other.c"), other_program).expect("Failed to write other.c");
+    
+    // Build the tc binary if running as a test
+    let tc_path = if cfg!(debug_assertions) {
+        // Running as a test, use the debug build
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("target/debug/tc");
+        if cfg!(windows) {
+            path.set_extension("exe");
+        }
+        path
+    } else {
+        // Running as a binary, use the current executable
+        std::env::current_exe().expect("Failed to get current executable path")
+    };
+    
+    // Run the tc build command in the project
+    let output = Command::new(&tc_path)
+        .arg("build")
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute tc build command");
+    
+    // Check that the build failed after reporting every unit
+    assert!(!output.status.success(), "tc build command succeeded: {:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("warning: #warning \"unfinished\"\n --> ./main.c:1:2\n  |\n1 | #warning \"unfinished\"\n"),
+        "Output does not contain the warning: {}",
+        stderr
+    );
+    assert!(
+        stderr.contains("2 | struct point { int x; };\n  | ----------------------- previous definition is here\n"),
+        "Output does not contain the previous definition: {}",
+        stderr
+    );
+    assert!(stderr.contains(" --> ./other.c:1:13\n"), "Output does not contain the second unit: {}", stderr);
+    assert!(stderr.contains("1 warning and 2 errors generated"), "Output does not contain the summary: {}", stderr);
+}